@@ -1,6 +1,10 @@
 use log::info;
+use std::fmt;
 
-#[derive(Debug, Clone)]
+/// The `input_type` of a field whose value is never shown or logged.
+pub const PASSWORD: &str = "password";
+
+#[derive(Clone)]
 pub struct InputBlock {
     pub prompt: String,
     pub value: String,
@@ -24,9 +28,26 @@ impl InputBlock {
         }
     }
 
+    pub fn password(prompt: String) -> Self {
+        Self::new(prompt, PASSWORD.to_string())
+    }
+
+    pub fn is_masked(&self) -> bool {
+        self.input_type == PASSWORD
+    }
+
+    /// The value as it may be shown: one bullet per character for password fields.
+    pub fn display_value(&self) -> String {
+        if self.is_masked() {
+            "•".repeat(self.value.chars().count())
+        } else {
+            self.value.clone()
+        }
+    }
+
     pub fn set_value(&mut self, new_value: String) {
         self.value = new_value;
-        info!("InputBlock value updated to: {}", self.value);
+        info!("InputBlock value updated to: {}", self.display_value());
     }
 
     pub fn get_value(&self) -> &str {
@@ -39,7 +60,18 @@ impl InputBlock {
     }
 
     pub fn render(&self) {
-        info!("Rendering InputBlock: Prompt='{}', Value='{}', Type='{}'", self.prompt, self.value, self.input_type);
+        info!("Rendering InputBlock: Prompt='{}', Value='{}', Type='{}'", self.prompt, self.display_value(), self.input_type);
         // In a real UI, this would involve drawing the input field
     }
 }
+
+// Masked like the rendered value, so `{:?}` in a log can't leak a password.
+impl fmt::Debug for InputBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InputBlock")
+            .field("prompt", &self.prompt)
+            .field("value", &self.display_value())
+            .field("input_type", &self.input_type)
+            .finish()
+    }
+}
@@ -10,9 +10,9 @@ pub fn run_input_block_examples() {
     info!("Text Input Block: Prompt='{}', Value='{}'", text_input.prompt, text_input.value);
 
     // Example 2: Password input
-    let mut password_input = InputBlock::new("Enter your password:".to_string(), "password".to_string());
+    let mut password_input = InputBlock::password("Enter your password:".to_string());
     password_input.set_value("secret123".to_string());
-    info!("Password Input Block: Prompt='{}', Value='{}'", password_input.prompt, password_input.value);
+    info!("Password Input Block: Prompt='{}', Value='{}'", password_input.prompt, password_input.display_value());
 
     // Example 3: Number input
     let mut number_input = InputBlock::new("Enter your age:".to_string(), "number".to_string());
@@ -23,7 +23,7 @@ impl InputBlockIntegrator {
             _ => info!("  (Unknown input type)"),
         }
 
-        info!("  Current value: '{}'", block.get_value());
+        info!("  Current value: '{}'", block.display_value());
     }
 
     /// Simulates processing input from an integrated block.
@@ -33,7 +33,7 @@ impl InputBlockIntegrator {
         if block.get_value().is_empty() {
             info!("  Input is empty. Please provide a value.");
         } else {
-            info!("  Input received: '{}'. Performing action...", block.get_value());
+            info!("  Input received: '{}'. Performing action...", block.display_value());
             // Example: save to a database, execute a command, etc.
         }
     }
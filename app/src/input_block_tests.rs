@@ -38,6 +38,20 @@ mod tests {
         assert_eq!(block.value, "");
     }
 
+    #[test]
+    fn test_password_values_are_masked() {
+        let mut block = InputBlock::password("API token:".to_string());
+        block.set_value("tök3n".to_string());
+        assert!(block.is_masked());
+        assert_eq!(block.get_value(), "tök3n");
+        assert_eq!(block.display_value(), "•••••");
+        assert!(!format!("{:?}", block).contains("tök3n"));
+
+        let text_block = InputBlock::with_value("Name:".to_string(), "Alice".to_string(), "text".to_string());
+        assert!(!text_block.is_masked());
+        assert_eq!(text_block.display_value(), "Alice");
+    }
+
     #[test]
     fn test_input_type_variations() {
         let text_block = InputBlock::new("Text:".to_string(), "text".to_string());
@@ -0,0 +1,38 @@
+# Shell integration for bash (4.4+), loaded via `--rcfile`.
+# Emits OSC 133 semantic prompt marks so the terminal can split output into blocks:
+#   A = prompt start, B = prompt end / input start, C = command output start, D;<status> = command finished.
+# Before each prompt it also reports the working directory with OSC 7 (file://<host><path>).
+
+if [ -f "$HOME/.bashrc" ]; then
+    . "$HOME/.bashrc"
+fi
+
+if [ -z "$__WARP_INTEGRATION_LOADED" ]; then
+    __WARP_INTEGRATION_LOADED=1
+
+    # D goes out before every prompt; the terminal ignores it when no command was running.
+    __warp_precmd() {
+        printf '\033]133;D;%s\007' "$1"
+        printf '\033]7;file://%s%s\007' "$HOSTNAME" "${PWD//%/%25}"
+        printf '\033]133;A\007'
+    }
+
+    # Runs last in PROMPT_COMMAND so that any prompt framework has already set PS1.
+    __warp_prompt_ready() {
+        case "$PS1" in
+            *'133;B'*) ;;
+            *) PS1="$PS1"'\[\e]133;B\a\]' ;;
+        esac
+    }
+
+    # Existing PROMPT_COMMANDs often end in ';' (e.g. pyenv), which would leave us with ';;'.
+    __warp_user_prompt_command="$PROMPT_COMMAND"
+    while [[ "$__warp_user_prompt_command" == *[\;[:space:]] ]]; do
+        __warp_user_prompt_command="${__warp_user_prompt_command%?}"
+    done
+    PROMPT_COMMAND='__warp_precmd "$?"'"${__warp_user_prompt_command:+; $__warp_user_prompt_command}"'; __warp_prompt_ready'
+    unset __warp_user_prompt_command
+
+    # PS0 is printed after a command line is read and before it runs (never for empty lines).
+    PS0="$PS0"'\e]133;C\a'
+fi
@@ -0,0 +1,25 @@
+# Shell integration for fish, loaded via `--init-command`.
+# Emits OSC 133 semantic prompt marks so the terminal can split output into blocks:
+#   A = prompt start, B = prompt end / input start, C = command output start, D;<status> = command finished.
+# Before each prompt it also reports the working directory with OSC 7 (file://<host><path>).
+
+if not set -q __WARP_INTEGRATION_LOADED
+    set -g __WARP_INTEGRATION_LOADED 1
+
+    function __warp_preexec --on-event fish_preexec
+        printf '\e]133;C\a'
+    end
+
+    function __warp_postexec --on-event fish_postexec
+        printf '\e]133;D;%s\a' $status
+    end
+
+    functions -q fish_prompt; and functions -c fish_prompt __warp_original_fish_prompt
+
+    function fish_prompt
+        printf '\e]7;file://%s%s\a' $hostname (string replace -a % %25 -- $PWD)
+        printf '\e]133;A\a'
+        functions -q __warp_original_fish_prompt; and __warp_original_fish_prompt
+        printf '\e]133;B\a'
+    end
+end
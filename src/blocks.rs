@@ -7,6 +7,7 @@ use chrono::{DateTime, Local};
 use crate::terminal::Message;
 use crate::themes::WarpTheme;
 use crate::shell::ShellOutput;
+use crate::vt::Screen;
 
 #[derive(Debug, Clone)]
 pub struct Block {
@@ -18,7 +19,7 @@ pub struct Block {
 pub enum BlockContent {
     Command {
         input: String,
-        output: String,
+        output: Screen,
         prompt: String, // The prompt string at the time of command execution
         exit_code: Option<i32>,
         execution_time_ms: Option<u128>,
@@ -49,7 +50,7 @@ impl Block {
             id,
             content: BlockContent::Command {
                 input,
-                output: String::new(),
+                output: Screen::default(),
                 prompt,
                 exit_code: None,
                 execution_time_ms: None,
@@ -72,17 +73,32 @@ impl Block {
     pub fn append_output(&mut self, output: ShellOutput) {
         if let BlockContent::Command { output: current_output, .. } = &mut self.content {
             match output {
-                ShellOutput::Stdout(s) => {
-                    current_output.push_str(&s);
-                    current_output.push('\n');
-                },
-                ShellOutput::Stderr(s) => {
-                    current_output.push_str(&format!("[ERROR] {}\n", s));
+                // PTY output arrives in arbitrary chunks; the screen keeps any partial sequence.
+                ShellOutput::Stdout(bytes) | ShellOutput::Stderr(bytes) => {
+                    current_output.feed(&bytes);
                 }
             }
         }
     }
 
+    /// Fills in the command line and prompt once the shell reports that the command was accepted.
+    pub fn start_command(&mut self, command: String, prompt_text: String) {
+        if let BlockContent::Command { input, prompt, is_running, timestamp, .. } = &mut self.content {
+            *input = command;
+            *prompt = prompt_text;
+            *is_running = true;
+            *timestamp = Local::now();
+        }
+    }
+
+    /// Whether this block has neither a command nor any output yet, i.e. it is still just a prompt.
+    pub fn is_empty_command(&self) -> bool {
+        match &self.content {
+            BlockContent::Command { input, output, .. } => input.is_empty() && output.text().is_empty(),
+            BlockContent::Markdown { .. } => false,
+        }
+    }
+
     pub fn complete_execution(&mut self, exit_code: i32, execution_time_ms: u128) {
         if let BlockContent::Command { is_running, exit_code: ec, execution_time_ms: et, .. } = &mut self.content {
             *is_running = false;
@@ -121,7 +137,7 @@ impl Block {
                     ]
                     .align_items(alignment::Vertical::Center)
                     .spacing(5),
-                    text(output).color(foreground_color).size(14),
+                    text(output.text()).color(foreground_color).size(14),
                     row![
                         Space::with_width(Length::Fill),
                         exit_status_text,
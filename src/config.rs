@@ -63,6 +63,8 @@ pub struct KeyBindings {
     pub open_profile_manager: String,
     pub open_workflow_browser: String,
     pub open_warp_drive: String, // New keybinding
+    #[serde(default = "default_history_search_binding")]
+    pub history_search: String,
     // Add more keybindings as needed
 }
 
@@ -80,10 +82,16 @@ impl Default for KeyBindings {
             open_profile_manager: "Ctrl+Shift+P".to_string(),
             open_workflow_browser: "Ctrl+W".to_string(),
             open_warp_drive: "Ctrl+Shift+D".to_string(), // Default value
+            history_search: default_history_search_binding(),
         }
     }
 }
 
+// Config files written before the binding existed don't have it.
+fn default_history_search_binding() -> String {
+    "Ctrl+R".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PromptSettings {
     pub show_user: bool,
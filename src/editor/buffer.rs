@@ -0,0 +1,568 @@
+// The command being edited at the prompt: any number of lines, a cursor, an optional
+// selection and undo history. Positions are byte offsets that always sit on grapheme
+// boundaries, so moving or deleting never splits "é" or an emoji. Consecutive typing (up to
+// the end of a word) or deleting is undone as one step.
+
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::clipboard::Clipboard;
+
+/// Older undo steps are dropped.
+const MAX_UNDO: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    LineStart,
+    LineEnd,
+    Up,
+    Down,
+    BufferStart,
+    BufferEnd,
+}
+
+/// Edits of the same kind in a row share an undo step, except `Other`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditKind {
+    Typing,
+    DeleteBackward,
+    DeleteForward,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Snapshot {
+    text: String,
+    cursor: usize,
+    anchor: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EditorBuffer {
+    text: String,
+    cursor: usize,
+    /// Where the selection started; it runs from here to the cursor.
+    anchor: Option<usize>,
+    /// The column Up/Down aim for, kept while passing through shorter lines.
+    goal_column: Option<usize>,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+    last_edit: Option<EditKind>,
+}
+
+impl EditorBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A buffer holding `text` with the cursor at its end and no undo history.
+    pub fn with_text(text: impl Into<String>) -> Self {
+        let text = text.into();
+        EditorBuffer { cursor: text.len(), text, ..Self::default() }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        (anchor != self.cursor).then(|| anchor.min(self.cursor)..anchor.max(self.cursor))
+    }
+
+    pub fn selected_text(&self) -> Option<&str> {
+        self.selection().map(|range| &self.text[range])
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.text.split('\n').count()
+    }
+
+    /// Byte ranges of the lines, without their newlines.
+    pub fn line_ranges(&self) -> Vec<Range<usize>> {
+        let mut start = 0;
+        self.text
+            .split('\n')
+            .map(|line| {
+                let range = start..start + line.len();
+                start = range.end + 1;
+                range
+            })
+            .collect()
+    }
+
+    /// The cursor's line and its column in graphemes, both from 0.
+    pub fn cursor_line_col(&self) -> (usize, usize) {
+        let before = &self.text[..self.cursor];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (before.matches('\n').count(), before[line_start..].graphemes(true).count())
+    }
+
+    /// Whether Enter should run the command rather than continue it on a new line.
+    pub fn is_complete(&self) -> bool {
+        is_complete(&self.text)
+    }
+
+    /// Replaces everything, e.g. with a command from history, as one undo step.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.begin_edit(EditKind::Other);
+        self.text = text.into();
+        self.cursor = self.text.len();
+        self.anchor = None;
+    }
+
+    /// Takes a whole new value from a widget that edits the text itself, like `text_input`,
+    /// as the smallest edit that produces it, so typing there still undoes word by word.
+    pub fn apply_value(&mut self, value: &str) {
+        let prefix = common_prefix(&self.text, value);
+        let suffix = common_suffix(&self.text[prefix..], &value[prefix..]);
+        let removed = prefix..self.text.len() - suffix;
+        let inserted = &value[prefix..value.len() - suffix];
+        let mut inserted_chars = inserted.chars();
+        match (inserted_chars.next(), inserted_chars.next()) {
+            _ if removed.is_empty() && inserted.is_empty() => {}
+            (Some(c), None) if removed.is_empty() => {
+                self.anchor = None;
+                self.cursor = prefix;
+                self.insert_char(c);
+            }
+            (None, _) if self.prev_boundary(removed.end) == removed.start => {
+                self.anchor = None;
+                self.cursor = removed.end;
+                self.delete_backward();
+            }
+            _ => {
+                self.anchor = Some(removed.start);
+                self.cursor = removed.end;
+                self.insert_str(inserted);
+            }
+        }
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        // A space after a word starts the next undo step.
+        let ends_word = c.is_whitespace() && self.text[..self.cursor].chars().next_back().is_some_and(|prev| !prev.is_whitespace());
+        if ends_word || self.selection().is_some() {
+            self.last_edit = None;
+        }
+        self.begin_edit(EditKind::Typing);
+        self.replace_selection(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Inserts `text`, e.g. pasted, over the selection as one undo step.
+    pub fn insert_str(&mut self, text: &str) {
+        if text.is_empty() && self.selection().is_none() {
+            return;
+        }
+        self.begin_edit(EditKind::Other);
+        self.replace_selection(text);
+    }
+
+    pub fn delete_backward(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        let start = self.prev_boundary(self.cursor);
+        if start < self.cursor {
+            self.begin_edit(EditKind::DeleteBackward);
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    pub fn delete_forward(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        let end = self.next_boundary(self.cursor);
+        if end > self.cursor {
+            self.begin_edit(EditKind::DeleteForward);
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    /// Ctrl-Backspace: deletes back to the start of the word.
+    pub fn delete_word_backward(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        let start = self.word_left(self.cursor);
+        if start < self.cursor {
+            self.begin_edit(EditKind::Other);
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        if self.selection().is_none() {
+            return false;
+        }
+        self.begin_edit(EditKind::Other);
+        self.replace_selection("");
+        true
+    }
+
+    /// Moves the cursor; with `select`, the selection is extended to it. Left and Right
+    /// without `select` collapse a selection to its start or end.
+    pub fn move_cursor(&mut self, motion: Motion, select: bool) {
+        let selection = self.selection().filter(|_| !select);
+        let target = match motion {
+            Motion::Left => selection.map_or_else(|| self.prev_boundary(self.cursor), |range| range.start),
+            Motion::Right => selection.map_or_else(|| self.next_boundary(self.cursor), |range| range.end),
+            Motion::WordLeft => self.word_left(self.cursor),
+            Motion::WordRight => self.word_right(self.cursor),
+            Motion::LineStart => self.text[..self.cursor].rfind('\n').map_or(0, |i| i + 1),
+            Motion::LineEnd => self.text[self.cursor..].find('\n').map_or(self.text.len(), |i| self.cursor + i),
+            Motion::Up | Motion::Down => self.vertical_target(motion == Motion::Down),
+            Motion::BufferStart => 0,
+            Motion::BufferEnd => self.text.len(),
+        };
+        if !matches!(motion, Motion::Up | Motion::Down) {
+            self.goal_column = None;
+        }
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = target;
+        self.last_edit = None;
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+        self.last_edit = None;
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.undo_stack.pop() else { return false };
+        self.redo_stack.push(self.snapshot());
+        self.restore(snapshot);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(snapshot) = self.redo_stack.pop() else { return false };
+        self.undo_stack.push(self.snapshot());
+        self.restore(snapshot);
+        true
+    }
+
+    pub fn copy(&self, clipboard: &mut dyn Clipboard) {
+        if let Some(selected) = self.selected_text() {
+            clipboard.write(selected);
+        }
+    }
+
+    pub fn cut(&mut self, clipboard: &mut dyn Clipboard) {
+        self.copy(clipboard);
+        self.delete_selection();
+    }
+
+    pub fn paste(&mut self, clipboard: &mut dyn Clipboard) {
+        if let Some(text) = clipboard.read() {
+            self.insert_str(&text);
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot { text: self.text.clone(), cursor: self.cursor, anchor: self.anchor }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.cursor = snapshot.cursor;
+        self.anchor = snapshot.anchor;
+        self.goal_column = None;
+        self.last_edit = None;
+    }
+
+    /// Saves an undo step unless this edit continues the previous one.
+    fn begin_edit(&mut self, kind: EditKind) {
+        if kind == EditKind::Other || self.last_edit != Some(kind) {
+            self.undo_stack.push(self.snapshot());
+            if self.undo_stack.len() > MAX_UNDO {
+                self.undo_stack.remove(0);
+            }
+        }
+        self.redo_stack.clear();
+        self.goal_column = None;
+        self.last_edit = Some(kind);
+    }
+
+    fn replace_selection(&mut self, text: &str) {
+        let range = self.selection().unwrap_or(self.cursor..self.cursor);
+        self.text.replace_range(range.clone(), text);
+        self.cursor = range.start + text.len();
+        self.anchor = None;
+    }
+
+    fn prev_boundary(&self, position: usize) -> usize {
+        self.text[..position].grapheme_indices(true).next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self, position: usize) -> usize {
+        self.text[position..].graphemes(true).next().map_or(position, |grapheme| position + grapheme.len())
+    }
+
+    fn word_left(&self, position: usize) -> usize {
+        self.text[..position].split_word_bound_indices().rev().find(|(_, segment)| is_word(segment)).map_or(0, |(i, _)| i)
+    }
+
+    fn word_right(&self, position: usize) -> usize {
+        self.text[position..]
+            .split_word_bound_indices()
+            .find(|(_, segment)| is_word(segment))
+            .map_or(self.text.len(), |(i, segment)| position + i + segment.len())
+    }
+
+    /// The same column on the next or previous line, or the end of the line if it's shorter.
+    /// Past the first or last line, the start or end of the buffer, which resets the column.
+    fn vertical_target(&mut self, down: bool) -> usize {
+        let (line, column) = self.cursor_line_col();
+        let goal = *self.goal_column.get_or_insert(column);
+        let target = match (down, line) {
+            (false, 0) => {
+                self.goal_column = None;
+                return 0;
+            }
+            (false, line) => line - 1,
+            (true, line) if line + 1 >= self.line_count() => {
+                self.goal_column = None;
+                return self.text.len();
+            }
+            (true, line) => line + 1,
+        };
+        let line = self.line_ranges().swap_remove(target);
+        line.start + self.text[line].graphemes(true).take(goal).map(str::len).sum::<usize>()
+    }
+}
+
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices().zip(b.chars()).find(|((_, x), y)| x != y).map_or(a.len().min(b.len()), |((i, _), _)| i)
+}
+
+fn common_suffix(a: &str, b: &str) -> usize {
+    a.chars().rev().zip(b.chars().rev()).take_while(|(x, y)| x == y).map(|(x, _)| x.len_utf8()).sum()
+}
+
+/// Whether `text` is a whole command: not ending in a backslash and with every quote closed.
+/// Quotes inside a `#` comment don't count.
+pub fn is_complete(text: &str) -> bool {
+    let mut quote: Option<char> = None;
+    let mut at_word_start = true;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                // The escaped character; a backslash at the very end continues the line.
+                let Some(_) = chars.next() else { return false };
+            }
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '#') if at_word_start => {
+                // Up to the end of the line, without consuming the newline's word start.
+                let rest = chars.as_str();
+                chars = rest[rest.find('\n').unwrap_or(rest.len())..].chars();
+            }
+            _ => {}
+        }
+        at_word_start = c.is_whitespace() || matches!(c, ';' | '|' | '&' | '(');
+    }
+    quote.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestClipboard(Option<String>);
+
+    impl Clipboard for TestClipboard {
+        fn read(&mut self) -> Option<String> {
+            self.0.clone()
+        }
+
+        fn write(&mut self, text: &str) {
+            self.0 = Some(text.to_string());
+        }
+    }
+
+    fn typed(text: &str) -> EditorBuffer {
+        let mut buffer = EditorBuffer::new();
+        for c in text.chars() {
+            buffer.insert_char(c);
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_cursor_moves_over_whole_graphemes() {
+        // "e" + combining acute, a flag, and a family emoji joined with ZWJs.
+        let mut buffer = EditorBuffer::with_text("ae\u{301}🇳🇴👨‍👩‍👧b");
+        buffer.move_cursor(Motion::Left, false);
+        buffer.delete_backward();
+        assert_eq!(buffer.text(), "ae\u{301}🇳🇴b");
+        buffer.delete_backward();
+        buffer.delete_backward();
+        assert_eq!(buffer.text(), "ab");
+        assert_eq!(buffer.cursor(), 1);
+
+        // Used to panic: `String::remove` on a byte offset inside "é".
+        let mut buffer = EditorBuffer::with_text("café");
+        buffer.move_cursor(Motion::BufferStart, false);
+        buffer.move_cursor(Motion::Right, false);
+        buffer.move_cursor(Motion::Right, false);
+        buffer.move_cursor(Motion::Right, false);
+        buffer.delete_forward();
+        assert_eq!(buffer.text(), "caf");
+    }
+
+    #[test]
+    fn test_word_motions() {
+        let mut buffer = EditorBuffer::with_text("git push --force origin/main");
+        buffer.move_cursor(Motion::WordLeft, false);
+        assert_eq!(&buffer.text()[buffer.cursor()..], "main");
+        buffer.move_cursor(Motion::WordLeft, false);
+        buffer.move_cursor(Motion::WordLeft, false);
+        assert_eq!(&buffer.text()[buffer.cursor()..], "force origin/main");
+        buffer.move_cursor(Motion::WordRight, false);
+        assert_eq!(&buffer.text()[buffer.cursor()..], " origin/main");
+        buffer.delete_word_backward();
+        assert_eq!(buffer.text(), "git push -- origin/main");
+    }
+
+    #[test]
+    fn test_selection_replace_and_collapse() {
+        let mut buffer = EditorBuffer::with_text("echo hello world");
+        buffer.move_cursor(Motion::WordLeft, false);
+        buffer.move_cursor(Motion::WordLeft, true);
+        assert_eq!(buffer.selected_text(), Some("hello "));
+        buffer.move_cursor(Motion::Right, false);
+        assert_eq!((buffer.selection(), buffer.cursor()), (None, 11));
+
+        buffer.move_cursor(Motion::WordLeft, true);
+        buffer.insert_char('X');
+        assert_eq!(buffer.text(), "echo Xworld");
+        buffer.select_all();
+        buffer.delete_backward();
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_vertical_motion_keeps_its_column() {
+        let mut buffer = EditorBuffer::with_text("for f in *; do\n  ls\n  echo \"$f\"\ndone");
+        buffer.move_cursor(Motion::Up, false);
+        buffer.move_cursor(Motion::LineEnd, false);
+        assert_eq!(buffer.cursor_line_col(), (2, 11));
+        buffer.move_cursor(Motion::Up, false);
+        assert_eq!(buffer.cursor_line_col(), (1, 4));
+        buffer.move_cursor(Motion::Up, false);
+        assert_eq!(buffer.cursor_line_col(), (0, 11));
+        buffer.move_cursor(Motion::Up, false);
+        assert_eq!(buffer.cursor(), 0);
+        buffer.move_cursor(Motion::Down, true);
+        assert_eq!(buffer.selected_text(), Some("for f in *; do\n"));
+    }
+
+    #[test]
+    fn test_typing_is_undone_a_word_at_a_time() {
+        let mut buffer = typed("cargo build");
+        buffer.delete_backward();
+        buffer.delete_backward();
+        assert_eq!(buffer.text(), "cargo bui");
+
+        assert!(buffer.undo());
+        assert_eq!(buffer.text(), "cargo build");
+        assert!(buffer.undo());
+        assert_eq!(buffer.text(), "cargo");
+        assert!(buffer.undo());
+        assert_eq!(buffer.text(), "");
+        assert!(!buffer.undo());
+
+        assert!(buffer.redo());
+        assert!(buffer.redo());
+        assert_eq!((buffer.text(), buffer.cursor()), ("cargo build", 11));
+        buffer.insert_char('!');
+        assert!(!buffer.redo());
+
+        // Moving the cursor ends a run of typing.
+        let mut buffer = typed("ls");
+        buffer.move_cursor(Motion::Left, false);
+        buffer.insert_char('s');
+        buffer.undo();
+        assert_eq!(buffer.text(), "ls");
+    }
+
+    #[test]
+    fn test_values_from_a_text_input_become_small_edits() {
+        let mut buffer = EditorBuffer::new();
+        for value in ["g", "gi", "git", "git ", "git s", "git st", "git s"] {
+            buffer.apply_value(value);
+        }
+        assert_eq!(buffer.text(), "git s");
+        buffer.apply_value("git status");
+        buffer.apply_value("echo hi");
+        assert_eq!(buffer.text(), "echo hi");
+        buffer.undo();
+        assert_eq!(buffer.text(), "git status");
+        buffer.undo();
+        buffer.undo();
+        assert_eq!(buffer.text(), "git st");
+        buffer.undo();
+        assert_eq!(buffer.text(), "git");
+    }
+
+    #[test]
+    fn test_clipboard() {
+        let mut clipboard = TestClipboard::default();
+        let mut buffer = EditorBuffer::with_text("echo one two");
+        buffer.move_cursor(Motion::WordLeft, true);
+        buffer.cut(&mut clipboard);
+        assert_eq!((buffer.text(), clipboard.0.as_deref()), ("echo one ", Some("two")));
+        buffer.move_cursor(Motion::BufferStart, false);
+        buffer.paste(&mut clipboard);
+        assert_eq!(buffer.text(), "twoecho one ");
+        buffer.undo();
+        assert_eq!(buffer.text(), "echo one ");
+    }
+
+    #[test]
+    fn test_unfinished_commands_continue() {
+        assert!(is_complete("echo hi"));
+        assert!(!is_complete("make \\"));
+        assert!(is_complete("make \\\n  install"));
+        assert!(!is_complete("echo 'it"));
+        assert!(!is_complete("echo \"a \\\" b"));
+        assert!(is_complete("echo \"a \\\" b\""));
+        assert!(is_complete("echo 'a\\'"));
+        assert!(!is_complete("echo `date"));
+        assert!(is_complete("ls # don't"));
+        assert!(!is_complete("ls # fine\necho 'x"));
+        assert!(is_complete("echo a#'b'"));
+        assert!(!is_complete("echo a#'b"));
+    }
+}
@@ -0,0 +1,45 @@
+// Clipboard access for the editor. The buffer only needs `Clipboard`, so it can be tested
+// without a display; `SystemClipboard` is the real one, through `arboard`.
+
+use log::warn;
+
+pub trait Clipboard {
+    fn read(&mut self) -> Option<String>;
+    fn write(&mut self, text: &str);
+}
+
+/// Connects on first use. Without a display, e.g. over SSH, copy and paste do nothing.
+#[derive(Default)]
+pub struct SystemClipboard {
+    inner: Option<arboard::Clipboard>,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn connect(&mut self) -> Option<&mut arboard::Clipboard> {
+        if self.inner.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(clipboard) => self.inner = Some(clipboard),
+                Err(e) => warn!("Clipboard unavailable: {}", e),
+            }
+        }
+        self.inner.as_mut()
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn read(&mut self) -> Option<String> {
+        self.connect()?.get_text().map_err(|e| warn!("Failed to read the clipboard: {}", e)).ok()
+    }
+
+    fn write(&mut self, text: &str) {
+        if let Some(clipboard) = self.connect() {
+            if let Err(e) = clipboard.set_text(text) {
+                warn!("Failed to write the clipboard: {}", e);
+            }
+        }
+    }
+}
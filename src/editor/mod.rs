@@ -1,3 +1,10 @@
+pub mod buffer; // Multi-line text, cursor, selection and undo for the prompt
+pub mod clipboard; // System clipboard access
+
+// Re-export key structs for easier access
+pub use buffer::{EditorBuffer, Motion};
+pub use clipboard::{Clipboard, SystemClipboard};
+
 use iced::{
     widget::{text_input, container},
     Element, Length, Command, Theme, Color,
@@ -21,8 +28,7 @@ pub enum EditorMessage {
 }
 
 pub struct Editor {
-    value: String,
-    cursor_position: usize,
+    buffer: EditorBuffer,
     font_size: u16,
     font_family: String,
 }
@@ -30,20 +36,18 @@ pub struct Editor {
 impl Editor {
     pub fn new() -> Self {
         Self {
-            value: String::new(),
-            cursor_position: 0,
+            buffer: EditorBuffer::new(),
             font_size: 16,
             font_family: "Fira Code".to_string(), // Default font
         }
     }
 
     pub fn get_value(&self) -> String {
-        self.value.clone()
+        self.buffer.text().to_string()
     }
 
     pub fn set_value(&mut self, new_value: String) {
-        self.value = new_value;
-        self.cursor_position = self.value.len();
+        self.buffer.set_text(new_value);
     }
 
     pub fn set_font_size(&mut self, size: u16) {
@@ -57,39 +61,17 @@ impl Editor {
     pub fn update(&mut self, message: EditorMessage) -> Command<EditorMessage> {
         match message {
             EditorMessage::InputChanged(new_value) => {
-                self.value = new_value;
-                self.cursor_position = self.value.len();
+                self.buffer.apply_value(&new_value);
             }
             EditorMessage::Submit => {
                 // Handled by the parent (Terminal)
             }
-            EditorMessage::MoveCursorLeft => {
-                if self.cursor_position > 0 {
-                    self.cursor_position -= 1;
-                }
-            }
-            EditorMessage::MoveCursorRight => {
-                if self.cursor_position < self.value.len() {
-                    self.cursor_position += 1;
-                }
-            }
-            EditorMessage::MoveCursorHome => {
-                self.cursor_position = 0;
-            }
-            EditorMessage::MoveCursorEnd => {
-                self.cursor_position = self.value.len();
-            }
-            EditorMessage::DeletePreviousChar => {
-                if self.cursor_position > 0 {
-                    self.value.remove(self.cursor_position - 1);
-                    self.cursor_position -= 1;
-                }
-            }
-            EditorMessage::DeleteNextChar => {
-                if self.cursor_position < self.value.len() {
-                    self.value.remove(self.cursor_position);
-                }
-            }
+            EditorMessage::MoveCursorLeft => self.buffer.move_cursor(Motion::Left, false),
+            EditorMessage::MoveCursorRight => self.buffer.move_cursor(Motion::Right, false),
+            EditorMessage::MoveCursorHome => self.buffer.move_cursor(Motion::LineStart, false),
+            EditorMessage::MoveCursorEnd => self.buffer.move_cursor(Motion::LineEnd, false),
+            EditorMessage::DeletePreviousChar => self.buffer.delete_backward(),
+            EditorMessage::DeleteNextChar => self.buffer.delete_forward(),
         }
         Command::none()
     }
@@ -97,7 +79,7 @@ impl Editor {
     pub fn view<'a>(&'a self, theme: &WarpTheme, prompt_renderer: &'a PromptRenderer) -> Element<'a, EditorMessage> {
         let prompt_text = prompt_renderer.render_prompt_text();
 
-        let input = text_input("", &self.value)
+        let input = text_input("", self.buffer.text())
             .on_input(EditorMessage::InputChanged)
             .on_submit(EditorMessage::Submit)
             .padding(8)
@@ -22,7 +22,7 @@ impl FuzzyMatcher {
 
     /// Performs a fuzzy match and returns the matched indices if a match is found.
     pub fn fuzzy_match_indices(&self, text: &str, query: &str) -> Option<(i64, Vec<usize>)> {
-        self.matcher.fuzzy_match_indices(text, query)
+        self.matcher.fuzzy_indices(text, query)
     }
 
     /// Filters a list of candidates based on a fuzzy query.
@@ -0,0 +1,414 @@
+// Importing the history of the shell used before this terminal, so history search has
+// something in it from day one. Each shell has its own file format:
+//
+// - bash: one command per line. With `HISTTIMEFORMAT` set, each command is preceded by a
+//   `#<epoch>` line and runs until the next one, so it may span several lines.
+// - zsh: `: <epoch>:<seconds taken>;<command>` with `EXTENDED_HISTORY`, plain lines without.
+//   A line ending in a backslash continues on the next, and some bytes are "metafied".
+// - fish: a YAML-like list of `- cmd:` items with a `when:` epoch and `paths:`, where the
+//   command's newlines and backslashes are escaped.
+//
+// Importing the same file again adds only the commands the store doesn't have yet.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+use super::store::{HistoryEntry, HistoryStore};
+
+/// zsh writes a byte it uses internally as this byte followed by the byte XOR 0x20.
+const ZSH_META: u8 = 0x83;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellHistory {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl ShellHistory {
+    pub const ALL: [ShellHistory; 3] = [ShellHistory::Bash, ShellHistory::Zsh, ShellHistory::Fish];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|shell| shell.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShellHistory::Bash => "bash",
+            ShellHistory::Zsh => "zsh",
+            ShellHistory::Fish => "fish",
+        }
+    }
+
+    /// Where the shell keeps its history unless configured otherwise.
+    pub fn default_path(self) -> Option<PathBuf> {
+        match self {
+            ShellHistory::Bash => dirs::home_dir().map(|home| home.join(".bash_history")),
+            ShellHistory::Zsh => dirs::home_dir().map(|home| home.join(".zsh_history")),
+            // fish uses the XDG location on every platform, macOS included.
+            ShellHistory::Fish => std::env::var_os("XDG_DATA_HOME")
+                .map(PathBuf::from)
+                .or_else(|| dirs::home_dir().map(|home| home.join(".local").join("share")))
+                .map(|data| data.join("fish").join("fish_history")),
+        }
+    }
+
+    pub fn parse(self, content: &[u8]) -> Vec<ImportedCommand> {
+        match self {
+            ShellHistory::Bash => parse_bash(content),
+            ShellHistory::Zsh => parse_zsh(content),
+            ShellHistory::Fish => parse_fish(content),
+        }
+    }
+}
+
+impl fmt::Display for ShellHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A command as read from a history file, in file order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedCommand {
+    pub command: String,
+    /// If the file records when it ran.
+    pub started_at: Option<DateTime<Utc>>,
+    pub duration_ms: Option<u64>,
+}
+
+impl ImportedCommand {
+    fn new(command: impl Into<String>, started_at: Option<DateTime<Utc>>) -> Self {
+        ImportedCommand { command: command.into(), started_at, duration_ms: None }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Commands in the file.
+    pub read: usize,
+    /// Commands added to the store; the rest were already there, repeats or dropped.
+    pub imported: usize,
+    /// New commands older than the newest `HistoryStore::max_entries`, which the store
+    /// doesn't keep.
+    pub dropped: usize,
+}
+
+/// Imports `shell`'s history file at `path` into `store`.
+pub fn import_file(store: &mut HistoryStore, shell: ShellHistory, path: &Path) -> Result<ImportSummary> {
+    let content = fs::read(path).with_context(|| format!("reading {} history from {}", shell, path.display()))?;
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now());
+    let commands = shell.parse(&content);
+    let entries = new_entries(&commands, modified, store.entries());
+    let new = entries.len();
+    let imported = if new > 0 { store.merge(entries)? } else { 0 };
+    Ok(ImportSummary { read: commands.len(), imported, dropped: new - imported })
+}
+
+/// The entries for `commands` that aren't in `existing` yet. A command with a timestamp is
+/// new unless the same command was recorded at the same time. One without is new unless the
+/// command is known at all, and only its last occurrence is kept; these are placed a second
+/// apart, in file order, before the file's earliest timestamp and its modification time.
+fn new_entries(commands: &[ImportedCommand], modified: DateTime<Utc>, existing: &[HistoryEntry]) -> Vec<HistoryEntry> {
+    let anchor = commands.iter().filter_map(|imported| imported.started_at).min().map_or(modified, |earliest| earliest.min(modified));
+    let untimed = commands.iter().filter(|imported| imported.started_at.is_none()).count();
+    let last_untimed: HashMap<&str, usize> = commands
+        .iter()
+        .enumerate()
+        .filter(|(_, imported)| imported.started_at.is_none())
+        .map(|(i, imported)| (imported.command.as_str(), i))
+        .collect();
+    let known: HashSet<&str> = existing.iter().map(|entry| entry.command.as_str()).collect();
+    let mut seen: HashSet<(&str, DateTime<Utc>)> = existing.iter().map(|entry| (entry.command.as_str(), entry.started_at)).collect();
+
+    let mut untimed_before = 0;
+    let mut entries = Vec::new();
+    for (i, imported) in commands.iter().enumerate() {
+        let started_at = match imported.started_at {
+            Some(started_at) => started_at,
+            None => {
+                let offset = untimed - untimed_before;
+                untimed_before += 1;
+                if known.contains(imported.command.as_str()) || last_untimed[imported.command.as_str()] != i {
+                    continue;
+                }
+                anchor - chrono::Duration::seconds(offset as i64)
+            }
+        };
+        if seen.insert((imported.command.as_str(), started_at)) {
+            entries.push(HistoryEntry { duration_ms: imported.duration_ms, ..HistoryEntry::new(imported.command.clone(), started_at) });
+        }
+    }
+    entries
+}
+
+fn epoch(text: &str) -> Option<DateTime<Utc>> {
+    if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    DateTime::from_timestamp(text.parse().ok()?, 0)
+}
+
+/// Drops blank commands and the blank lines bash leaves after a multi-line one. Returns
+/// whether the command was kept.
+fn push_command(commands: &mut Vec<ImportedCommand>, mut imported: ImportedCommand) -> bool {
+    imported.command.truncate(imported.command.trim_end_matches(['\n', '\r']).len());
+    let keep = !imported.command.trim().is_empty();
+    if keep {
+        commands.push(imported);
+    }
+    keep
+}
+
+pub fn parse_bash(content: &[u8]) -> Vec<ImportedCommand> {
+    let text = String::from_utf8_lossy(content);
+    let mut commands = Vec::new();
+    let mut current: Option<ImportedCommand> = None;
+    let mut timestamp = None;
+    for line in text.lines() {
+        if let Some(started_at) = line.strip_prefix('#').and_then(epoch) {
+            if let Some(done) = current.take() {
+                push_command(&mut commands, done);
+            }
+            timestamp = Some(started_at);
+            continue;
+        }
+        match &mut current {
+            // Without timestamps there is no telling where a command ends, so every line is one.
+            Some(command) if timestamp.is_some() => {
+                command.command.push('\n');
+                command.command.push_str(line);
+            }
+            _ if line.trim().is_empty() => {}
+            _ => {
+                if let Some(done) = current.replace(ImportedCommand::new(line, timestamp)) {
+                    push_command(&mut commands, done);
+                }
+            }
+        }
+    }
+    if let Some(done) = current {
+        push_command(&mut commands, done);
+    }
+    commands
+}
+
+pub fn parse_zsh(content: &[u8]) -> Vec<ImportedCommand> {
+    let text = String::from_utf8_lossy(&unmetafy(content)).into_owned();
+    let mut commands = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let mut imported = match zsh_extended_line(line) {
+            Some((started_at, seconds, command)) => {
+                ImportedCommand { duration_ms: Some(seconds * 1000), ..ImportedCommand::new(command, Some(started_at)) }
+            }
+            None => ImportedCommand::new(line, None),
+        };
+        while imported.command.ends_with('\\') {
+            let Some(next) = lines.next() else { break };
+            imported.command.pop();
+            imported.command.push('\n');
+            imported.command.push_str(next);
+        }
+        push_command(&mut commands, imported);
+    }
+    commands
+}
+
+/// `: <epoch>:<seconds>;<command>`
+fn zsh_extended_line(line: &str) -> Option<(DateTime<Utc>, u64, &str)> {
+    let (header, command) = line.strip_prefix(": ")?.split_once(';')?;
+    let (started_at, seconds) = header.split_once(':')?;
+    let seconds = if seconds.bytes().all(|b| b.is_ascii_digit()) { seconds.parse().ok()? } else { return None };
+    Some((epoch(started_at)?, seconds, command))
+}
+
+fn unmetafy(content: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(content.len());
+    let mut content = content.iter();
+    while let Some(&byte) = content.next() {
+        if byte == ZSH_META {
+            bytes.extend(content.next().map(|next| next ^ 0x20));
+        } else {
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+pub fn parse_fish(content: &[u8]) -> Vec<ImportedCommand> {
+    let text = String::from_utf8_lossy(content);
+    let mut commands: Vec<ImportedCommand> = Vec::new();
+    // Whether the current item's command was kept, so its `when:` belongs to the last one.
+    let mut kept = false;
+    for line in text.lines() {
+        if let Some(command) = line.strip_prefix("- cmd: ") {
+            kept = push_command(&mut commands, ImportedCommand::new(unescape_fish(command), None));
+        } else if let Some(when) = line.strip_prefix("  when: ") {
+            if let Some(last) = commands.last_mut().filter(|_| kept) {
+                last.started_at = epoch(when.trim());
+            }
+        }
+        // `paths:` and its items are of no use here.
+    }
+    commands
+}
+
+/// fish escapes a command's newlines as `\n` and its backslashes as `\\`.
+fn unescape_fish(escaped: &str) -> String {
+    let mut command = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            command.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => command.push('\n'),
+            Some('\\') => command.push('\\'),
+            Some(other) => {
+                command.push('\\');
+                command.push(other);
+            }
+            None => command.push('\\'),
+        }
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const BASH: &[u8] = include_bytes!("fixtures/bash_history");
+    const ZSH: &[u8] = include_bytes!("fixtures/zsh_history");
+    const FISH: &[u8] = include_bytes!("fixtures/fish_history");
+
+    fn at(epoch: i64) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(epoch, 0)
+    }
+
+    fn parsed(commands: &[ImportedCommand]) -> Vec<(&str, Option<i64>)> {
+        commands.iter().map(|imported| (imported.command.as_str(), imported.started_at.map(|t| t.timestamp()))).collect()
+    }
+
+    #[test]
+    fn test_bash_timestamps_and_multi_line_commands() {
+        assert_eq!(
+            parsed(&parse_bash(BASH)),
+            [
+                ("ls -la", None),
+                ("cd /tmp", None),
+                ("ls -la", None),
+                ("git status", Some(1700000000)),
+                ("echo \"first line\nsecond line\"", Some(1700000060)),
+                ("# a comment typed at the prompt", Some(1700000120)),
+                ("cat caf\u{FFFD}.txt", Some(1700000240)),
+                ("git status", Some(1700000300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zsh_extended_history_continuations_and_metafied_bytes() {
+        let commands = parse_zsh(ZSH);
+        assert_eq!(
+            parsed(&commands),
+            [
+                ("git status", Some(1700000000)),
+                ("cargo build --release", Some(1700000005)),
+                ("for f in *.txt; do\n  echo $f\ndone", Some(1700000030)),
+                ("echo from before extended history", None),
+                ("echo —", Some(1700000040)),
+                ("git status", Some(1700000050)),
+                ("git status", Some(1700000050)),
+                (": no-op, not a header", None),
+            ]
+        );
+        assert_eq!(commands[1].duration_ms, Some(12_000));
+    }
+
+    #[test]
+    fn test_fish_items_and_escapes() {
+        assert_eq!(
+            parsed(&parse_fish(FISH)),
+            [
+                ("git status", Some(1700000000)),
+                ("cd ~/src/app", Some(1700000010)),
+                ("begin\n  echo hi\nend", Some(1700000020)),
+                ("printf 'a\\nb'", Some(1700000030)),
+                ("echo key: value", Some(1700000040)),
+                ("history with no timestamp", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_imports_are_deduplicated_and_repeatable() {
+        let dir = tempdir().unwrap();
+        let (bash, zsh) = (dir.path().join("bash_history"), dir.path().join("zsh_history"));
+        fs::write(&bash, BASH).unwrap();
+        fs::write(&zsh, ZSH).unwrap();
+        let mut store = HistoryStore::open(&dir.path().join("history.jsonl"), 100).unwrap();
+        store.append(HistoryEntry::new("cd /tmp", at(1600000000).unwrap())).unwrap();
+
+        let summary = import_file(&mut store, ShellHistory::Bash, &bash).unwrap();
+        assert_eq!(summary, ImportSummary { read: 8, imported: 6, dropped: 0 });
+        let summary = import_file(&mut store, ShellHistory::Zsh, &zsh).unwrap();
+        // `git status` at 1700000000 came with bash, and the repeat at 1700000050 is dropped.
+        assert_eq!(summary, ImportSummary { read: 8, imported: 6, dropped: 0 });
+        assert_eq!(import_file(&mut store, ShellHistory::Bash, &bash).unwrap().imported, 0);
+        assert_eq!(import_file(&mut store, ShellHistory::Zsh, &zsh).unwrap().imported, 0);
+
+        let store = HistoryStore::open(store.path(), 100).unwrap();
+        let commands: Vec<&str> = store.entries().iter().map(|entry| entry.command.as_str()).collect();
+        assert_eq!(
+            commands,
+            [
+                "cd /tmp",
+                "echo from before extended history",
+                "ls -la",
+                ": no-op, not a header",
+                "git status",
+                "cargo build --release",
+                "for f in *.txt; do\n  echo $f\ndone",
+                "echo —",
+                "git status",
+                "echo \"first line\nsecond line\"",
+                "# a comment typed at the prompt",
+                "cat caf\u{FFFD}.txt",
+                "git status",
+            ]
+        );
+        assert_eq!(store.entries()[2].started_at, at(1700000000 - 1).unwrap());
+        assert_eq!(store.entries()[5].duration_ms, Some(12_000));
+    }
+
+    #[test]
+    fn test_imports_beyond_the_limit_are_counted_as_dropped() {
+        let dir = tempdir().unwrap();
+        let bash = dir.path().join("bash_history");
+        let content: String = (0..12).map(|i| format!("#{}\necho {}\n", 1700000000 + i, i)).collect();
+        fs::write(&bash, content).unwrap();
+        let mut store = HistoryStore::open(&dir.path().join("history.jsonl"), 8).unwrap();
+        store.append(HistoryEntry::new("make", at(1800000000).unwrap())).unwrap();
+
+        let summary = import_file(&mut store, ShellHistory::Bash, &bash).unwrap();
+        assert_eq!(summary, ImportSummary { read: 12, imported: 7, dropped: 5 });
+        let store = HistoryStore::open(store.path(), 8).unwrap();
+        let commands: Vec<&str> = store.entries().iter().map(|entry| entry.command.as_str()).collect();
+        assert_eq!(commands, ["echo 5", "echo 6", "echo 7", "echo 8", "echo 9", "echo 10", "echo 11", "make"]);
+    }
+
+    #[test]
+    fn test_shell_names() {
+        assert_eq!(ShellHistory::from_name("Zsh"), Some(ShellHistory::Zsh));
+        assert_eq!(ShellHistory::from_name("pwsh"), None);
+    }
+}
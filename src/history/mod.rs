@@ -0,0 +1,11 @@
+pub mod import; // Importing bash, zsh and fish history files
+pub mod search; // Fuzzy, frecency-ranked history search
+pub mod store; // Append-only command history file with queries
+pub mod suggest; // Autosuggestions for the editor's input
+pub mod ui; // The Ctrl-R history search panel
+
+// Re-export key structs for easier access
+pub use import::{ImportSummary, ShellHistory};
+pub use search::SearchMatch;
+pub use store::{ExitFilter, HistoryEntry, HistoryQuery, HistoryStore, Session};
+pub use ui::{HistorySearchMessage, HistorySearchPanel};
@@ -0,0 +1,174 @@
+// Ranking for the Ctrl-R history search. Each distinct command in the history is fuzzy
+// matched against the query with `FuzzyMatcher`, then ranked by its match score plus a boost
+// for its frecency: how often and how recently it ran. A command run every day outranks one
+// that matches a little better but ran once months ago.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::store::HistoryEntry;
+use crate::fuzzy_match::FuzzyMatcher;
+
+/// Frecency counts logarithmically, scaled so that a command run a few times today is worth
+/// about as much as two well-placed matched characters.
+const FRECENCY_WEIGHT: f64 = 8.0;
+
+/// What one run adds to a command's frecency, by how long ago it was.
+pub(crate) fn recency_weight(age: Duration) -> f64 {
+    match age.num_hours() {
+        hours if hours < 4 => 100.0,
+        hours if hours < 24 => 70.0,
+        hours if hours < 24 * 7 => 50.0,
+        hours if hours < 24 * 30 => 30.0,
+        _ => 10.0,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    pub command: String,
+    /// Character (not byte) indices of the matched characters, in order.
+    pub indices: Vec<usize>,
+    pub score: f64,
+    pub runs: usize,
+    pub last_run: DateTime<Utc>,
+    pub last_exit_code: Option<i32>,
+}
+
+impl SearchMatch {
+    /// The command split into runs of matched and unmatched text, for highlighting.
+    pub fn segments(&self) -> Vec<(String, bool)> {
+        let mut segments: Vec<(String, bool)> = Vec::new();
+        for (i, c) in self.command.chars().enumerate() {
+            let matched = self.indices.binary_search(&i).is_ok();
+            match segments.last_mut() {
+                Some((text, was_matched)) if *was_matched == matched => text.push(c),
+                _ => segments.push((c.to_string(), matched)),
+            }
+        }
+        segments
+    }
+}
+
+/// Searches `entries`, newest first as `HistoryStore::query` returns them. Best first; equal
+/// scores go to the command that ran last. An empty query matches everything, so the list
+/// is ranked by frecency alone.
+pub fn search(matcher: &FuzzyMatcher, entries: &[&HistoryEntry], query: &str, now: DateTime<Utc>) -> Vec<SearchMatch> {
+    // Each command once, with its newest run and its frecency.
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    let mut candidates: Vec<(SearchMatch, f64)> = Vec::new();
+    for entry in entries {
+        let weight = recency_weight(now - entry.started_at);
+        match positions.get(entry.command.as_str()) {
+            Some(&i) => {
+                candidates[i].0.runs += 1;
+                candidates[i].1 += weight;
+            }
+            None => {
+                positions.insert(entry.command.as_str(), candidates.len());
+                let candidate = SearchMatch {
+                    command: entry.command.clone(),
+                    indices: Vec::new(),
+                    score: 0.0,
+                    runs: 1,
+                    last_run: entry.started_at,
+                    last_exit_code: entry.exit_code,
+                };
+                candidates.push((candidate, weight));
+            }
+        }
+    }
+
+    let mut matches: Vec<SearchMatch> = candidates
+        .into_iter()
+        .filter_map(|(mut candidate, frecency)| {
+            let (score, indices) = if query.is_empty() { (0, Vec::new()) } else { matcher.fuzzy_match_indices(&candidate.command, query)? };
+            candidate.score = score as f64 + FRECENCY_WEIGHT * frecency.ln_1p();
+            candidate.indices = indices;
+            Some(candidate)
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| b.last_run.cmp(&a.last_run)));
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::store::{HistoryQuery, HistoryStore, Session};
+    use crate::history::ExitFilter;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn entry(command: &str, hours_ago: i64) -> HistoryEntry {
+        HistoryEntry::new(command, now() - Duration::hours(hours_ago))
+    }
+
+    fn ranked(entries: &[HistoryEntry], query: &str) -> Vec<SearchMatch> {
+        let newest_first: Vec<&HistoryEntry> = entries.iter().rev().collect();
+        search(&FuzzyMatcher::new(), &newest_first, query, now())
+    }
+
+    fn commands(matches: &[SearchMatch]) -> Vec<&str> {
+        matches.iter().map(|found| found.command.as_str()).collect()
+    }
+
+    #[test]
+    fn test_frequent_recent_commands_rank_first() {
+        let entries = [
+            entry("git pull --rebase", 24 * 90),
+            entry("git push", 30),
+            entry("git push", 5),
+            entry("git push", 1),
+            entry("make", 0),
+        ];
+        let matches = ranked(&entries, "gitpu");
+        assert_eq!(commands(&matches), ["git push", "git pull --rebase"]);
+        assert_eq!((matches[0].runs, matches[0].last_run), (3, now() - Duration::hours(1)));
+
+        // Without a query, frecency alone decides.
+        assert_eq!(commands(&ranked(&entries, "")), ["git push", "make", "git pull --rebase"]);
+    }
+
+    #[test]
+    fn test_matched_characters_are_highlighted() {
+        let matches = ranked(&[entry("cargo build", 0), entry("ls", 0)], "cb");
+        assert_eq!(commands(&matches), ["cargo build"]);
+        assert_eq!(matches[0].indices, [0, 6]);
+        let segments = matches[0].segments();
+        let segments: Vec<(&str, bool)> = segments.iter().map(|(text, matched)| (text.as_str(), *matched)).collect();
+        assert_eq!(segments, [("c", true), ("argo ", false), ("b", true), ("uild", false)]);
+
+        let multibyte = ranked(&[entry("echo é → ok", 0)], "ok");
+        assert_eq!(multibyte[0].indices, [9, 10]);
+    }
+
+    #[test]
+    fn test_searching_within_a_directory_and_successful_commands() {
+        let dir = tempdir().unwrap();
+        let session = Session::new(None);
+        let mut store = HistoryStore::open(&dir.path().join("history.jsonl"), 100).unwrap();
+        let (app, docs) = (Path::new("/src/app"), Path::new("/src/docs"));
+        for (hours_ago, command, cwd, exit_code) in
+            [(3, "cargo test", app, 101), (2, "cargo test", app, 0), (1, "cargo doc", docs, 0), (0, "cargo fmt", app, 1)]
+        {
+            let started_at = now() - Duration::hours(hours_ago);
+            store.append(session.entry(command, Some(cwd), started_at, exit_code, std::time::Duration::ZERO)).unwrap();
+        }
+
+        let matcher = FuzzyMatcher::new();
+        let query = HistoryQuery::new().in_dir(app).exit(ExitFilter::Succeeded);
+        let matches = search(&matcher, &store.query(&query), "cargo", now());
+        assert_eq!(commands(&matches), ["cargo test"]);
+        assert_eq!((matches[0].runs, matches[0].last_exit_code), (1, Some(0)));
+
+        let matches = search(&matcher, &store.query(&HistoryQuery::new().in_dir(app)), "cargo", now());
+        assert_eq!(commands(&matches), ["cargo test", "cargo fmt"]);
+        assert_eq!(matches[1].last_exit_code, Some(1));
+    }
+}
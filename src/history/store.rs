@@ -0,0 +1,398 @@
+// The persistent command history. Every command run in a session is appended, with where,
+// when and how it ran, as one JSON line to a local `JsonlFile`. The file may grow past
+// `max_entries` by a little before it is compacted to the newest `max_entries`, so
+// compaction stays occasional.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::jsonl::JsonlFile;
+
+/// Matches `UserPreferences::max_history_size`'s default.
+pub const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub command: String,
+    pub started_at: DateTime<Utc>,
+    /// The rest is unknown for commands from shells without integration or from imports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(command: impl Into<String>, started_at: DateTime<Utc>) -> Self {
+        HistoryEntry {
+            command: command.into(),
+            started_at,
+            cwd: None,
+            exit_code: None,
+            duration_ms: None,
+            session_id: None,
+            profile: None,
+            hostname: None,
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// What every command run in one terminal session shares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub id: Uuid,
+    pub profile: Option<String>,
+    pub hostname: Option<String>,
+}
+
+impl Session {
+    pub fn new(profile: Option<String>) -> Self {
+        Session { id: Uuid::new_v4(), profile, hostname: hostname() }
+    }
+
+    /// An entry for a command that finished in this session.
+    pub fn entry(&self, command: &str, cwd: Option<&Path>, started_at: DateTime<Utc>, exit_code: i32, duration: Duration) -> HistoryEntry {
+        HistoryEntry {
+            cwd: cwd.map(Path::to_path_buf),
+            exit_code: Some(exit_code),
+            duration_ms: Some(duration.as_millis() as u64),
+            session_id: Some(self.id),
+            profile: self.profile.clone(),
+            hostname: self.hostname.clone(),
+            ..HistoryEntry::new(command, started_at)
+        }
+    }
+}
+
+fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitFilter {
+    Succeeded,
+    /// Any non-zero exit code.
+    Failed,
+    Code(i32),
+}
+
+/// Which entries `HistoryStore::query` returns. Every filter that is set must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryQuery {
+    pub prefix: Option<String>,
+    pub substring: Option<String>,
+    pub cwd: Option<PathBuf>,
+    pub exit: Option<ExitFilter>,
+    /// Only the newest entry for each command.
+    pub unique: bool,
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn containing(mut self, substring: impl Into<String>) -> Self {
+        self.substring = Some(substring.into());
+        self
+    }
+
+    pub fn in_dir(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    pub fn exit(mut self, exit: ExitFilter) -> Self {
+        self.exit = Some(exit);
+        self
+    }
+
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        self.prefix.as_ref().is_none_or(|prefix| entry.command.starts_with(prefix.as_str()))
+            && self.substring.as_ref().is_none_or(|substring| entry.command.contains(substring.as_str()))
+            && self.cwd.as_ref().is_none_or(|cwd| entry.cwd.as_ref() == Some(cwd))
+            && self.exit.is_none_or(|exit| match (exit, entry.exit_code) {
+                (ExitFilter::Succeeded, Some(code)) => code == 0,
+                (ExitFilter::Failed, Some(code)) => code != 0,
+                (ExitFilter::Code(wanted), Some(code)) => code == wanted,
+                (_, None) => false,
+            })
+    }
+}
+
+pub struct HistoryStore {
+    file: JsonlFile,
+    max_entries: usize,
+    /// Oldest first; at most `max_entries`.
+    entries: Vec<HistoryEntry>,
+    /// Entries in the file, which may exceed `max_entries` until the next compaction.
+    lines_in_file: usize,
+}
+
+impl HistoryStore {
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir().unwrap_or_else(|| PathBuf::from(".")).join("warp-terminal-clone").join("history.jsonl")
+    }
+
+    /// A history with no entries yet that will save to `path`.
+    pub fn empty(path: &Path, max_entries: usize) -> Self {
+        HistoryStore { file: JsonlFile::new(path), max_entries, entries: Vec::new(), lines_in_file: 0 }
+    }
+
+    /// Loads the history at `path`, starting empty if there is none. A torn last line is cut
+    /// off the file; other unreadable lines are skipped and compacted away.
+    pub fn open(path: &Path, max_entries: usize) -> Result<Self> {
+        let mut store = HistoryStore::empty(path, max_entries);
+        let (entries, skipped) = store.file.read()?;
+        store.entries = entries;
+        store.lines_in_file = store.entries.len();
+
+        if skipped > 0 || store.lines_in_file > store.compaction_threshold() {
+            store.compact()?;
+        } else {
+            store.trim();
+        }
+        Ok(store)
+    }
+
+    fn compaction_threshold(&self) -> usize {
+        self.max_entries + self.max_entries / 4
+    }
+
+    fn trim(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.max_entries);
+        self.entries.drain(..excess);
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    /// Applies a new limit, compacting the file if it is now over it.
+    pub fn set_max_entries(&mut self, max_entries: usize) -> Result<()> {
+        self.max_entries = max_entries;
+        if self.lines_in_file > self.compaction_threshold() {
+            self.compact()
+        } else {
+            self.trim();
+            Ok(())
+        }
+    }
+
+    /// Appends an entry to the history and its file.
+    pub fn append(&mut self, entry: HistoryEntry) -> Result<()> {
+        self.file.append(&entry)?;
+        self.entries.push(entry);
+        self.lines_in_file += 1;
+
+        if self.lines_in_file > self.compaction_threshold() {
+            self.compact()
+        } else {
+            self.trim();
+            Ok(())
+        }
+    }
+
+    /// Adds entries from elsewhere, e.g. another shell's history, in time order among the
+    /// existing ones, and rewrites the file. Returns how many of them were kept: over
+    /// `max_entries`, the oldest go, whether they were just merged or not.
+    pub fn merge(&mut self, entries: Vec<HistoryEntry>) -> Result<usize> {
+        let mut merged: Vec<(HistoryEntry, bool)> =
+            self.entries.drain(..).map(|entry| (entry, false)).chain(entries.into_iter().map(|entry| (entry, true))).collect();
+        merged.sort_by_key(|(entry, _)| entry.started_at);
+        let excess = merged.len().saturating_sub(self.max_entries);
+        let kept = merged[excess..].iter().filter(|(_, is_new)| *is_new).count();
+        self.entries = merged.into_iter().map(|(entry, _)| entry).collect();
+        self.compact()?;
+        Ok(kept)
+    }
+
+    /// Rewrites the file with only the newest `max_entries` entries.
+    pub fn compact(&mut self) -> Result<()> {
+        self.trim();
+        self.file.rewrite(&self.entries)?;
+        self.lines_in_file = self.entries.len();
+        Ok(())
+    }
+
+    /// Oldest first.
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Matching entries, newest first.
+    pub fn query(&self, query: &HistoryQuery) -> Vec<&HistoryEntry> {
+        let mut seen = std::collections::HashSet::new();
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| query.matches(entry))
+            .filter(|entry| !query.unique || seen.insert(entry.command.as_str()))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2024-03-01T10:{:02}:00Z", minute)).unwrap().with_timezone(&Utc)
+    }
+
+    fn commands(entries: &[&HistoryEntry]) -> Vec<String> {
+        entries.iter().map(|entry| entry.command.clone()).collect()
+    }
+
+    #[test]
+    fn test_entries_survive_reopening_with_their_metadata() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let session = Session { id: Uuid::new_v4(), profile: Some("work".to_string()), hostname: Some("devbox".to_string()) };
+
+        let mut store = HistoryStore::open(&path, 100).unwrap();
+        store.append(session.entry("cargo test", Some(Path::new("/src/app")), at(1), 101, Duration::from_millis(2500))).unwrap();
+        store.append(HistoryEntry::new("ls", at(2))).unwrap();
+
+        let store = HistoryStore::open(&path, 100).unwrap();
+        assert_eq!(store.len(), 2);
+        let entry = &store.entries()[0];
+        assert_eq!(entry.cwd.as_deref(), Some(Path::new("/src/app")));
+        assert_eq!((entry.exit_code, entry.duration_ms), (Some(101), Some(2500)));
+        assert_eq!((entry.session_id, entry.profile.as_deref(), entry.hostname.as_deref()), (Some(session.id), Some("work"), Some("devbox")));
+        assert_eq!(store.entries()[1], HistoryEntry::new("ls", at(2)));
+    }
+
+    #[test]
+    fn test_torn_writes_are_cut_off() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let mut store = HistoryStore::open(&path, 100).unwrap();
+        store.append(HistoryEntry::new("make", at(1))).unwrap();
+        store.append(HistoryEntry::new("make install", at(2))).unwrap();
+        let intact = fs::read(&path).unwrap();
+        let mut torn = intact.clone();
+        torn.extend_from_slice(br#"{"command":"rm -rf bu"#);
+        fs::write(&path, torn).unwrap();
+
+        let mut store = HistoryStore::open(&path, 100).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), intact);
+        store.append(HistoryEntry::new("make clean", at(3))).unwrap();
+        let store = HistoryStore::open(&path, 100).unwrap();
+        assert_eq!(commands(&store.query(&HistoryQuery::new())), ["make clean", "make install", "make"]);
+    }
+
+    #[test]
+    fn test_corrupt_lines_are_skipped_and_compacted_away() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let good = serde_json::to_string(&HistoryEntry::new("whoami", at(1))).unwrap();
+        fs::write(&path, format!("not json\n{}\n\n", good)).unwrap();
+
+        let store = HistoryStore::open(&path, 100).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\n", good));
+    }
+
+    #[test]
+    fn test_size_limit_is_enforced_with_occasional_compaction() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let lines = || fs::read_to_string(&path).unwrap().lines().count();
+        let mut store = HistoryStore::open(&path, 8).unwrap();
+        for i in 0..10 {
+            store.append(HistoryEntry::new(format!("echo {}", i), at(i))).unwrap();
+        }
+        // 8 plus a quarter of slack before the file is rewritten.
+        assert_eq!((store.len(), lines()), (8, 10));
+        store.append(HistoryEntry::new("echo 10", at(10))).unwrap();
+        assert_eq!((store.len(), lines()), (8, 8));
+        assert_eq!(store.entries()[0].command, "echo 3");
+
+        store.set_max_entries(4).unwrap();
+        assert_eq!((store.len(), lines()), (4, 4));
+        assert_eq!(HistoryStore::open(&path, 2).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_queries() {
+        let dir = tempdir().unwrap();
+        let session = Session::new(None);
+        let mut store = HistoryStore::open(&dir.path().join("history.jsonl"), 100).unwrap();
+        let (app, docs) = (Path::new("/src/app"), Path::new("/src/docs"));
+        for (minute, (command, cwd, exit_code)) in [
+            ("git status", app, 0),
+            ("git push", app, 1),
+            ("cargo build", app, 0),
+            ("git push", docs, 0),
+            ("mdbook build", docs, 2),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            store.append(session.entry(command, Some(cwd), at(minute as u32), exit_code, Duration::ZERO)).unwrap();
+        }
+        store.append(HistoryEntry::new("git log", at(9))).unwrap();
+
+        let query = |query: HistoryQuery| commands(&store.query(&query));
+        assert_eq!(query(HistoryQuery::new().prefix("git")), ["git log", "git push", "git push", "git status"]);
+        assert_eq!(query(HistoryQuery::new().prefix("git").unique()), ["git log", "git push", "git status"]);
+        assert_eq!(query(HistoryQuery::new().containing("build")), ["mdbook build", "cargo build"]);
+        assert_eq!(query(HistoryQuery::new().in_dir(app).prefix("git")), ["git push", "git status"]);
+        assert_eq!(query(HistoryQuery::new().exit(ExitFilter::Failed)), ["mdbook build", "git push"]);
+        assert_eq!(query(HistoryQuery::new().exit(ExitFilter::Code(2))), ["mdbook build"]);
+        assert_eq!(query(HistoryQuery::new().exit(ExitFilter::Succeeded).limit(2)), ["git push", "cargo build"]);
+    }
+}
@@ -0,0 +1,129 @@
+// Fish-style autosuggestions: while typing, the likeliest command from the history that
+// starts with the input is shown dimmed after it. Each earlier run of a command adds its
+// recency weight, counted more if it ran in the current directory and less if it failed.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+
+use super::search::recency_weight;
+use super::store::HistoryEntry;
+
+/// A run in the directory being suggested for counts this much more.
+const SAME_DIR_WEIGHT: f64 = 3.0;
+/// A failed run counts this much; it was likely mistyped or is being fixed.
+const FAILED_WEIGHT: f64 = 0.2;
+
+/// The command to suggest for `input`, which it extends. Entries without a known exit code,
+/// e.g. imported ones, count as successful. Multi-line commands are never suggested.
+pub fn suggest<'a>(entries: &'a [HistoryEntry], input: &str, cwd: Option<&Path>, now: DateTime<Utc>) -> Option<&'a str> {
+    if input.trim().is_empty() {
+        return None;
+    }
+    // Score and newest run for each candidate.
+    let mut candidates: HashMap<&str, (f64, DateTime<Utc>)> = HashMap::new();
+    for entry in entries {
+        let command = entry.command.as_str();
+        if command.len() <= input.len() || !command.starts_with(input) || command.contains('\n') {
+            continue;
+        }
+        let mut weight = recency_weight(now - entry.started_at);
+        if cwd.is_some() && entry.cwd.as_deref() == cwd {
+            weight *= SAME_DIR_WEIGHT;
+        }
+        if entry.exit_code.is_some_and(|code| code != 0) {
+            weight *= FAILED_WEIGHT;
+        }
+        let (score, last_run) = candidates.entry(command).or_insert((0.0, entry.started_at));
+        *score += weight;
+        *last_run = (*last_run).max(entry.started_at);
+    }
+    candidates
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)))
+        .map(|(command, _)| command)
+}
+
+/// How much of a suggestion's untyped `rest` Alt-Right accepts: the next word, or the next
+/// path component.
+pub fn next_word(rest: &str) -> &str {
+    let start = rest.len() - rest.trim_start().len();
+    for (i, c) in rest[start..].char_indices() {
+        if c.is_whitespace() {
+            return &rest[..start + i];
+        }
+        if c == '/' {
+            return &rest[..start + i + 1];
+        }
+    }
+    rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use std::path::PathBuf;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn run(command: &str, hours_ago: i64, cwd: &str, exit_code: Option<i32>) -> HistoryEntry {
+        HistoryEntry {
+            cwd: Some(PathBuf::from(cwd)),
+            exit_code,
+            ..HistoryEntry::new(command, now() - Duration::hours(hours_ago))
+        }
+    }
+
+    fn suggestion<'a>(entries: &'a [HistoryEntry], input: &str) -> Option<&'a str> {
+        suggest(entries, input, Some(Path::new("/src/app")), now())
+    }
+
+    #[test]
+    fn test_suggestions_extend_the_input() {
+        let entries = [
+            run("ls -la", 2, "/tmp", Some(0)),
+            run("ls -l", 1, "/tmp", Some(0)),
+            run("ls\n-R", 0, "/tmp", Some(0)),
+        ];
+        // Equal scores: the one that ran last.
+        assert_eq!(suggestion(&entries, "ls"), Some("ls -l"));
+        assert_eq!(suggestion(&entries, "ls -l"), Some("ls -la"));
+        assert_eq!(suggestion(&entries, "ls -la"), None);
+        assert_eq!(suggestion(&entries, "  "), None);
+        assert_eq!(suggestion(&entries, "LS"), None);
+    }
+
+    #[test]
+    fn test_same_directory_outweighs_recency() {
+        let entries = [
+            run("cargo run -- serve", 10, "/src/app", Some(0)),
+            run("cargo run --release", 1, "/src/other", Some(0)),
+        ];
+        assert_eq!(suggestion(&entries, "cargo r"), Some("cargo run -- serve"));
+        assert_eq!(suggest(&entries, "cargo r", None, now()), Some("cargo run --release"));
+    }
+
+    #[test]
+    fn test_failed_runs_count_less() {
+        let entries = [
+            run("git push origin main", 30, "/src/app", Some(0)),
+            run("git push --force", 0, "/src/app", Some(1)),
+            run("git pull", 0, "/src/app", None),
+        ];
+        assert_eq!(suggestion(&entries, "git pu"), Some("git pull"));
+        assert_eq!(suggestion(&entries, "git pus"), Some("git push origin main"));
+    }
+
+    #[test]
+    fn test_accepting_a_word_at_a_time() {
+        assert_eq!(next_word(" origin main"), " origin");
+        assert_eq!(next_word("sh origin"), "sh");
+        assert_eq!(next_word(" src/app/main.rs"), " src/");
+        assert_eq!(next_word("main.rs"), "main.rs");
+        assert_eq!(next_word(""), "");
+    }
+}
@@ -0,0 +1,273 @@
+use iced::{
+    widget::{button, checkbox, column, container, row, scrollable, text, text_input, Row, Space},
+    Alignment, Element, Length,
+};
+use std::path::{Path, PathBuf};
+
+use crate::config::theme::WarpTheme;
+use crate::fuzzy_match::FuzzyMatcher;
+use crate::history::search::{search, SearchMatch};
+use crate::history::store::{ExitFilter, HistoryQuery, HistoryStore};
+
+/// More than fit on screen; the rest are a keystroke of refinement away.
+const MAX_RESULTS: usize = 50;
+
+#[derive(Debug, Clone)]
+pub enum HistorySearchMessage {
+    QueryChanged(String),
+    CurrentDirOnlyToggled(bool),
+    SucceededOnlyToggled(bool),
+    SelectNext,
+    SelectPrevious,
+    /// Enter: insert the selected command.
+    Accept,
+    Pick(usize),
+    Close,
+}
+
+/// The Ctrl-R overlay: fuzzy search over the persistent history.
+pub struct HistorySearchPanel {
+    is_visible: bool,
+    matcher: FuzzyMatcher,
+    query: String,
+    // The session's directory when the panel was opened.
+    cwd: Option<PathBuf>,
+    current_dir_only: bool,
+    succeeded_only: bool,
+    matches: Vec<SearchMatch>,
+    selected: usize,
+}
+
+impl HistorySearchPanel {
+    pub fn new() -> Self {
+        HistorySearchPanel {
+            is_visible: false,
+            matcher: FuzzyMatcher::new(),
+            query: String::new(),
+            cwd: None,
+            current_dir_only: false,
+            succeeded_only: false,
+            matches: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    /// Shows the panel with an empty query. The filters stay as they were last set.
+    pub fn open(&mut self, history: &HistoryStore, cwd: Option<&Path>) {
+        self.is_visible = true;
+        self.query.clear();
+        self.cwd = cwd.map(Path::to_path_buf);
+        self.refresh(history);
+    }
+
+    pub fn hide(&mut self) {
+        self.is_visible = false;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    /// Returns the command to put in the editor once one is chosen.
+    pub fn update(&mut self, message: HistorySearchMessage, history: &HistoryStore) -> Option<String> {
+        match message {
+            HistorySearchMessage::QueryChanged(query) => {
+                self.query = query;
+                self.refresh(history);
+            }
+            HistorySearchMessage::CurrentDirOnlyToggled(enabled) => {
+                self.current_dir_only = enabled;
+                self.refresh(history);
+            }
+            HistorySearchMessage::SucceededOnlyToggled(enabled) => {
+                self.succeeded_only = enabled;
+                self.refresh(history);
+            }
+            HistorySearchMessage::SelectNext => {
+                self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1));
+            }
+            HistorySearchMessage::SelectPrevious => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            HistorySearchMessage::Accept => return self.choose(self.selected),
+            HistorySearchMessage::Pick(index) => return self.choose(index),
+            HistorySearchMessage::Close => self.hide(),
+        }
+        None
+    }
+
+    fn choose(&mut self, index: usize) -> Option<String> {
+        let command = self.matches.get(index)?.command.clone();
+        self.hide();
+        Some(command)
+    }
+
+    fn query(&self) -> HistoryQuery {
+        let mut query = HistoryQuery::new();
+        if let Some(cwd) = self.cwd.as_ref().filter(|_| self.current_dir_only) {
+            query = query.in_dir(cwd);
+        }
+        if self.succeeded_only {
+            query = query.exit(ExitFilter::Succeeded);
+        }
+        query
+    }
+
+    fn refresh(&mut self, history: &HistoryStore) {
+        let entries = history.query(&self.query());
+        self.matches = search(&self.matcher, &entries, &self.query, chrono::Utc::now());
+        self.matches.truncate(MAX_RESULTS);
+        self.selected = 0;
+    }
+
+    pub fn view(&self, theme: &WarpTheme) -> Element<HistorySearchMessage> {
+        let background_color = theme.get_block_background_color(theme.is_dark_theme());
+        let foreground_color = theme.get_foreground_color();
+        let border_color = theme.get_border_color();
+        let accent_color = theme.get_accent_color();
+
+        let results = scrollable(
+            column(
+                self.matches.iter().enumerate().map(|(index, found)| {
+                    // Multi-line commands are shown on one line; `↵` keeps the indices valid.
+                    let command = Row::with_children(found.segments().into_iter().map(|(segment, matched)| {
+                        let segment = segment.replace('\n', "↵");
+                        text(segment).size(16).color(if matched { accent_color } else { foreground_color }).into()
+                    }));
+                    let status = match found.last_exit_code {
+                        Some(0) => "✓",
+                        Some(_) => "✗",
+                        None => "",
+                    };
+                    let details = format!("{} ×{}", status, found.runs);
+                    let selected = index == self.selected;
+                    button(
+                        row![
+                            command.width(Length::Fill),
+                            text(details).size(14).color(foreground_color.scale_rgb(0.7)),
+                        ]
+                        .spacing(10)
+                        .align_items(Alignment::Center)
+                    )
+                    .on_press(HistorySearchMessage::Pick(index))
+                    .width(Length::Fill)
+                    .padding(6)
+                    .style(iced::widget::button::text::Appearance {
+                        background: Some(iced::Background::Color(background_color)),
+                        border_radius: 4.0.into(),
+                        border_width: if selected { 2.0 } else { 0.0 },
+                        border_color: accent_color,
+                        text_color: foreground_color,
+                    })
+                    .into()
+                }).collect()
+            ).spacing(2)
+        )
+        .width(Length::Fill)
+        .height(Length::FillPortion(1));
+
+        let empty_note: Element<HistorySearchMessage> = if self.matches.is_empty() {
+            text("No matching commands").size(14).color(foreground_color.scale_rgb(0.7)).into()
+        } else {
+            Space::with_height(Length::Shrink).into()
+        };
+
+        container(
+            column![
+                text_input("Search history...", &self.query)
+                    .on_input(HistorySearchMessage::QueryChanged)
+                    .on_submit(HistorySearchMessage::Accept)
+                    .width(Length::Fill)
+                    .padding(10)
+                    .size(18),
+                row![
+                    checkbox("This directory only", self.current_dir_only)
+                        .on_toggle(HistorySearchMessage::CurrentDirOnlyToggled),
+                    checkbox("Succeeded only", self.succeeded_only)
+                        .on_toggle(HistorySearchMessage::SucceededOnlyToggled),
+                    Space::with_width(Length::Fill),
+                    button("Close").on_press(HistorySearchMessage::Close),
+                ]
+                .spacing(15)
+                .align_items(Alignment::Center),
+                empty_note,
+                results,
+            ]
+            .spacing(10)
+            .padding(20)
+        )
+        .width(Length::Fixed(700.0))
+        .height(Length::Fixed(450.0))
+        .style(move |_theme: &iced::Theme| container::Appearance {
+            background: Some(iced::Background::Color(background_color)),
+            border: iced::Border {
+                color: border_color,
+                width: 2.0,
+                radius: 8.0.into(),
+            },
+            ..Default::default()
+        })
+        .into()
+    }
+}
+
+impl Default for HistorySearchPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::store::Session;
+    use chrono::{DateTime, Utc};
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2024-03-01T10:{:02}:00Z", minute)).unwrap().with_timezone(&Utc)
+    }
+
+    fn commands(panel: &HistorySearchPanel) -> Vec<&str> {
+        let mut commands: Vec<&str> = panel.matches.iter().map(|found| found.command.as_str()).collect();
+        commands.sort_unstable();
+        commands
+    }
+
+    #[test]
+    fn test_filters_refresh_the_matches_and_accept_inserts_the_selection() {
+        let dir = tempdir().unwrap();
+        let (project, elsewhere) = (dir.path().join("project"), dir.path().join("elsewhere"));
+        let session = Session::new(None);
+        let mut history = HistoryStore::empty(&dir.path().join("history.jsonl"), 100);
+        for (minute, command, cwd, exit_code) in [
+            (1, "cargo build", &project, 0),
+            (2, "cargo test", &project, 101),
+            (3, "cargo build", &elsewhere, 0),
+            (4, "git status", &project, 0),
+        ] {
+            history.append(session.entry(command, Some(cwd), at(minute), exit_code, Duration::from_secs(1))).unwrap();
+        }
+        let mut panel = HistorySearchPanel::new();
+        panel.open(&history, Some(&project));
+        assert!(panel.is_visible());
+        assert_eq!(commands(&panel), ["cargo build", "cargo test", "git status"]);
+
+        panel.update(HistorySearchMessage::QueryChanged("cargo".to_string()), &history);
+        assert_eq!(commands(&panel), ["cargo build", "cargo test"]);
+        panel.update(HistorySearchMessage::SucceededOnlyToggled(true), &history);
+        assert_eq!(commands(&panel), ["cargo build"]);
+        assert_eq!(panel.matches[0].runs, 2);
+        panel.update(HistorySearchMessage::CurrentDirOnlyToggled(true), &history);
+        assert_eq!(panel.matches[0].runs, 1);
+        panel.update(HistorySearchMessage::SucceededOnlyToggled(false), &history);
+        panel.update(HistorySearchMessage::QueryChanged(String::new()), &history);
+        assert_eq!(commands(&panel), ["cargo build", "cargo test", "git status"]);
+
+        panel.update(HistorySearchMessage::SelectNext, &history);
+        let selected = panel.matches[1].command.clone();
+        assert_eq!(panel.update(HistorySearchMessage::Accept, &history), Some(selected));
+        assert!(!panel.is_visible());
+    }
+}
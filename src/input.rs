@@ -1,24 +1,55 @@
 use iced::{
-    widget::{text_input, container, column, Text},
+    keyboard,
+    widget::{text_input, container, column, row, text, Row, Text},
     Element, Length, Command,
 };
-use crate::terminal::Message;
+use crate::terminal::TerminalMessage;
 use crate::themes::WarpTheme;
 use crate::prompt::PromptRenderer;
+use crate::history::suggest::next_word;
+use crate::editor::{EditorBuffer, Motion, SystemClipboard};
 
-#[derive(Debug, Clone)]
+/// The single-line prompt input's id, for moving its cursor from outside.
+pub fn prompt_input_id() -> text_input::Id {
+    text_input::Id::new("prompt")
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum EditorMessage {
     InputChanged(String),
+    /// Enter: runs the command, or starts a new line if it's unfinished (see `buffer::is_complete`).
     Submit,
+    /// Shift-Enter: always a new line.
+    NewLine,
     HistoryUp,
     HistoryDown,
-    // Add more editor-specific messages like CursorMoved, SelectText, etc.
+    /// Right/End: take the whole autosuggestion.
+    AcceptSuggestion,
+    /// Alt-Right: take the autosuggestion's next word.
+    AcceptSuggestionWord,
+    InsertText(String),
+    DeleteBackward,
+    DeleteForward,
+    DeleteWordBackward,
+    /// The motion, and whether it extends the selection (Shift).
+    Move(Motion, bool),
+    SelectAll,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
 }
 
 pub struct Editor {
-    input_value: String,
+    buffer: EditorBuffer,
+    clipboard: SystemClipboard,
+    // Oldest first, at most `max_history` long. The full record is `history::HistoryStore`.
     history: Vec<String>,
     history_index: Option<usize>,
+    max_history: usize,
+    // A history command extending the input, shown dimmed after it; see `history::suggest`.
+    suggestion: Option<String>,
     font_size: u16,
     font_family: String,
     input_handler: InputHandler,
@@ -27,55 +58,160 @@ pub struct Editor {
 impl Editor {
     pub fn new() -> Self {
         Editor {
-            input_value: String::new(),
+            buffer: EditorBuffer::new(),
+            clipboard: SystemClipboard::new(),
             history: Vec::new(),
             history_index: None,
+            max_history: crate::history::store::DEFAULT_MAX_ENTRIES,
+            suggestion: None,
             font_size: 16,
             font_family: "Fira Code".to_string(),
             input_handler: InputHandler::new(),
         }
     }
 
-    pub fn update(&mut self, message: EditorMessage) -> Command<Message> {
+    pub fn update(&mut self, message: EditorMessage) -> Command<TerminalMessage> {
         match message {
             EditorMessage::InputChanged(value) => {
-                self.input_value = value;
-                Command::none()
+                self.buffer.apply_value(&value);
             }
             EditorMessage::Submit => {
-                let submitted_value = self.input_value.clone();
+                if !self.buffer.is_complete() {
+                    self.buffer.insert_char('\n');
+                    return Command::none();
+                }
+                let submitted_value = self.buffer.text().to_string();
                 if !submitted_value.trim().is_empty() {
                     self.history.push(submitted_value.clone());
-                    self.input_value.clear();
+                    self.trim_history();
+                    self.buffer = EditorBuffer::new();
                     self.history_index = None;
+                    self.suggestion = None;
                 }
-                Command::perform(async {}, move |_| Message::InputSubmitted(submitted_value))
+                return Command::perform(async {}, move |_| TerminalMessage::SubmitCommand(submitted_value));
             }
+            EditorMessage::NewLine => self.buffer.insert_char('\n'),
             EditorMessage::HistoryUp => {
                 if let Some(idx) = self.history_index {
                     if idx > 0 {
                         self.history_index = Some(idx - 1);
-                        self.input_value = self.history[idx - 1].clone();
+                        self.buffer.set_text(self.history[idx - 1].clone());
                     }
                 } else if !self.history.is_empty() {
                     self.history_index = Some(self.history.len() - 1);
-                    self.input_value = self.history[self.history.len() - 1].clone();
+                    self.buffer.set_text(self.history[self.history.len() - 1].clone());
                 }
-                Command::none()
             }
             EditorMessage::HistoryDown => {
                 if let Some(idx) = self.history_index {
                     if idx < self.history.len() - 1 {
                         self.history_index = Some(idx + 1);
-                        self.input_value = self.history[idx + 1].clone();
+                        self.buffer.set_text(self.history[idx + 1].clone());
                     } else {
                         self.history_index = None;
-                        self.input_value.clear();
+                        self.buffer.set_text(String::new());
                     }
                 }
-                Command::none()
             }
+            EditorMessage::AcceptSuggestion => {
+                if let Some(rest) = self.suggestion_suffix().map(str::to_string) {
+                    self.buffer.move_cursor(Motion::BufferEnd, false);
+                    self.buffer.insert_str(&rest);
+                }
+            }
+            EditorMessage::AcceptSuggestionWord => {
+                if let Some(word) = self.suggestion_suffix().map(|rest| next_word(rest).to_string()) {
+                    self.buffer.move_cursor(Motion::BufferEnd, false);
+                    self.buffer.insert_str(&word);
+                }
+            }
+            EditorMessage::InsertText(inserted) => {
+                let mut chars = inserted.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => self.buffer.insert_char(c),
+                    _ => self.buffer.insert_str(&inserted),
+                }
+            }
+            EditorMessage::DeleteBackward => self.buffer.delete_backward(),
+            EditorMessage::DeleteForward => self.buffer.delete_forward(),
+            EditorMessage::DeleteWordBackward => self.buffer.delete_word_backward(),
+            EditorMessage::Move(motion, select) => self.buffer.move_cursor(motion, select),
+            EditorMessage::SelectAll => self.buffer.select_all(),
+            EditorMessage::Undo => {
+                self.buffer.undo();
+            }
+            EditorMessage::Redo => {
+                self.buffer.redo();
+            }
+            EditorMessage::Copy => self.buffer.copy(&mut self.clipboard),
+            EditorMessage::Cut => self.buffer.cut(&mut self.clipboard),
+            EditorMessage::Paste => self.buffer.paste(&mut self.clipboard),
         }
+        Command::none()
+    }
+
+    /// The message for a key pressed while the editor has focus, if it handles the key.
+    /// Up and Down walk the history only from the first and last line.
+    pub fn key_message(&self, key: &keyboard::Key, modifiers: keyboard::Modifiers) -> Option<EditorMessage> {
+        use keyboard::key::Named;
+
+        let select = modifiers.shift();
+        // Ctrl on Linux and Windows, Alt (Option) on macOS.
+        let by_word = modifiers.control() || modifiers.alt();
+        let at_end = self.buffer.cursor() == self.buffer.text().len();
+        let suggesting = at_end && !select && self.suggestion_suffix().is_some();
+        let (line, _) = self.buffer.cursor_line_col();
+        let message = match key.as_ref() {
+            keyboard::Key::Named(Named::Enter) if modifiers.shift() => EditorMessage::NewLine,
+            keyboard::Key::Named(Named::Enter) => EditorMessage::Submit,
+            keyboard::Key::Named(Named::Backspace) if by_word => EditorMessage::DeleteWordBackward,
+            keyboard::Key::Named(Named::Backspace) => EditorMessage::DeleteBackward,
+            keyboard::Key::Named(Named::Delete) => EditorMessage::DeleteForward,
+            keyboard::Key::Named(Named::ArrowRight) if suggesting && modifiers.alt() => EditorMessage::AcceptSuggestionWord,
+            keyboard::Key::Named(Named::ArrowRight | Named::End) if suggesting && !by_word => EditorMessage::AcceptSuggestion,
+            keyboard::Key::Named(Named::ArrowLeft) => EditorMessage::Move(if by_word { Motion::WordLeft } else { Motion::Left }, select),
+            keyboard::Key::Named(Named::ArrowRight) => EditorMessage::Move(if by_word { Motion::WordRight } else { Motion::Right }, select),
+            keyboard::Key::Named(Named::ArrowUp) if line == 0 && !select => EditorMessage::HistoryUp,
+            keyboard::Key::Named(Named::ArrowUp) => EditorMessage::Move(Motion::Up, select),
+            keyboard::Key::Named(Named::ArrowDown) if line + 1 == self.buffer.line_count() && !select => EditorMessage::HistoryDown,
+            keyboard::Key::Named(Named::ArrowDown) => EditorMessage::Move(Motion::Down, select),
+            keyboard::Key::Named(Named::Home) if modifiers.command() => EditorMessage::Move(Motion::BufferStart, select),
+            keyboard::Key::Named(Named::Home) => EditorMessage::Move(Motion::LineStart, select),
+            keyboard::Key::Named(Named::End) if modifiers.command() => EditorMessage::Move(Motion::BufferEnd, select),
+            keyboard::Key::Named(Named::End) => EditorMessage::Move(Motion::LineEnd, select),
+            keyboard::Key::Named(Named::Space) => EditorMessage::InsertText(" ".to_string()),
+            keyboard::Key::Character(c) if modifiers.command() => match c {
+                "a" => EditorMessage::SelectAll,
+                "z" | "Z" if modifiers.shift() => EditorMessage::Redo,
+                "z" => EditorMessage::Undo,
+                "y" => EditorMessage::Redo,
+                "c" => EditorMessage::Copy,
+                "x" => EditorMessage::Cut,
+                "v" => EditorMessage::Paste,
+                _ => return None,
+            },
+            keyboard::Key::Character(c) => EditorMessage::InsertText(c.to_string()),
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    /// Whether the command is drawn by `multi_line_view`, which takes no input of its own.
+    pub fn is_multi_line(&self) -> bool {
+        self.buffer.line_count() > 1
+    }
+
+    /// Sets the command to suggest, e.g. from `history::suggest::suggest`.
+    pub fn set_suggestion(&mut self, suggestion: Option<String>) {
+        self.suggestion = suggestion;
+    }
+
+    /// The untyped rest of the suggestion, if it still extends the input.
+    pub fn suggestion_suffix(&self) -> Option<&str> {
+        self.suggestion
+            .as_deref()
+            .and_then(|suggestion| suggestion.strip_prefix(self.buffer.text()))
+            .filter(|rest| !rest.is_empty())
     }
 
     pub fn view<'a>(&'a self, theme: &WarpTheme, prompt_renderer: &'a PromptRenderer) -> Element<'a, EditorMessage> {
@@ -94,16 +230,31 @@ impl Editor {
 
         let prompt_view = prompt_renderer.render_prompt(theme);
 
-        container(
-            column![
-                prompt_view,
-                text_input("", &self.input_value)
+        let input_view: Element<'a, EditorMessage> = if self.is_multi_line() {
+            self.multi_line_view(theme)
+        } else {
+            // `text_input` can't draw past its value, so the suggestion's rest goes beside it.
+            let ghost_color = theme.get_foreground_color().scale_rgb(0.5);
+            row![
+                text_input("", self.buffer.text())
+                    .id(prompt_input_id())
                     .on_input(EditorMessage::InputChanged)
-                    .on_submit(EditorMessage::Submit)
                     .padding(8)
                     .size(self.font_size)
                     .font(iced::Font::with_name(&self.font_family))
                     .style(input_style),
+                text(self.suggestion_suffix().unwrap_or_default())
+                    .size(self.font_size)
+                    .font(iced::Font::with_name(&self.font_family))
+                    .color(ghost_color),
+            ]
+            .into()
+        };
+
+        container(
+            column![
+                prompt_view,
+                input_view,
             ]
             .spacing(4)
             .padding(8)
@@ -112,18 +263,80 @@ impl Editor {
         .into()
     }
 
+    /// `text_input` is single-line, so a multi-line command is drawn line by line, with the
+    /// selection highlighted and a bar at the cursor; keys arrive through `key_message`.
+    fn multi_line_view(&self, theme: &WarpTheme) -> Element<EditorMessage> {
+        let text_color = theme.get_foreground_color();
+        let accent_color = theme.get_accent_color();
+        let font = iced::Font::with_name(&self.font_family);
+        let selection = self.buffer.selection().unwrap_or(0..0);
+        let cursor = self.buffer.cursor();
+        let cursor_bar = || text("▏").size(self.font_size).font(font).color(accent_color);
+
+        let lines = self.buffer.line_ranges().into_iter().map(|line| {
+            let mut cuts = vec![line.start, line.end];
+            cuts.extend([selection.start, selection.end, cursor].into_iter().filter(|cut| line.contains(cut)));
+            cuts.sort_unstable();
+            cuts.dedup();
+
+            let mut spans = Row::new();
+            for span in cuts.windows(2) {
+                if span[0] == cursor {
+                    spans = spans.push(cursor_bar());
+                }
+                let segment = text(&self.buffer.text()[span[0]..span[1]]).size(self.font_size).font(font).color(text_color);
+                spans = if selection.contains(&span[0]) {
+                    spans.push(container(segment).style(move |_theme: &iced::Theme| container::Appearance {
+                        background: Some(iced::Background::Color(accent_color.scale_alpha(0.4))),
+                        ..Default::default()
+                    }))
+                } else {
+                    spans.push(segment)
+                };
+            }
+            if cursor == line.end {
+                spans = spans.push(cursor_bar());
+            }
+            spans.into()
+        });
+
+        column(lines.collect::<Vec<_>>()).spacing(2).padding(8).width(Length::Fill).into()
+    }
+
+    /// Replaces the Up/Down history, e.g. with the commands loaded from the history store.
+    pub fn set_history(&mut self, commands: Vec<String>) {
+        self.history = commands;
+        self.history_index = None;
+        self.trim_history();
+    }
+
+    /// Applies `UserPreferences::max_history_size`.
+    pub fn set_max_history(&mut self, max_history: usize) {
+        self.max_history = max_history;
+        self.trim_history();
+    }
+
+    fn trim_history(&mut self) {
+        let excess = self.history.len().saturating_sub(self.max_history);
+        if excess > 0 {
+            self.history.drain(..excess);
+            self.history_index = None;
+        }
+    }
+
     pub fn get_input_value(&self) -> &str {
-        &self.input_value
+        self.buffer.text()
     }
 
     pub fn set_input_value(&mut self, value: String) {
-        self.input_value = value;
+        self.buffer.set_text(value);
         self.history_index = None; // Reset history index when input is manually set
     }
 
     pub fn clear(&mut self) {
-        self.input_value.clear();
+        self.buffer = EditorBuffer::new();
         self.history_index = None;
+        self.suggestion = None;
         self.input_handler.clear_input();
     }
 
@@ -137,7 +350,7 @@ impl Editor {
 
     pub fn handle_key_event(&mut self, key: char) {
         self.input_handler.handle_key_event(key);
-        self.input_value = self.input_handler.current_input.clone();
+        self.buffer.apply_value(&self.input_handler.current_input);
     }
 }
 
@@ -161,3 +374,48 @@ impl InputHandler {
         self.current_input.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keyboard::key::Named;
+    use keyboard::{Key, Modifiers};
+
+    fn named(key: Named) -> Key {
+        Key::Named(key)
+    }
+
+    #[test]
+    fn test_shift_enter_starts_a_new_line_in_a_one_line_command() {
+        let mut editor = Editor::new();
+        editor.set_input_value("echo 'one line'".to_string());
+        assert!(!editor.is_multi_line());
+        assert_eq!(editor.key_message(&named(Named::Enter), Modifiers::SHIFT), Some(EditorMessage::NewLine));
+        assert_eq!(editor.key_message(&named(Named::Enter), Modifiers::empty()), Some(EditorMessage::Submit));
+    }
+
+    #[test]
+    fn test_key_messages() {
+        let mut editor = Editor::new();
+        assert_eq!(editor.key_message(&named(Named::Enter), Modifiers::SHIFT), Some(EditorMessage::NewLine));
+        assert_eq!(editor.key_message(&named(Named::Enter), Modifiers::empty()), Some(EditorMessage::Submit));
+        assert_eq!(editor.key_message(&named(Named::ArrowUp), Modifiers::empty()), Some(EditorMessage::HistoryUp));
+
+        editor.set_input_value("echo 'a\nb'".to_string());
+        assert_eq!(editor.key_message(&named(Named::ArrowUp), Modifiers::empty()), Some(EditorMessage::Move(Motion::Up, false)));
+        assert_eq!(editor.key_message(&named(Named::ArrowDown), Modifiers::empty()), Some(EditorMessage::HistoryDown));
+
+        editor.set_input_value("git st".to_string());
+        assert_eq!(editor.key_message(&named(Named::ArrowRight), Modifiers::empty()), Some(EditorMessage::Move(Motion::Right, false)));
+        editor.set_suggestion(Some("git status --short".to_string()));
+        assert_eq!(editor.key_message(&named(Named::ArrowRight), Modifiers::empty()), Some(EditorMessage::AcceptSuggestion));
+        assert_eq!(editor.key_message(&named(Named::End), Modifiers::empty()), Some(EditorMessage::AcceptSuggestion));
+        assert_eq!(editor.key_message(&named(Named::ArrowRight), Modifiers::ALT), Some(EditorMessage::AcceptSuggestionWord));
+        assert_eq!(editor.key_message(&named(Named::ArrowRight), Modifiers::SHIFT), Some(EditorMessage::Move(Motion::Right, true)));
+
+        let ctrl = Modifiers::COMMAND;
+        assert_eq!(editor.key_message(&Key::Character("Z".into()), ctrl | Modifiers::SHIFT), Some(EditorMessage::Redo));
+        assert_eq!(editor.key_message(&Key::Character("z".into()), ctrl), Some(EditorMessage::Undo));
+        assert_eq!(editor.key_message(&Key::Character("q".into()), ctrl), None);
+    }
+}
@@ -0,0 +1,109 @@
+// Append-only files of one JSON record per line, shared by the command history and the
+// workflow run history. An append is a single write, so a crash can only tear the last
+// line: `read` cuts a torn last line off the file and skips (and counts) any other line
+// that doesn't parse, for the owner to drop with its next `rewrite`. A rewrite goes
+// through a temporary file renamed over the log, so it never leaves half a file behind.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub struct JsonlFile {
+    path: PathBuf,
+}
+
+impl JsonlFile {
+    pub fn new(path: &Path) -> Self {
+        JsonlFile { path: path.to_path_buf() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every readable record, oldest first, and how many unreadable lines were skipped.
+    /// A file that doesn't exist yet has no records.
+    pub fn read<T: DeserializeOwned>(&self) -> Result<(Vec<T>, usize)> {
+        if !self.path.exists() {
+            return Ok((Vec::new(), 0));
+        }
+        let content = fs::read(&self.path)?;
+        let mut records = Vec::new();
+        let mut skipped = 0;
+        let mut complete = 0;
+        for line in content.split_inclusive(|&b| b == b'\n') {
+            if !line.ends_with(b"\n") {
+                break;
+            }
+            complete += line.len();
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            match serde_json::from_slice(line) {
+                Ok(record) => records.push(record),
+                Err(e) => {
+                    warn!("Skipping unreadable line in {:?}: {}", self.path, e);
+                    skipped += 1;
+                }
+            }
+        }
+        if complete < content.len() {
+            warn!("Dropping a partly written line at the end of {:?}", self.path);
+            OpenOptions::new().write(true).open(&self.path)?.set_len(complete as u64)?;
+        }
+        Ok((records, skipped))
+    }
+
+    pub fn append<T: Serialize>(&self, record: &T) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Replaces the file's content with `records`.
+    pub fn rewrite<'a, T: Serialize + 'a>(&self, records: impl IntoIterator<Item = &'a T>) -> Result<()> {
+        let mut content = String::new();
+        for record in records {
+            content.push_str(&serde_json::to_string(record)?);
+            content.push('\n');
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temporary = self.path.with_extension("jsonl.tmp");
+        fs::write(&temporary, content)?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_torn_last_line_is_cut_and_bad_lines_are_skipped() {
+        let dir = tempdir().unwrap();
+        let file = JsonlFile::new(&dir.path().join("log.jsonl"));
+        assert_eq!(file.read::<u32>().unwrap(), (vec![], 0));
+
+        file.append(&1).unwrap();
+        fs::write(file.path(), format!("{}not json\n\n2\n{{\"torn", fs::read_to_string(file.path()).unwrap())).unwrap();
+        assert_eq!(file.read::<u32>().unwrap(), (vec![1, 2], 1));
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "1\nnot json\n\n2\n");
+
+        file.rewrite(&[1, 2]).unwrap();
+        file.append(&3).unwrap();
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "1\n2\n3\n");
+    }
+}
@@ -1,3 +1,4 @@
+use clap::{Parser, Subcommand};
 use tokio::signal;
 use log::{info, error};
 use app::server::telemetry::TelemetryCollector;
@@ -11,7 +12,9 @@ use ui::platform::app::PlatformApp;
 use uuid::Uuid; // Import Uuid for generating IDs
 
 mod shell;
+mod shell_integration;
 mod editor; // This is now `input.rs`
+mod input; // The prompt's editor, on `editor::EditorBuffer`
 mod fuzzy;
 mod renderer;
 mod themes; // This is now `config/theme.rs`, `config/yaml_theme.rs`, `config/yaml_theme_manager.rs`
@@ -50,12 +53,69 @@ mod watcher;
 mod drive; // Import the new drive module
 mod websocket;
 mod fuzzy_match;
+mod history; // Persistent command history
+mod jsonl; // Append-only JSON-lines files
 mod virtual_fs;
+mod vt;
+
+#[derive(Parser)]
+#[command(version, about = "Warp Terminal")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Check workflow files without starting the terminal; exits non-zero on problems.
+    LintWorkflows {
+        /// Workflow files, or directories to search for `.yaml`, `.yml` and `.json` files.
+        #[arg(required = true)]
+        paths: Vec<std::path::PathBuf>,
+        /// Fail on warnings as well as errors.
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+    /// Import another shell's history into the command history.
+    ImportHistory {
+        /// `bash`, `zsh` or `fish`.
+        shell: String,
+        /// The history file; where the shell keeps it by default if not given.
+        path: Option<std::path::PathBuf>,
+    },
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     env_logger::init();
 
+    // Headless commands, e.g. for CI, run before any of the app starts.
+    match Cli::parse().command {
+        Some(CliCommand::LintWorkflows { paths, deny_warnings }) => {
+            let passed = workflows::lint::lint_paths(&paths, deny_warnings, &mut std::io::stdout())?;
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+        Some(CliCommand::ImportHistory { shell, path }) => {
+            let shell = history::ShellHistory::from_name(&shell)
+                .ok_or_else(|| anyhow::anyhow!("unknown shell '{}', expected bash, zsh or fish", shell))?;
+            let path = path.or_else(|| shell.default_path()).ok_or_else(|| anyhow::anyhow!("no home directory to find the {} history in", shell))?;
+            let max_entries = config::ConfigManager::new()
+                .map(|config| config.get_preferences().max_history_size)
+                .unwrap_or(history::store::DEFAULT_MAX_ENTRIES);
+            let mut store = history::HistoryStore::open(&history::HistoryStore::default_path(), max_entries)?;
+            let summary = history::import::import_file(&mut store, shell, &path)?;
+            println!("Imported {} of {} commands from {}", summary.imported, summary.read, path.display());
+            if summary.dropped > 0 {
+                println!(
+                    "{} older commands were not kept: the history holds the newest {} (max_history_size)",
+                    summary.dropped, max_entries
+                );
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
     // Initialize Sentry for error reporting
     let _guard = sentry::init((
         "https://examplePublicKey@o0.ingest.sentry.io/0",
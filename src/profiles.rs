@@ -1,9 +1,14 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use uuid::Uuid;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::path::{PathBuf, Path};
 use std::fs;
 use chrono::{DateTime, Utc};
+use glob::{MatchOptions, Pattern};
+use log::warn;
+use regex::RegexBuilder;
 
 use crate::config::{WarpConfig, KeyBindings, PromptSettings};
 use crate::themes::WarpTheme;
@@ -13,7 +18,16 @@ pub struct UserProfile {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
-    pub config: WarpConfig,
+    /// Profile this one inherits its settings from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Uuid>,
+    /// Full settings of a base profile. Ignored when `parent` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<WarpConfig>,
+    /// Settings that differ from the parent, as a partial `WarpConfig` in JSON form.
+    /// Nested objects are merged key by key, so `{"prompt": {"show_host": false}}` is valid.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub overrides: Map<String, Value>,
     pub auto_switch_rules: Vec<AutoSwitchRule>,
     pub is_quick_switch: bool,
 }
@@ -24,39 +38,226 @@ impl Default for UserProfile {
             id: Uuid::new_v4(),
             name: "Default Profile".to_string(),
             description: Some("The default terminal profile.".to_string()),
-            config: WarpConfig::default(),
+            parent: None,
+            config: Some(WarpConfig::default()),
+            overrides: Map::new(),
             auto_switch_rules: Vec::new(),
             is_quick_switch: true,
         }
     }
 }
 
+/// Why a profile's effective config could not be computed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InheritanceError {
+    NotFound(Uuid),
+    /// `profile` names a `parent` that does not exist.
+    MissingParent { profile: Uuid, parent: Uuid },
+    /// The profiles in the cycle, starting and ending with the same ID.
+    Cycle(Vec<Uuid>),
+    /// The merged settings do not form a valid `WarpConfig`.
+    InvalidConfig(String),
+}
+
+impl fmt::Display for InheritanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InheritanceError::NotFound(id) => write!(f, "Profile with ID {} not found.", id),
+            InheritanceError::MissingParent { profile, parent } => {
+                write!(f, "Profile {} inherits from missing profile {}.", profile, parent)
+            }
+            InheritanceError::Cycle(chain) => {
+                let chain: Vec<String> = chain.iter().map(Uuid::to_string).collect();
+                write!(f, "Profile inheritance cycle: {}", chain.join(" -> "))
+            }
+            InheritanceError::InvalidConfig(e) => write!(f, "Invalid profile overrides: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for InheritanceError {}
+
+/// Persisted across runs, next to `config.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ProfileState {
+    active_profile_id: Option<Uuid>,
+}
+
+/// Recursively merges `overrides` into `base`: objects are merged key by key,
+/// anything else replaces the base value.
+fn merge_values(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge_values(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+/// The minimal overrides that turn `base` into `target` (the inverse of `merge_values`).
+fn diff_values(base: &Value, target: &Value) -> Option<Value> {
+    match (base, target) {
+        (Value::Object(base), Value::Object(target)) => {
+            let diff: Map<String, Value> = target
+                .iter()
+                .filter_map(|(key, value)| match base.get(key) {
+                    Some(base_value) => diff_values(base_value, value).map(|d| (key.clone(), d)),
+                    None => Some((key.clone(), value.clone())),
+                })
+                .collect();
+            (!diff.is_empty()).then_some(Value::Object(diff))
+        }
+        (base, target) => (base != target).then(|| target.clone()),
+    }
+}
+
+/// Switches to the owning profile when the shell's working directory matches `path`.
+/// For `Glob` and `Regex` rules, `path` holds the pattern. Matching is case-insensitive.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AutoSwitchRule {
     pub path: PathBuf,
     pub rule_type: AutoSwitchRuleType,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Rule kinds, from most to least specific. When several rules match, the most
+/// specific kind wins, then the longest match (see `AutoSwitchRule::match_length`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum AutoSwitchRuleType {
     Exact,
     StartsWith,
+    Glob,
+    Regex,
     Contains,
 }
 
+impl AutoSwitchRule {
+    /// Checks that a glob or regex pattern compiles.
+    pub fn validate(&self) -> Result<(), String> {
+        let pattern = self.path.to_string_lossy();
+        match self.rule_type {
+            AutoSwitchRuleType::Glob => Pattern::new(&pattern).map(|_| ()).map_err(|e| format!("Invalid glob '{}': {}", pattern, e)),
+            AutoSwitchRuleType::Regex => build_regex(&pattern).map(|_| ()).map_err(|e| format!("Invalid regex '{}': {}", pattern, e)),
+            AutoSwitchRuleType::Exact | AutoSwitchRuleType::StartsWith | AutoSwitchRuleType::Contains => Ok(()),
+        }
+    }
+
+    /// Returns how much of `current_path` the rule matched, or `None` if it doesn't match.
+    /// `StartsWith` compares whole path components, so `/work` does not match `/workshop`.
+    /// Invalid glob/regex patterns never match.
+    pub fn match_length(&self, current_path: &Path) -> Option<usize> {
+        let rule_path_str = self.path.to_string_lossy().to_lowercase();
+        let current_path_str = current_path.to_string_lossy().to_lowercase();
+
+        match self.rule_type {
+            AutoSwitchRuleType::Exact => {
+                (Path::new(&current_path_str) == Path::new(&rule_path_str)).then_some(rule_path_str.len())
+            }
+            AutoSwitchRuleType::StartsWith => {
+                Path::new(&current_path_str).starts_with(&rule_path_str).then_some(rule_path_str.len())
+            }
+            AutoSwitchRuleType::Contains => {
+                current_path_str.contains(&rule_path_str).then_some(rule_path_str.len())
+            }
+            AutoSwitchRuleType::Glob => {
+                let options = MatchOptions {
+                    case_sensitive: false,
+                    require_literal_separator: true,
+                    require_literal_leading_dot: false,
+                };
+                let pattern = Pattern::new(&self.path.to_string_lossy()).ok()?;
+                // The literal part of the pattern is what makes one glob more specific than another.
+                pattern
+                    .matches_path_with(current_path, options)
+                    .then(|| rule_path_str.chars().filter(|c| !"*?[]!".contains(*c)).count())
+            }
+            AutoSwitchRuleType::Regex => {
+                let regex = build_regex(&self.path.to_string_lossy()).ok()?;
+                regex.find(&current_path.to_string_lossy()).map(|m| m.len())
+            }
+        }
+    }
+}
+
+fn build_regex(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+/// Why the active profile changed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileSwitchTrigger {
+    Manual,
+    /// An auto-switch rule matched this working directory.
+    AutoSwitch(PathBuf),
+}
+
+/// Emitted when the active profile changes, carrying the config to apply live.
+#[derive(Debug, Clone)]
+pub struct ProfileSwitched {
+    pub previous_id: Uuid,
+    pub profile_id: Uuid,
+    pub config: WarpConfig,
+    pub trigger: ProfileSwitchTrigger,
+}
+
+const BUNDLE_VERSION: u32 = 1;
+
+/// A profile packaged for sharing between machines. The profile is flattened to a base
+/// profile (its effective config, which includes its keybindings), and a user theme it
+/// references travels along as YAML. Built-in themes are not bundled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileBundle {
+    pub version: u32,
+    pub profile: UserProfile,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme_yaml: Option<String>,
+}
+
+/// What to do when an imported profile's ID or name is already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportConflictStrategy {
+    /// Import as a new profile, with a fresh ID if its ID is taken and a numbered name if
+    /// its name is, e.g. "Onboarding (2)".
+    Rename,
+    /// Replace the profile with the same ID, or else the one with the same name, keeping
+    /// its ID. If yet another profile has the name, the name is numbered as with `Rename`.
+    Overwrite,
+    /// Leave the existing profile alone.
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportOutcome {
+    Created(Uuid),
+    Renamed { id: Uuid, name: String },
+    Overwritten(Uuid),
+    Skipped(Uuid),
+}
+
 pub struct ProfileManager {
     profiles: HashMap<Uuid, UserProfile>,
     active_profile_id: Uuid,
     profiles_dir: PathBuf,
+    state_path: PathBuf,
+    themes_dir: PathBuf,
 }
 
 impl ProfileManager {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let profiles_dir = PathBuf::from("profiles");
+        let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        Self::with_config_dir(&config_dir.join("warp-terminal-clone"))
+    }
+
+    /// Loads profiles from `<app_config_dir>/profiles` and the active profile from
+    /// `<app_config_dir>/profile_state.json`.
+    pub fn with_config_dir(app_config_dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
         let mut manager = Self {
             profiles: HashMap::new(),
             active_profile_id: Uuid::new_v4(), // Will be set to default or loaded active
-            profiles_dir,
+            profiles_dir: app_config_dir.join("profiles"),
+            state_path: app_config_dir.join("profile_state.json"),
+            themes_dir: app_config_dir.join("themes"),
         };
         manager.load_profiles()?;
         Ok(manager)
@@ -71,7 +272,7 @@ impl ProfileManager {
         for entry in fs::read_dir(&self.profiles_dir)? {
             let entry = entry?;
             let path = entry.path();
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
                 let profile_str = fs::read_to_string(&path)?;
                 let profile: UserProfile = serde_json::from_str(&profile_str)?;
                 loaded_profiles.insert(profile.id, profile);
@@ -84,17 +285,46 @@ impl ProfileManager {
             loaded_profiles.insert(default_profile.id, default_profile.clone());
             self.save_profile_to_file(&default_profile)?;
         } else {
-            // Try to find a previously active profile, or pick the first one
-            self.active_profile_id = *loaded_profiles.keys().next().unwrap();
-            // TODO: Load active profile ID from a separate state file
+            // Restore the previously active profile, or fall back to the first by name
+            let saved_id = self.load_state().active_profile_id.filter(|id| loaded_profiles.contains_key(id));
+            self.active_profile_id = saved_id.unwrap_or_else(|| {
+                loaded_profiles.values().min_by(|a, b| a.name.cmp(&b.name)).unwrap().id
+            });
         }
         self.profiles = loaded_profiles;
+
+        for id in self.profiles.keys() {
+            if let Err(e) = self.effective_config(id) {
+                warn!("Profile {} has invalid inheritance: {}", id, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn load_state(&self) -> ProfileState {
+        fs::read_to_string(&self.state_path)
+            .ok()
+            .and_then(|state_str| serde_json::from_str(&state_str).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_state(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = self.state_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let state = ProfileState { active_profile_id: Some(self.active_profile_id) };
+        fs::write(&self.state_path, serde_json::to_string_pretty(&state)?)?;
         Ok(())
     }
 
     fn save_profile_to_file(&self, profile: &UserProfile) -> Result<(), Box<dyn std::error::Error>> {
+        Self::write_profile_file(&self.profiles_dir, profile)
+    }
+
+    // Takes the directory rather than `&self` so callers can hold a `&mut` into `self.profiles`.
+    fn write_profile_file(profiles_dir: &Path, profile: &UserProfile) -> Result<(), Box<dyn std::error::Error>> {
         let file_name = format!("{}.json", profile.id);
-        let path = self.profiles_dir.join(file_name);
+        let path = profiles_dir.join(file_name);
         let json_string = serde_json::to_string_pretty(profile)?;
         fs::write(path, json_string)?;
         Ok(())
@@ -116,26 +346,31 @@ impl ProfileManager {
     pub fn switch_profile(&mut self, profile_id: &Uuid) -> Result<(), String> {
         if self.profiles.contains_key(profile_id) {
             self.active_profile_id = *profile_id;
-            // TODO: Save active profile ID to a state file
+            // The switch itself succeeded; failing to remember it is not worth surfacing as an error.
+            if let Err(e) = self.save_state() {
+                warn!("Failed to save active profile: {}", e);
+            }
             Ok(())
         } else {
             Err(format!("Profile with ID {} not found.", profile_id))
         }
     }
 
+    /// Adds a profile that inherits from the active one, with no overrides yet. Unlike a
+    /// copy, it keeps following the active profile's settings until it overrides them;
+    /// `set_parent(id, None)` detaches it with its settings as they are.
     pub fn create_profile(&mut self, name: String, description: Option<String>) -> Result<Uuid, Box<dyn std::error::Error>> {
-        let mut new_profile = UserProfile {
+        let parent = self.get_active_profile().map(|p| p.id);
+        let new_profile = UserProfile {
             id: Uuid::new_v4(),
             name,
             description,
-            config: WarpConfig::default(), // Default config for new profile
+            parent,
+            config: parent.is_none().then(WarpConfig::default),
+            overrides: Map::new(),
             auto_switch_rules: Vec::new(),
             is_quick_switch: false,
         };
-        // Inherit current active profile's config
-        if let Some(active_profile) = self.get_active_profile() {
-            new_profile.config = active_profile.config.clone();
-        }
 
         self.profiles.insert(new_profile.id, new_profile.clone());
         self.save_profile_to_file(&new_profile)?;
@@ -143,13 +378,258 @@ impl ProfileManager {
     }
 
     pub fn update_profile(&mut self, id: &Uuid, updated_profile: UserProfile) -> Result<(), Box<dyn std::error::Error>> {
-        if self.profiles.contains_key(id) {
-            self.profiles.insert(*id, updated_profile.clone());
-            self.save_profile_to_file(&updated_profile)?;
-            Ok(())
-        } else {
-            Err(format!("Profile with ID {} not found.", id).into())
+        if !self.profiles.contains_key(id) {
+            return Err(InheritanceError::NotFound(*id).into());
         }
+        // Only this profile's own parent link can introduce a cycle or dangling parent
+        if let Some(parent) = updated_profile.parent {
+            self.check_parent(id, parent)?;
+        }
+        self.commit_profile(*id, updated_profile)
+    }
+
+    /// Saves `profile`, then replaces the in-memory copy, so a failed write changes nothing.
+    fn commit_profile(&mut self, id: Uuid, profile: UserProfile) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_profile_to_file(&profile)?;
+        self.profiles.insert(id, profile);
+        Ok(())
+    }
+
+    /// Checks that `profile_id` can inherit from `parent`: the parent exists, its own chain
+    /// is valid, and it does not (indirectly) inherit from `profile_id`.
+    fn check_parent(&self, profile_id: &Uuid, parent: Uuid) -> Result<(), InheritanceError> {
+        if !self.profiles.contains_key(&parent) {
+            return Err(InheritanceError::MissingParent { profile: *profile_id, parent });
+        }
+        self.effective_config(&parent)?;
+        // The parent's chain is finite, so this ends.
+        let mut chain = vec![*profile_id];
+        let mut current = Some(parent);
+        while let Some(id) = current {
+            chain.push(id);
+            if id == *profile_id {
+                return Err(InheritanceError::Cycle(chain));
+            }
+            current = self.profiles[&id].parent;
+        }
+        Ok(())
+    }
+
+    /// `profile` storing `config` as its settings: in full for a base profile, and as the
+    /// fields that differ from the parent's effective config otherwise.
+    fn with_settings(&self, mut profile: UserProfile, config: WarpConfig) -> Result<UserProfile, Box<dyn std::error::Error>> {
+        match profile.parent {
+            Some(parent) => {
+                let base = serde_json::to_value(self.effective_config(&parent)?)?;
+                let target = serde_json::to_value(&config)?;
+                profile.config = None;
+                profile.overrides = match diff_values(&base, &target) {
+                    Some(Value::Object(overrides)) => overrides,
+                    _ => Map::new(),
+                };
+            }
+            None => {
+                profile.config = Some(config);
+                profile.overrides = Map::new();
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Makes `profile_id` inherit from `parent` (or become a base profile with `None`),
+    /// keeping its current effective settings.
+    pub fn set_parent(&mut self, profile_id: &Uuid, parent: Option<Uuid>) -> Result<(), Box<dyn std::error::Error>> {
+        let effective = self.effective_config(profile_id)?;
+        let mut updated = self.profiles[profile_id].clone();
+        if let Some(parent) = parent {
+            self.check_parent(profile_id, parent)?;
+        }
+        updated.parent = parent;
+        let updated = self.with_settings(updated, effective)?;
+        self.commit_profile(*profile_id, updated)
+    }
+
+    /// Stores `config` as the profile's settings. Profiles with a parent only record
+    /// the fields that differ from the parent's effective config.
+    pub fn set_profile_config(&mut self, profile_id: &Uuid, config: WarpConfig) -> Result<(), Box<dyn std::error::Error>> {
+        let profile = self
+            .profiles
+            .get(profile_id)
+            .cloned()
+            .ok_or(InheritanceError::NotFound(*profile_id))?;
+        let updated = self.with_settings(profile, config)?;
+        self.commit_profile(*profile_id, updated)
+    }
+
+    /// Computes the settings of `profile_id` by applying the overrides of each profile
+    /// in its inheritance chain over the base profile's config.
+    pub fn effective_config(&self, profile_id: &Uuid) -> Result<WarpConfig, InheritanceError> {
+        let mut chain = Vec::new();
+        let mut visited = HashSet::new();
+        let mut current = self.profiles.get(profile_id).ok_or(InheritanceError::NotFound(*profile_id))?;
+        loop {
+            if !visited.insert(current.id) {
+                let start = chain.iter().position(|p: &&UserProfile| p.id == current.id).unwrap();
+                let mut cycle: Vec<Uuid> = chain[start..].iter().map(|p| p.id).collect();
+                cycle.push(current.id);
+                return Err(InheritanceError::Cycle(cycle));
+            }
+            chain.push(current);
+            match current.parent {
+                Some(parent_id) => {
+                    current = self.profiles.get(&parent_id).ok_or(InheritanceError::MissingParent {
+                        profile: current.id,
+                        parent: parent_id,
+                    })?;
+                }
+                None => break,
+            }
+        }
+
+        let base = chain.pop().unwrap();
+        let mut merged = serde_json::to_value(base.config.clone().unwrap_or_default())
+            .map_err(|e| InheritanceError::InvalidConfig(e.to_string()))?;
+        merge_values(&mut merged, &Value::Object(base.overrides.clone()));
+        for profile in chain.iter().rev() {
+            merge_values(&mut merged, &Value::Object(profile.overrides.clone()));
+        }
+        serde_json::from_value(merged).map_err(|e| InheritanceError::InvalidConfig(e.to_string()))
+    }
+
+    pub fn get_active_config(&self) -> WarpConfig {
+        self.effective_config(&self.active_profile_id).unwrap_or_else(|e| {
+            warn!("Falling back to default settings: {}", e);
+            WarpConfig::default()
+        })
+    }
+
+    /// Packages a profile with its effective settings and user theme.
+    pub fn export_bundle(&self, profile_id: &Uuid) -> Result<ProfileBundle, Box<dyn std::error::Error>> {
+        let mut profile = self
+            .profiles
+            .get(profile_id)
+            .cloned()
+            .ok_or(InheritanceError::NotFound(*profile_id))?;
+        let config = self.effective_config(profile_id)?;
+        let theme_yaml = self.find_theme_file(&config.theme).map(|(_, yaml)| yaml);
+        profile.parent = None;
+        profile.config = Some(config);
+        profile.overrides = Map::new();
+        Ok(ProfileBundle { version: BUNDLE_VERSION, profile, theme_yaml })
+    }
+
+    pub fn export_profile(&self, profile_id: &Uuid, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let bundle = self.export_bundle(profile_id)?;
+        fs::write(path, serde_json::to_string_pretty(&bundle)?)?;
+        Ok(())
+    }
+
+    pub fn import_profile(&mut self, path: &Path, strategy: ImportConflictStrategy) -> Result<ImportOutcome, Box<dyn std::error::Error>> {
+        let bundle: ProfileBundle = serde_json::from_str(&fs::read_to_string(path)?)?;
+        self.import_bundle(bundle, strategy)
+    }
+
+    /// Adds a bundled profile. A profile conflicts with an existing one when it has the
+    /// same ID or the same name; `strategy` decides what happens then.
+    pub fn import_bundle(&mut self, bundle: ProfileBundle, strategy: ImportConflictStrategy) -> Result<ImportOutcome, Box<dyn std::error::Error>> {
+        if bundle.version > BUNDLE_VERSION {
+            return Err(format!("Unsupported profile bundle version {}.", bundle.version).into());
+        }
+        let mut profile = bundle.profile;
+        if profile.parent.is_some() {
+            return Err("Bundled profiles must not inherit from another profile.".into());
+        }
+
+        let id_taken = self.profiles.contains_key(&profile.id);
+        let name_owner = self.profiles.values().find(|p| p.name == profile.name).map(|p| p.id);
+        let outcome = match (id_taken, name_owner, strategy) {
+            (false, None, _) => ImportOutcome::Created(profile.id),
+            (true, _, ImportConflictStrategy::Skip) => return Ok(ImportOutcome::Skipped(profile.id)),
+            (false, Some(owner), ImportConflictStrategy::Skip) => return Ok(ImportOutcome::Skipped(owner)),
+            (_, _, ImportConflictStrategy::Overwrite) => {
+                if !id_taken {
+                    profile.id = name_owner.unwrap();
+                }
+                profile.name = self.unique_profile_name(&profile.name, Some(&profile.id));
+                ImportOutcome::Overwritten(profile.id)
+            }
+            (_, _, ImportConflictStrategy::Rename) => {
+                if id_taken {
+                    profile.id = Uuid::new_v4();
+                }
+                match name_owner {
+                    Some(_) => {
+                        profile.name = self.unique_profile_name(&profile.name, None);
+                        ImportOutcome::Renamed { id: profile.id, name: profile.name.clone() }
+                    }
+                    None => ImportOutcome::Created(profile.id),
+                }
+            }
+        };
+
+        if let Some(theme_yaml) = &bundle.theme_yaml {
+            self.import_theme(theme_yaml, strategy == ImportConflictStrategy::Overwrite)?;
+        }
+        self.profiles.insert(profile.id, profile.clone());
+        self.save_profile_to_file(&profile)?;
+        Ok(outcome)
+    }
+
+    /// `name` if no profile but `replacing` has it, else the first free "name (2)",
+    /// "name (3)", ... A name that is already numbered counts on from its number rather
+    /// than gaining a second one.
+    fn unique_profile_name(&self, name: &str, replacing: Option<&Uuid>) -> String {
+        let taken = |candidate: &str| self.profiles.values().any(|p| p.name == candidate && Some(&p.id) != replacing);
+        if !taken(name) {
+            return name.to_string();
+        }
+        let numbered = name
+            .strip_suffix(')')
+            .and_then(|rest| rest.rsplit_once(" ("))
+            .and_then(|(stem, n)| Some((stem, n.parse::<u32>().ok()?)));
+        let (stem, last) = numbered.unwrap_or((name, 1));
+        (last + 1..)
+            .map(|n| format!("{} ({})", stem, n))
+            .find(|candidate| !taken(candidate))
+            .unwrap()
+    }
+
+    /// Finds the user theme file whose `name:` is `theme_name`, returning its path and contents.
+    fn find_theme_file(&self, theme_name: &str) -> Option<(PathBuf, String)> {
+        fs::read_dir(&self.themes_dir).ok()?.flatten().find_map(|entry| {
+            let path = entry.path();
+            if !path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml") {
+                return None;
+            }
+            let yaml = fs::read_to_string(&path).ok()?;
+            let value: serde_yaml::Value = serde_yaml::from_str(&yaml).ok()?;
+            (value.get("name")?.as_str()? == theme_name).then_some((path, yaml))
+        })
+    }
+
+    /// Installs a bundled theme. An existing theme with the same name is only replaced
+    /// when `overwrite` is set, so importing never silently changes other profiles' colors.
+    fn import_theme(&self, theme_yaml: &str, overwrite: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let value: serde_yaml::Value = serde_yaml::from_str(theme_yaml)?;
+        let theme_name = value.get("name").and_then(|n| n.as_str()).ok_or("Bundled theme has no name.")?;
+        let path = match self.find_theme_file(theme_name) {
+            Some((_, existing)) if existing == theme_yaml => return Ok(()),
+            Some(_) if !overwrite => {
+                warn!("Keeping the existing theme '{}', which differs from the imported one.", theme_name);
+                return Ok(());
+            }
+            Some((path, _)) => path,
+            None => {
+                let file_stem: String = theme_name
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+                    .collect();
+                self.themes_dir.join(format!("{}.yaml", file_stem))
+            }
+        };
+        fs::create_dir_all(&self.themes_dir)?;
+        fs::write(path, theme_yaml)?;
+        Ok(())
     }
 
     pub fn duplicate_profile(&mut self, source_id: &Uuid, new_name: String) -> Result<Uuid, Box<dyn std::error::Error>> {
@@ -167,16 +647,34 @@ impl ProfileManager {
     }
 
     pub fn delete_profile(&mut self, profile_id: &Uuid) -> Result<(), Box<dyn std::error::Error>> {
+        let grandparent = self.profiles.get(profile_id).ok_or(InheritanceError::NotFound(*profile_id))?.parent;
         if self.profiles.len() <= 1 {
             return Err("Cannot delete the last profile.".into());
         }
+
+        // Children take over the deleted profile's place in the chain, keeping their settings.
+        // Work them all out before changing anything, so a child with broken settings
+        // leaves every profile as it was.
+        let mut reparented = Vec::new();
+        for child in self.profiles.values().filter(|p| p.parent == Some(*profile_id)) {
+            let effective = self.effective_config(&child.id)?;
+            let mut updated = child.clone();
+            updated.parent = grandparent;
+            reparented.push(self.with_settings(updated, effective)?);
+        }
+
+        // A reparented child has the same settings either way, so committing some before a
+        // later write fails still leaves the profiles consistent.
+        for child in reparented {
+            self.commit_profile(child.id, child)?;
+        }
+        self.delete_profile_file(profile_id)?;
+        self.profiles.remove(profile_id);
+
         if self.active_profile_id == *profile_id {
-            // Switch to another profile before deleting the active one
-            let new_active_id = *self.profiles.keys().find(|&id| id != profile_id).unwrap();
+            let new_active_id = *self.profiles.keys().next().unwrap();
             self.switch_profile(&new_active_id)?;
         }
-        self.profiles.remove(profile_id);
-        self.delete_profile_file(profile_id)?;
         Ok(())
     }
 
@@ -191,7 +689,7 @@ impl ProfileManager {
     pub fn add_to_quick_switch(&mut self, profile_id: Uuid) -> Result<(), String> {
         if let Some(profile) = self.profiles.get_mut(&profile_id) {
             profile.is_quick_switch = true;
-            self.save_profile_to_file(profile).map_err(|e| e.to_string())?;
+            Self::write_profile_file(&self.profiles_dir, profile).map_err(|e| e.to_string())?;
             Ok(())
         } else {
             Err(format!("Profile with ID {} not found.", profile_id))
@@ -201,7 +699,7 @@ impl ProfileManager {
     pub fn remove_from_quick_switch(&mut self, profile_id: &Uuid) -> Result<(), String> {
         if let Some(profile) = self.profiles.get_mut(profile_id) {
             profile.is_quick_switch = false;
-            self.save_profile_to_file(profile).map_err(|e| e.to_string())?;
+            Self::write_profile_file(&self.profiles_dir, profile).map_err(|e| e.to_string())?;
             Ok(())
         } else {
             Err(format!("Profile with ID {} not found.", profile_id))
@@ -209,9 +707,10 @@ impl ProfileManager {
     }
 
     pub fn add_auto_switch_rule(&mut self, profile_id: Uuid, rule: AutoSwitchRule) -> Result<(), String> {
+        rule.validate()?;
         if let Some(profile) = self.profiles.get_mut(&profile_id) {
             profile.auto_switch_rules.push(rule);
-            self.save_profile_to_file(profile).map_err(|e| e.to_string())?;
+            Self::write_profile_file(&self.profiles_dir, profile).map_err(|e| e.to_string())?;
             Ok(())
         } else {
             Err(format!("Profile with ID {} not found.", profile_id))
@@ -222,7 +721,7 @@ impl ProfileManager {
         if let Some(profile) = self.profiles.get_mut(&profile_id) {
             if index < profile.auto_switch_rules.len() {
                 profile.auto_switch_rules.remove(index);
-                self.save_profile_to_file(profile).map_err(|e| e.to_string())?;
+                Self::write_profile_file(&self.profiles_dir, profile).map_err(|e| e.to_string())?;
                 Ok(())
             } else {
                 Err("Rule index out of bounds.".to_string())
@@ -232,23 +731,514 @@ impl ProfileManager {
         }
     }
 
+    /// Picks the profile whose rule best matches `current_path`: the most specific rule
+    /// type wins, then the longest match. Remaining ties go to the profile name that sorts first.
     pub fn check_auto_switch_rules(&self, current_path: &Path) -> Option<Uuid> {
-        for profile in self.profiles.values() {
-            for rule in &profile.auto_switch_rules {
-                let rule_path_str = rule.path.to_string_lossy().to_lowercase();
-                let current_path_str = current_path.to_string_lossy().to_lowercase();
-
-                let matches = match rule.rule_type {
-                    AutoSwitchRuleType::Exact => current_path_str == rule_path_str,
-                    AutoSwitchRuleType::StartsWith => current_path_str.starts_with(&rule_path_str),
-                    AutoSwitchRuleType::Contains => current_path_str.contains(&rule_path_str),
-                };
+        self.profiles
+            .values()
+            .flat_map(|profile| {
+                profile.auto_switch_rules.iter().filter_map(move |rule| {
+                    rule.match_length(current_path).map(|length| (rule.rule_type, length, profile))
+                })
+            })
+            .min_by(|(type_a, len_a, profile_a), (type_b, len_b, profile_b)| {
+                type_a
+                    .cmp(type_b)
+                    .then(len_b.cmp(len_a))
+                    .then_with(|| profile_a.name.cmp(&profile_b.name))
+                    .then_with(|| profile_a.id.cmp(&profile_b.id))
+            })
+            .map(|(_, _, profile)| profile.id)
+    }
 
-                if matches {
-                    return Some(profile.id);
-                }
-            }
+    /// Evaluates the auto-switch rules for a new working directory and activates the
+    /// matching profile. Returns the switch event, or `None` if nothing matched or the
+    /// matching profile is already active.
+    pub fn apply_auto_switch(&mut self, current_path: &Path) -> Option<ProfileSwitched> {
+        let profile_id = self.check_auto_switch_rules(current_path)?;
+        if profile_id == self.active_profile_id {
+            return None;
         }
-        None
+        self.activate(profile_id, ProfileSwitchTrigger::AutoSwitch(current_path.to_path_buf())).ok()
+    }
+
+    fn activate(&mut self, profile_id: Uuid, trigger: ProfileSwitchTrigger) -> Result<ProfileSwitched, String> {
+        let previous_id = self.active_profile_id;
+        let config = self.effective_config(&profile_id).map_err(|e| e.to_string())?;
+        self.switch_profile(&profile_id)?;
+        Ok(ProfileSwitched {
+            previous_id,
+            profile_id,
+            config,
+            trigger,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn rule(path: &str, rule_type: AutoSwitchRuleType) -> AutoSwitchRule {
+        AutoSwitchRule { path: PathBuf::from(path), rule_type }
+    }
+
+    fn profile(name: &str, rules: Vec<AutoSwitchRule>) -> UserProfile {
+        UserProfile {
+            name: name.to_string(),
+            auto_switch_rules: rules,
+            ..UserProfile::default()
+        }
+    }
+
+    fn manager(profiles: Vec<UserProfile>) -> ProfileManager {
+        ProfileManager {
+            active_profile_id: profiles[0].id,
+            profiles: profiles.into_iter().map(|p| (p.id, p)).collect(),
+            profiles_dir: PathBuf::from("profiles"),
+            state_path: PathBuf::from("profile_state.json"),
+            themes_dir: PathBuf::from("themes"),
+        }
+    }
+
+    fn matching_name(manager: &ProfileManager, path: &str) -> Option<String> {
+        manager
+            .check_auto_switch_rules(Path::new(path))
+            .map(|id| manager.profiles[&id].name.clone())
+    }
+
+    #[test]
+    fn test_rule_types_match() {
+        let cases = [
+            (rule("/home/me/work", AutoSwitchRuleType::Exact), "/home/me/work", true),
+            (rule("/home/me/work", AutoSwitchRuleType::Exact), "/home/me/work/api", false),
+            (rule("/home/me/work", AutoSwitchRuleType::StartsWith), "/Home/Me/Work/api", true),
+            (rule("/home/me/work", AutoSwitchRuleType::StartsWith), "/home/me/workshop", false),
+            (rule("node_modules", AutoSwitchRuleType::Contains), "/srv/app/node_modules/x", true),
+            (rule("/home/*/work/*", AutoSwitchRuleType::Glob), "/home/me/work/api", true),
+            (rule("/home/*/work/*", AutoSwitchRuleType::Glob), "/home/me/work/api/src", false),
+            (rule("/home/me/**/infra", AutoSwitchRuleType::Glob), "/home/me/a/b/infra", true),
+            (rule(r"/clients/[a-z]+-prod\b", AutoSwitchRuleType::Regex), "/srv/clients/acme-prod/db", true),
+            (rule(r"/clients/[a-z]+-prod\b", AutoSwitchRuleType::Regex), "/srv/clients/acme-staging", false),
+        ];
+        for (rule, path, expected) in cases {
+            assert_eq!(rule.match_length(Path::new(path)).is_some(), expected, "{:?} vs {}", rule, path);
+        }
+    }
+
+    #[test]
+    fn test_precedence_exact_beats_prefix_beats_contains() {
+        let manager = manager(vec![
+            profile("default", vec![]),
+            profile("contains", vec![rule("work", AutoSwitchRuleType::Contains)]),
+            profile("prefix", vec![rule("/home/me/work", AutoSwitchRuleType::StartsWith)]),
+            profile("exact", vec![rule("/home/me/work/api", AutoSwitchRuleType::Exact)]),
+        ]);
+        assert_eq!(matching_name(&manager, "/home/me/work/api").as_deref(), Some("exact"));
+        assert_eq!(matching_name(&manager, "/home/me/work/web").as_deref(), Some("prefix"));
+        assert_eq!(matching_name(&manager, "/tmp/homework").as_deref(), Some("contains"));
+        assert_eq!(matching_name(&manager, "/tmp"), None);
+    }
+
+    #[test]
+    fn test_longest_match_wins_within_a_type() {
+        let manager = manager(vec![
+            profile("home", vec![rule("/home/me", AutoSwitchRuleType::StartsWith)]),
+            profile("client", vec![rule("/home/me/clients/acme", AutoSwitchRuleType::StartsWith)]),
+            profile("glob", vec![rule("/home/me/clients/*", AutoSwitchRuleType::Glob)]),
+        ]);
+        assert_eq!(matching_name(&manager, "/home/me/clients/acme/src").as_deref(), Some("client"));
+        assert_eq!(matching_name(&manager, "/home/me/notes").as_deref(), Some("home"));
+    }
+
+    #[test]
+    fn test_glob_and_regex_rank_between_prefix_and_contains() {
+        let manager = manager(vec![
+            profile("contains", vec![rule("infra", AutoSwitchRuleType::Contains)]),
+            profile("regex", vec![rule(r"infra$", AutoSwitchRuleType::Regex)]),
+            profile("glob", vec![rule("/srv/*/infra", AutoSwitchRuleType::Glob)]),
+        ]);
+        assert_eq!(matching_name(&manager, "/srv/team/infra").as_deref(), Some("glob"));
+        assert_eq!(matching_name(&manager, "/opt/infra").as_deref(), Some("regex"));
+        assert_eq!(matching_name(&manager, "/opt/infra/modules").as_deref(), Some("contains"));
+    }
+
+    #[test]
+    fn test_invalid_patterns_are_rejected_and_never_match() {
+        let bad_glob = rule("/srv/[", AutoSwitchRuleType::Glob);
+        let bad_regex = rule("(unclosed", AutoSwitchRuleType::Regex);
+        assert!(bad_glob.validate().is_err());
+        assert!(bad_regex.validate().is_err());
+        assert_eq!(bad_glob.match_length(Path::new("/srv/[")), None);
+        assert_eq!(bad_regex.match_length(Path::new("/(unclosed")), None);
+    }
+
+    #[test]
+    fn test_apply_auto_switch_emits_event_once() {
+        let work = profile("work", vec![rule("/home/me/work", AutoSwitchRuleType::StartsWith)]);
+        let work_id = work.id;
+        let dir = tempdir().unwrap();
+        let mut manager = manager(vec![profile("default", vec![]), work]);
+        manager.state_path = dir.path().join("profile_state.json");
+        let default_id = manager.active_profile_id;
+
+        let event = manager.apply_auto_switch(Path::new("/home/me/work/api")).expect("expected a switch");
+        assert_eq!(event.previous_id, default_id);
+        assert_eq!(event.profile_id, work_id);
+        assert_eq!(event.trigger, ProfileSwitchTrigger::AutoSwitch(PathBuf::from("/home/me/work/api")));
+        assert_eq!(manager.get_active_profile().map(|p| p.id), Some(work_id));
+
+        // Already active, and unrelated directories leave the profile alone.
+        assert!(manager.apply_auto_switch(Path::new("/home/me/work/web")).is_none());
+        assert!(manager.apply_auto_switch(Path::new("/tmp")).is_none());
+        assert_eq!(manager.get_active_profile().map(|p| p.id), Some(work_id));
+    }
+
+    fn child(name: &str, parent: Uuid, overrides: Value) -> UserProfile {
+        UserProfile {
+            name: name.to_string(),
+            parent: Some(parent),
+            config: None,
+            overrides: overrides.as_object().cloned().unwrap(),
+            ..UserProfile::default()
+        }
+    }
+
+    #[test]
+    fn test_active_profile_survives_reload() {
+        let dir = tempdir().unwrap();
+        let mut manager = ProfileManager::with_config_dir(dir.path()).unwrap();
+        let work_id = manager.create_profile("work".to_string(), None).unwrap();
+        manager.switch_profile(&work_id).unwrap();
+
+        let reloaded = ProfileManager::with_config_dir(dir.path()).unwrap();
+        assert_eq!(reloaded.get_active_profile().map(|p| p.id), Some(work_id));
+        assert!(dir.path().join("profile_state.json").exists());
+        assert!(dir.path().join("profiles").join(format!("{}.json", work_id)).exists());
+    }
+
+    #[test]
+    fn test_new_profiles_follow_the_active_profile() {
+        let dir = tempdir().unwrap();
+        let mut manager = ProfileManager::with_config_dir(dir.path()).unwrap();
+        let base_id = manager.get_active_profile().unwrap().id;
+        let child_id = manager.create_profile("child".to_string(), None).unwrap();
+        assert_eq!(manager.profiles[&child_id].parent, Some(base_id));
+        assert!(manager.profiles[&child_id].config.is_none());
+
+        let mut config = manager.effective_config(&base_id).unwrap();
+        config.font_size += 4;
+        manager.set_profile_config(&base_id, config.clone()).unwrap();
+        assert_eq!(manager.effective_config(&child_id).unwrap().font_size, config.font_size);
+
+        manager.set_parent(&child_id, None).unwrap();
+        manager.set_profile_config(&base_id, WarpConfig::default()).unwrap();
+        assert_eq!(manager.effective_config(&child_id).unwrap().font_size, config.font_size);
+    }
+
+    #[test]
+    fn test_effective_config_applies_overrides_down_the_chain() {
+        let mut base = UserProfile::default();
+        base.config.as_mut().unwrap().font_size = 14;
+        let work = child("work", base.id, serde_json::json!({ "theme": "Solarized", "prompt": { "show_host": false } }));
+        let client = child("client", work.id, serde_json::json!({ "font_size": 18 }));
+        let (base_id, client_id) = (base.id, client.id);
+        let manager = manager(vec![base, work, client]);
+
+        let config = manager.effective_config(&client_id).unwrap();
+        assert_eq!(config.font_size, 18);
+        assert_eq!(config.theme, "Solarized");
+        assert!(!config.prompt.show_host);
+        // Untouched nested fields still come from the base profile.
+        assert_eq!(config.prompt.show_cwd, manager.effective_config(&base_id).unwrap().prompt.show_cwd);
+    }
+
+    #[test]
+    fn test_set_profile_config_stores_only_differences() {
+        let dir = tempdir().unwrap();
+        let mut manager = ProfileManager::with_config_dir(dir.path()).unwrap();
+        let child_id = manager.create_profile("child".to_string(), None).unwrap();
+
+        let mut config = manager.effective_config(&child_id).unwrap();
+        config.font_size += 2;
+        config.prompt.show_host = !config.prompt.show_host;
+        manager.set_profile_config(&child_id, config.clone()).unwrap();
+
+        let overrides = &manager.profiles[&child_id].overrides;
+        assert_eq!(
+            Value::Object(overrides.clone()),
+            serde_json::json!({ "font_size": config.font_size, "prompt": { "show_host": config.prompt.show_host } })
+        );
+        assert_eq!(manager.effective_config(&child_id).unwrap().font_size, config.font_size);
+    }
+
+    #[test]
+    fn test_inheritance_cycles_are_detected() {
+        let a = UserProfile { name: "a".to_string(), ..UserProfile::default() };
+        let mut b = child("b", a.id, serde_json::json!({}));
+        let c = child("c", b.id, serde_json::json!({}));
+        let (a_id, b_id, c_id) = (a.id, b.id, c.id);
+
+        let dir = tempdir().unwrap();
+        let mut manager = manager(vec![a, b.clone(), c]);
+        manager.profiles_dir = dir.path().to_path_buf();
+        let err = manager.set_parent(&a_id, Some(c_id)).unwrap_err();
+        assert!(err.to_string().contains("cycle"), "unexpected error: {}", err);
+        assert_eq!(manager.profiles[&a_id].parent, None);
+
+        b.parent = Some(c_id);
+        manager.profiles.insert(b_id, b);
+        assert_eq!(manager.effective_config(&c_id), Err(InheritanceError::Cycle(vec![c_id, b_id, c_id])));
+    }
+
+    #[test]
+    fn test_missing_parent_is_reported() {
+        let orphan = child("orphan", Uuid::new_v4(), serde_json::json!({}));
+        let orphan_id = orphan.id;
+        let manager = manager(vec![orphan]);
+        assert!(matches!(
+            manager.effective_config(&orphan_id),
+            Err(InheritanceError::MissingParent { profile, .. }) if profile == orphan_id
+        ));
+    }
+
+    #[test]
+    fn test_deleting_a_parent_keeps_child_settings() {
+        let dir = tempdir().unwrap();
+        let mut manager = ProfileManager::with_config_dir(dir.path()).unwrap();
+        let middle_id = manager.create_profile("middle".to_string(), None).unwrap();
+        let mut config = manager.effective_config(&middle_id).unwrap();
+        config.theme = "Middle Theme".to_string();
+        manager.set_profile_config(&middle_id, config).unwrap();
+        manager.switch_profile(&middle_id).unwrap();
+        let leaf_id = manager.create_profile("leaf".to_string(), None).unwrap();
+        let before = serde_json::to_value(manager.effective_config(&leaf_id).unwrap()).unwrap();
+
+        manager.delete_profile(&middle_id).unwrap();
+
+        assert_ne!(manager.profiles[&leaf_id].parent, Some(middle_id));
+        assert_eq!(serde_json::to_value(manager.effective_config(&leaf_id).unwrap()).unwrap(), before);
+    }
+
+    fn stored(manager: &ProfileManager, id: &Uuid) -> (Value, String) {
+        let file = manager.profiles_dir.join(format!("{}.json", id));
+        (serde_json::to_value(&manager.profiles[id]).unwrap(), fs::read_to_string(file).unwrap())
+    }
+
+    #[test]
+    fn test_failed_set_parent_changes_nothing() {
+        let dir = tempdir().unwrap();
+        let mut manager = ProfileManager::with_config_dir(dir.path()).unwrap();
+        let base_id = manager.get_active_profile().unwrap().id;
+        let child_id = manager.create_profile("child".to_string(), None).unwrap();
+        let mut config = manager.effective_config(&child_id).unwrap();
+        config.font_size = 22;
+        manager.set_profile_config(&child_id, config).unwrap();
+        let (base_before, child_before) = (stored(&manager, &base_id), stored(&manager, &child_id));
+
+        assert!(manager.set_parent(&child_id, Some(Uuid::new_v4())).is_err());
+        assert!(manager.set_parent(&base_id, Some(child_id)).unwrap_err().to_string().contains("cycle"));
+        assert!(manager.set_parent(&child_id, Some(child_id)).is_err());
+        assert_eq!(stored(&manager, &base_id), base_before);
+        assert_eq!(stored(&manager, &child_id), child_before);
+
+        // A write that fails leaves the in-memory profile alone too.
+        manager.profiles_dir = dir.path().join("missing");
+        assert!(manager.set_parent(&child_id, None).is_err());
+        assert_eq!(manager.profiles[&child_id].parent, Some(base_id));
+        assert_eq!(manager.effective_config(&child_id).unwrap().font_size, 22);
+    }
+
+    #[test]
+    fn test_failed_delete_changes_nothing() {
+        let dir = tempdir().unwrap();
+        let mut manager = ProfileManager::with_config_dir(dir.path()).unwrap();
+        let only_id = manager.get_active_profile().unwrap().id;
+        assert!(manager.delete_profile(&only_id).is_err());
+        assert!(manager.delete_profile(&Uuid::new_v4()).is_err());
+
+        let middle_id = manager.create_profile("middle".to_string(), None).unwrap();
+        manager.switch_profile(&middle_id).unwrap();
+        let good_id = manager.create_profile("good".to_string(), None).unwrap();
+        let broken_id = manager.create_profile("broken".to_string(), None).unwrap();
+        let mut broken = manager.profiles[&broken_id].clone();
+        broken.overrides.insert("font_size".to_string(), serde_json::json!("huge"));
+        manager.profiles.insert(broken_id, broken);
+        let good_before = stored(&manager, &good_id);
+
+        assert!(manager.delete_profile(&middle_id).is_err());
+        assert!(manager.profiles.contains_key(&middle_id));
+        assert!(manager.profiles_dir.join(format!("{}.json", middle_id)).exists());
+        assert_eq!(manager.get_active_profile().map(|p| p.id), Some(middle_id));
+        assert_eq!(stored(&manager, &good_id), good_before);
+    }
+
+    #[test]
+    fn test_legacy_profile_without_parent_loads() {
+        let json = serde_json::json!({
+            "id": Uuid::new_v4(),
+            "name": "legacy",
+            "description": null,
+            "config": serde_json::to_value(WarpConfig::default()).unwrap(),
+            "auto_switch_rules": [],
+            "is_quick_switch": true,
+        });
+        let profile: UserProfile = serde_json::from_value(json).unwrap();
+        assert_eq!(profile.parent, None);
+        assert!(profile.config.is_some());
+        assert!(profile.overrides.is_empty());
+    }
+
+    const SOLARIZED_YAML: &str = "name: Solarized\nauthor: Team\n";
+
+    fn onboarding_bundle(dir: &Path) -> ProfileBundle {
+        let mut source = ProfileManager::with_config_dir(dir).unwrap();
+        fs::create_dir_all(dir.join("themes")).unwrap();
+        fs::write(dir.join("themes").join("solarized.yaml"), SOLARIZED_YAML).unwrap();
+        let id = source.create_profile("Onboarding".to_string(), None).unwrap();
+        let mut config = source.effective_config(&id).unwrap();
+        config.theme = "Solarized".to_string();
+        config.font_size = 20;
+        source.set_profile_config(&id, config).unwrap();
+        source.add_auto_switch_rule(id, rule("~/work", AutoSwitchRuleType::StartsWith)).unwrap();
+
+        let path = dir.join("onboarding.json");
+        source.export_profile(&id, &path).unwrap();
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_export_flattens_profile_and_bundles_theme() {
+        let dir = tempdir().unwrap();
+        let bundle = onboarding_bundle(dir.path());
+        assert_eq!(bundle.version, BUNDLE_VERSION);
+        assert_eq!(bundle.profile.parent, None);
+        assert!(bundle.profile.overrides.is_empty());
+        let config = bundle.profile.config.as_ref().unwrap();
+        assert_eq!((config.theme.as_str(), config.font_size), ("Solarized", 20));
+        assert_eq!(bundle.theme_yaml.as_deref(), Some(SOLARIZED_YAML));
+        assert_eq!(bundle.profile.auto_switch_rules.len(), 1);
+    }
+
+    #[test]
+    fn test_import_into_fresh_install() {
+        let (source_dir, target_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let bundle = onboarding_bundle(source_dir.path());
+        let mut target = ProfileManager::with_config_dir(target_dir.path()).unwrap();
+
+        let outcome = target.import_bundle(bundle.clone(), ImportConflictStrategy::Rename).unwrap();
+        assert_eq!(outcome, ImportOutcome::Created(bundle.profile.id));
+        assert_eq!(target.effective_config(&bundle.profile.id).unwrap().font_size, 20);
+        let (_, theme) = target.find_theme_file("Solarized").expect("theme was not installed");
+        assert_eq!(theme, SOLARIZED_YAML);
+
+        // The imported profile survives a reload.
+        let reloaded = ProfileManager::with_config_dir(target_dir.path()).unwrap();
+        assert!(reloaded.profiles.contains_key(&bundle.profile.id));
+    }
+
+    #[test]
+    fn test_import_conflict_strategies() {
+        let (source_dir, target_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let bundle = onboarding_bundle(source_dir.path());
+        let mut target = ProfileManager::with_config_dir(target_dir.path()).unwrap();
+        target.import_bundle(bundle.clone(), ImportConflictStrategy::Rename).unwrap();
+        let count = target.profiles.len();
+
+        assert_eq!(
+            target.import_bundle(bundle.clone(), ImportConflictStrategy::Skip).unwrap(),
+            ImportOutcome::Skipped(bundle.profile.id)
+        );
+        assert_eq!(target.profiles.len(), count);
+
+        let outcome = target.import_bundle(bundle.clone(), ImportConflictStrategy::Rename).unwrap();
+        let ImportOutcome::Renamed { id, name } = outcome else { panic!("unexpected outcome {:?}", outcome) };
+        assert_ne!(id, bundle.profile.id);
+        assert_eq!(name, "Onboarding (2)");
+        assert_eq!(target.profiles.len(), count + 1);
+
+        // A name collision with a different ID overwrites in place, keeping the local ID.
+        let mut edited = bundle.clone();
+        edited.profile.id = Uuid::new_v4();
+        edited.profile.config.as_mut().unwrap().font_size = 12;
+        assert_eq!(
+            target.import_bundle(edited, ImportConflictStrategy::Overwrite).unwrap(),
+            ImportOutcome::Overwritten(bundle.profile.id)
+        );
+        assert_eq!(target.effective_config(&bundle.profile.id).unwrap().font_size, 12);
+        assert_eq!(target.profiles.len(), count + 1);
+    }
+
+    #[test]
+    fn test_rename_numbers_only_taken_names() {
+        let (source_dir, target_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let bundle = onboarding_bundle(source_dir.path());
+        let mut target = ProfileManager::with_config_dir(target_dir.path()).unwrap();
+        target.import_bundle(bundle.clone(), ImportConflictStrategy::Rename).unwrap();
+        let renamed_name = |target: &mut ProfileManager, bundle: ProfileBundle| {
+            match target.import_bundle(bundle, ImportConflictStrategy::Rename).unwrap() {
+                ImportOutcome::Renamed { name, .. } => name,
+                outcome => panic!("unexpected outcome {:?}", outcome),
+            }
+        };
+
+        // Same ID under a free name: only the ID is replaced, so nothing was renamed.
+        let mut retitled = bundle.clone();
+        retitled.profile.name = "Onboarding v2".to_string();
+        let outcome = target.import_bundle(retitled, ImportConflictStrategy::Rename).unwrap();
+        let ImportOutcome::Created(id) = outcome else { panic!("unexpected outcome {:?}", outcome) };
+        assert_ne!(id, bundle.profile.id);
+        assert_eq!(target.profiles[&id].name, "Onboarding v2");
+
+        let mut numbered = bundle.clone();
+        numbered.profile.id = Uuid::new_v4();
+        numbered.profile.name = "Onboarding (2)".to_string();
+        target.import_bundle(numbered.clone(), ImportConflictStrategy::Rename).unwrap();
+        numbered.profile.id = Uuid::new_v4();
+        assert_eq!(renamed_name(&mut target, numbered), "Onboarding (3)");
+        assert_eq!(renamed_name(&mut target, bundle), "Onboarding (4)");
+    }
+
+    #[test]
+    fn test_overwrite_by_id_keeps_names_unique() {
+        let (source_dir, target_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let bundle = onboarding_bundle(source_dir.path());
+        let mut target = ProfileManager::with_config_dir(target_dir.path()).unwrap();
+        target.import_bundle(bundle.clone(), ImportConflictStrategy::Rename).unwrap();
+        let other_id = target.create_profile("Work".to_string(), None).unwrap();
+
+        // The ID is the imported profile's, the name another profile's.
+        let mut retitled = bundle.clone();
+        retitled.profile.name = "Work".to_string();
+        assert_eq!(
+            target.import_bundle(retitled, ImportConflictStrategy::Overwrite).unwrap(),
+            ImportOutcome::Overwritten(bundle.profile.id)
+        );
+        assert_eq!(target.profiles[&bundle.profile.id].name, "Work (2)");
+        assert_eq!(target.profiles[&other_id].name, "Work");
+
+        // Re-importing under its own name keeps it.
+        let mut again = bundle.clone();
+        again.profile.name = "Work (2)".to_string();
+        target.import_bundle(again, ImportConflictStrategy::Overwrite).unwrap();
+        assert_eq!(target.profiles[&bundle.profile.id].name, "Work (2)");
+    }
+
+    #[test]
+    fn test_import_keeps_differing_local_theme_unless_overwriting() {
+        let (source_dir, target_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let bundle = onboarding_bundle(source_dir.path());
+        let local_theme = "name: Solarized\nauthor: Me\n";
+        fs::create_dir_all(target_dir.path().join("themes")).unwrap();
+        fs::write(target_dir.path().join("themes").join("mine.yaml"), local_theme).unwrap();
+        let mut target = ProfileManager::with_config_dir(target_dir.path()).unwrap();
+
+        target.import_bundle(bundle.clone(), ImportConflictStrategy::Rename).unwrap();
+        assert_eq!(target.find_theme_file("Solarized").unwrap().1, local_theme);
+
+        target.import_bundle(bundle, ImportConflictStrategy::Overwrite).unwrap();
+        let (path, theme) = target.find_theme_file("Solarized").unwrap();
+        assert_eq!((path.file_name().unwrap().to_str().unwrap(), theme.as_str()), ("mine.yaml", SOLARIZED_YAML));
     }
 }
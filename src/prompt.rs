@@ -1,7 +1,8 @@
 use iced::{
     widget::{column, row, text, text_input, Space},
-    Alignment, Element, Length,
+    Alignment, Color, Element, Length,
 };
+use std::path::{Path, PathBuf};
 use crate::config::theme::WarpTheme;
 use crate::config::PromptSettings;
 use crate::input::EditorMessage; // Use the new input module
@@ -13,20 +14,111 @@ pub enum PromptMessage {
     Editor(EditorMessage),
 }
 
+/// Renders the informational line (user, host, cwd, git status) shown above the input.
+/// The working directory is kept up to date from the shell's OSC 7 reports.
+pub struct PromptRenderer {
+    settings: PromptSettings,
+    current_dir: Option<PathBuf>,
+}
+
+impl PromptRenderer {
+    pub fn new(settings: PromptSettings) -> Self {
+        PromptRenderer {
+            settings,
+            current_dir: None,
+        }
+    }
+
+    pub fn set_current_dir(&mut self, path: PathBuf) {
+        self.current_dir = Some(path);
+    }
+
+    pub fn set_settings(&mut self, settings: PromptSettings) {
+        self.settings = settings;
+    }
+
+    pub fn current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+
+    fn cwd_display(&self) -> String {
+        match &self.current_dir {
+            Some(path) => display_cwd(path, dirs::home_dir().as_deref()),
+            None => "~".to_string(), // Not reported yet
+        }
+    }
+
+    /// The prompt line as plain text, for contexts that can't render styled segments.
+    pub fn render_prompt_text(&self) -> String {
+        let mut segments = Vec::new();
+        if self.settings.show_user {
+            segments.push(format!("{}user", self.settings.user_symbol));
+        }
+        if self.settings.show_host {
+            segments.push(format!("{}host", self.settings.host_symbol));
+        }
+        if self.settings.show_cwd {
+            segments.push(format!("{}{}", self.settings.cwd_symbol, self.cwd_display()));
+        }
+        segments.join(" ")
+    }
+
+    pub fn render_prompt<'a, M: 'a>(&self, theme: &WarpTheme) -> Element<'a, M> {
+        let foreground_color = theme.get_foreground_color();
+
+        let mut prompt_elements = Vec::new();
+
+        if self.settings.show_user {
+            prompt_elements.push(text(format!("{}user", self.settings.user_symbol)).color(foreground_color).size(16).into());
+        }
+        if self.settings.show_host {
+            prompt_elements.push(text(format!("{}host", self.settings.host_symbol)).color(foreground_color).size(16).into());
+        }
+        if self.settings.show_cwd {
+            prompt_elements.push(text(format!("{}{}", self.settings.cwd_symbol, self.cwd_display())).color(foreground_color).size(16).into());
+        }
+        if self.settings.show_git_status {
+            // Placeholder for git status
+            prompt_elements.push(text(format!("{}main*", self.settings.git_symbol)).color(Color::from_rgb(0.0, 0.7, 0.0)).size(16).into());
+        }
+
+        row(prompt_elements).spacing(5).align_items(Alignment::Center).into()
+    }
+}
+
+/// Formats `path` for the prompt, abbreviating the home directory to `~`.
+pub fn display_cwd(path: &Path, home: Option<&Path>) -> String {
+    if let Some(home) = home {
+        if let Ok(relative) = path.strip_prefix(home) {
+            return if relative.as_os_str().is_empty() {
+                "~".to_string()
+            } else {
+                format!("~/{}", relative.display())
+            };
+        }
+    }
+    path.display().to_string()
+}
+
 pub struct Prompt {
     input_value: String,
     settings: PromptSettings,
-    // Potentially add state for current working directory, git status, etc.
+    renderer: PromptRenderer,
 }
 
 impl Prompt {
     pub fn new(settings: PromptSettings) -> Self {
         Prompt {
             input_value: String::new(),
+            renderer: PromptRenderer::new(settings.clone()),
             settings,
         }
     }
 
+    pub fn set_current_dir(&mut self, path: PathBuf) {
+        self.renderer.set_current_dir(path);
+    }
+
     pub fn update(&mut self, message: PromptMessage) -> Option<String> {
         match message {
             PromptMessage::InputChanged(value) => {
@@ -56,26 +148,9 @@ impl Prompt {
     }
 
     pub fn view(&self, theme: &WarpTheme) -> Element<PromptMessage> {
-        let foreground_color = theme.get_foreground_color();
         let accent_color = theme.get_accent_color();
 
-        let mut prompt_elements = Vec::new();
-
-        if self.settings.show_user {
-            prompt_elements.push(text(format!("{}user", self.settings.user_symbol)).color(foreground_color).size(16));
-        }
-        if self.settings.show_host {
-            prompt_elements.push(text(format!("{}host", self.settings.host_symbol)).color(foreground_color).size(16));
-        }
-        if self.settings.show_cwd {
-            prompt_elements.push(text(format!("{}~", self.settings.cwd_symbol)).color(foreground_color).size(16)); // Placeholder for actual CWD
-        }
-        if self.settings.show_git_status {
-            // Placeholder for git status
-            prompt_elements.push(text(format!("{}main*", self.settings.git_symbol)).color(Color::from_rgb(0.0, 0.7, 0.0)).size(16));
-        }
-
-        let prompt_line = row(prompt_elements).spacing(5).align_items(Alignment::Center);
+        let prompt_line = self.renderer.render_prompt(theme);
 
         column![
             prompt_line,
@@ -102,3 +177,31 @@ impl Prompt {
         &self.input_value
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_cwd_abbreviates_home() {
+        let home = Path::new("/home/alice");
+        assert_eq!(display_cwd(Path::new("/home/alice"), Some(home)), "~");
+        assert_eq!(display_cwd(Path::new("/home/alice/src/gg"), Some(home)), "~/src/gg");
+        assert_eq!(display_cwd(Path::new("/home/alicia"), Some(home)), "/home/alicia");
+        assert_eq!(display_cwd(Path::new("/tmp"), None), "/tmp");
+    }
+
+    #[test]
+    fn test_render_prompt_text_uses_current_dir() {
+        let settings = PromptSettings {
+            show_user: false,
+            show_host: false,
+            cwd_symbol: String::new(),
+            ..PromptSettings::default()
+        };
+        let mut renderer = PromptRenderer::new(settings);
+        assert_eq!(renderer.render_prompt_text(), "~");
+        renderer.set_current_dir(PathBuf::from("/var/log"));
+        assert_eq!(renderer.render_prompt_text(), "/var/log");
+    }
+}
@@ -47,6 +47,7 @@ impl KeybindingEditor {
                     "open_profile_manager" => self.current_keybindings.open_profile_manager = new_value,
                     "open_workflow_browser" => self.current_keybindings.open_workflow_browser = new_value,
                     "open_warp_drive" => self.current_keybindings.open_warp_drive = new_value,
+                    "history_search" => self.current_keybindings.history_search = new_value,
                     _ => info!("Unknown keybinding: {}", key_name),
                 }
             }
@@ -87,6 +88,7 @@ impl KeybindingEditor {
             self.keybinding_row("Open Profile Manager", "open_profile_manager", &self.current_keybindings.open_profile_manager, theme),
             self.keybinding_row("Open Workflow Browser", "open_workflow_browser", &self.current_keybindings.open_workflow_browser, theme),
             self.keybinding_row("Open Warp Drive", "open_warp_drive", &self.current_keybindings.open_warp_drive, theme),
+            self.keybinding_row("Search History", "history_search", &self.current_keybindings.history_search, theme),
         ]
         .spacing(10)
         .width(Length::Fill);
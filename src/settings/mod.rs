@@ -7,7 +7,7 @@ use iced::{
     Element, Length, Color,
 };
 use crate::config::{UserPreferences, KeyBindings, PromptSettings};
-use crate::terminal::Message;
+use crate::terminal::TerminalMessage;
 use crate::config::theme::WarpTheme; // Updated import
 use crate::settings::keybinding_editor::KeybindingEditor; // New import
 use crate::settings::theme_editor::ThemeEditor; // New import
@@ -100,7 +100,7 @@ impl PreferencesWindow {
         self.is_visible
     }
 
-    pub fn update(&mut self, message: PreferencesMessage) -> Option<Message> {
+    pub fn update(&mut self, message: PreferencesMessage) -> Option<TerminalMessage> {
         match message {
             PreferencesMessage::ToggleVisibility => {
                 self.is_visible = !self.is_visible;
@@ -182,7 +182,7 @@ impl PreferencesWindow {
             }
             PreferencesMessage::SavePreferences => {
                 let font_size_parsed = self.font_size.parse::<u16>().unwrap_or(16);
-                Some(Message::PreferencesSaved {
+                Some(TerminalMessage::PreferencesSaved {
                     preferences: self.preferences.clone(),
                     font_family: self.font_family.clone(),
                     font_size: font_size_parsed,
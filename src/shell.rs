@@ -1,33 +1,68 @@
-use portable_pty::{CommandBuilder, PtySize, PtyPair, MasterPty, SlavePty};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use portable_pty::{native_pty_system, Child, MasterPty, PtySize};
 use tokio::sync::mpsc;
 use log::{info, error};
-use std::io::Write; // For `write_all` on `SlavePty`
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-#[derive(Debug)]
+use crate::shell_integration;
+
+#[derive(Debug, Clone)]
 pub enum ShellMessage {
-    Output(String),
+    /// Raw bytes read from the PTY. They may split escape sequences and UTF-8
+    /// characters; `vt::Screen` reassembles them.
+    Output(Vec<u8>),
     Command(String),
     Resize(PtySize),
     Exit(u32),
     Error(String),
 }
 
+/// Output produced by the shell, as consumed by `blocks::Block::append_output`.
+/// A PTY merges stdout and stderr, so everything read from the master arrives as `Stdout`.
+#[derive(Debug, Clone)]
+pub enum ShellOutput {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+/// Control characters that can be sent to the foreground process of the shell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlKey {
+    Interrupt,  // Ctrl-C
+    EndOfFile,  // Ctrl-D
+    Suspend,    // Ctrl-Z
+    Quit,       // Ctrl-\
+    ClearLine,  // Ctrl-U
+}
+
+impl ControlKey {
+    pub fn as_byte(self) -> u8 {
+        match self {
+            ControlKey::Interrupt => 0x03,
+            ControlKey::EndOfFile => 0x04,
+            ControlKey::Suspend => 0x1a,
+            ControlKey::Quit => 0x1c,
+            ControlKey::ClearLine => 0x15,
+        }
+    }
+}
+
 pub struct Shell {
-    pty_pair: PtyPair,
-    master_writer: tokio::io::Stdin, // Placeholder for actual master writer
-    _master_reader_task: tokio::task::JoinHandle<()>,
-    _shell_process: portable_pty::Child,
-    tx: mpsc::Sender<ShellMessage>,
+    master: Box<dyn MasterPty + Send>,
+    master_writer: Box<dyn Write + Send>,
+    _master_reader_thread: std::thread::JoinHandle<()>,
+    // Shared with the reader thread, which reports the exit status once the PTY closes.
+    shell_process: Arc<Mutex<Box<dyn Child + Send + Sync>>>,
 }
 
 impl Shell {
-    pub async fn new(
+    pub fn new(
         shell_path: &str,
         tx: mpsc::Sender<ShellMessage>,
     ) -> anyhow::Result<Self> {
         info!("Spawning shell: {}", shell_path);
-        let pty_system = portable_pty::PtySystem::native()?;
+        let pty_system = native_pty_system();
         let pty_pair = pty_system.openpty(PtySize {
             rows: 24,
             cols: 80,
@@ -35,77 +70,258 @@ impl Shell {
             pixel_height: 0,
         })?;
 
-        let mut cmd = CommandBuilder::new(shell_path);
-        cmd.arg("-l"); // Login shell for bash/zsh
+        // Injects the OSC 133 integration script for bash/zsh/fish; other shells start as login shells.
+        let mut cmd = shell_integration::build_command(shell_path)?;
         cmd.env("TERM", "xterm-256color"); // Set terminal type
 
-        let shell_process = pty_pair.slave.spawn_command(cmd)?;
+        let shell_process = Arc::new(Mutex::new(pty_pair.slave.spawn_command(cmd)?));
 
         // It's important to drop the slave pty on the main thread,
         // otherwise the shell process will not exit when the master dies
         drop(pty_pair.slave);
 
-        let mut master_reader = pty_pair.master.try_clone_reader()?;
-        let master_writer = pty_pair.master.try_clone_writer()?;
+        let master = pty_pair.master;
+        let mut master_reader = master.try_clone_reader()?;
+        let master_writer = master.take_writer()?;
+        let reader_process = Arc::clone(&shell_process);
 
-        let output_tx = tx.clone();
-        let master_reader_task = tokio::spawn(async move {
-            let mut buf = [0u8; 4096];
-            loop {
-                match master_reader.read(&mut buf).await {
-                    Ok(0) => {
-                        info!("Shell master reader closed.");
-                        break;
-                    }
-                    Ok(n) => {
-                        if let Ok(s) = String::from_utf8(buf[..n].to_vec()) {
-                            if output_tx.send(ShellMessage::Output(s)).await.is_err() {
+        // The PTY reader is a blocking `std::io::Read`, so it gets its own OS thread
+        // rather than occupying a tokio worker.
+        let master_reader_thread = std::thread::Builder::new()
+            .name("shell-pty-reader".to_string())
+            .spawn(move || {
+                let mut buf = [0u8; 4096];
+                loop {
+                    match master_reader.read(&mut buf) {
+                        Ok(0) => {
+                            info!("Shell master reader closed.");
+                            break;
+                        }
+                        Ok(n) => {
+                            if tx.blocking_send(ShellMessage::Output(buf[..n].to_vec())).is_err() {
                                 error!("Failed to send shell output message, receiver dropped.");
                                 break;
                             }
-                        } else {
-                            error!("Received invalid UTF-8 from shell.");
-                            // Handle non-UTF8 output, perhaps send raw bytes or a placeholder
                         }
-                    }
-                    Err(e) => {
-                        error!("Error reading from shell master: {:?}", e);
-                        if output_tx.send(ShellMessage::Error(e.to_string())).await.is_err() {
-                            error!("Failed to send shell error message, receiver dropped.");
+                        Err(e) => {
+                            // EIO is how Linux reports that the slave side has gone away.
+                            info!("Shell master reader stopped: {:?}", e);
+                            break;
                         }
-                        break;
                     }
                 }
-            }
-        });
+                // The PTY closes when the shell exits, so its status is due.
+                let message = match wait_blocking(&reader_process) {
+                    Ok(code) => ShellMessage::Exit(code),
+                    Err(e) => ShellMessage::Error(e.to_string()),
+                };
+                if tx.blocking_send(message).is_err() {
+                    error!("Failed to send shell exit message, receiver dropped.");
+                }
+            })?;
 
         Ok(Self {
-            pty_pair,
-            master_writer: tokio::io::stdin(), // This is a placeholder, actual writer should be used
-            _master_reader_task: master_reader_task,
-            _shell_process: shell_process,
-            tx,
+            master,
+            master_writer,
+            _master_reader_thread: master_reader_thread,
+            shell_process,
         })
     }
 
-    pub async fn write_to_shell(&mut self, input: &str) -> anyhow::Result<()> {
-        // In a real implementation, you'd write to `self.pty_pair.master.writer()`
-        // For this placeholder, we'll just log and simulate.
-        info!("Writing to shell (simulated): {}", input);
-        // Example: self.pty_pair.master.writer().write_all(input.as_bytes()).await?;
-        // self.pty_pair.master.writer().write_all(b"\r\n").await?; // Send newline
+    /// Writes raw bytes to the shell's PTY, exactly as given.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        self.master_writer.write_all(bytes)?;
+        self.master_writer.flush()?;
         Ok(())
     }
 
+    /// Writes user input to the shell. No newline is appended; callers submitting a
+    /// command should include the trailing `\r` themselves (see `submit_command`).
+    pub async fn write_to_shell(&mut self, input: &str) -> anyhow::Result<()> {
+        self.write_bytes(input.as_bytes())
+    }
+
+    /// Sends a complete command line followed by a carriage return, as a terminal would on Enter.
+    pub fn submit_command(&mut self, command: &str) -> anyhow::Result<()> {
+        info!("Submitting command to shell: {}", command);
+        let mut bytes = Vec::with_capacity(command.len() + 1);
+        bytes.extend_from_slice(command.as_bytes());
+        bytes.push(b'\r');
+        self.write_bytes(&bytes)
+    }
+
+    /// Sends a control character (e.g. Ctrl-C) to the foreground process.
+    pub fn send_control(&mut self, key: ControlKey) -> anyhow::Result<()> {
+        info!("Sending control key to shell: {:?}", key);
+        self.write_bytes(&[key.as_byte()])
+    }
+
     pub async fn resize(&mut self, size: PtySize) -> anyhow::Result<()> {
         info!("Resizing shell to: {:?}", size);
-        self.pty_pair.master.resize(size)?;
+        self.master.resize(size)?;
         Ok(())
     }
 
     pub async fn wait_for_exit(&mut self) -> anyhow::Result<u32> {
-        let exit_status = self._shell_process.wait().await?;
-        info!("Shell exited with status: {:?}", exit_status);
-        Ok(exit_status.code().unwrap_or(1) as u32)
+        // `Child::wait` blocks, so poll instead to keep the runtime free.
+        loop {
+            if let Some(exit_status) = self.shell_process.lock().unwrap().try_wait()? {
+                info!("Shell exited with status: {:?}", exit_status);
+                return Ok(exit_status.exit_code());
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    pub fn kill(&mut self) -> anyhow::Result<()> {
+        self.shell_process.lock().unwrap().kill()?;
+        Ok(())
+    }
+}
+
+/// `wait_for_exit` for the reader thread. It polls too, so `kill` can take the lock meanwhile.
+fn wait_blocking(process: &Mutex<Box<dyn Child + Send + Sync>>) -> std::io::Result<u32> {
+    loop {
+        if let Some(exit_status) = process.lock().unwrap().try_wait()? {
+            info!("Shell exited with status: {:?}", exit_status);
+            return Ok(exit_status.exit_code());
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+
+    async fn read_until(rx: &mut mpsc::Receiver<ShellMessage>, needle: impl Fn(&str) -> bool) -> String {
+        let mut output = String::new();
+        let _ = timeout(Duration::from_secs(5), async {
+            while let Some(message) = rx.recv().await {
+                if let ShellMessage::Output(bytes) = message {
+                    output.push_str(&String::from_utf8_lossy(&bytes));
+                    if needle(&output) {
+                        break;
+                    }
+                }
+            }
+        })
+        .await;
+        output
+    }
+
+    // The PTY echoes our input back and the prompt may land on the same line as the
+    // command's output, so count occurrences rather than matching whole lines.
+    fn occurrences(output: &str, needle: &str) -> usize {
+        output.matches(needle).count()
+    }
+
+    #[tokio::test]
+    async fn test_echo_round_trip() {
+        let (tx, mut rx) = mpsc::channel(64);
+        let mut shell = Shell::new("/bin/sh", tx).expect("failed to spawn /bin/sh");
+
+        shell.write_to_shell("echo hi\r").await.unwrap();
+        // Once for the echoed input, once for the command's output.
+        let output = read_until(&mut rx, |o| occurrences(o, "hi") >= 2).await;
+        assert!(occurrences(&output, "hi") >= 2, "unexpected shell output: {:?}", output);
+
+        shell.kill().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bash_integration_reports_blocks() {
+        use crate::shell_integration::{BlockEvent, BlockTracker};
+
+        if !std::path::Path::new("/bin/bash").exists() {
+            return;
+        }
+        let (tx, mut rx) = mpsc::channel(64);
+        let mut shell = Shell::new("/bin/bash", tx).expect("failed to spawn /bin/bash");
+        let mut tracker = BlockTracker::new();
+        let mut events = Vec::new();
+
+        let _ = timeout(Duration::from_secs(5), async {
+            let mut submitted = false;
+            while let Some(message) = rx.recv().await {
+                if let ShellMessage::Output(bytes) = message {
+                    events.extend(tracker.feed(&bytes));
+                }
+                if !submitted && events.contains(&BlockEvent::PromptStarted) {
+                    shell.submit_command("(exit 3)").unwrap();
+                    submitted = true;
+                }
+                if events.iter().any(|e| matches!(e, BlockEvent::CommandFinished { .. })) {
+                    break;
+                }
+            }
+        })
+        .await;
+
+        assert!(
+            events.iter().any(|e| matches!(e, BlockEvent::CommandStarted { command, .. } if command == "(exit 3)")),
+            "unexpected events: {:?}",
+            events
+        );
+        assert!(
+            events.iter().any(|e| matches!(e, BlockEvent::CommandFinished { exit_code: 3, .. })),
+            "unexpected events: {:?}",
+            events
+        );
+        assert!(
+            events.iter().any(|e| matches!(e, BlockEvent::DirectoryChanged(_))),
+            "unexpected events: {:?}",
+            events
+        );
+        shell.kill().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_control_d_exits_shell() {
+        let (tx, _rx) = mpsc::channel(64);
+        let mut shell = Shell::new("/bin/sh", tx).expect("failed to spawn /bin/sh");
+
+        shell.send_control(ControlKey::EndOfFile).unwrap();
+        let exit_code = timeout(Duration::from_secs(5), shell.wait_for_exit())
+            .await
+            .expect("shell did not exit after Ctrl-D")
+            .unwrap();
+        assert_eq!(exit_code, 0);
+    }
+
+    #[tokio::test]
+    async fn test_exit_is_reported_by_the_reader() {
+        let (tx, mut rx) = mpsc::channel(64);
+        let mut shell = Shell::new("/bin/sh", tx).expect("failed to spawn /bin/sh");
+
+        shell.submit_command("exit 7").unwrap();
+        let exit = timeout(Duration::from_secs(5), async {
+            while let Some(message) = rx.recv().await {
+                if let ShellMessage::Exit(code) = message {
+                    return Some(code);
+                }
+            }
+            None
+        })
+        .await
+        .expect("shell exit was not reported");
+        assert_eq!(exit, Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_control_c_interrupts_foreground_command() {
+        let (tx, mut rx) = mpsc::channel(64);
+        let mut shell = Shell::new("/bin/sh", tx).expect("failed to spawn /bin/sh");
+
+        shell.submit_command("sleep 30").unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        shell.send_control(ControlKey::Interrupt).unwrap();
+        shell.submit_command("echo $((40 + 2))").unwrap();
+
+        let output = read_until(&mut rx, |o| o.contains("42")).await;
+        assert!(output.contains("42"), "unexpected shell output: {:?}", output);
+
+        shell.kill().unwrap();
     }
 }
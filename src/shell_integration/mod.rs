@@ -0,0 +1,116 @@
+// Shell integration: bundled bash/zsh/fish scripts that make the shell emit
+// OSC 133 semantic prompt marks and OSC 7 working-directory reports, and the
+// machinery that turns those into block boundaries and directory changes.
+
+pub mod scanner; // Splits integration OSC sequences out of PTY output
+pub mod tracker; // Maps semantic marks to block lifecycle events
+
+// Re-export for easier access
+pub use scanner::{OscScanner, SemanticMark, ShellEvent};
+pub use tracker::{BlockEvent, BlockTracker};
+
+use portable_pty::CommandBuilder;
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::info;
+
+const BASH_SCRIPT: &str = crate::include_asset!("assets/shell_integration/bash_integration.bash");
+const ZSH_SCRIPT: &str = crate::include_asset!("assets/shell_integration/zsh_integration.zsh");
+const FISH_SCRIPT: &str = crate::include_asset!("assets/shell_integration/fish_integration.fish");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+    Other,
+}
+
+impl ShellKind {
+    pub fn from_path(shell_path: &str) -> Self {
+        let name = Path::new(shell_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match name.as_str() {
+            "bash" => ShellKind::Bash,
+            "zsh" => ShellKind::Zsh,
+            "fish" => ShellKind::Fish,
+            _ => ShellKind::Other,
+        }
+    }
+}
+
+/// Builds the command used to spawn `shell_path` with the integration script for its kind
+/// injected. Shells we have no script for are started as plain login shells.
+pub fn build_command(shell_path: &str) -> anyhow::Result<CommandBuilder> {
+    let mut cmd = CommandBuilder::new(shell_path);
+    match ShellKind::from_path(shell_path) {
+        ShellKind::Bash => {
+            // `--rcfile` is ignored by login shells, so bash starts interactive-only and the
+            // script sources ~/.bashrc itself.
+            let rcfile = write_script(&integration_dir().join("bash"), "warp_integration.bash", BASH_SCRIPT)?;
+            cmd.arg("--rcfile");
+            cmd.arg(rcfile);
+        }
+        ShellKind::Zsh => {
+            let zdotdir = integration_dir().join("zsh");
+            write_script(&zdotdir, ".zshenv", ZSH_SCRIPT)?;
+            if let Ok(user_zdotdir) = std::env::var("ZDOTDIR") {
+                cmd.env("WARP_USER_ZDOTDIR", user_zdotdir);
+            }
+            cmd.env("ZDOTDIR", zdotdir);
+            cmd.arg("-l");
+        }
+        ShellKind::Fish => {
+            let script = write_script(&integration_dir().join("fish"), "warp_integration.fish", FISH_SCRIPT)?;
+            cmd.arg("-l");
+            cmd.arg("--init-command");
+            cmd.arg(format!("source '{}'", script.to_string_lossy().replace('\'', "\\'")));
+        }
+        ShellKind::Other => {
+            cmd.arg("-l");
+        }
+    }
+    Ok(cmd)
+}
+
+fn integration_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("warp-terminal-clone")
+        .join("shell_integration")
+}
+
+fn write_script(dir: &Path, file_name: &str, contents: &str) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(file_name);
+    // Skip the write when unchanged so concurrently starting sessions don't race on the file.
+    if !fs::read_to_string(&path).is_ok_and(|existing| existing == contents) {
+        fs::write(&path, contents)?;
+        info!("Wrote shell integration script to {:?}", path);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_kind_from_path() {
+        assert_eq!(ShellKind::from_path("/bin/bash"), ShellKind::Bash);
+        assert_eq!(ShellKind::from_path("/usr/local/bin/zsh"), ShellKind::Zsh);
+        assert_eq!(ShellKind::from_path("fish"), ShellKind::Fish);
+        assert_eq!(ShellKind::from_path("/bin/sh"), ShellKind::Other);
+    }
+
+    #[test]
+    fn test_scripts_emit_all_marks() {
+        for script in [BASH_SCRIPT, ZSH_SCRIPT, FISH_SCRIPT] {
+            for mark in ["133;A", "133;B", "133;C", "133;D", "]7;file://"] {
+                assert!(script.contains(mark), "script is missing {}", mark);
+            }
+        }
+    }
+}
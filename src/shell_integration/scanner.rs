@@ -0,0 +1,291 @@
+use log::debug;
+use std::path::PathBuf;
+
+/// Longest integration OSC we are willing to buffer before giving up on it.
+const MAX_SEQUENCE_LEN: usize = 4096;
+const INTEGRATION_PREFIXES: &[&[u8]] = &[b"133;", b"7;"];
+
+/// An OSC 133 semantic prompt mark (the FinalTerm protocol).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SemanticMark {
+    /// `A`: the shell is about to draw the prompt.
+    PromptStart,
+    /// `B`: the prompt is drawn and user input begins.
+    CommandStart,
+    /// `C`: the command line was accepted and its output follows.
+    CommandExecuted,
+    /// `D[;<exit code>]`: the command finished.
+    CommandFinished { exit_code: Option<i32> },
+}
+
+impl SemanticMark {
+    fn parse(body: &[u8]) -> Option<Self> {
+        let body = std::str::from_utf8(body).ok()?;
+        let mut parts = body.strip_prefix("133;")?.split(';');
+        match parts.next()? {
+            "A" => Some(SemanticMark::PromptStart),
+            "B" => Some(SemanticMark::CommandStart),
+            "C" => Some(SemanticMark::CommandExecuted),
+            "D" => Some(SemanticMark::CommandFinished {
+                exit_code: parts.next().and_then(|code| code.trim().parse().ok()),
+            }),
+            other => {
+                debug!("Ignoring unknown OSC 133 mark '{}'", other);
+                None
+            }
+        }
+    }
+}
+
+/// Parses the body of an OSC 7 report, `7;file://<host><percent-encoded path>`.
+/// The host is ignored: the shell runs on this machine.
+fn parse_working_directory(body: &[u8]) -> Option<PathBuf> {
+    let url = std::str::from_utf8(body).ok()?.strip_prefix("7;")?;
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    Some(PathBuf::from(percent_decode(path)))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShellEvent {
+    /// Bytes that are not part of an integration sequence, in stream order.
+    Output(Vec<u8>),
+    Mark(SemanticMark),
+    /// OSC 7: the shell's working directory.
+    WorkingDirectory(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Normal,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+/// Splits raw PTY bytes into ordinary output and shell-integration OSC sequences.
+/// Integration sequences are removed from the output; every other escape sequence
+/// (including non-integration OSCs) passes through untouched for `vt::Screen`.
+/// Sequences split across reads are held back until they can be classified.
+#[derive(Debug, Clone)]
+pub struct OscScanner {
+    state: State,
+    pending: Vec<u8>,
+}
+
+impl OscScanner {
+    pub fn new() -> Self {
+        OscScanner { state: State::Normal, pending: Vec::new() }
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<ShellEvent> {
+        let mut events = Vec::new();
+        let mut output = Vec::with_capacity(bytes.len());
+        for &byte in bytes {
+            self.advance(byte, &mut output, &mut events);
+        }
+        if !output.is_empty() {
+            events.push(ShellEvent::Output(output));
+        }
+        events
+    }
+
+    fn advance(&mut self, byte: u8, output: &mut Vec<u8>, events: &mut Vec<ShellEvent>) {
+        match self.state {
+            State::Normal => {
+                if byte == 0x1b {
+                    self.pending.push(byte);
+                    self.state = State::Escape;
+                } else {
+                    output.push(byte);
+                }
+            }
+            State::Escape => {
+                if byte == b']' {
+                    self.pending.push(byte);
+                    self.state = State::Osc;
+                } else {
+                    self.flush_pending(output);
+                    self.advance(byte, output, events);
+                }
+            }
+            State::Osc => match byte {
+                0x07 => self.finish_sequence(&[0x07], output, events),
+                0x1b => self.state = State::OscEscape,
+                _ => {
+                    self.pending.push(byte);
+                    let body = &self.pending[2..];
+                    let could_match = INTEGRATION_PREFIXES
+                        .iter()
+                        .any(|prefix| body.starts_with(prefix) || prefix.starts_with(body));
+                    if !could_match || self.pending.len() > MAX_SEQUENCE_LEN {
+                        // Not ours: hand it to the terminal emulator as-is.
+                        self.flush_pending(output);
+                    }
+                }
+            },
+            State::OscEscape => {
+                // ESC \ is the proper string terminator; anything else ends the OSC early,
+                // and the ESC starts the next sequence.
+                if byte == b'\\' {
+                    self.finish_sequence(b"\x1b\\", output, events);
+                } else {
+                    self.finish_sequence(&[], output, events);
+                    self.advance(0x1b, output, events);
+                    self.advance(byte, output, events);
+                }
+            }
+        }
+    }
+
+    /// Ends the buffered OSC, which `terminator` closed.
+    fn finish_sequence(&mut self, terminator: &[u8], output: &mut Vec<u8>, events: &mut Vec<ShellEvent>) {
+        let pending = std::mem::take(&mut self.pending);
+        self.state = State::Normal;
+        let body = &pending[2..];
+        let event = SemanticMark::parse(body)
+            .map(ShellEvent::Mark)
+            .or_else(|| parse_working_directory(body).map(ShellEvent::WorkingDirectory));
+        match event {
+            Some(event) => {
+                if !output.is_empty() {
+                    events.push(ShellEvent::Output(std::mem::take(output)));
+                }
+                events.push(event);
+            }
+            // It only looked like ours, e.g. OSC 1 (icon name) or an unknown 133 mark.
+            None => {
+                output.extend_from_slice(&pending);
+                output.extend_from_slice(terminator);
+            }
+        }
+    }
+
+    fn flush_pending(&mut self, output: &mut Vec<u8>) {
+        output.append(&mut self.pending);
+        self.state = State::Normal;
+    }
+}
+
+impl Default for OscScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(s: &str) -> ShellEvent {
+        ShellEvent::Output(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_marks_are_split_out() {
+        let mut scanner = OscScanner::new();
+        let events = scanner.feed(b"\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n\x1b]133;C\x1b\\file\r\n\x1b]133;D;2\x07");
+        assert_eq!(
+            events,
+            vec![
+                ShellEvent::Mark(SemanticMark::PromptStart),
+                output("$ "),
+                ShellEvent::Mark(SemanticMark::CommandStart),
+                output("ls\r\n"),
+                ShellEvent::Mark(SemanticMark::CommandExecuted),
+                output("file\r\n"),
+                ShellEvent::Mark(SemanticMark::CommandFinished { exit_code: Some(2) }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mark_split_across_reads() {
+        let mut scanner = OscScanner::new();
+        assert_eq!(scanner.feed(b"out\x1b]13"), vec![output("out")]);
+        assert_eq!(scanner.feed(b"3;D"), vec![]);
+        assert_eq!(
+            scanner.feed(b";0\x07next"),
+            vec![ShellEvent::Mark(SemanticMark::CommandFinished { exit_code: Some(0) }), output("next")]
+        );
+    }
+
+    #[test]
+    fn test_other_sequences_pass_through() {
+        let mut scanner = OscScanner::new();
+        let input = b"\x1b[1mbold\x1b]0;title\x07\x1b]1337;x\x07";
+        assert_eq!(scanner.feed(input), vec![ShellEvent::Output(input.to_vec())]);
+    }
+
+    #[test]
+    fn test_unrecognised_integration_lookalikes_pass_through() {
+        let mut scanner = OscScanner::new();
+        let input = b"\x1b]1\x07\x1b]133;Z\x07\x1b]7;bogus\x1b\\$ ";
+        assert_eq!(scanner.feed(input), vec![ShellEvent::Output(input.to_vec())]);
+        // Unterminated: the ESC that cuts it short starts the next sequence.
+        assert_eq!(scanner.feed(b"\x1b]13\x1b[0m"), vec![output("\x1b]13\x1b[0m")]);
+    }
+
+    #[test]
+    fn test_unterminated_mark_keeps_next_escape() {
+        let mut scanner = OscScanner::new();
+        assert_eq!(
+            scanner.feed(b"\x1b]133;A\x1b[1m$ "),
+            vec![ShellEvent::Mark(SemanticMark::PromptStart), output("\x1b[1m$ ")]
+        );
+        // Split between the ESC and the byte after it.
+        assert_eq!(scanner.feed(b"\x1b]133;B\x1b"), vec![]);
+        assert_eq!(
+            scanner.feed(b"[0mls"),
+            vec![ShellEvent::Mark(SemanticMark::CommandStart), output("\x1b[0mls")]
+        );
+    }
+
+    #[test]
+    fn test_working_directory_report() {
+        let mut scanner = OscScanner::new();
+        assert_eq!(
+            scanner.feed(b"\x1b]7;file://myhost/home/me/my%20project\x07$ "),
+            vec![ShellEvent::WorkingDirectory(PathBuf::from("/home/me/my project")), output("$ ")]
+        );
+        assert_eq!(
+            scanner.feed(b"\x1b]7;file:///tmp/100%25\x1b\\"),
+            vec![ShellEvent::WorkingDirectory(PathBuf::from("/tmp/100%"))]
+        );
+    }
+
+    #[test]
+    fn test_percent_decode_leaves_invalid_escapes() {
+        assert_eq!(percent_decode("/a%2"), "/a%2");
+        assert_eq!(percent_decode("/a%zz/%C3%A9"), "/a%zz/é");
+    }
+
+    #[test]
+    fn test_finished_without_exit_code() {
+        let mut scanner = OscScanner::new();
+        assert_eq!(
+            scanner.feed(b"\x1b]133;D\x07"),
+            vec![ShellEvent::Mark(SemanticMark::CommandFinished { exit_code: None })]
+        );
+    }
+}
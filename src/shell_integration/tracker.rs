@@ -0,0 +1,186 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::scanner::{OscScanner, SemanticMark, ShellEvent};
+use crate::vt::{Screen, DEFAULT_COLS, DEFAULT_ROWS};
+
+/// What the terminal should do with its block list in response to shell output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockEvent {
+    /// A prompt is being drawn: open a new block (or reuse an empty one).
+    PromptStarted,
+    /// The user submitted a command; `prompt` and `command` are the rendered text
+    /// of what the shell drew between the A/B and B/C marks.
+    CommandStarted { prompt: String, command: String },
+    /// Output belonging to the current block.
+    Output(Vec<u8>),
+    /// The command finished. A `D` mark without a status is reported as exit code 0.
+    CommandFinished { exit_code: i32, execution_time_ms: u128 },
+    /// The shell reported a working directory different from the last one (OSC 7).
+    DirectoryChanged(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// No marks seen yet (or the shell has no integration): all output goes to the current block.
+    Unknown,
+    Prompt,
+    Input,
+    Running,
+}
+
+/// Turns OSC 133 marks in the PTY stream into block boundaries.
+/// Prompt and input bytes are rendered through their own `Screen`s so that line
+/// editing (backspaces, redraws) collapses to the text the user actually sees.
+pub struct BlockTracker {
+    scanner: OscScanner,
+    phase: Phase,
+    prompt: Screen,
+    input: Screen,
+    started_at: Option<Instant>,
+    current_dir: Option<PathBuf>,
+}
+
+impl BlockTracker {
+    pub fn new() -> Self {
+        BlockTracker {
+            scanner: OscScanner::new(),
+            phase: Phase::Unknown,
+            prompt: Screen::new(DEFAULT_ROWS, DEFAULT_COLS),
+            input: Screen::new(DEFAULT_ROWS, DEFAULT_COLS),
+            started_at: None,
+            current_dir: None,
+        }
+    }
+
+    /// The session's working directory as last reported by the shell.
+    pub fn current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+
+    /// Whether the shell has reported at least one semantic mark.
+    pub fn has_integration(&self) -> bool {
+        self.phase != Phase::Unknown
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<BlockEvent> {
+        let mut events = Vec::new();
+        for event in self.scanner.feed(bytes) {
+            match event {
+                ShellEvent::Output(bytes) => match self.phase {
+                    Phase::Prompt => self.prompt.feed(&bytes),
+                    Phase::Input => self.input.feed(&bytes),
+                    Phase::Unknown | Phase::Running => events.push(BlockEvent::Output(bytes)),
+                },
+                ShellEvent::Mark(mark) => self.handle_mark(mark, &mut events),
+                ShellEvent::WorkingDirectory(path) => {
+                    // Shells report on every prompt; only changes are interesting.
+                    if self.current_dir.as_ref() != Some(&path) {
+                        self.current_dir = Some(path.clone());
+                        events.push(BlockEvent::DirectoryChanged(path));
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    fn handle_mark(&mut self, mark: SemanticMark, events: &mut Vec<BlockEvent>) {
+        match mark {
+            SemanticMark::PromptStart => {
+                self.phase = Phase::Prompt;
+                self.prompt = Screen::new(DEFAULT_ROWS, DEFAULT_COLS);
+                self.input = Screen::new(DEFAULT_ROWS, DEFAULT_COLS);
+                self.started_at = None;
+                events.push(BlockEvent::PromptStarted);
+            }
+            SemanticMark::CommandStart => {
+                self.phase = Phase::Input;
+            }
+            SemanticMark::CommandExecuted => {
+                self.phase = Phase::Running;
+                self.started_at = Some(Instant::now());
+                events.push(BlockEvent::CommandStarted {
+                    prompt: self.prompt.text().trim().to_string(),
+                    command: self.input.text().trim().to_string(),
+                });
+            }
+            SemanticMark::CommandFinished { exit_code } => {
+                // A D mark outside a running command (e.g. before the first prompt) has nothing to close.
+                if let Some(started_at) = self.started_at.take() {
+                    events.push(BlockEvent::CommandFinished {
+                        exit_code: exit_code.unwrap_or(0),
+                        execution_time_ms: started_at.elapsed().as_millis(),
+                    });
+                }
+                self.phase = Phase::Unknown;
+            }
+        }
+    }
+}
+
+impl Default for BlockTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_command_cycle() {
+        let mut tracker = BlockTracker::new();
+        let mut events = tracker.feed(b"\x1b]133;A\x07user@host:~$ \x1b]133;B\x07");
+        events.extend(tracker.feed(b"lss\x08 \x08 -la\r\n\x1b]133;C\x07total 0\r\n"));
+        events.extend(tracker.feed(b"\x1b]133;D;1\x07"));
+
+        assert_eq!(events[0], BlockEvent::PromptStarted);
+        assert_eq!(
+            events[1],
+            BlockEvent::CommandStarted { prompt: "user@host:~$".to_string(), command: "ls -la".to_string() }
+        );
+        assert_eq!(events[2], BlockEvent::Output(b"total 0\r\n".to_vec()));
+        assert!(matches!(events[3], BlockEvent::CommandFinished { exit_code: 1, .. }));
+        assert_eq!(events.len(), 4);
+    }
+
+    #[test]
+    fn test_output_before_integration_goes_to_current_block() {
+        let mut tracker = BlockTracker::new();
+        assert_eq!(tracker.feed(b"motd\r\n"), vec![BlockEvent::Output(b"motd\r\n".to_vec())]);
+        assert!(!tracker.has_integration());
+    }
+
+    #[test]
+    fn test_empty_command_reopens_prompt() {
+        let mut tracker = BlockTracker::new();
+        let events = tracker.feed(b"\x1b]133;A\x07$ \x1b]133;B\x07\r\n\x1b]133;A\x07$ \x1b]133;B\x07");
+        assert_eq!(events, vec![BlockEvent::PromptStarted, BlockEvent::PromptStarted]);
+    }
+
+    #[test]
+    fn test_directory_changes_are_deduplicated() {
+        let mut tracker = BlockTracker::new();
+        let mut events = tracker.feed(b"\x1b]7;file://h/home/me\x07\x1b]133;A\x07");
+        events.extend(tracker.feed(b"\x1b]7;file://h/home/me\x07\x1b]133;A\x07"));
+        events.extend(tracker.feed(b"\x1b]7;file://h/tmp\x07"));
+        assert_eq!(
+            events,
+            vec![
+                BlockEvent::DirectoryChanged(PathBuf::from("/home/me")),
+                BlockEvent::PromptStarted,
+                BlockEvent::PromptStarted,
+                BlockEvent::DirectoryChanged(PathBuf::from("/tmp")),
+            ]
+        );
+        assert_eq!(tracker.current_dir(), Some(Path::new("/tmp")));
+    }
+
+    #[test]
+    fn test_finish_without_command_is_ignored() {
+        let mut tracker = BlockTracker::new();
+        assert!(tracker.feed(b"\x1b]133;D;0\x07").is_empty());
+    }
+}
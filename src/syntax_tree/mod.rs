@@ -4,7 +4,7 @@
 pub mod parser; // For the actual parsing logic
 
 // Re-export for easier access
-pub use parser::SyntaxTreeParser;
+pub use parser::{CommandAnalysis, SyntaxTreeParser};
 
 pub struct SyntaxTree {
     // Root node of the AST
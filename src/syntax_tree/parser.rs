@@ -1,9 +1,22 @@
 use tree_sitter::{Parser, Language, Node, Tree};
 use log::{info, error};
 
-// You would typically load the language dynamically or from a pre-compiled source.
-// For demonstration, we'll assume `tree_sitter_bash` is available.
-extern "C" { fn tree_sitter_bash() -> Language; }
+
+/// What `SyntaxTreeParser::analyze_command` found in a command line, each list in order of appearance.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandAnalysis {
+    pub commands: Vec<String>,
+    pub redirects: Vec<String>,
+    pub variables: Vec<String>,
+    /// The parser had to recover from syntax errors, so the lists may be incomplete.
+    pub has_errors: bool,
+}
+
+fn push_unique(list: &mut Vec<String>, item: String) {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+}
 
 /// A parser for generating syntax trees from code.
 pub struct SyntaxTreeParser {
@@ -13,7 +26,7 @@ pub struct SyntaxTreeParser {
 impl SyntaxTreeParser {
     pub fn new() -> anyhow::Result<Self> {
         let mut parser = Parser::new();
-        let language = unsafe { tree_sitter_bash() }; // Load Bash language
+        let language: Language = tree_sitter_bash::language();
         parser.set_language(language)?;
         info!("SyntaxTreeParser initialized with Bash language.");
         Ok(SyntaxTreeParser { parser })
@@ -34,7 +47,7 @@ impl SyntaxTreeParser {
         loop {
             let node = cursor.node();
             let node_text = node.utf8_text(source_code.as_bytes()).unwrap_or("[ERROR DECODING TEXT]");
-            info!("{}{} ({}): '{}'",
+            info!("{}{} ({:?}): '{}'",
                 "  ".repeat(indent_level),
                 node.kind(),
                 node.range(),
@@ -50,20 +63,62 @@ impl SyntaxTreeParser {
                 continue;
             }
 
-            let mut retreated = false;
-            while cursor.goto_parent() {
+            // Climb until an ancestor has a next sibling; reaching the root means we are done.
+            loop {
+                if !cursor.goto_parent() {
+                    info!("--- End Syntax Tree Structure ---");
+                    return;
+                }
                 indent_level -= 1;
-                retreated = true;
                 if cursor.goto_next_sibling() {
                     break;
                 }
             }
+        }
+    }
+
+    /// Summarises what a shell command line does: the programs it runs, the files it
+    /// redirects to or from, and the variables it expands. Returns `None` if it can't be parsed.
+    pub fn analyze_command(&mut self, command: &str) -> Option<CommandAnalysis> {
+        let tree = self.parse(command)?;
+        let text = |node: Node| node.utf8_text(command.as_bytes()).unwrap_or_default().to_string();
+        let mut analysis = CommandAnalysis { has_errors: tree.root_node().has_error(), ..CommandAnalysis::default() };
 
-            if !retreated {
-                break;
+        for node in Self::find_nodes_by_kind(&tree, "command") {
+            if let Some(name) = node.child_by_field_name("name") {
+                push_unique(&mut analysis.commands, text(name));
             }
         }
-        info!("--- End Syntax Tree Structure ---");
+        for node in Self::find_nodes_by_kind(&tree, "file_redirect") {
+            if let Some(destination) = node.child_by_field_name("destination") {
+                push_unique(&mut analysis.redirects, text(destination));
+            }
+        }
+        for node in Self::find_nodes_by_kind(&tree, "variable_name") {
+            // `FOO=1` and `for FOO in` assign rather than expand.
+            let assigns = node.parent().is_some_and(|p| matches!(p.kind(), "variable_assignment" | "for_statement"));
+            if !assigns {
+                push_unique(&mut analysis.variables, text(node));
+            }
+        }
+        Some(analysis)
+    }
+
+    /// The places where the parser had to recover: `ERROR` nodes, and tokens it assumed
+    /// were there (`is_missing`, e.g. a closing quote). Errors nested in an error are skipped.
+    pub fn find_error_nodes(tree: &Tree) -> Vec<Node<'_>> {
+        let mut nodes = Vec::new();
+        let mut pending = vec![tree.root_node()];
+        while let Some(node) = pending.pop() {
+            if node.is_error() || node.is_missing() {
+                nodes.push(node);
+            } else if node.has_error() {
+                let mut cursor = node.walk();
+                pending.extend(node.children(&mut cursor));
+            }
+        }
+        nodes.sort_by_key(|node| node.start_byte());
+        nodes
     }
 
     /// Finds all occurrences of a specific node kind in the tree.
@@ -139,6 +194,23 @@ mod tests {
         assert!(variables.iter().any(|n| n.utf8_text(source_code.as_bytes()).unwrap() == "VAR"));
     }
 
+    #[test]
+    fn test_analyze_command() {
+        let mut parser = SyntaxTreeParser::new().unwrap();
+        let analysis = parser
+            .analyze_command(r#"OUT=dist; cargo build --release 2> "$OUT/err.log" && tar czf $OUT/app.tgz target | tee -a ${LOG_FILE} < input.txt"#)
+            .unwrap();
+        assert_eq!(analysis.commands, ["cargo", "tar", "tee"]);
+        assert_eq!(analysis.redirects, [r#""$OUT/err.log""#, "input.txt"]);
+        assert_eq!(analysis.variables, ["OUT", "LOG_FILE"]);
+        assert!(!analysis.has_errors);
+
+        let nested = parser.analyze_command("for f in $(ls *.rs); do wc -l \"$f\" >> counts; done").unwrap();
+        assert_eq!(nested.commands, ["ls", "wc"]);
+        assert_eq!(nested.redirects, ["counts"]);
+        assert_eq!(nested.variables, ["f"]);
+    }
+
     #[test]
     fn test_print_tree_structure() {
         let mut parser = SyntaxTreeParser::new().unwrap();
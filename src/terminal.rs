@@ -3,28 +3,39 @@ use iced::{
     Element, Length, Command, Subscription, Theme, Color,
 };
 use iced::futures::{self, SinkExt, StreamExt};
+use log::info;
 use std::collections::VecDeque;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 use std::path::PathBuf;
 use std::collections::HashMap;
-use std::time::Instant;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
-use crate::block::{Block, BlockContent, BlockMessage}; // Updated import
+use crate::blocks::{Block, BlockContent, BlockMessage};
 use crate::input::{Editor, EditorMessage}; // Updated import
-use crate::shell::{Shell, ShellMessage, ShellOutput};
+use crate::shell::{Shell, ShellMessage, ShellOutput, ControlKey};
+use crate::shell_integration::{BlockEvent, BlockTracker};
 use crate::fuzzy::FuzzyMatcher;
+use crate::history::{HistorySearchMessage, HistorySearchPanel, HistoryStore, Session};
+use crate::history::suggest::suggest;
 use crate::collaboration::CollaborationManager;
 use crate::config::yaml_theme_manager::YamlThemeManager; // Updated import
 use crate::config::theme::WarpTheme; // Updated import
 use crate::settings::{PreferencesWindow, PreferencesMessage}; // Updated import
 use crate::settings::theme_editor::{ThemeEditor, ThemeEditorMessage}; // Updated import
-use crate::config::{ConfigManager, UserPreferences, KeyBindings, PromptSettings}; // Updated import
-use crate::profiles::{ProfileManager, UserProfile};
+use crate::config::{ConfigManager, UserPreferences, KeyBindings, PromptSettings, WarpConfig}; // Updated import
+use crate::profiles::{ProfileManager, ProfileSwitched, UserProfile};
 use crate::profile_manager_ui::{ProfileManagerUI, ProfileManagerMessage};
 use crate::profile_switcher::{ProfileSwitcher, ProfileSwitcherMessage};
 use crate::workflows::{WorkflowManager, Workflow, WorkflowExecutor, WorkflowUI}; // Updated imports
-use crate::workflows::executor::WorkflowExecutorMessage; // Updated import
+use crate::workflows::executor::{format_timeline, OutputStream, WorkflowEvent};
+use crate::workflows::history::{RunHistory, RunRecord, RunTrigger};
+use crate::workflows::secrets::Masked;
+use crate::workflows::scheduler::{ScheduledRun, Scheduler, SystemClock};
+use crate::workflows::triggers::ChangeTriggers;
+use crate::workflow_browser::{WorkflowBrowser, WorkflowBrowserMessage};
+use crate::workflow_executor::{WorkflowExecutor as WorkflowForm, WorkflowExecutorMessage};
 use crate::workflows::ui::WorkflowUIMessage; // Updated import
 use crate::watcher::{FileWatcherService, FileWatcherEvent};
 use crate::prompt::PromptRenderer;
@@ -34,13 +45,20 @@ use crate::config::preferences::PreferencesManager;
 use crate::settings::keybinding_editor::{KeybindingEditor, KeybindingMessage};
 use crate::settings::yaml_theme_ui::{YamlThemeUI, YamlThemeMessage};
 
+/// How long a file must stay unchanged before `on_change:` workflows see the change.
+const FILE_CHANGE_DEBOUNCE: Duration = Duration::from_millis(300);
+/// How often shell output and background events are drained; about once a frame.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
 pub struct WarpTerminal {
     // Core state
     blocks: VecDeque<Block>,
     current_block_id: Uuid,
     
     // Advanced features
-    shell: Shell,
+    shell: Option<Shell>,
+    shell_events: mpsc::Receiver<ShellMessage>,
+    block_tracker: BlockTracker,
     fuzzy_matcher: FuzzyMatcher,
     collaboration: CollaborationManager,
     yaml_theme_manager: YamlThemeManager, // Renamed
@@ -64,7 +82,21 @@ pub struct WarpTerminal {
     // Workflow system
     workflow_manager: WorkflowManager,
     workflow_ui: WorkflowUI, // Renamed
-    workflow_executor: WorkflowExecutor,
+    workflow_browser: WorkflowBrowser,
+    // Asks for a workflow's arguments, with generator suggestions, before running it.
+    workflow_form: WorkflowForm,
+    // Progress of running workflows; each step attempt becomes a block.
+    workflow_events: mpsc::UnboundedReceiver<WorkflowEvent>,
+    workflow_event_sender: mpsc::UnboundedSender<WorkflowEvent>,
+    // Re-runs `on_change:` workflows when the watcher reports matching files.
+    workflow_triggers: ChangeTriggers,
+    // Scheduled workflows run in the background; results come back on this channel.
+    workflow_scheduler: Scheduler<SystemClock>,
+    scheduled_results: mpsc::UnboundedReceiver<(Uuid, ScheduledRun, RunRecord)>,
+    scheduled_result_sender: mpsc::UnboundedSender<(Uuid, ScheduledRun, RunRecord)>,
+    run_history: RunHistory,
+    run_records: mpsc::UnboundedReceiver<RunRecord>,
+    run_record_sender: mpsc::UnboundedSender<RunRecord>,
 
     // Prompt rendering
     prompt_renderer: PromptRenderer,
@@ -91,9 +123,18 @@ pub struct WarpTerminal {
 
     // File watcher service instance (to keep it alive)
     _file_watcher_service: FileWatcherService,
+    file_watcher_events: mpsc::UnboundedReceiver<FileWatcherEvent>,
 
     // Editor
     editor: Editor,
+
+    // Command history
+    command_history: HistoryStore,
+    history_session: Session,
+    // The command the shell is running and when it started, until it finishes.
+    running_command: Option<(String, chrono::DateTime<chrono::Utc>)>,
+    // Ctrl-R; the chosen command goes into the editor.
+    history_search: HistorySearchPanel,
 }
 
 #[derive(Debug, Clone)]
@@ -104,6 +145,38 @@ pub enum TerminalMessage {
     KeybindingEditor(KeybindingMessage),
     ThemeEditor(ThemeEditorMessage),
     YamlThemeUI(YamlThemeMessage),
+    Shell(ShellMessage),
+    /// Drains the shell, workflow, watcher and scheduler channels; see `subscription`.
+    Tick,
+    SubmitCommand(String),
+    SendControl(ControlKey),
+    /// Argument values are masked in debug output: they may include secrets.
+    RunWorkflow(Uuid, Masked<HashMap<String, String>>),
+    DryRunWorkflow(Uuid, Masked<HashMap<String, String>>),
+    RefreshCollection(String),
+    RefreshWorkflows,
+    ToggleWorkflowBrowser,
+    WorkflowBrowser(WorkflowBrowserMessage),
+    /// Opens the argument form for a workflow; it sends `RunWorkflow` once they are valid.
+    OpenWorkflow(Uuid),
+    WorkflowForm(WorkflowExecutorMessage),
+    SetWorkflowFavorite(Uuid, bool),
+    Preferences(PreferencesMessage),
+    PreferencesSaved {
+        preferences: UserPreferences,
+        font_family: String,
+        font_size: u16,
+        shell: String,
+        keybindings: KeyBindings,
+        prompt_settings: PromptSettings,
+    },
+    /// Any key press, widgets' included, for the configured bindings; see `subscription`.
+    KeyPressed(iced::keyboard::Key, iced::keyboard::Modifiers),
+    ToggleHistorySearch,
+    HistorySearch(HistorySearchMessage),
+    Editor(EditorMessage),
+    /// A key pressed while the prompt has focus; `Editor::key_message` decides what it does.
+    EditorKey(iced::keyboard::Key, iced::keyboard::Modifiers),
     // Add other terminal-wide messages
 }
 
@@ -156,20 +229,34 @@ impl WarpTerminal {
             "Configs".to_string(),
         ];
 
-        let (mut file_watcher_service, file_watcher_event_receiver) = FileWatcherService::new().unwrap_or_else(|e| {
+        // Debounced, so one save re-runs an `on_change:` workflow once.
+        let (mut file_watcher_service, file_watcher_events) = FileWatcherService::debounced(FILE_CHANGE_DEBOUNCE).unwrap_or_else(|e| {
             eprintln!("Failed to initialize file watcher service: {}", e);
-            let (tx, rx) = mpsc::unbounded_channel();
+            let (_tx, rx) = mpsc::unbounded_channel();
             (FileWatcherService::new_dummy(), rx)
         });
 
         let current_directory = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
-        if let Err(e) = file_watcher_service.watch(&current_directory) {
+        if let Err(e) = file_watcher_service.watch_directory(&current_directory) {
             eprintln!("Failed to start watching current directory: {}", e);
         }
+        let workflow_triggers = ChangeTriggers::new(&current_directory);
+        if let Err(e) = file_watcher_service.watch_roots(workflow_triggers.watch_roots(workflow_manager.get_all_workflows())) {
+            eprintln!("Failed to watch on_change directories: {}", e);
+        }
 
         let preferences = config_manager.get_preferences().clone();
         let config = config_manager.get_config();
 
+        let (shell_tx, shell_events) = mpsc::channel(256);
+        let shell = match Shell::new(&config.shell, shell_tx) {
+            Ok(shell) => Some(shell),
+            Err(e) => {
+                eprintln!("Failed to spawn shell '{}': {}", config.shell, e);
+                None
+            }
+        };
+
         let preferences_window = PreferencesWindow::new(
             preferences,
             config.font_family.clone(),
@@ -198,8 +285,32 @@ impl WarpTerminal {
 
         let mut workflow_ui = WorkflowUI::new(); // Renamed
         workflow_ui.update_workflows(&workflow_manager);
+        let mut workflow_browser = WorkflowBrowser::new();
+        workflow_browser.update_workflows(&workflow_manager);
+        let (workflow_event_sender, workflow_events) = mpsc::unbounded_channel();
+        let mut workflow_scheduler = Scheduler::new(SystemClock);
+        workflow_scheduler.sync(workflow_manager.get_all_workflows());
+        let (scheduled_result_sender, scheduled_results) = mpsc::unbounded_channel();
+        let run_history_path = RunHistory::default_path();
+        let run_history = RunHistory::open(&run_history_path).unwrap_or_else(|e| {
+            eprintln!("Failed to load workflow run history from {:?}: {}", run_history_path, e);
+            RunHistory::empty(&run_history_path)
+        });
+        workflow_browser.update_history(&run_history);
+        let (run_record_sender, run_records) = mpsc::unbounded_channel();
 
         let preferences_manager = PreferencesManager::new();
+
+        let max_history_size = config_manager.get_preferences().max_history_size;
+        let history_path = HistoryStore::default_path();
+        let command_history = HistoryStore::open(&history_path, max_history_size).unwrap_or_else(|e| {
+            eprintln!("Failed to load command history from {:?}: {}", history_path, e);
+            HistoryStore::empty(&history_path, max_history_size)
+        });
+        let history_session = Session::new(profile_manager.get_active_profile().map(|profile| profile.name.clone()));
+        let mut editor = Editor::new();
+        editor.set_max_history(max_history_size);
+        editor.set_history(command_history.entries().iter().map(|entry| entry.command.clone()).collect());
         let initial_theme = WarpTheme::default_dark(); // Or load from preferences
         let keybinding_editor = KeybindingEditor::new(preferences_manager.clone());
         let yaml_theme_ui = YamlThemeUI::new(yaml_theme_manager.clone());
@@ -208,7 +319,9 @@ impl WarpTerminal {
             blocks,
             current_block_id: initial_block_id,
             
-            shell: Shell::new(),
+            shell,
+            shell_events,
+            block_tracker: BlockTracker::new(),
             fuzzy_matcher: FuzzyMatcher::new(),
             collaboration: CollaborationManager::new(),
             yaml_theme_manager, // Renamed
@@ -225,7 +338,17 @@ impl WarpTerminal {
 
             workflow_manager,
             workflow_ui, // Renamed
-            workflow_executor: WorkflowExecutor::new(),
+            workflow_browser,
+            workflow_form: WorkflowForm::new(),
+            workflow_events,
+            workflow_event_sender,
+            workflow_triggers,
+            workflow_scheduler,
+            scheduled_results,
+            scheduled_result_sender,
+            run_history,
+            run_records,
+            run_record_sender,
             prompt_renderer: PromptRenderer::new(prompt_settings),
             command_palette: CommandPalette::new(),
             show_command_palette: false,
@@ -239,11 +362,16 @@ impl WarpTerminal {
             yaml_theme_ui,
             active_context_menu_block_id: None,
             _file_watcher_service: file_watcher_service,
-            editor: Editor::new(),
+            file_watcher_events,
+            editor,
+            command_history,
+            history_session,
+            running_command: None,
+            history_search: HistorySearchPanel::new(),
         }
     }
 
-    pub fn update(&mut self, message: TerminalMessage) {
+    pub fn update(&mut self, message: TerminalMessage) -> Command<TerminalMessage> {
         match message {
             TerminalMessage::ToggleWarpDrive => {
                 self.show_warp_drive = !self.show_warp_drive;
@@ -264,7 +392,540 @@ impl WarpTerminal {
             TerminalMessage::YamlThemeUI(msg) => {
                 self.yaml_theme_ui.update(msg);
             }
+            TerminalMessage::Shell(msg) => {
+                self.handle_shell_message(msg);
+            }
+            TerminalMessage::Tick => {
+                self.poll_shell_events();
+            }
+            TerminalMessage::SubmitCommand(command) => {
+                if let Some(shell) = self.shell.as_mut() {
+                    if let Err(e) = shell.submit_command(&command) {
+                        eprintln!("Failed to write command to shell: {}", e);
+                    }
+                }
+            }
+            TerminalMessage::SendControl(key) => {
+                if let Some(shell) = self.shell.as_mut() {
+                    if let Err(e) = shell.send_control(key) {
+                        eprintln!("Failed to send {:?} to shell: {}", key, e);
+                    }
+                }
+            }
+            TerminalMessage::RunWorkflow(id, Masked(arguments)) => {
+                self.run_workflow(&id, arguments);
+            }
+            TerminalMessage::DryRunWorkflow(id, Masked(arguments)) => {
+                self.dry_run_workflow(&id, &arguments);
+            }
+            TerminalMessage::RefreshCollection(name) => {
+                self.refresh_collection(&name);
+            }
+            TerminalMessage::RefreshWorkflows => {
+                if let Err(e) = self.workflow_manager.load_workflows() {
+                    eprintln!("Failed to reload workflows: {}", e);
+                }
+                self.refresh_workflow_views();
+            }
+            TerminalMessage::ToggleWorkflowBrowser => {
+                if self.workflow_browser.is_visible() {
+                    self.workflow_browser.hide();
+                } else {
+                    self.workflow_browser.show();
+                }
+            }
+            TerminalMessage::WorkflowBrowser(msg) => {
+                if let Some(next) = self.workflow_browser.update(msg) {
+                    return self.update(next);
+                }
+            }
+            TerminalMessage::OpenWorkflow(id) => {
+                return self.open_workflow_form(&id);
+            }
+            TerminalMessage::WorkflowForm(msg) => {
+                if let Some(next) = self.workflow_form.update(msg) {
+                    return self.update(next);
+                }
+            }
+            TerminalMessage::SetWorkflowFavorite(id, is_favorite) => {
+                if let Err(e) = self.workflow_manager.set_favorite(&id, is_favorite) {
+                    eprintln!("Failed to update favorite workflows: {}", e);
+                }
+                self.refresh_workflow_views();
+            }
+            TerminalMessage::Preferences(msg) => {
+                if let Some(next) = self.preferences_window.update(msg) {
+                    return self.update(next);
+                }
+            }
+            TerminalMessage::PreferencesSaved { preferences, font_family, font_size, shell, keybindings, prompt_settings } => {
+                let max_history_size = preferences.max_history_size;
+                let config = WarpConfig {
+                    preferences,
+                    font_family,
+                    font_size,
+                    shell,
+                    keybindings,
+                    prompt: prompt_settings,
+                    ..self.config_manager.get_config().clone()
+                };
+                if let Err(e) = self.config_manager.update_config(config) {
+                    eprintln!("Failed to save preferences: {}", e);
+                }
+                self.set_max_history_size(max_history_size);
+                self.preferences_window.hide();
+            }
+            TerminalMessage::KeyPressed(key, modifiers) => return self.key_pressed(key, modifiers),
+            TerminalMessage::ToggleHistorySearch => {
+                if self.history_search.is_visible() {
+                    self.history_search.hide();
+                } else {
+                    self.history_search.open(&self.command_history, self.block_tracker.current_dir());
+                }
+            }
+            TerminalMessage::HistorySearch(msg) => {
+                if let Some(command) = self.history_search.update(msg, &self.command_history) {
+                    self.editor.set_input_value(command);
+                    self.refresh_suggestion();
+                }
+            }
+            TerminalMessage::Editor(msg) => {
+                let command = self.editor.update(msg);
+                self.refresh_suggestion();
+                return command;
+            }
+            TerminalMessage::EditorKey(key, modifiers) => {
+                if let Some(msg) = self.editor.key_message(&key, modifiers) {
+                    return self.update(TerminalMessage::Editor(msg));
+                }
+            }
+        }
+        Command::none()
+    }
+
+    /// Opens the argument form for a workflow, hiding the browser it was picked from.
+    /// Generator suggestions load in the background, in the session's directory.
+    fn open_workflow_form(&mut self, id: &Uuid) -> Command<TerminalMessage> {
+        let Some(workflow) = self.workflow_manager.get_workflow(id).cloned() else {
+            eprintln!("Workflow {} not found.", id);
+            return Command::none();
+        };
+        let cwd = self.block_tracker.current_dir()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")));
+        self.workflow_browser.hide();
+        self.workflow_form.show_workflow(workflow, cwd).map(TerminalMessage::WorkflowForm)
+    }
+
+    /// Runs a workflow in the background with the session's shell and working directory.
+    /// Its steps show up as blocks through `poll_shell_events`.
+    fn run_workflow(&mut self, id: &Uuid, arguments: HashMap<String, String>) {
+        let Some(workflow) = self.workflow_manager.get_workflow(id).cloned() else {
+            eprintln!("Workflow {} not found.", id);
+            return;
+        };
+        tokio::spawn(self.recorded_run(self.workflow_executor(), workflow, arguments, RunTrigger::Manual));
+    }
+
+    /// Runs a workflow, then sends the run to `run_history` through `poll_shell_events`.
+    /// Runs that never started (invalid arguments, a `needs:` cycle) are not recorded.
+    fn recorded_run(
+        &self,
+        executor: WorkflowExecutor,
+        workflow: Workflow,
+        arguments: HashMap<String, String>,
+        trigger: RunTrigger,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let records = self.run_record_sender.clone();
+        async move {
+            let started_at = chrono::Utc::now();
+            let started = Instant::now();
+            match executor.execute(&workflow, &arguments).await {
+                Ok(run) => {
+                    let _ = records.send(RunRecord::new(&workflow, &run, &arguments, trigger, started_at, started.elapsed()));
+                }
+                Err(e) => eprintln!("Workflow '{}' was not run: {}", workflow.name, e),
+            }
+        }
+    }
+
+    /// Shows what a workflow would run, with every argument filled in, without running it.
+    fn dry_run_workflow(&mut self, id: &Uuid, arguments: &HashMap<String, String>) {
+        let Some(workflow) = self.workflow_manager.get_workflow(id) else {
+            eprintln!("Workflow {} not found.", id);
+            return;
+        };
+        let explanation = match self.workflow_executor().dry_run(workflow, arguments) {
+            Ok(plan) => format!("```\n{}```", plan),
+            Err(e) => format!("Workflow **{}** would not run: {}", workflow.name, e),
+        };
+        self.insert_block_before_prompt(Block::new_markdown(Uuid::new_v4(), explanation));
+    }
+
+    /// Pulls a workflow collection's repo and lists what changed since the last refresh.
+    fn refresh_collection(&mut self, name: &str) {
+        let summary = match self.workflow_manager.refresh_collection(name) {
+            Ok(changes) if changes.is_empty() => format!("Collection **{}** is up to date.", name),
+            Ok(changes) => {
+                let lines: Vec<String> = changes.iter().map(|change| format!("- {}", change)).collect();
+                format!("Collection **{}** changed:\n\n{}", name, lines.join("\n"))
+            }
+            Err(e) => format!("Failed to refresh collection **{}**: {:#}", name, e),
+        };
+        self.refresh_workflow_views();
+        self.insert_block_before_prompt(Block::new_markdown(Uuid::new_v4(), summary));
+    }
+
+    /// Call after the workflows change: updates the lists showing them and the watches
+    /// their `on_change:` globs need.
+    fn refresh_workflow_views(&mut self) {
+        self.workflow_ui.update_workflows(&self.workflow_manager);
+        self.workflow_browser.update_workflows(&self.workflow_manager);
+        self.watch_trigger_roots();
+    }
+
+    /// Watches the directories `on_change:` globs need, recursively only where they need it.
+    fn watch_trigger_roots(&mut self) {
+        let roots = self.workflow_triggers.watch_roots(self.workflow_manager.get_all_workflows());
+        if let Err(e) = self._file_watcher_service.watch_roots(roots) {
+            eprintln!("Failed to watch on_change directories: {}", e);
+        }
+    }
+
+    fn workflow_executor(&self) -> WorkflowExecutor {
+        let mut executor = WorkflowExecutor::with_shell(self.config_manager.get_config().shell.clone())
+            .with_event_sender(self.workflow_event_sender.clone());
+        if let Some(cwd) = self.block_tracker.current_dir() {
+            executor = executor.with_cwd(cwd);
+        }
+        executor
+    }
+
+    fn apply_workflow_event(&mut self, event: WorkflowEvent) {
+        match event {
+            WorkflowEvent::StepStarted { block_id, title, command, .. } => {
+                self.insert_block_before_prompt(Block::new_command(block_id, command, title));
+            }
+            WorkflowEvent::StepOutput { block_id, stream, bytes } => {
+                if let Some(block) = self.blocks.iter_mut().find(|block| block.id == block_id) {
+                    block.append_output(match stream {
+                        OutputStream::Stdout => ShellOutput::Stdout(bytes),
+                        OutputStream::Stderr => ShellOutput::Stderr(bytes),
+                    });
+                }
+            }
+            WorkflowEvent::StepFinished { block_id, result } => {
+                if let Some(block) = self.blocks.iter_mut().find(|block| block.id == block_id) {
+                    block.complete_execution(result.exit_code.unwrap_or(-1), result.duration.as_millis());
+                }
+            }
+            WorkflowEvent::StepSkipped { title, reason, .. } => {
+                self.insert_block_before_prompt(Block::new_markdown(Uuid::new_v4(), format!("Skipped **{}**: {}", title, reason)));
+            }
+            WorkflowEvent::WorkflowFinished { workflow_id, succeeded, timeline } => {
+                info!("Workflow {} finished (succeeded: {})", workflow_id, succeeded);
+                let summary = format!("```\n{}\n```", format_timeline(&timeline, 40));
+                self.insert_block_before_prompt(Block::new_markdown(Uuid::new_v4(), summary));
+            }
+        }
+    }
+
+    /// Keeps the live prompt block last, so workflow output appears above it.
+    fn insert_block_before_prompt(&mut self, block: Block) {
+        let current_block_id = self.current_block_id;
+        match self.blocks.iter().position(|b| b.id == current_block_id) {
+            Some(position) => self.blocks.insert(position, block),
+            None => self.blocks.push_back(block),
+        }
+    }
+
+    /// The PTY reader, workflow runs, the file watcher and the scheduler all report on
+    /// channels, so a tick polls them rather than each needing its own subscription.
+    /// Key presses are listened to whether or not a widget handled them: a focused
+    /// `text_input` captures every key but Tab, Up and Down, Ctrl+R included.
+    pub fn subscription(&self) -> Subscription<TerminalMessage> {
+        Subscription::batch([
+            iced::time::every(EVENT_POLL_INTERVAL).map(|_| TerminalMessage::Tick),
+            iced::event::listen_with(|event, _status| match event {
+                iced::Event::Keyboard(iced::keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+                    Some(TerminalMessage::KeyPressed(key, modifiers))
+                }
+                _ => None,
+            }),
+        ])
+    }
+
+    /// Applies the configured bindings, then the open history search's navigation keys, then
+    /// the prompt's keys: all of them for a multi-line command, else the suggestion keys.
+    fn key_pressed(&mut self, key: iced::keyboard::Key, modifiers: iced::keyboard::Modifiers) -> Command<TerminalMessage> {
+        use iced::keyboard::{key::Named, Key};
+
+        if binding_matches(&self.key_bindings().history_search, &key, modifiers) {
+            return self.update(TerminalMessage::ToggleHistorySearch);
+        }
+        if self.history_search.is_visible() {
+            // Typing goes to the panel's own input; Enter submits it there.
+            let msg = match key {
+                Key::Named(Named::ArrowUp) => HistorySearchMessage::SelectPrevious,
+                Key::Named(Named::ArrowDown) => HistorySearchMessage::SelectNext,
+                Key::Named(Named::Escape) => HistorySearchMessage::Close,
+                _ => return Command::none(),
+            };
+            return self.update(TerminalMessage::HistorySearch(msg));
+        }
+        if self.panel_has_focus() {
+            return Command::none();
+        }
+        // A multi-line command has no `text_input` to type into, so every key goes to the
+        // editor; a single line's keys reach it through the `text_input` instead, bar Enter:
+        // `on_submit` can't tell Shift-Enter (a new line) from Enter.
+        if self.editor.is_multi_line() || key == Key::Named(Named::Enter) {
+            return self.update(TerminalMessage::EditorKey(key, modifiers));
+        }
+        // The prompt's `text_input` moves its cursor on Right and End itself; taking the
+        // suggestion is left to the editor, then the cursor is put after what it added.
+        if let Some(msg @ (EditorMessage::AcceptSuggestion | EditorMessage::AcceptSuggestionWord)) = self.editor.key_message(&key, modifiers) {
+            let _ = self.update(TerminalMessage::Editor(msg));
+            return iced::widget::text_input::move_cursor_to_end(crate::input::prompt_input_id());
+        }
+        Command::none()
+    }
+
+    /// Whether a panel is open over the prompt, so key presses are meant for it.
+    fn panel_has_focus(&self) -> bool {
+        self.history_search.is_visible()
+            || self.workflow_browser.is_visible()
+            || self.workflow_form.is_visible()
+            || self.preferences_window.is_visible()
+            || self.show_command_palette
+            || self.show_warp_drive
+            || self.show_keybinding_editor
+            || self.show_theme_editor
+            || self.show_yaml_theme_ui
+    }
+
+    /// Drains pending shell output and workflow progress into the terminal state.
+    pub fn poll_shell_events(&mut self) {
+        while let Ok(msg) = self.shell_events.try_recv() {
+            self.handle_shell_message(msg);
+        }
+        while let Ok(event) = self.workflow_events.try_recv() {
+            self.apply_workflow_event(event);
+        }
+
+        let mut changed = Vec::new();
+        while let Ok(event) = self.file_watcher_events.try_recv() {
+            match event {
+                FileWatcherEvent::FileChanged(path) | FileWatcherEvent::FileCreated(path) | FileWatcherEvent::FileDeleted(path) => {
+                    changed.push(path);
+                }
+                FileWatcherEvent::DirectoryChanged(_) => {}
+                FileWatcherEvent::Error(e) => eprintln!("File watcher error: {}", e),
+            }
+        }
+        if !changed.is_empty() {
+            self.run_triggered_workflows(&changed);
+        }
+
+        self.run_scheduled_workflows();
+
+        while let Ok(record) = self.run_records.try_recv() {
+            self.record_run(record);
+        }
+    }
+
+    /// Saves a finished run and shows it in the browser's usage stats.
+    fn record_run(&mut self, record: RunRecord) {
+        if let Err(e) = self.run_history.record(record) {
+            eprintln!("Failed to save workflow run: {}", e);
+        }
+        self.workflow_browser.update_history(&self.run_history);
+    }
+
+    /// Records finished scheduled runs in the run history, surfacing failures, then starts
+    /// whatever is due.
+    fn run_scheduled_workflows(&mut self) {
+        while let Ok((id, run, record)) = self.scheduled_results.try_recv() {
+            if let Some(notification) = self.workflow_scheduler.record(id, &run, &self.run_history) {
+                let note = format!("**{}**\n\n{}", notification.title, notification.body);
+                self.insert_block_before_prompt(Block::new_markdown(Uuid::new_v4(), note));
+            }
+            self.record_run(record);
+        }
+
+        self.workflow_scheduler.sync(self.workflow_manager.get_all_workflows());
+        for id in self.workflow_scheduler.due() {
+            let Some(workflow) = self.workflow_manager.get_workflow(&id).cloned() else { continue };
+            // A background session: same shell and directory, but no blocks for its steps.
+            let mut executor = WorkflowExecutor::with_shell(self.config_manager.get_config().shell.clone());
+            if let Some(cwd) = self.block_tracker.current_dir() {
+                executor = executor.with_cwd(cwd);
+            }
+            let results = self.scheduled_result_sender.clone();
+            tokio::spawn(async move {
+                let started_at = chrono::Utc::now();
+                let started = Instant::now();
+                let arguments = HashMap::new();
+                let result = executor.execute(&workflow, &arguments).await;
+                let record = match &result {
+                    Ok(run) => RunRecord::new(&workflow, run, &arguments, RunTrigger::Schedule, started_at, started.elapsed()),
+                    Err(e) => RunRecord::not_started(&workflow, RunTrigger::Schedule, started_at, e.to_string()),
+                };
+                let run = ScheduledRun::from_result(&workflow, started_at, chrono::Utc::now(), &result);
+                let _ = results.send((workflow.id, run, record));
+            });
+        }
+    }
+
+    /// Re-runs every `on_change:` workflow matching the changed files, with its default
+    /// arguments. A run still going from an earlier change is cancelled first.
+    fn run_triggered_workflows(&mut self, changed: &[PathBuf]) {
+        let triggered = self.workflow_triggers.triggered(self.workflow_manager.get_all_workflows(), changed);
+        for id in triggered {
+            let Some(workflow) = self.workflow_manager.get_workflow(&id).cloned() else { continue };
+            if self.workflow_triggers.is_running(&id) {
+                let note = format!("Files changed; cancelled the previous run of **{}**", workflow.name);
+                self.insert_block_before_prompt(Block::new_markdown(Uuid::new_v4(), note));
+            }
+            let run = self.recorded_run(self.workflow_executor(), workflow, HashMap::new(), RunTrigger::FileChange);
+            self.workflow_triggers.restart(id, run);
+        }
+    }
+
+    fn handle_shell_message(&mut self, message: ShellMessage) {
+        match message {
+            ShellMessage::Output(bytes) => {
+                for event in self.block_tracker.feed(&bytes) {
+                    self.apply_block_event(event);
+                }
+            }
+            ShellMessage::Error(e) => {
+                eprintln!("Shell error: {}", e);
+            }
+            ShellMessage::Exit(code) => {
+                info!("Shell exited with code {}", code);
+                self.shell = None;
+            }
+            ShellMessage::Command(_) | ShellMessage::Resize(_) => {}
+        }
+    }
+
+    fn apply_block_event(&mut self, event: BlockEvent) {
+        match event {
+            BlockEvent::PromptStarted => {
+                // Pressing Enter on an empty line redraws the prompt; keep using the same block.
+                if !self.active_block_mut().is_some_and(|block| block.is_empty_command()) {
+                    let block_id = Uuid::new_v4();
+                    self.blocks.push_back(Block::new_command(block_id, String::new(), String::new()));
+                    self.current_block_id = block_id;
+                }
+            }
+            BlockEvent::CommandStarted { prompt, command } => {
+                self.running_command = Some((command.clone(), chrono::Utc::now()));
+                if let Some(block) = self.active_block_mut() {
+                    block.start_command(command, prompt);
+                }
+            }
+            BlockEvent::Output(bytes) => {
+                if let Some(block) = self.active_block_mut() {
+                    block.append_output(ShellOutput::Stdout(bytes));
+                }
+            }
+            BlockEvent::CommandFinished { exit_code, execution_time_ms } => {
+                if let Some(block) = self.active_block_mut() {
+                    block.complete_execution(exit_code, execution_time_ms);
+                }
+                self.record_command(exit_code, Duration::from_millis(execution_time_ms as u64));
+            }
+            BlockEvent::DirectoryChanged(path) => {
+                self.handle_directory_change(path);
+            }
+        }
+    }
+
+    /// Appends the command that just finished to the persistent history.
+    fn record_command(&mut self, exit_code: i32, duration: Duration) {
+        let Some((command, started_at)) = self.running_command.take() else { return };
+        if command.trim().is_empty() {
+            return;
+        }
+        let entry = self.history_session.entry(&command, self.block_tracker.current_dir(), started_at, exit_code, duration);
+        if let Err(e) = self.command_history.append(entry) {
+            eprintln!("Failed to save command history: {}", e);
+        }
+    }
+
+    /// Suggests a completion of the editor's input from the history, fish-style.
+    fn refresh_suggestion(&mut self) {
+        let suggestion = suggest(self.command_history.entries(), self.editor.get_input_value(), self.block_tracker.current_dir(), chrono::Utc::now());
+        self.editor.set_suggestion(suggestion.map(str::to_string));
+    }
+
+    /// Applies a changed `UserPreferences::max_history_size`.
+    pub fn set_max_history_size(&mut self, max_history_size: usize) {
+        self.editor.set_max_history(max_history_size);
+        if let Err(e) = self.command_history.set_max_entries(max_history_size) {
+            eprintln!("Failed to compact command history: {}", e);
+        }
+    }
+
+    /// Follows the shell's working directory (reported via OSC 7): updates the prompt,
+    /// re-points the file watcher and applies any matching profile auto-switch rule.
+    fn handle_directory_change(&mut self, path: PathBuf) {
+        self.prompt_renderer.set_current_dir(path.clone());
+
+        if let Err(e) = self._file_watcher_service.watch_directory(&path) {
+            eprintln!("Failed to watch directory {:?}: {}", path, e);
+        }
+        self.workflow_triggers.set_root(&path);
+        self.watch_trigger_roots();
+
+        if let Some(switched) = self.profile_manager.apply_auto_switch(&path) {
+            self.apply_profile_switch(switched);
+        }
+    }
+
+    /// Applies a newly activated profile's settings to the running terminal.
+    fn apply_profile_switch(&mut self, switched: ProfileSwitched) {
+        info!("Switched profile ({:?}): {} -> {}", switched.trigger, switched.previous_id, switched.profile_id);
+        let config = switched.config;
+
+        self.theme = WarpTheme::from_name(&config.theme);
+        self.editor.set_font_family(config.font_family.clone());
+        self.editor.set_font_size(config.font_size);
+        self.prompt_renderer.set_settings(config.prompt.clone());
+        self.history_session.profile = self.profile_manager.get_active_profile().map(|profile| profile.name.clone());
+
+        // Replacing the shell would throw away the session the user just cd'ed in,
+        // so a different shell is reported rather than swapped in.
+        let running_shell = &self.config_manager.get_config().shell;
+        if config.shell != *running_shell {
+            let profile_name = self.profile_manager.get_active_profile().map_or("This profile", |profile| profile.name.as_str());
+            let note = format!(
+                "**{}** uses `{}`; this session is still running `{}`. Open a new session to use it.",
+                profile_name, config.shell, running_shell
+            );
+            self.insert_block_before_prompt(Block::new_markdown(Uuid::new_v4(), note));
         }
+
+        self.refresh_profile_views();
+    }
+
+    fn refresh_profile_views(&mut self) {
+        let profiles = self.profile_manager.get_all_profiles().into_iter().cloned().collect();
+        let active_profile_id = self.profile_manager.get_active_profile().map(|p| p.id).unwrap_or_else(|| Uuid::new_v4());
+        let quick_switch_profiles = self.profile_manager.get_quick_switch_profiles().into_iter().map(|p| p.id).collect();
+
+        self.profile_manager_ui.update_profiles(profiles, active_profile_id, quick_switch_profiles);
+        self.profile_switcher.update_profiles(
+            self.profile_manager.get_quick_switch_profiles().into_iter().cloned().collect(),
+            active_profile_id
+        );
+    }
+
+    fn active_block_mut(&mut self) -> Option<&mut Block> {
+        let current_block_id = self.current_block_id;
+        self.blocks.iter_mut().find(|block| block.id == current_block_id)
     }
 
     pub fn view(&self) -> Element<TerminalMessage> {
@@ -293,6 +954,46 @@ impl WarpTerminal {
             );
         }
 
+        if self.preferences_window.is_visible() {
+            content = content.push(
+                Container::new(self.preferences_window.view().map(TerminalMessage::Preferences))
+                    .width(iced::Length::Fill)
+                    .height(iced::Length::Shrink)
+                    .center_x()
+                    .style(iced::theme::Container::Box)
+            );
+        }
+
+        if self.workflow_browser.is_visible() {
+            content = content.push(
+                Container::new(self.workflow_browser.view().map(TerminalMessage::WorkflowBrowser))
+                    .width(iced::Length::Fill)
+                    .height(iced::Length::Shrink)
+                    .center_x()
+                    .style(iced::theme::Container::Box)
+            );
+        }
+
+        if self.workflow_form.is_visible() {
+            content = content.push(
+                Container::new(self.workflow_form.view().map(TerminalMessage::WorkflowForm))
+                    .width(iced::Length::Fill)
+                    .height(iced::Length::Shrink)
+                    .center_x()
+                    .style(iced::theme::Container::Box)
+            );
+        }
+
+        if self.history_search.is_visible() {
+            content = content.push(
+                Container::new(self.history_search.view(&self.theme).map(TerminalMessage::HistorySearch))
+                    .width(iced::Length::Fill)
+                    .height(iced::Length::Shrink)
+                    .center_x()
+                    .style(iced::theme::Container::Box)
+            );
+        }
+
         if self.show_keybinding_editor {
             content = content.push(
                 Container::new(self.keybinding_editor.view().map(TerminalMessage::KeybindingEditor))
@@ -340,12 +1041,26 @@ impl WarpTerminal {
     }
 }
 
-impl FileWatcherService {
-    fn new_dummy() -> Self {
-        let (tx, _rx) = mpsc::unbounded_channel();
-        FileWatcherService {
-            watcher: notify::RecommendedWatcher::new(move |res| { /* do nothing */ }, notify::Config::default()).unwrap(),
-            event_receiver: tx.subscribe(),
+/// Whether a key press is a binding from the config, such as "Ctrl+R" or "Ctrl+Shift+P":
+/// the modifiers must be exactly the ones named.
+fn binding_matches(binding: &str, key: &iced::keyboard::Key, modifiers: iced::keyboard::Modifiers) -> bool {
+    let mut parts: Vec<&str> = binding.split('+').map(str::trim).collect();
+    let Some(name) = parts.pop() else { return false };
+    let named = |modifier: &[&str]| parts.iter().any(|part| modifier.iter().any(|m| part.eq_ignore_ascii_case(m)));
+    if named(&["Ctrl", "Control"]) != modifiers.control()
+        || named(&["Shift"]) != modifiers.shift()
+        || named(&["Alt", "Option"]) != modifiers.alt()
+        || named(&["Cmd", "Super", "Meta"]) != modifiers.logo()
+    {
+        return false;
+    }
+    match key {
+        iced::keyboard::Key::Character(c) => c.eq_ignore_ascii_case(name),
+        // `Named`'s variants are spelled like the config's keys, bar the arrows' prefix.
+        iced::keyboard::Key::Named(named_key) => {
+            let key_name = format!("{:?}", named_key);
+            key_name.eq_ignore_ascii_case(name) || key_name.strip_prefix("Arrow").is_some_and(|arrow| arrow.eq_ignore_ascii_case(name))
         }
+        _ => false,
     }
 }
@@ -0,0 +1,760 @@
+use std::collections::VecDeque;
+use log::debug;
+
+use super::parser::Perform;
+
+const TAB_WIDTH: usize = 8;
+
+/// A cell colour as set by SGR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Default,
+    /// One of the 256 palette entries; 0-15 are the themeable ANSI colours.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellAttributes {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+    pub hidden: bool,
+    pub strikethrough: bool,
+}
+
+impl Default for CellAttributes {
+    fn default() -> Self {
+        Self {
+            fg: Color::Default,
+            bg: Color::Default,
+            bold: false,
+            dim: false,
+            italic: false,
+            underline: false,
+            blink: false,
+            inverse: false,
+            hidden: false,
+            strikethrough: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub c: char,
+    pub attrs: CellAttributes,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { c: ' ', attrs: CellAttributes::default() }
+    }
+}
+
+pub type Row = Vec<Cell>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub row: usize,
+    pub col: usize,
+}
+
+/// Terminal modes toggled by `CSI h`/`CSI l` (and their DEC private `?` variants).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Modes {
+    pub insert: bool,
+    pub autowrap: bool,
+    pub cursor_visible: bool,
+    pub application_cursor_keys: bool,
+    pub bracketed_paste: bool,
+    pub alternate_screen: bool,
+}
+
+impl Default for Modes {
+    fn default() -> Self {
+        Self {
+            insert: false,
+            autowrap: true,
+            cursor_visible: true,
+            application_cursor_keys: false,
+            bracketed_paste: false,
+            alternate_screen: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SavedCursor {
+    cursor: Cursor,
+    attrs: CellAttributes,
+}
+
+/// The visible cell grid of a terminal plus its scrollback.
+/// Rows scrolled off the top of the primary screen are kept in `scrollback`
+/// (up to `scrollback_limit`); the alternate screen never contributes to it.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    rows: usize,
+    cols: usize,
+    lines: Vec<Row>,
+    scrollback: VecDeque<Row>,
+    scrollback_limit: usize,
+    cursor: Cursor,
+    /// Set after printing into the last column; the wrap happens on the next print.
+    wrap_pending: bool,
+    attrs: CellAttributes,
+    saved_cursor: Option<SavedCursor>,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    modes: Modes,
+    saved_primary: Option<Vec<Row>>,
+    title: Option<String>,
+}
+
+impl Grid {
+    pub fn new(rows: usize, cols: usize, scrollback_limit: usize) -> Self {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        Grid {
+            rows,
+            cols,
+            lines: vec![vec![Cell::default(); cols]; rows],
+            scrollback: VecDeque::new(),
+            scrollback_limit,
+            cursor: Cursor::default(),
+            wrap_pending: false,
+            attrs: CellAttributes::default(),
+            saved_cursor: None,
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            modes: Modes::default(),
+            saved_primary: None,
+            title: None,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn cursor(&self) -> Cursor {
+        self.cursor
+    }
+
+    pub fn modes(&self) -> &Modes {
+        &self.modes
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn cell(&self, row: usize, col: usize) -> &Cell {
+        &self.lines[row][col]
+    }
+
+    pub fn visible_rows(&self) -> &[Row] {
+        &self.lines
+    }
+
+    pub fn scrollback(&self) -> &VecDeque<Row> {
+        &self.scrollback
+    }
+
+    /// Text of a visible row with trailing blanks removed.
+    pub fn line_text(&self, row: usize) -> String {
+        row_text(&self.lines[row])
+    }
+
+    /// All scrollback and visible text, one line per row, with trailing empty rows dropped.
+    pub fn contents(&self) -> String {
+        let mut lines: Vec<String> = self.scrollback.iter().chain(self.lines.iter()).map(row_text).collect();
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        lines.join("\n")
+    }
+
+    pub fn resize(&mut self, rows: usize, cols: usize) {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        for line in self.lines.iter_mut().chain(self.saved_primary.iter_mut().flatten()) {
+            line.resize(cols, Cell::default());
+        }
+        // Shrinking pushes rows above the cursor into scrollback so the cursor line stays visible.
+        while self.lines.len() > rows {
+            if self.cursor.row > 0 {
+                let line = self.lines.remove(0);
+                if self.saved_primary.is_none() {
+                    self.push_scrollback(line);
+                }
+                self.cursor.row -= 1;
+            } else {
+                self.lines.pop();
+            }
+        }
+        while self.lines.len() < rows {
+            self.lines.push(vec![Cell::default(); cols]);
+        }
+        if let Some(primary) = self.saved_primary.as_mut() {
+            primary.resize(rows, vec![Cell::default(); cols]);
+        }
+        self.rows = rows;
+        self.cols = cols;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.cursor.row = self.cursor.row.min(rows - 1);
+        self.cursor.col = self.cursor.col.min(cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn blank_cell(&self) -> Cell {
+        // Erased cells take the current background colour (back colour erase), as xterm does.
+        Cell { c: ' ', attrs: CellAttributes { bg: self.attrs.bg, ..CellAttributes::default() } }
+    }
+
+    fn blank_row(&self) -> Row {
+        vec![self.blank_cell(); self.cols]
+    }
+
+    fn push_scrollback(&mut self, line: Row) {
+        if self.scrollback_limit == 0 {
+            return;
+        }
+        if self.scrollback.len() >= self.scrollback_limit {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(line);
+    }
+
+    fn linefeed(&mut self) {
+        if self.cursor.row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        if self.cursor.row == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.cursor.row > 0 {
+            self.cursor.row -= 1;
+        }
+    }
+
+    fn scroll_up(&mut self, count: usize) {
+        let count = count.min(self.scroll_bottom - self.scroll_top + 1);
+        for _ in 0..count {
+            let line = self.lines.remove(self.scroll_top);
+            if self.scroll_top == 0 && self.saved_primary.is_none() {
+                self.push_scrollback(line);
+            }
+            let blank = self.blank_row();
+            self.lines.insert(self.scroll_bottom, blank);
+        }
+    }
+
+    fn scroll_down(&mut self, count: usize) {
+        let count = count.min(self.scroll_bottom - self.scroll_top + 1);
+        for _ in 0..count {
+            self.lines.remove(self.scroll_bottom);
+            let blank = self.blank_row();
+            self.lines.insert(self.scroll_top, blank);
+        }
+    }
+
+    fn goto(&mut self, row: usize, col: usize) {
+        self.cursor.row = row.min(self.rows - 1);
+        self.cursor.col = col.min(self.cols - 1);
+    }
+
+    fn erase_in_display(&mut self, mode: u16) {
+        let blank = self.blank_cell();
+        let Cursor { row, col } = self.cursor;
+        match mode {
+            0 => {
+                self.lines[row][col..].fill(blank);
+                for line in &mut self.lines[row + 1..] {
+                    line.fill(blank);
+                }
+            }
+            1 => {
+                self.lines[row][..=col].fill(blank);
+                for line in &mut self.lines[..row] {
+                    line.fill(blank);
+                }
+            }
+            2 => {
+                for line in &mut self.lines {
+                    line.fill(blank);
+                }
+            }
+            3 => self.scrollback.clear(),
+            _ => debug!("Unhandled ED mode {}", mode),
+        }
+    }
+
+    fn erase_in_line(&mut self, mode: u16) {
+        let blank = self.blank_cell();
+        let Cursor { row, col } = self.cursor;
+        match mode {
+            0 => self.lines[row][col..].fill(blank),
+            1 => self.lines[row][..=col].fill(blank),
+            2 => self.lines[row].fill(blank),
+            _ => debug!("Unhandled EL mode {}", mode),
+        }
+    }
+
+    fn insert_lines(&mut self, count: usize) {
+        if self.cursor.row < self.scroll_top || self.cursor.row > self.scroll_bottom {
+            return;
+        }
+        let count = count.min(self.scroll_bottom - self.cursor.row + 1);
+        for _ in 0..count {
+            self.lines.remove(self.scroll_bottom);
+            let blank = self.blank_row();
+            self.lines.insert(self.cursor.row, blank);
+        }
+        self.cursor.col = 0;
+    }
+
+    fn delete_lines(&mut self, count: usize) {
+        if self.cursor.row < self.scroll_top || self.cursor.row > self.scroll_bottom {
+            return;
+        }
+        let count = count.min(self.scroll_bottom - self.cursor.row + 1);
+        for _ in 0..count {
+            self.lines.remove(self.cursor.row);
+            let blank = self.blank_row();
+            self.lines.insert(self.scroll_bottom, blank);
+        }
+        self.cursor.col = 0;
+    }
+
+    fn insert_chars(&mut self, count: usize) {
+        let blank = self.blank_cell();
+        let Cursor { row, col } = self.cursor;
+        let count = count.min(self.cols - col);
+        let line = &mut self.lines[row];
+        line.truncate(self.cols - count);
+        line.splice(col..col, std::iter::repeat_n(blank, count));
+    }
+
+    fn delete_chars(&mut self, count: usize) {
+        let blank = self.blank_cell();
+        let Cursor { row, col } = self.cursor;
+        let count = count.min(self.cols - col);
+        let line = &mut self.lines[row];
+        line.drain(col..col + count);
+        line.extend(std::iter::repeat_n(blank, count));
+    }
+
+    fn erase_chars(&mut self, count: usize) {
+        let blank = self.blank_cell();
+        let Cursor { row, col } = self.cursor;
+        let end = (col + count).min(self.cols);
+        self.lines[row][col..end].fill(blank);
+    }
+
+    fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let top = top.saturating_sub(1);
+        let bottom = bottom.min(self.rows).saturating_sub(1);
+        if top < bottom {
+            self.scroll_top = top;
+            self.scroll_bottom = bottom;
+            self.goto(0, 0);
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(SavedCursor { cursor: self.cursor, attrs: self.attrs });
+    }
+
+    fn restore_cursor(&mut self) {
+        if let Some(saved) = self.saved_cursor {
+            self.attrs = saved.attrs;
+            self.goto(saved.cursor.row, saved.cursor.col);
+        }
+        self.wrap_pending = false;
+    }
+
+    fn enter_alternate_screen(&mut self, save_cursor: bool) {
+        if self.saved_primary.is_some() {
+            return;
+        }
+        if save_cursor {
+            self.save_cursor();
+        }
+        let blank = self.blank_row();
+        let primary = std::mem::replace(&mut self.lines, vec![blank; self.rows]);
+        self.saved_primary = Some(primary);
+        self.modes.alternate_screen = true;
+    }
+
+    fn leave_alternate_screen(&mut self, restore_cursor: bool) {
+        if let Some(primary) = self.saved_primary.take() {
+            self.lines = primary;
+        }
+        self.modes.alternate_screen = false;
+        if restore_cursor {
+            self.restore_cursor();
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: u16, enabled: bool) {
+        match mode {
+            1 => self.modes.application_cursor_keys = enabled,
+            7 => self.modes.autowrap = enabled,
+            25 => self.modes.cursor_visible = enabled,
+            47 | 1047 => {
+                if enabled {
+                    self.enter_alternate_screen(false);
+                } else {
+                    self.leave_alternate_screen(false);
+                }
+            }
+            1049 => {
+                if enabled {
+                    self.enter_alternate_screen(true);
+                } else {
+                    self.leave_alternate_screen(true);
+                }
+            }
+            2004 => self.modes.bracketed_paste = enabled,
+            _ => debug!("Unhandled DEC private mode {} ({})", mode, enabled),
+        }
+    }
+
+    fn set_graphic_rendition(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            self.attrs = CellAttributes::default();
+            return;
+        }
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => self.attrs = CellAttributes::default(),
+                1 => self.attrs.bold = true,
+                2 => self.attrs.dim = true,
+                3 => self.attrs.italic = true,
+                4 => self.attrs.underline = true,
+                5 | 6 => self.attrs.blink = true,
+                7 => self.attrs.inverse = true,
+                8 => self.attrs.hidden = true,
+                9 => self.attrs.strikethrough = true,
+                22 => {
+                    self.attrs.bold = false;
+                    self.attrs.dim = false;
+                }
+                23 => self.attrs.italic = false,
+                24 => self.attrs.underline = false,
+                25 => self.attrs.blink = false,
+                27 => self.attrs.inverse = false,
+                28 => self.attrs.hidden = false,
+                29 => self.attrs.strikethrough = false,
+                n @ 30..=37 => self.attrs.fg = Color::Indexed((n - 30) as u8),
+                38 => {
+                    if let Some((color, consumed)) = parse_extended_color(&params[i + 1..]) {
+                        self.attrs.fg = color;
+                        i += consumed;
+                    }
+                }
+                39 => self.attrs.fg = Color::Default,
+                n @ 40..=47 => self.attrs.bg = Color::Indexed((n - 40) as u8),
+                48 => {
+                    if let Some((color, consumed)) = parse_extended_color(&params[i + 1..]) {
+                        self.attrs.bg = color;
+                        i += consumed;
+                    }
+                }
+                49 => self.attrs.bg = Color::Default,
+                n @ 90..=97 => self.attrs.fg = Color::Indexed((n - 90 + 8) as u8),
+                n @ 100..=107 => self.attrs.bg = Color::Indexed((n - 100 + 8) as u8),
+                n => debug!("Unhandled SGR parameter {}", n),
+            }
+            i += 1;
+        }
+    }
+}
+
+impl Perform for Grid {
+    fn print(&mut self, c: char) {
+        if self.wrap_pending {
+            if self.modes.autowrap {
+                self.cursor.col = 0;
+                self.linefeed();
+            }
+            self.wrap_pending = false;
+        }
+        if self.modes.insert {
+            self.insert_chars(1);
+        }
+        let Cursor { row, col } = self.cursor;
+        self.lines[row][col] = Cell { c, attrs: self.attrs };
+        if col + 1 >= self.cols {
+            self.wrap_pending = true;
+        } else {
+            self.cursor.col += 1;
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' | 0x0b | 0x0c => {
+                self.wrap_pending = false;
+                self.linefeed();
+            }
+            b'\r' => {
+                self.wrap_pending = false;
+                self.cursor.col = 0;
+            }
+            0x08 => {
+                self.wrap_pending = false;
+                self.cursor.col = self.cursor.col.saturating_sub(1);
+            }
+            b'\t' => {
+                let next_stop = (self.cursor.col / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.cursor.col = next_stop.min(self.cols - 1);
+            }
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &[u16], intermediates: &[u8], private_marker: Option<u8>, action: char) {
+        // Count-style parameters treat both "missing" and 0 as 1.
+        let arg = |i: usize, default: usize| {
+            params.get(i).copied().filter(|&v| v != 0).map(usize::from).unwrap_or(default)
+        };
+
+        if private_marker == Some(b'?') {
+            match action {
+                'h' => params.iter().for_each(|&mode| self.set_private_mode(mode, true)),
+                'l' => params.iter().for_each(|&mode| self.set_private_mode(mode, false)),
+                _ => debug!("Unhandled private CSI {:?} {}", params, action),
+            }
+            return;
+        }
+        if private_marker.is_some() || !intermediates.is_empty() {
+            debug!("Ignoring CSI {:?} {:?} {:?} {}", private_marker, params, intermediates, action);
+            return;
+        }
+
+        if action != 'm' {
+            self.wrap_pending = false;
+        }
+        let Cursor { row, col } = self.cursor;
+        match action {
+            'A' => self.goto(row.saturating_sub(arg(0, 1)), col),
+            'B' | 'e' => self.goto(row + arg(0, 1), col),
+            'C' | 'a' => self.goto(row, col + arg(0, 1)),
+            'D' => self.goto(row, col.saturating_sub(arg(0, 1))),
+            'E' => self.goto(row + arg(0, 1), 0),
+            'F' => self.goto(row.saturating_sub(arg(0, 1)), 0),
+            'G' | '`' => self.goto(row, arg(0, 1) - 1),
+            'H' | 'f' => self.goto(arg(0, 1) - 1, arg(1, 1) - 1),
+            'd' => self.goto(arg(0, 1) - 1, col),
+            'J' => self.erase_in_display(params.first().copied().unwrap_or(0)),
+            'K' => self.erase_in_line(params.first().copied().unwrap_or(0)),
+            'L' => self.insert_lines(arg(0, 1)),
+            'M' => self.delete_lines(arg(0, 1)),
+            '@' => self.insert_chars(arg(0, 1)),
+            'P' => self.delete_chars(arg(0, 1)),
+            'X' => self.erase_chars(arg(0, 1)),
+            'S' => self.scroll_up(arg(0, 1)),
+            'T' => self.scroll_down(arg(0, 1)),
+            'm' => self.set_graphic_rendition(params),
+            'r' => self.set_scroll_region(arg(0, 1), arg(1, self.rows)),
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            'h' | 'l' => {
+                if params.contains(&4) {
+                    self.modes.insert = action == 'h';
+                }
+            }
+            _ => debug!("Unhandled CSI {:?} {}", params, action),
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], byte: u8) {
+        if !intermediates.is_empty() {
+            // Charset designation (`ESC ( B`) and friends; we always use UTF-8.
+            return;
+        }
+        match byte {
+            b'7' => self.save_cursor(),
+            b'8' => self.restore_cursor(),
+            b'D' => self.linefeed(),
+            b'E' => {
+                self.cursor.col = 0;
+                self.linefeed();
+            }
+            b'M' => self.reverse_index(),
+            b'c' => {
+                let scrollback = std::mem::take(&mut self.scrollback);
+                *self = Grid::new(self.rows, self.cols, self.scrollback_limit);
+                self.scrollback = scrollback;
+            }
+            b'\\' => {} // String terminator
+            _ => debug!("Unhandled ESC {}", byte as char),
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]]) {
+        match params.first().copied() {
+            Some(b"0") | Some(b"2") => {
+                self.title = params.get(1).map(|t| String::from_utf8_lossy(t).into_owned());
+            }
+            _ => debug!("Unhandled OSC {:?}", params.first().map(|p| String::from_utf8_lossy(p))),
+        }
+    }
+}
+
+fn row_text(row: &Row) -> String {
+    let text: String = row.iter().map(|cell| cell.c).collect();
+    text.trim_end().to_string()
+}
+
+/// Parses the tail of an SGR 38/48 sequence: `5;n` or `2;r;g;b`.
+/// Returns the colour and how many parameters were consumed.
+fn parse_extended_color(params: &[u16]) -> Option<(Color, usize)> {
+    match params.first() {
+        Some(5) => params.get(1).map(|&index| (Color::Indexed(index.min(255) as u8), 2)),
+        Some(2) if params.len() >= 4 => Some((
+            Color::Rgb(params[1].min(255) as u8, params[2].min(255) as u8, params[3].min(255) as u8),
+            4,
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vt::Screen;
+
+    fn screen(rows: usize, cols: usize, input: &[u8]) -> Screen {
+        let mut screen = Screen::new(rows, cols);
+        screen.feed(input);
+        screen
+    }
+
+    #[test]
+    fn test_plain_text_and_newlines() {
+        let s = screen(3, 10, b"hello\r\nworld");
+        assert_eq!(s.grid().line_text(0), "hello");
+        assert_eq!(s.grid().line_text(1), "world");
+        assert_eq!(s.grid().cursor(), Cursor { row: 1, col: 5 });
+    }
+
+    #[test]
+    fn test_carriage_return_progress_bar() {
+        let s = screen(2, 20, b"progress 10%\rprogress 50%\rprogress 100%\r\n");
+        assert_eq!(s.grid().line_text(0), "progress 100%");
+        assert_eq!(s.text(), "progress 100%");
+    }
+
+    #[test]
+    fn test_clear_line_sequence() {
+        let s = screen(2, 20, b"downloading...\r\x1b[2Kdone");
+        assert_eq!(s.grid().line_text(0), "done");
+
+        let s = screen(2, 20, b"abcdef\x1b[3D\x1b[K");
+        assert_eq!(s.grid().line_text(0), "abc");
+    }
+
+    #[test]
+    fn test_cursor_movement() {
+        let s = screen(5, 10, b"\x1b[3;4HX\x1b[AY\x1b[2DZ");
+        assert_eq!(s.grid().cell(2, 3).c, 'X');
+        assert_eq!(s.grid().cell(1, 4).c, 'Y');
+        assert_eq!(s.grid().cell(1, 3).c, 'Z');
+    }
+
+    #[test]
+    fn test_sgr_attributes() {
+        let s = screen(2, 20, b"\x1b[1;31mA\x1b[0mB\x1b[38;5;208mC\x1b[48;2;1;2;3mD\x1b[94mE");
+        let grid = s.grid();
+        assert!(grid.cell(0, 0).attrs.bold);
+        assert_eq!(grid.cell(0, 0).attrs.fg, Color::Indexed(1));
+        assert_eq!(grid.cell(0, 1).attrs, CellAttributes::default());
+        assert_eq!(grid.cell(0, 2).attrs.fg, Color::Indexed(208));
+        assert_eq!(grid.cell(0, 3).attrs.bg, Color::Rgb(1, 2, 3));
+        assert_eq!(grid.cell(0, 4).attrs.fg, Color::Indexed(12));
+    }
+
+    #[test]
+    fn test_autowrap_and_scrollback() {
+        let s = screen(2, 4, b"abcdefgh\r\nij");
+        assert_eq!(s.grid().scrollback().len(), 1);
+        assert_eq!(s.grid().line_text(0), "efgh");
+        assert_eq!(s.grid().line_text(1), "ij");
+        assert_eq!(s.text(), "abcd\nefgh\nij");
+    }
+
+    #[test]
+    fn test_autowrap_disabled() {
+        let s = screen(2, 4, b"\x1b[?7labcdef");
+        assert_eq!(s.grid().line_text(0), "abcf");
+        assert!(!s.grid().modes().autowrap);
+    }
+
+    #[test]
+    fn test_scrollback_limit() {
+        let mut s = Screen::with_scrollback(2, 10, 3);
+        for i in 0..10 {
+            s.feed(format!("line {}\r\n", i).as_bytes());
+        }
+        let scrollback: Vec<String> = s.grid().scrollback().iter().map(row_text).collect();
+        assert_eq!(scrollback, vec!["line 6", "line 7", "line 8"]);
+    }
+
+    #[test]
+    fn test_alternate_screen_restores_primary() {
+        let s = screen(3, 10, b"prompt$ \x1b[?1049h\x1b[2J\x1b[Hvim stuff\x1b[?1049l");
+        assert_eq!(s.grid().line_text(0), "prompt$");
+        assert_eq!(s.grid().cursor(), Cursor { row: 0, col: 8 });
+        assert!(!s.grid().modes().alternate_screen);
+        assert!(s.grid().scrollback().is_empty());
+    }
+
+    #[test]
+    fn test_scroll_region() {
+        let s = screen(4, 10, b"top\r\n\x1b[2;3r\x1b[2;1Ha\r\nb\r\nc\x1b[4;1Hbottom");
+        assert_eq!(s.grid().line_text(0), "top");
+        assert_eq!(s.grid().line_text(1), "b");
+        assert_eq!(s.grid().line_text(2), "c");
+        assert_eq!(s.grid().line_text(3), "bottom");
+        assert!(s.grid().scrollback().is_empty());
+    }
+
+    #[test]
+    fn test_insert_and_delete_chars() {
+        let s = screen(1, 10, b"abcdef\x1b[1;3H\x1b[2P");
+        assert_eq!(s.grid().line_text(0), "abef");
+        let s = screen(1, 10, b"abcdef\x1b[1;3H\x1b[2@");
+        assert_eq!(s.grid().line_text(0), "ab  cdef");
+    }
+
+    #[test]
+    fn test_osc_title() {
+        let s = screen(1, 10, b"\x1b]2;build\x07");
+        assert_eq!(s.grid().title(), Some("build"));
+    }
+
+    #[test]
+    fn test_tabs_and_backspace() {
+        let s = screen(1, 20, b"a\tb\x08c");
+        assert_eq!(s.grid().line_text(0), "a       c");
+    }
+}
@@ -0,0 +1,57 @@
+// Terminal emulation for PTY output: an escape-sequence parser feeding a cell grid.
+// Blocks hold a `Screen` per command so colours, cursor movement and `\r`
+// progress bars render the way they would in a real terminal.
+
+pub mod parser; // VT100/xterm escape-sequence state machine
+pub mod grid; // Cell grid, attributes and scrollback
+
+// Re-export for easier access
+pub use grid::{Cell, CellAttributes, Color, Cursor, Grid, Modes};
+pub use parser::{Parser, Perform};
+
+pub const DEFAULT_ROWS: usize = 24;
+pub const DEFAULT_COLS: usize = 80;
+pub const DEFAULT_SCROLLBACK: usize = 10_000;
+
+/// A parser and the grid it drives, fed raw bytes from the shell.
+#[derive(Debug, Clone)]
+pub struct Screen {
+    parser: Parser,
+    grid: Grid,
+}
+
+impl Screen {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self::with_scrollback(rows, cols, DEFAULT_SCROLLBACK)
+    }
+
+    pub fn with_scrollback(rows: usize, cols: usize, scrollback_limit: usize) -> Self {
+        Screen {
+            parser: Parser::new(),
+            grid: Grid::new(rows, cols, scrollback_limit),
+        }
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.parser.advance(&mut self.grid, bytes);
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn resize(&mut self, rows: usize, cols: usize) {
+        self.grid.resize(rows, cols);
+    }
+
+    /// Plain text of the scrollback and visible rows, for copying and display.
+    pub fn text(&self) -> String {
+        self.grid.contents()
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new(DEFAULT_ROWS, DEFAULT_COLS)
+    }
+}
@@ -0,0 +1,391 @@
+use log::debug;
+
+const MAX_PARAMS: usize = 32;
+const MAX_INTERMEDIATES: usize = 2;
+const MAX_OSC_LEN: usize = 64 * 1024;
+const REPLACEMENT_CHAR: char = '\u{FFFD}';
+
+/// Receives the actions recognised by `Parser`.
+/// `vt::Grid` implements this to update the cell model.
+pub trait Perform {
+    /// A printable character, already decoded from UTF-8.
+    fn print(&mut self, c: char);
+    /// A C0 control byte such as `\n`, `\r` or BEL.
+    fn execute(&mut self, byte: u8);
+    /// A complete `CSI ... <action>` sequence. Missing parameters are reported as 0.
+    fn csi_dispatch(&mut self, params: &[u16], intermediates: &[u8], private_marker: Option<u8>, action: char);
+    /// A complete `ESC <intermediates> <byte>` sequence.
+    fn esc_dispatch(&mut self, intermediates: &[u8], byte: u8);
+    /// An `OSC ... ST` string, split on `;`.
+    fn osc_dispatch(&mut self, params: &[&[u8]]);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    CsiEntry,
+    CsiParam,
+    CsiIntermediate,
+    CsiIgnore,
+    OscString,
+    /// DCS, SOS, PM and APC strings are consumed and discarded.
+    StringIgnore,
+}
+
+/// A byte-at-a-time VT500-style state machine, modelled on the DEC ANSI parser diagram.
+/// It keeps partial sequences (and partial UTF-8 characters) across calls to `advance`,
+/// so PTY output can be fed in whatever chunks it arrives in.
+#[derive(Debug, Clone)]
+pub struct Parser {
+    state: State,
+    params: Vec<u16>,
+    param: u16,
+    has_param: bool,
+    intermediates: Vec<u8>,
+    private_marker: Option<u8>,
+    osc: Vec<u8>,
+    utf8_buf: [u8; 4],
+    utf8_len: usize,
+    utf8_needed: usize,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Parser {
+            state: State::Ground,
+            params: Vec::new(),
+            param: 0,
+            has_param: false,
+            intermediates: Vec::new(),
+            private_marker: None,
+            osc: Vec::new(),
+            utf8_buf: [0; 4],
+            utf8_len: 0,
+            utf8_needed: 0,
+        }
+    }
+
+    /// Feeds a chunk of bytes through the state machine, dispatching to `performer`.
+    pub fn advance<P: Perform>(&mut self, performer: &mut P, bytes: &[u8]) {
+        for &byte in bytes {
+            self.advance_byte(performer, byte);
+        }
+    }
+
+    fn advance_byte<P: Perform>(&mut self, performer: &mut P, byte: u8) {
+        if self.state == State::Ground && (self.utf8_needed > 0 || byte >= 0x80) {
+            self.advance_utf8(performer, byte);
+            return;
+        }
+
+        // Transitions that apply from any state.
+        match byte {
+            0x18 | 0x1a => {
+                self.state = State::Ground;
+                return;
+            }
+            0x1b => {
+                if self.state == State::OscString {
+                    // ESC terminates the OSC string; the `\` of ST is then swallowed by esc_dispatch.
+                    self.dispatch_osc(performer);
+                }
+                self.clear_sequence();
+                self.state = State::Escape;
+                return;
+            }
+            _ => {}
+        }
+
+        match self.state {
+            State::Ground => match byte {
+                0x00..=0x1f => performer.execute(byte),
+                0x20..=0x7e => performer.print(byte as char),
+                _ => {}
+            },
+            State::Escape => match byte {
+                0x00..=0x1f => performer.execute(byte),
+                0x20..=0x2f => {
+                    self.collect_intermediate(byte);
+                    self.state = State::EscapeIntermediate;
+                }
+                b'[' => {
+                    self.clear_sequence();
+                    self.state = State::CsiEntry;
+                }
+                b']' => {
+                    self.osc.clear();
+                    self.state = State::OscString;
+                }
+                b'P' | b'X' | b'^' | b'_' => self.state = State::StringIgnore,
+                0x30..=0x7e => {
+                    performer.esc_dispatch(&self.intermediates, byte);
+                    self.state = State::Ground;
+                }
+                _ => {}
+            },
+            State::EscapeIntermediate => match byte {
+                0x00..=0x1f => performer.execute(byte),
+                0x20..=0x2f => self.collect_intermediate(byte),
+                0x30..=0x7e => {
+                    performer.esc_dispatch(&self.intermediates, byte);
+                    self.state = State::Ground;
+                }
+                _ => {}
+            },
+            State::CsiEntry => match byte {
+                0x00..=0x1f => performer.execute(byte),
+                b'<' | b'=' | b'>' | b'?' => {
+                    self.private_marker = Some(byte);
+                    self.state = State::CsiParam;
+                }
+                b'0'..=b'9' | b';' | b':' => {
+                    self.collect_param(byte);
+                    self.state = State::CsiParam;
+                }
+                0x20..=0x2f => {
+                    self.collect_intermediate(byte);
+                    self.state = State::CsiIntermediate;
+                }
+                0x40..=0x7e => self.dispatch_csi(performer, byte),
+                _ => {}
+            },
+            State::CsiParam => match byte {
+                0x00..=0x1f => performer.execute(byte),
+                b'0'..=b'9' | b';' | b':' => self.collect_param(byte),
+                b'<' | b'=' | b'>' | b'?' => self.state = State::CsiIgnore,
+                0x20..=0x2f => {
+                    self.collect_intermediate(byte);
+                    self.state = State::CsiIntermediate;
+                }
+                0x40..=0x7e => self.dispatch_csi(performer, byte),
+                _ => {}
+            },
+            State::CsiIntermediate => match byte {
+                0x00..=0x1f => performer.execute(byte),
+                0x20..=0x2f => self.collect_intermediate(byte),
+                0x30..=0x3f => self.state = State::CsiIgnore,
+                0x40..=0x7e => self.dispatch_csi(performer, byte),
+                _ => {}
+            },
+            State::CsiIgnore => match byte {
+                0x00..=0x1f => performer.execute(byte),
+                0x40..=0x7e => self.state = State::Ground,
+                _ => {}
+            },
+            State::OscString => match byte {
+                0x07 => {
+                    self.dispatch_osc(performer);
+                    self.state = State::Ground;
+                }
+                0x00..=0x1f => {}
+                _ => {
+                    if self.osc.len() < MAX_OSC_LEN {
+                        self.osc.push(byte);
+                    }
+                }
+            },
+            State::StringIgnore => {}
+        }
+    }
+
+    fn advance_utf8<P: Perform>(&mut self, performer: &mut P, byte: u8) {
+        if self.utf8_needed == 0 {
+            let needed = match byte {
+                0xc2..=0xdf => 1,
+                0xe0..=0xef => 2,
+                0xf0..=0xf4 => 3,
+                _ => {
+                    performer.print(REPLACEMENT_CHAR);
+                    return;
+                }
+            };
+            self.utf8_buf[0] = byte;
+            self.utf8_len = 1;
+            self.utf8_needed = needed;
+            return;
+        }
+
+        if byte & 0xc0 != 0x80 {
+            // Truncated sequence: emit a replacement and reprocess this byte from scratch.
+            self.utf8_needed = 0;
+            performer.print(REPLACEMENT_CHAR);
+            self.advance_byte(performer, byte);
+            return;
+        }
+
+        self.utf8_buf[self.utf8_len] = byte;
+        self.utf8_len += 1;
+        self.utf8_needed -= 1;
+        if self.utf8_needed == 0 {
+            let c = std::str::from_utf8(&self.utf8_buf[..self.utf8_len])
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(REPLACEMENT_CHAR);
+            performer.print(c);
+        }
+    }
+
+    fn clear_sequence(&mut self) {
+        self.params.clear();
+        self.param = 0;
+        self.has_param = false;
+        self.intermediates.clear();
+        self.private_marker = None;
+    }
+
+    fn collect_intermediate(&mut self, byte: u8) {
+        if self.intermediates.len() < MAX_INTERMEDIATES {
+            self.intermediates.push(byte);
+        }
+    }
+
+    fn collect_param(&mut self, byte: u8) {
+        match byte {
+            b';' | b':' => {
+                if self.params.len() < MAX_PARAMS {
+                    self.params.push(self.param);
+                }
+                self.param = 0;
+                self.has_param = false;
+            }
+            _ => {
+                self.param = self.param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                self.has_param = true;
+            }
+        }
+    }
+
+    fn dispatch_csi<P: Perform>(&mut self, performer: &mut P, action: u8) {
+        // A trailing parameter is pushed even when empty if a separator preceded it (`CSI 1;H`).
+        if (self.has_param || !self.params.is_empty()) && self.params.len() < MAX_PARAMS {
+            self.params.push(self.param);
+        }
+        performer.csi_dispatch(&self.params, &self.intermediates, self.private_marker, action as char);
+        self.state = State::Ground;
+    }
+
+    fn dispatch_osc<P: Perform>(&mut self, performer: &mut P) {
+        let params: Vec<&[u8]> = self.osc.split(|&b| b == b';').collect();
+        debug!("OSC dispatch with {} params", params.len());
+        performer.osc_dispatch(&params);
+        self.osc.clear();
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Action {
+        Print(char),
+        Execute(u8),
+        Csi(Vec<u16>, Vec<u8>, Option<u8>, char),
+        Esc(Vec<u8>, u8),
+        Osc(Vec<String>),
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        actions: Vec<Action>,
+    }
+
+    impl Perform for Recorder {
+        fn print(&mut self, c: char) {
+            self.actions.push(Action::Print(c));
+        }
+        fn execute(&mut self, byte: u8) {
+            self.actions.push(Action::Execute(byte));
+        }
+        fn csi_dispatch(&mut self, params: &[u16], intermediates: &[u8], private_marker: Option<u8>, action: char) {
+            self.actions.push(Action::Csi(params.to_vec(), intermediates.to_vec(), private_marker, action));
+        }
+        fn esc_dispatch(&mut self, intermediates: &[u8], byte: u8) {
+            self.actions.push(Action::Esc(intermediates.to_vec(), byte));
+        }
+        fn osc_dispatch(&mut self, params: &[&[u8]]) {
+            self.actions.push(Action::Osc(params.iter().map(|p| String::from_utf8_lossy(p).into_owned()).collect()));
+        }
+    }
+
+    fn parse(bytes: &[u8]) -> Vec<Action> {
+        let mut parser = Parser::new();
+        let mut recorder = Recorder::default();
+        parser.advance(&mut recorder, bytes);
+        recorder.actions
+    }
+
+    #[test]
+    fn test_print_and_execute() {
+        assert_eq!(
+            parse(b"a\r\n"),
+            vec![Action::Print('a'), Action::Execute(b'\r'), Action::Execute(b'\n')]
+        );
+    }
+
+    #[test]
+    fn test_csi_params() {
+        assert_eq!(parse(b"\x1b[1;31m"), vec![Action::Csi(vec![1, 31], vec![], None, 'm')]);
+        assert_eq!(parse(b"\x1b[m"), vec![Action::Csi(vec![], vec![], None, 'm')]);
+        assert_eq!(parse(b"\x1b[;5H"), vec![Action::Csi(vec![0, 5], vec![], None, 'H')]);
+        assert_eq!(parse(b"\x1b[?1049h"), vec![Action::Csi(vec![1049], vec![], Some(b'?'), 'h')]);
+    }
+
+    #[test]
+    fn test_esc_dispatch() {
+        assert_eq!(parse(b"\x1b7"), vec![Action::Esc(vec![], b'7')]);
+        assert_eq!(parse(b"\x1b(B"), vec![Action::Esc(vec![b'('], b'B')]);
+    }
+
+    #[test]
+    fn test_osc_terminators() {
+        let expected = vec![Action::Osc(vec!["0".to_string(), "my title".to_string()])];
+        assert_eq!(parse(b"\x1b]0;my title\x07"), expected);
+
+        let mut st = parse(b"\x1b]0;my title\x1b\\");
+        assert_eq!(st.remove(0), Action::Osc(vec!["0".to_string(), "my title".to_string()]));
+        assert_eq!(st, vec![Action::Esc(vec![], b'\\')]);
+    }
+
+    #[test]
+    fn test_utf8_split_across_chunks() {
+        let mut parser = Parser::new();
+        let mut recorder = Recorder::default();
+        let bytes = "é✓".as_bytes();
+        parser.advance(&mut recorder, &bytes[..1]);
+        parser.advance(&mut recorder, &bytes[1..4]);
+        parser.advance(&mut recorder, &bytes[4..]);
+        assert_eq!(recorder.actions, vec![Action::Print('é'), Action::Print('✓')]);
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_replaced() {
+        assert_eq!(parse(b"\xffa"), vec![Action::Print(REPLACEMENT_CHAR), Action::Print('a')]);
+        assert_eq!(parse(b"\xc3a"), vec![Action::Print(REPLACEMENT_CHAR), Action::Print('a')]);
+    }
+
+    #[test]
+    fn test_sequence_split_across_chunks() {
+        let mut parser = Parser::new();
+        let mut recorder = Recorder::default();
+        parser.advance(&mut recorder, b"\x1b[3");
+        parser.advance(&mut recorder, b"2mX");
+        assert_eq!(
+            recorder.actions,
+            vec![Action::Csi(vec![32], vec![], None, 'm'), Action::Print('X')]
+        );
+    }
+
+    #[test]
+    fn test_dcs_is_ignored() {
+        assert_eq!(parse(b"\x1bPq#0;2;0;0;0\x1b\\a"), vec![Action::Esc(vec![], b'\\'), Action::Print('a')]);
+    }
+}
@@ -4,6 +4,11 @@
 
 pub mod service; // For the actual file watching service
 
+// Re-export for easier access
+pub use service::FileWatcherService;
+
+use std::path::PathBuf;
+
 #[derive(Debug, Clone)]
 pub enum FileWatcherEvent {
     FileChanged(PathBuf),
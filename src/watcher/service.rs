@@ -1,20 +1,35 @@
+use log::debug;
 use notify::{RecommendedWatcher, Watcher, RecursiveMode, EventKind};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, DebouncedEventKind, Debouncer};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use tokio::sync::mpsc;
 use std::time::Duration;
 
 use super::FileWatcherEvent;
 
+enum Backend {
+    Raw(RecommendedWatcher),
+    // Owns the debouncing thread, which stops when this is dropped.
+    Debounced(Debouncer<RecommendedWatcher>),
+}
+
 pub struct FileWatcherService {
-    watcher: RecommendedWatcher,
-    event_receiver: mpsc::UnboundedReceiver<FileWatcherEvent>,
+    watcher: Backend,
+    // The shell's working directory, re-pointed as the user `cd`s around. Not recursive:
+    // after `cd /` that would put a watch on every directory on the system.
+    watched_dir: Option<PathBuf>,
+    // Directories something needs watched as well, e.g. for `on_change:` globs.
+    roots: Vec<(PathBuf, RecursiveMode)>,
+    // What notify is watching, to diff against when either of the above changes.
+    watches: HashMap<PathBuf, RecursiveMode>,
 }
 
 impl FileWatcherService {
     pub fn new() -> Result<(Self, mpsc::UnboundedReceiver<FileWatcherEvent>), Box<dyn std::error::Error>> {
         let (tx, rx) = mpsc::unbounded_channel();
 
-        let watcher = RecommendedWatcher::new(move |res| {
+        let watcher = RecommendedWatcher::new(move |res: notify::Result<notify::Event>| {
             match res {
                 Ok(event) => {
                     let event_type = event.kind;
@@ -26,7 +41,7 @@ impl FileWatcherService {
                             FileWatcherEvent::FileChanged(path)
                         } else if event_type.is_remove() {
                             FileWatcherEvent::FileDeleted(path)
-                        } else if event_type.is_any() && path.is_dir() {
+                        } else if matches!(event_type, EventKind::Any) && path.is_dir() {
                             FileWatcherEvent::DirectoryChanged(path)
                         } else {
                             continue; // Ignore other event types for now
@@ -45,27 +60,197 @@ impl FileWatcherService {
             }
         }, notify::Config::default().with_poll_interval(Duration::from_secs(1)))?; // Poll every second
 
-        Ok((Self { watcher, event_receiver: rx }, rx))
+        Ok((Self { watcher: Backend::Raw(watcher), watched_dir: None, roots: Vec::new(), watches: HashMap::new() }, rx))
+    }
+
+    /// Like `new`, but a burst of changes to a path (an editor saving via a temp file, a
+    /// formatter rewriting it) is reported once, `delay` after it settles. Debounced events
+    /// don't tell creation from modification, so paths that still exist are `FileChanged`.
+    /// A path that never settles, like a log being appended to, is not reported.
+    pub fn debounced(delay: Duration) -> Result<(Self, mpsc::UnboundedReceiver<FileWatcherEvent>), Box<dyn std::error::Error>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let debouncer = new_debouncer(delay, move |res: DebounceEventResult| {
+            let events = match res {
+                Ok(events) => events
+                    .into_iter()
+                    // The debouncer also reports a path still changing `delay` after its
+                    // first change, which would make most bursts count twice.
+                    .filter(|event| event.kind == DebouncedEventKind::Any)
+                    .map(|event| if event.path.exists() {
+                        FileWatcherEvent::FileChanged(event.path)
+                    } else {
+                        FileWatcherEvent::FileDeleted(event.path)
+                    })
+                    .collect(),
+                Err(e) => vec![FileWatcherEvent::Error(e.to_string())],
+            };
+            for file_event in events {
+                if tx.send(file_event).is_err() {
+                    eprintln!("FileWatcherService: Failed to send event, receiver dropped.");
+                    break;
+                }
+            }
+        })?;
+
+        Ok((Self { watcher: Backend::Debounced(debouncer), watched_dir: None, roots: Vec::new(), watches: HashMap::new() }, rx))
+    }
+
+    fn watcher(&mut self) -> &mut dyn Watcher {
+        match &mut self.watcher {
+            Backend::Raw(watcher) => watcher,
+            Backend::Debounced(debouncer) => debouncer.watcher(),
+        }
     }
 
-    pub fn watch(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        self.watcher.watch(path, RecursiveMode::Recursive)?;
-        println!("FileWatcherService: Watching {:?}", path);
+    pub fn watch(&mut self, path: &Path, mode: RecursiveMode) -> Result<(), Box<dyn std::error::Error>> {
+        self.watcher().watch(path, mode)?;
+        debug!("FileWatcherService: Watching {:?} ({:?})", path, mode);
         Ok(())
     }
 
     pub fn unwatch(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        self.watcher.unwatch(path)?;
-        println!("FileWatcherService: Unwatching {:?}", path);
+        self.watcher().unwatch(path)?;
+        debug!("FileWatcherService: Unwatching {:?}", path);
         Ok(())
     }
 
+    /// Moves the working-directory watch to `path`, dropping the previous one. Only the
+    /// directory's own entries are watched; see `watch_roots` for going deeper.
+    /// A no-op when `path` is already the watched directory.
+    pub fn watch_directory(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if self.watched_dir.as_deref() == Some(path) {
+            return Ok(());
+        }
+        self.watched_dir = Some(path.to_path_buf());
+        self.sync_watches()
+    }
+
+    /// Replaces the extra directories to watch, e.g. `ChangeTriggers::watch_roots`.
+    pub fn watch_roots(&mut self, roots: Vec<(PathBuf, RecursiveMode)>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.roots == roots {
+            return Ok(());
+        }
+        self.roots = roots;
+        self.sync_watches()
+    }
+
+    /// Points notify at the working directory and the roots, changing only what differs.
+    /// Returns the last failure, after trying every watch.
+    fn sync_watches(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut wanted: HashMap<PathBuf, RecursiveMode> = HashMap::new();
+        let working_dir = self.watched_dir.iter().map(|dir| (dir.clone(), RecursiveMode::NonRecursive));
+        for (path, mode) in working_dir.chain(self.roots.iter().cloned()) {
+            let wanted_mode = wanted.entry(path).or_insert(mode);
+            if mode == RecursiveMode::Recursive {
+                *wanted_mode = mode;
+            }
+        }
+
+        let stale: Vec<PathBuf> = self.watches.iter()
+            .filter(|(path, mode)| wanted.get(*path) != Some(*mode))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in stale {
+            self.watches.remove(&path);
+            // The directory may have been removed, in which case notify already dropped it.
+            if let Err(e) = self.unwatch(&path) {
+                eprintln!("FileWatcherService: Failed to unwatch {:?}: {}", path, e);
+            }
+        }
+
+        let mut result = Ok(());
+        for (path, mode) in wanted {
+            if self.watches.contains_key(&path) {
+                continue;
+            }
+            match self.watch(&path, mode) {
+                Ok(()) => {
+                    self.watches.insert(path, mode);
+                }
+                Err(e) => result = Err(e),
+            }
+        }
+        result
+    }
+
+    pub fn watched_directory(&self) -> Option<&Path> {
+        self.watched_dir.as_deref()
+    }
+
     // Dummy constructor for when real watcher fails
     pub fn new_dummy() -> Self {
-        let (tx, rx) = mpsc::unbounded_channel();
         FileWatcherService {
-            watcher: notify::RecommendedWatcher::new(move |res| { /* do nothing */ }, notify::Config::default()).unwrap(),
-            event_receiver: rx, // This receiver will never receive anything from the dummy watcher
+            watcher: Backend::Raw(notify::RecommendedWatcher::new(|_res: notify::Result<notify::Event>| { /* do nothing */ }, notify::Config::default()).unwrap()),
+            watched_dir: None,
+            roots: Vec::new(),
+            watches: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+    use tempfile::tempdir;
+
+    const SETTLE: Duration = Duration::from_millis(300);
+
+    fn changed_paths(events: &mut mpsc::UnboundedReceiver<FileWatcherEvent>) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            match event {
+                FileWatcherEvent::FileChanged(path) | FileWatcherEvent::FileCreated(path) => paths.push(path),
+                _ => {}
+            }
+        }
+        paths
+    }
+
+    #[test]
+    fn test_working_directory_is_watched_one_level_deep() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir(&src).unwrap();
+        let (mut service, mut events) = FileWatcherService::new().unwrap();
+        service.watch_directory(dir.path()).unwrap();
+
+        std::fs::write(src.join("lib.rs"), "").unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+        sleep(SETTLE);
+        let changed = changed_paths(&mut events);
+        assert!(changed.contains(&dir.path().join("Cargo.toml")));
+        assert!(!changed.iter().any(|path| path.starts_with(&src)));
+
+        service.watch_roots(vec![(src.clone(), RecursiveMode::Recursive)]).unwrap();
+        std::fs::write(src.join("lib.rs"), "pub fn f() {}").unwrap();
+        sleep(SETTLE);
+        assert!(changed_paths(&mut events).contains(&src.join("lib.rs")));
+
+        // Dropping the root stops the nested watch but keeps the working directory's.
+        service.watch_roots(Vec::new()).unwrap();
+        std::fs::write(src.join("lib.rs"), "").unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        sleep(SETTLE);
+        let mut changed = changed_paths(&mut events);
+        changed.dedup();
+        assert_eq!(changed, vec![dir.path().join("Cargo.toml")]);
+    }
+
+    #[test]
+    fn test_debounced_burst_is_reported_once() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        let delay = Duration::from_millis(200);
+        let (mut service, mut events) = FileWatcherService::debounced(delay).unwrap();
+        service.watch_directory(dir.path()).unwrap();
+
+        for i in 0..5 {
+            std::fs::write(&file, format!("// save {}", i)).unwrap();
+            sleep(Duration::from_millis(20));
         }
+        sleep(delay * 4);
+        assert_eq!(changed_paths(&mut events), vec![file]);
     }
 }
@@ -5,9 +5,12 @@ use iced::{Element, widget::{
 use iced::{Alignment, Length, Color};
 use uuid::Uuid;
 use std::collections::HashMap;
+use std::fmt;
 
-use crate::terminal::Message;
-use crate::workflows::{Workflow, WorkflowManager, Shell, WorkflowCollection};
+use crate::terminal::TerminalMessage;
+use crate::workflows::history::{RunHistory, WorkflowStats};
+use crate::workflows::secrets::Masked;
+use crate::workflows::{Workflow, WorkflowManager};
 use crate::themes::WarpTheme; // Assuming WarpTheme is accessible
 
 #[derive(Debug, Clone)]
@@ -23,9 +26,12 @@ pub enum WorkflowBrowserMessage {
     ImportWorkflowClicked,
     ExportWorkflowClicked(Uuid),
     RefreshWorkflowsClicked,
+    CategorySelected(WorkflowCategory),
+    SortOrderSelected(WorkflowSortOrder),
+    RerunWorkflowClicked(Uuid),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WorkflowCategory {
     All,
     Favorites,
@@ -34,7 +40,29 @@ pub enum WorkflowCategory {
     Custom,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl WorkflowCategory {
+    pub const ALL: [WorkflowCategory; 5] = [
+        WorkflowCategory::All,
+        WorkflowCategory::Favorites,
+        WorkflowCategory::Recent,
+        WorkflowCategory::Collections,
+        WorkflowCategory::Custom,
+    ];
+}
+
+impl fmt::Display for WorkflowCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WorkflowCategory::All => "All",
+            WorkflowCategory::Favorites => "Favorites",
+            WorkflowCategory::Recent => "Recent",
+            WorkflowCategory::Collections => "Collections",
+            WorkflowCategory::Custom => "Custom",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WorkflowSortOrder {
     Name,
     LastUsed,
@@ -42,6 +70,25 @@ pub enum WorkflowSortOrder {
     Created,
 }
 
+impl WorkflowSortOrder {
+    pub const ALL: [WorkflowSortOrder; 4] =
+        [WorkflowSortOrder::Name, WorkflowSortOrder::LastUsed, WorkflowSortOrder::UsageCount, WorkflowSortOrder::Created];
+}
+
+impl fmt::Display for WorkflowSortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WorkflowSortOrder::Name => "Name",
+            WorkflowSortOrder::LastUsed => "Last used",
+            WorkflowSortOrder::UsageCount => "Most used",
+            WorkflowSortOrder::Created => "Newest",
+        })
+    }
+}
+
+/// How many workflows the Recent category shows.
+const RECENT_LIMIT: usize = 10;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ViewMode {
     List,
@@ -54,6 +101,12 @@ pub struct WorkflowBrowser {
     search_query: String,
     workflows: Vec<Workflow>,
     favorite_workflow_ids: Vec<Uuid>,
+    category: WorkflowCategory,
+    sort_order: WorkflowSortOrder,
+    // From the run history; workflows that never ran have no entry.
+    stats: HashMap<Uuid, WorkflowStats>,
+    recent_workflow_ids: Vec<Uuid>,
+    last_arguments: HashMap<Uuid, HashMap<String, String>>,
 }
 
 impl WorkflowBrowser {
@@ -63,6 +116,11 @@ impl WorkflowBrowser {
             search_query: String::new(),
             workflows: Vec::new(),
             favorite_workflow_ids: Vec::new(),
+            category: WorkflowCategory::All,
+            sort_order: WorkflowSortOrder::Name,
+            stats: HashMap::new(),
+            recent_workflow_ids: Vec::new(),
+            last_arguments: HashMap::new(),
         }
     }
 
@@ -83,7 +141,63 @@ impl WorkflowBrowser {
         self.favorite_workflow_ids = workflow_manager.get_favorite_workflow_ids();
     }
 
-    pub fn update(&mut self, message: WorkflowBrowserMessage) -> Option<Message> {
+    /// Refreshes usage stats, e.g. after a run finished.
+    pub fn update_history(&mut self, history: &RunHistory) {
+        self.recent_workflow_ids = history.recent_workflows();
+        self.stats = self.recent_workflow_ids.iter().map(|id| (*id, history.stats(id))).collect();
+        self.last_arguments = self
+            .recent_workflow_ids
+            .iter()
+            .filter_map(|id| history.last_run(id).map(|run| (*id, run.arguments.clone().into_iter().collect())))
+            .collect();
+    }
+
+    /// The workflows to list: matching the search and category, in the chosen order.
+    fn visible_workflows(&self) -> Vec<&Workflow> {
+        let query = self.search_query.to_lowercase();
+        let recent = &self.recent_workflow_ids[..self.recent_workflow_ids.len().min(RECENT_LIMIT)];
+        let mut workflows: Vec<&Workflow> = self.workflows.iter()
+            .filter(|w| w.name.to_lowercase().contains(&query))
+            .filter(|w| match self.category {
+                WorkflowCategory::All => true,
+                WorkflowCategory::Favorites => self.favorite_workflow_ids.contains(&w.id),
+                WorkflowCategory::Recent => recent.contains(&w.id),
+                // Imported from a shared collection, or written here.
+                WorkflowCategory::Collections => w.source_url.is_some(),
+                WorkflowCategory::Custom => w.source_url.is_none(),
+            })
+            .collect();
+
+        let stats = |w: &Workflow| self.stats.get(&w.id);
+        match self.sort_order {
+            WorkflowSortOrder::Name => workflows.sort_by_key(|w| w.name.to_lowercase()),
+            // Most recent or most used first; workflows that never ran go last.
+            WorkflowSortOrder::LastUsed => workflows.sort_by_key(|w| std::cmp::Reverse(stats(w).and_then(|s| s.last_used))),
+            WorkflowSortOrder::UsageCount => workflows.sort_by_key(|w| std::cmp::Reverse(stats(w).map_or(0, |s| s.runs))),
+            WorkflowSortOrder::Created => workflows.sort_by_key(|w| std::cmp::Reverse(w.created_at)),
+        }
+        if self.category == WorkflowCategory::Recent && self.sort_order == WorkflowSortOrder::Name {
+            // Recent is about order of use unless asked otherwise.
+            workflows.sort_by_key(|w| recent.iter().position(|id| *id == w.id));
+        }
+        workflows
+    }
+
+    /// e.g. "12 runs · 92% ok · 1.4s avg"
+    fn stats_summary(&self, id: &Uuid) -> String {
+        match self.stats.get(id) {
+            Some(stats) if stats.runs > 0 => format!(
+                "{} run{} · {:.0}% ok · {:.1}s avg",
+                stats.runs,
+                if stats.runs == 1 { "" } else { "s" },
+                stats.success_rate() * 100.0,
+                stats.average_duration.as_secs_f64()
+            ),
+            _ => "never run".to_string(),
+        }
+    }
+
+    pub fn update(&mut self, message: WorkflowBrowserMessage) -> Option<TerminalMessage> {
         match message {
             WorkflowBrowserMessage::ToggleVisibility => {
                 self.is_visible = !self.is_visible;
@@ -94,13 +208,14 @@ impl WorkflowBrowser {
                 None
             }
             WorkflowBrowserMessage::ExecuteWorkflowClicked(id) => {
-                Some(Message::ExecuteWorkflow(id))
+                // The argument form; it runs the workflow once the arguments are valid.
+                Some(TerminalMessage::OpenWorkflow(id))
             }
             WorkflowBrowserMessage::AddFavoriteClicked(id) => {
-                Some(Message::AddWorkflowToFavorites(id))
+                Some(TerminalMessage::SetWorkflowFavorite(id, true))
             }
             WorkflowBrowserMessage::RemoveFavoriteClicked(id) => {
-                Some(Message::RemoveWorkflowFromFavorites(id))
+                Some(TerminalMessage::SetWorkflowFavorite(id, false))
             }
             WorkflowBrowserMessage::EditWorkflowClicked(id) => {
                 println!("Edit workflow: {:?}", id); // Placeholder
@@ -123,7 +238,24 @@ impl WorkflowBrowser {
                 None
             }
             WorkflowBrowserMessage::RefreshWorkflowsClicked => {
-                Some(Message::RefreshWorkflows)
+                Some(TerminalMessage::RefreshWorkflows)
+            }
+            WorkflowBrowserMessage::CategorySelected(category) => {
+                self.category = category;
+                None
+            }
+            WorkflowBrowserMessage::SortOrderSelected(sort_order) => {
+                self.sort_order = sort_order;
+                None
+            }
+            WorkflowBrowserMessage::RerunWorkflowClicked(id) => {
+                // Secret arguments aren't recorded, so they have to be entered again.
+                let has_secrets = self.workflows.iter().any(|w| w.id == id && w.arguments.iter().any(|arg| arg.secret));
+                if has_secrets {
+                    return Some(TerminalMessage::OpenWorkflow(id));
+                }
+                let arguments = self.last_arguments.get(&id).cloned().unwrap_or_default();
+                Some(TerminalMessage::RunWorkflow(id, Masked(arguments)))
             }
         }
     }
@@ -135,16 +267,30 @@ impl WorkflowBrowser {
         let border_color = theme.get_border_color();
         let accent_color = theme.get_accent_color();
 
-        let filtered_workflows: Vec<&Workflow> = self.workflows.iter()
-            .filter(|w| w.name.to_lowercase().contains(&self.search_query.to_lowercase()))
-            .collect();
-
         let workflow_list = scrollable(
             column(
-                filtered_workflows.into_iter().map(|workflow| {
+                self.visible_workflows().into_iter().map(|workflow| {
                     let is_favorite = self.favorite_workflow_ids.contains(&workflow.id);
+                    let rerun: Element<WorkflowBrowserMessage> = if self.last_arguments.contains_key(&workflow.id) {
+                        button(text("Re-run").color(foreground_color))
+                            .on_press(WorkflowBrowserMessage::RerunWorkflowClicked(workflow.id))
+                            .style(iced::widget::button::text::Appearance {
+                                background: Some(iced::Background::Color(background_color)),
+                                border_radius: 4.0.into(),
+                                text_color: foreground_color,
+                                ..Default::default()
+                            })
+                            .into()
+                    } else {
+                        Space::with_width(Length::Shrink).into()
+                    };
                     row![
-                        text(&workflow.name).size(16).color(foreground_color).width(Length::Fill),
+                        column![
+                            text(&workflow.name).size(16).color(foreground_color),
+                            text(self.stats_summary(&workflow.id)).size(12).color(border_color),
+                        ]
+                        .width(Length::Fill),
+                        rerun,
                         button(text(if is_favorite { "Unfavorite" } else { "Favorite" }).color(foreground_color))
                             .on_press(if is_favorite {
                                 WorkflowBrowserMessage::RemoveFavoriteClicked(workflow.id)
@@ -223,6 +369,8 @@ impl WorkflowBrowser {
                             selection_color: accent_color,
                         })
                         .width(Length::Fill),
+                    pick_list(&WorkflowCategory::ALL[..], Some(self.category), WorkflowBrowserMessage::CategorySelected),
+                    pick_list(&WorkflowSortOrder::ALL[..], Some(self.sort_order), WorkflowBrowserMessage::SortOrderSelected),
                     button("Refresh").on_press(WorkflowBrowserMessage::RefreshWorkflowsClicked)
                         .style(iced::widget::button::text::Appearance {
                             background: Some(iced::Background::Color(background_color)),
@@ -271,3 +419,96 @@ impl WorkflowBrowser {
         .into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::history::{RunRecord, RunTrigger};
+    use chrono::{DateTime, Utc};
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2024-03-01T10:{:02}:00Z", minute)).unwrap().with_timezone(&Utc)
+    }
+
+    fn run(workflow: &Workflow, minute: u32, succeeded: bool, duration_ms: u64) -> RunRecord {
+        RunRecord {
+            workflow_id: workflow.id,
+            started_at: at(minute),
+            duration_ms,
+            arguments: BTreeMap::new(),
+            succeeded,
+            exit_code: Some(if succeeded { 0 } else { 1 }),
+            trigger: RunTrigger::Manual,
+            error: None,
+        }
+    }
+
+    /// `build` ran twice, then `test` once; `Deploy` comes from a collection and never ran.
+    fn browser(history: &mut RunHistory) -> (WorkflowBrowser, [Workflow; 3]) {
+        let mut build = Workflow::from_command("build", "cargo build");
+        build.created_at = at(0);
+        let mut deploy = Workflow::from_command("Deploy", "make deploy");
+        deploy.created_at = at(20);
+        deploy.source_url = Some("https://example.com/team.yaml".to_string());
+        let mut test = Workflow::from_command("test", "cargo test");
+        test.created_at = at(10);
+        history.record(run(&build, 1, true, 1000)).unwrap();
+        history.record(run(&build, 2, false, 2000)).unwrap();
+        history.record(run(&test, 3, true, 300)).unwrap();
+
+        let mut browser = WorkflowBrowser::new();
+        browser.workflows = vec![build.clone(), deploy.clone(), test.clone()];
+        browser.favorite_workflow_ids = vec![test.id];
+        browser.update_history(history);
+        (browser, [build, deploy, test])
+    }
+
+    fn names(browser: &WorkflowBrowser) -> Vec<&str> {
+        browser.visible_workflows().iter().map(|w| w.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_visible_workflows_sort_and_filter() {
+        let dir = tempdir().unwrap();
+        let (mut browser, _) = browser(&mut RunHistory::empty(&dir.path().join("runs.jsonl")));
+
+        assert_eq!(names(&browser), ["build", "Deploy", "test"]);
+        browser.update(WorkflowBrowserMessage::SortOrderSelected(WorkflowSortOrder::LastUsed));
+        assert_eq!(names(&browser), ["test", "build", "Deploy"]);
+        browser.update(WorkflowBrowserMessage::SortOrderSelected(WorkflowSortOrder::UsageCount));
+        assert_eq!(names(&browser), ["build", "test", "Deploy"]);
+        browser.update(WorkflowBrowserMessage::SortOrderSelected(WorkflowSortOrder::Created));
+        assert_eq!(names(&browser), ["Deploy", "test", "build"]);
+
+        browser.update(WorkflowBrowserMessage::SortOrderSelected(WorkflowSortOrder::Name));
+        browser.update(WorkflowBrowserMessage::CategorySelected(WorkflowCategory::Recent));
+        assert_eq!(names(&browser), ["test", "build"]);
+        browser.update(WorkflowBrowserMessage::CategorySelected(WorkflowCategory::Favorites));
+        assert_eq!(names(&browser), ["test"]);
+        browser.update(WorkflowBrowserMessage::CategorySelected(WorkflowCategory::Collections));
+        assert_eq!(names(&browser), ["Deploy"]);
+        browser.update(WorkflowBrowserMessage::CategorySelected(WorkflowCategory::Custom));
+        assert_eq!(names(&browser), ["build", "test"]);
+
+        browser.update(WorkflowBrowserMessage::CategorySelected(WorkflowCategory::All));
+        browser.update(WorkflowBrowserMessage::SearchInputChanged("DEP".to_string()));
+        assert_eq!(names(&browser), ["Deploy"]);
+    }
+
+    #[test]
+    fn test_stats_summary() {
+        let dir = tempdir().unwrap();
+        let mut history = RunHistory::empty(&dir.path().join("runs.jsonl"));
+        let (mut browser, [build, deploy, test]) = browser(&mut history);
+
+        assert_eq!(browser.stats_summary(&build.id), "2 runs · 50% ok · 1.5s avg");
+        assert_eq!(browser.stats_summary(&test.id), "1 run · 100% ok · 0.3s avg");
+        assert_eq!(browser.stats_summary(&deploy.id), "never run");
+
+        history.record(run(&deploy, 4, true, 4000)).unwrap();
+        browser.update_history(&history);
+        assert_eq!(browser.stats_summary(&deploy.id), "1 run · 100% ok · 4.0s avg");
+    }
+}
@@ -1,25 +1,38 @@
 use iced::{
-    widget::{column, row, text, button, text_input, Space},
+    widget::{column, row, text, button, text_input, checkbox, pick_list, Space},
     Element, Length, Color,
 };
+use log::warn;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use uuid::Uuid;
 
-use crate::workflows::Workflow;
-use crate::terminal::Message;
+use crate::fuzzy_match::FuzzyMatcher;
+use crate::workflows::secrets::Masked;
+use crate::workflows::suggestions::{filter_suggestions, SuggestionCache};
+use crate::workflows::{args, ArgumentErrors, ArgumentType, Workflow, WorkflowArgument};
+use crate::terminal::TerminalMessage;
 use crate::themes::WarpTheme; // Assuming WarpTheme is accessible
 
 #[derive(Debug, Clone)]
 pub enum WorkflowExecutorMessage {
     ToggleVisibility,
-    ArgumentInputChanged(String, String), // (argument_name, new_value)
+    ArgumentInputChanged(String, Masked<String>), // (argument_name, new_value), which may be a secret
+    SuggestionsLoaded(String, Result<Vec<String>, String>), // (argument_name, generator output)
     ExecuteWorkflow,
 }
 
+/// Suggestions shown under an argument's input.
+const MAX_SUGGESTIONS: usize = 5;
+
 pub struct WorkflowExecutor {
     is_visible: bool,
     workflow: Option<Workflow>,
     argument_values: HashMap<String, String>,
+    errors: Option<ArgumentErrors>,
+    suggestion_cache: SuggestionCache,
+    suggestions: HashMap<String, Vec<String>>,
+    matcher: FuzzyMatcher,
 }
 
 impl WorkflowExecutor {
@@ -28,52 +41,155 @@ impl WorkflowExecutor {
             is_visible: false,
             workflow: None,
             argument_values: HashMap::new(),
+            errors: None,
+            suggestion_cache: SuggestionCache::new(),
+            suggestions: HashMap::new(),
+            matcher: FuzzyMatcher::new(),
         }
     }
 
-    pub fn show_workflow(&mut self, workflow: Workflow) {
+    /// Opens the form for `workflow`. Arguments with a generator get their suggestions
+    /// from it, run in `cwd`, by the returned command.
+    pub fn show_workflow(&mut self, workflow: Workflow, cwd: PathBuf) -> iced::Command<WorkflowExecutorMessage> {
         self.workflow = Some(workflow.clone());
         self.argument_values.clear();
-        if let Some(args) = workflow.arguments {
-            for arg_name in args.keys() {
-                self.argument_values.insert(arg_name.clone(), String::new());
+        self.suggestions.clear();
+        self.errors = None;
+        let mut loads = Vec::new();
+        for arg in &workflow.arguments {
+            self.argument_values.insert(arg.name.clone(), arg.default_value.clone().unwrap_or_default());
+            if let Some(generator) = arg.generator.clone().filter(|_| !arg.secret) {
+                let (cache, cwd, name) = (self.suggestion_cache.clone(), cwd.clone(), arg.name.clone());
+                loads.push(iced::Command::perform(
+                    async move {
+                        let result = cache.suggestions(&generator, &cwd).await;
+                        result.map(|suggestions| suggestions.to_vec()).map_err(|e| e.to_string())
+                    },
+                    move |result| WorkflowExecutorMessage::SuggestionsLoaded(name.clone(), result),
+                ));
             }
         }
         self.is_visible = true;
+        iced::Command::batch(loads)
     }
 
     pub fn hide(&mut self) {
         self.is_visible = false;
         self.workflow = None;
         self.argument_values.clear();
+        self.suggestions.clear();
+        self.errors = None;
     }
 
     pub fn is_visible(&self) -> bool {
         self.is_visible
     }
 
-    pub fn update(&mut self, message: WorkflowExecutorMessage) -> Option<Message> {
+    pub fn update(&mut self, message: WorkflowExecutorMessage) -> Option<TerminalMessage> {
         match message {
             WorkflowExecutorMessage::ToggleVisibility => {
                 self.hide();
                 None
             }
-            WorkflowExecutorMessage::ArgumentInputChanged(name, value) => {
+            WorkflowExecutorMessage::ArgumentInputChanged(name, Masked(value)) => {
                 self.argument_values.insert(name, value);
+                // Re-check as the user types once a submit has failed.
+                if self.errors.is_some() {
+                    self.errors = self.validate().err();
+                }
+                None
+            }
+            WorkflowExecutorMessage::SuggestionsLoaded(name, result) => {
+                match result {
+                    Ok(suggestions) => {
+                        self.suggestions.insert(name, suggestions);
+                    }
+                    Err(e) => warn!("No suggestions for argument '{}': {}", name, e),
+                }
                 None
             }
             WorkflowExecutorMessage::ExecuteWorkflow => {
+                if let Err(errors) = self.validate() {
+                    self.errors = Some(errors);
+                    return None;
+                }
                 if let Some(workflow) = &self.workflow {
                     let workflow_id = workflow.id;
                     let arguments = self.argument_values.clone();
                     self.hide();
-                    return Some(Message::ExecuteWorkflowWithArguments(workflow_id, arguments));
+                    return Some(TerminalMessage::RunWorkflow(workflow_id, Masked(arguments)));
                 }
                 None
             }
         }
     }
 
+    fn validate(&self) -> Result<(), ArgumentErrors> {
+        match &self.workflow {
+            Some(workflow) => args::resolve_arguments(workflow, &self.argument_values).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// The input widget matching the argument's type.
+    fn argument_input<'a>(&self, arg: &'a WorkflowArgument, style: text_input::Appearance) -> Element<'a, WorkflowExecutorMessage> {
+        let name = arg.name.clone();
+        let current_value = self.argument_values.get(&arg.name).cloned().unwrap_or_default();
+        match &arg.arg_type {
+            // Masked, and without suggestions: a generator's output could list other secrets.
+            _ if arg.secret => {
+                text_input("Enter secret...", &current_value)
+                    .on_input(move |s| WorkflowExecutorMessage::ArgumentInputChanged(name.clone(), Masked(s)))
+                    .secure(true)
+                    .padding(8)
+                    .size(16)
+                    .style(style)
+                    .into()
+            }
+            ArgumentType::Boolean => {
+                let checked = ArgumentType::Boolean.normalize(&current_value).is_ok_and(|v| v == "true");
+                checkbox(arg.name.as_str(), checked)
+                    .on_toggle(move |checked| WorkflowExecutorMessage::ArgumentInputChanged(name.clone(), Masked(checked.to_string())))
+                    .into()
+            }
+            ArgumentType::Enum(options) => {
+                let selected = options.iter().find(|o| **o == current_value).cloned();
+                pick_list(options.as_slice(), selected, move |choice| {
+                    WorkflowExecutorMessage::ArgumentInputChanged(name.clone(), Masked(choice))
+                })
+                .placeholder("Choose a value...")
+                .padding(8)
+                .into()
+            }
+            arg_type => {
+                let placeholder = match arg_type {
+                    ArgumentType::Int => "Enter a number...",
+                    ArgumentType::Path => "Enter a path...",
+                    _ => "Enter value...",
+                };
+                let on_input_name = name.clone();
+                let input = text_input(placeholder, &current_value)
+                    .on_input(move |s| WorkflowExecutorMessage::ArgumentInputChanged(on_input_name.clone(), Masked(s)))
+                    .padding(8)
+                    .size(16)
+                    .style(style);
+                let candidates = self.suggestions.get(&arg.name).map(Vec::as_slice).unwrap_or_default();
+                let matches = filter_suggestions(&self.matcher, &current_value, candidates, MAX_SUGGESTIONS);
+                if matches.is_empty() {
+                    return input.into();
+                }
+                let chips = matches.into_iter().fold(row![].spacing(5), |chips, suggestion| {
+                    chips.push(
+                        button(text(suggestion.clone()).size(14))
+                            .padding([2, 6])
+                            .on_press(WorkflowExecutorMessage::ArgumentInputChanged(name.clone(), Masked(suggestion))),
+                    )
+                });
+                column![input, chips].spacing(5).into()
+            }
+        }
+    }
+
     pub fn view(&self) -> Element<WorkflowExecutorMessage> {
         let theme = WarpTheme::default_dark(); // Use a default theme for the executor UI
         let background_color = theme.get_block_background_color(theme.is_dark_theme());
@@ -85,35 +201,35 @@ impl WorkflowExecutor {
 
         if let Some(workflow) = &self.workflow {
             content = content.push(text(format!("Execute Workflow: {}", workflow.name)).size(24).color(foreground_color));
-            content = content.push(text(format!("Command: {}", workflow.command)).size(16).color(foreground_color));
+            for step in &workflow.steps {
+                content = content.push(text(format!("Command: {}", step.command)).size(16).color(foreground_color));
+            }
             content = content.push(Space::with_height(Length::Fixed(20.0)));
 
-            if let Some(args) = &workflow.arguments {
-                for (arg_name, arg_description) in args {
-                    let current_value = self.argument_values.get(arg_name).unwrap_or(&String::new()).clone();
-                    content = content.push(
-                        column![
-                            text(format!("{}: {}", arg_name, arg_description)).size(16).color(foreground_color),
-                            text_input("Enter value...", &current_value)
-                                .on_input(move |s| WorkflowExecutorMessage::ArgumentInputChanged(arg_name.clone(), s))
-                                .padding(8)
-                                .size(16)
-                                .style(iced::widget::text_input::Appearance {
-                                    background: iced::Background::Color(background_color),
-                                    border: iced::Border {
-                                        color: border_color,
-                                        width: 1.0,
-                                        radius: 4.0.into(),
-                                    },
-                                    icon_color: foreground_color,
-                                    placeholder_color: theme.get_terminal_color("white", false),
-                                    value_color: foreground_color,
-                                    selection_color: accent_color,
-                                }),
-                        ]
-                        .spacing(5)
-                    );
+            for arg in &workflow.arguments {
+                let arg_description = arg.description.as_deref().unwrap_or_default();
+                let marker = if arg.required && arg.default_value.is_none() { " *" } else { "" };
+                let input_style = iced::widget::text_input::Appearance {
+                    background: iced::Background::Color(background_color),
+                    border: iced::Border {
+                        color: border_color,
+                        width: 1.0,
+                        radius: 4.0.into(),
+                    },
+                    icon_color: foreground_color,
+                    placeholder_color: theme.get_terminal_color("white", false),
+                    value_color: foreground_color,
+                    selection_color: accent_color,
+                };
+                let mut field = column![
+                    text(format!("{}{}: {}", arg.name, marker, arg_description)).size(16).color(foreground_color),
+                    self.argument_input(arg, input_style),
+                ]
+                .spacing(5);
+                if let Some(error) = self.errors.as_ref().and_then(|errors| errors.for_argument(&arg.name)) {
+                    field = field.push(text(error.to_string()).size(14).color(Color::from_rgb(0.9, 0.3, 0.3)));
                 }
+                content = content.push(field);
             }
 
             content = content.push(Space::with_height(Length::Fixed(20.0)));
@@ -0,0 +1,380 @@
+// Argument validation and substitution for workflow commands.
+//
+// Placeholders are `${name}` for declared arguments (plus any extra name the caller
+// supplies); other `${...}` text is left for the shell. Values are quoted for the
+// position they land in, so `echo "${msg}"` and `grep ${pattern}` both receive the
+// value as exactly one word.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+use regex::Regex;
+
+use super::model::{ArgumentType, Workflow, WorkflowArgument};
+use super::secrets::{Secrets, MASK};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentError {
+    Missing { name: String },
+    Invalid { name: String, value: String, reason: String },
+}
+
+impl ArgumentError {
+    pub fn name(&self) -> &str {
+        match self {
+            ArgumentError::Missing { name } | ArgumentError::Invalid { name, .. } => name,
+        }
+    }
+}
+
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgumentError::Missing { name } => write!(f, "missing argument '{}'", name),
+            ArgumentError::Invalid { name, value, reason } => {
+                write!(f, "invalid value '{}' for argument '{}': {}", value, name, reason)
+            }
+        }
+    }
+}
+
+/// Every problem found with a set of arguments, in the order the arguments are declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgumentErrors(pub Vec<ArgumentError>);
+
+impl ArgumentErrors {
+    pub fn for_argument(&self, name: &str) -> Option<&ArgumentError> {
+        self.0.iter().find(|e| e.name() == name)
+    }
+}
+
+impl fmt::Display for ArgumentErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ArgumentErrors {}
+
+impl ArgumentType {
+    /// Checks `value` against the type and returns it in canonical form
+    /// (booleans become `true`/`false`, a leading `~/` in paths is expanded).
+    pub fn normalize(&self, value: &str) -> Result<String, String> {
+        match self {
+            ArgumentType::String => Ok(value.to_string()),
+            ArgumentType::Int => value
+                .trim()
+                .parse::<i64>()
+                .map(|n| n.to_string())
+                .map_err(|_| "expected a whole number".to_string()),
+            ArgumentType::Boolean => match value.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" | "y" | "1" | "on" => Ok("true".to_string()),
+                "false" | "no" | "n" | "0" | "off" => Ok("false".to_string()),
+                _ => Err("expected true or false".to_string()),
+            },
+            ArgumentType::Path => {
+                if value.contains('\0') {
+                    return Err("paths cannot contain NUL bytes".to_string());
+                }
+                // Quoting the value stops the shell from expanding `~`, so do it here.
+                match dirs::home_dir() {
+                    Some(home) if value == "~" || value.starts_with("~/") => Ok(format!("{}{}", home.display(), &value[1..])),
+                    _ => Ok(value.to_string()),
+                }
+            }
+            ArgumentType::Enum(options) => {
+                if options.iter().any(|o| o == value) {
+                    Ok(value.to_string())
+                } else {
+                    Err(format!("expected one of: {}", options.join(", ")))
+                }
+            }
+        }
+    }
+}
+
+impl WorkflowArgument {
+    /// Validates a non-empty value against the argument's type and validation pattern.
+    pub fn validate(&self, value: &str) -> Result<String, String> {
+        let value = self.arg_type.normalize(value)?;
+        if let Some(pattern) = &self.validation {
+            let regex = Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| format!("invalid validation pattern '{}': {}", pattern, e))?;
+            if !regex.is_match(&value) {
+                return Err(format!("must match /{}/", pattern));
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Works out the final value of every declared argument. Empty values count as not given,
+/// so form fields left blank fall back to the default. Optional arguments with no value
+/// resolve to an empty string.
+pub fn resolve_arguments(
+    workflow: &Workflow,
+    provided: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, ArgumentErrors> {
+    let mut values = HashMap::new();
+    let mut errors = Vec::new();
+    for arg in &workflow.arguments {
+        let value = provided
+            .get(&arg.name)
+            .filter(|v| !v.is_empty())
+            .or(arg.default_value.as_ref().filter(|v| !v.is_empty()));
+        match value {
+            Some(value) => match arg.validate(value) {
+                Ok(value) => {
+                    values.insert(arg.name.clone(), value);
+                }
+                Err(reason) => errors.push(ArgumentError::Invalid {
+                    name: arg.name.clone(),
+                    // Errors end up in logs and on screen.
+                    value: if arg.secret { MASK.to_string() } else { value.clone() },
+                    reason,
+                }),
+            },
+            None if arg.required => errors.push(ArgumentError::Missing { name: arg.name.clone() }),
+            None => {
+                values.insert(arg.name.clone(), String::new());
+            }
+        }
+    }
+    // Undeclared extras (e.g. from legacy workflows) are substituted as given.
+    for (name, value) in provided {
+        values.entry(name.clone()).or_insert_with(|| value.clone());
+    }
+    if errors.is_empty() {
+        Ok(values)
+    } else {
+        Err(ArgumentErrors(errors))
+    }
+}
+
+/// Resolves the arguments and substitutes them into every step, returning the commands to run
+/// and the secrets they need. Secret arguments become references to their environment
+/// variable, so the commands only work with `Secrets::env` set on the process running them.
+pub fn render_commands(workflow: &Workflow, provided: &HashMap<String, String>) -> Result<(Vec<String>, Secrets), ArgumentErrors> {
+    let values = resolve_arguments(workflow, provided)?;
+    let secrets = Secrets::from_arguments(workflow, &values);
+    let secret_vars = secrets.env_vars();
+    let commands = workflow.steps.iter().map(|step| substitute_with_secrets(&step.command, &values, &secret_vars)).collect();
+    Ok((commands, secrets))
+}
+
+/// Quotes `value` as a single word for a POSIX shell, leaving plain words bare.
+pub fn shell_quote(value: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c);
+    if !value.is_empty() && value.chars().all(is_plain) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Quoting {
+    None,
+    Single,
+    Double,
+}
+
+/// Replaces `${name}` for each name in `values`, quoting for the surrounding context:
+/// bare words are single-quoted, inside double quotes only `\ " $ `` ` are escaped, and
+/// inside single quotes the quote is closed around the value.
+pub fn substitute(template: &str, values: &HashMap<String, String>) -> String {
+    substitute_with_secrets(template, values, &HashMap::new())
+}
+
+/// Like `substitute`, but placeholders of the arguments in `secret_vars` (name to variable)
+/// become a quoted reference to the variable, so the value never appears in the command.
+pub fn substitute_with_secrets(template: &str, values: &HashMap<String, String>, secret_vars: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut copied = 0;
+    for (range, name, quoting) in scan_placeholders(template) {
+        if let Some(var) = secret_vars.get(name) {
+            out.push_str(&template[copied..range.start]);
+            match quoting {
+                Quoting::None => out.push_str(&format!("\"${{{}}}\"", var)),
+                Quoting::Single => out.push_str(&format!("'\"${{{}}}\"'", var)),
+                Quoting::Double => out.push_str(&format!("${{{}}}", var)),
+            }
+            copied = range.end;
+            continue;
+        }
+        let Some(value) = values.get(name) else { continue };
+        out.push_str(&template[copied..range.start]);
+        match quoting {
+            // An optional argument left empty disappears instead of becoming ''.
+            Quoting::None if value.is_empty() => {}
+            Quoting::None => out.push_str(&shell_quote(value)),
+            Quoting::Single => out.push_str(&format!("'{}'", shell_quote(value))),
+            Quoting::Double => {
+                for ch in value.chars() {
+                    if matches!(ch, '\\' | '"' | '$' | '`') {
+                        out.push('\\');
+                    }
+                    out.push(ch);
+                }
+            }
+        }
+        copied = range.end;
+    }
+    out.push_str(&template[copied..]);
+    out
+}
+
+/// Every `${name}` in `template` that `substitute` would replace if `name` had a value,
+/// with its byte range.
+pub fn placeholders(template: &str) -> Vec<(Range<usize>, &str)> {
+    scan_placeholders(template).into_iter().map(|(range, name, _)| (range, name)).collect()
+}
+
+fn scan_placeholders(template: &str) -> Vec<(Range<usize>, &str, Quoting)> {
+    let mut found = Vec::new();
+    let mut quoting = Quoting::None;
+    let mut offset = 0;
+    while let Some(c) = template[offset..].chars().next() {
+        let rest = &template[offset..];
+        if let Some((name, after)) = placeholder(rest) {
+            let end = template.len() - after.len();
+            found.push((offset..end, name, quoting));
+            offset = end;
+            continue;
+        }
+
+        let len = c.len_utf8();
+        match (quoting, c) {
+            // A backslash outside single quotes protects the next character.
+            (Quoting::None | Quoting::Double, '\\') => {
+                offset += len + rest[len..].chars().next().map_or(0, char::len_utf8);
+                continue;
+            }
+            (Quoting::None, '\'') => quoting = Quoting::Single,
+            (Quoting::None, '"') => quoting = Quoting::Double,
+            (Quoting::Single, '\'') | (Quoting::Double, '"') => quoting = Quoting::None,
+            _ => {}
+        }
+        offset += len;
+    }
+    found
+}
+
+/// Splits a leading `${name}` off `s`.
+fn placeholder(s: &str) -> Option<(&str, &str)> {
+    let body = s.strip_prefix("${")?;
+    let end = body.find('}')?;
+    let name = &body[..end];
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    valid.then(|| (name, &body[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_values_are_quoted_for_their_context() {
+        let v = values(&[("msg", "it's $HOME"), ("file", "my notes.txt"), ("n", "3")]);
+        assert_eq!(substitute("head -n ${n} ${file}", &v), "head -n 3 'my notes.txt'");
+        assert_eq!(substitute(r#"echo "say: ${msg}""#, &v), r#"echo "say: it's \$HOME""#);
+        assert_eq!(substitute("echo 'say: ${msg}'", &v), r"echo 'say: ''it'\''s $HOME'''");
+        assert_eq!(substitute(r#"echo \"${file}\" ${HOME}"#, &v), r#"echo \"'my notes.txt'\" ${HOME}"#);
+    }
+
+    #[test]
+    fn test_quoted_output_survives_the_shell() {
+        let value = r#"a 'b' "c" $d `e` \f"#;
+        let v = values(&[("x", value)]);
+        for template in ["printf %s ${x}", r#"printf %s "${x}""#, "printf %s '${x}'"] {
+            let output = std::process::Command::new("sh").arg("-c").arg(substitute(template, &v)).output().unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), value, "template: {}", template);
+        }
+    }
+
+    #[test]
+    fn test_secrets_are_passed_in_the_environment() {
+        let value = r#"p@ss 'w"rd $x"#;
+        let v = values(&[("token", value), ("user", "me")]);
+        let secret_vars = HashMap::from([("token".to_string(), "WORKFLOW_SECRET_TOKEN".to_string())]);
+        for template in ["printf %s ${token}", r#"printf %s "${token}""#, "printf %s '${token}'"] {
+            let command = substitute_with_secrets(template, &v, &secret_vars);
+            assert!(!command.contains("p@ss"), "{}", command);
+            let output = std::process::Command::new("sh").arg("-c").arg(&command).env("WORKFLOW_SECRET_TOKEN", value).output().unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), value, "template: {}", template);
+        }
+        assert_eq!(substitute_with_secrets("login ${user} ${token}", &v, &secret_vars), r#"login me "${WORKFLOW_SECRET_TOKEN}""#);
+
+        let mut workflow = Workflow::from_command("Login", "printf %s ${token}");
+        workflow.arguments = vec![WorkflowArgument { secret: true, ..WorkflowArgument::new("token") }];
+        let (commands, secrets) = render_commands(&workflow, &v).unwrap();
+        let output = std::process::Command::new("sh").arg("-c").arg(&commands[0]).envs(secrets.env()).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), value);
+
+        let mut workflow = Workflow::from_command("Login", "login ${token}");
+        workflow.arguments = vec![WorkflowArgument { secret: true, validation: Some("[a-z]+".to_string()), ..WorkflowArgument::new("token") }];
+        let errors = resolve_arguments(&workflow, &v).unwrap_err();
+        assert!(!errors.to_string().contains("p@ss"), "{}", errors);
+    }
+
+    #[test]
+    fn test_all_errors_are_reported_together() {
+        let mut workflow = Workflow::from_command("Scale", "kubectl scale --replicas=${count} deploy/${name} -n ${env}");
+        workflow.arguments = vec![
+            WorkflowArgument { arg_type: ArgumentType::Int, ..WorkflowArgument::new("count") },
+            WorkflowArgument { validation: Some("[a-z][a-z0-9-]*".to_string()), ..WorkflowArgument::new("name") },
+            WorkflowArgument {
+                arg_type: ArgumentType::Enum(vec!["staging".to_string(), "prod".to_string()]),
+                ..WorkflowArgument::new("env")
+            },
+        ];
+
+        let errors = render_commands(&workflow, &values(&[("count", "three"), ("name", "Api")])).unwrap_err();
+        let names: Vec<_> = errors.0.iter().map(|e| e.name()).collect();
+        assert_eq!(names, ["count", "name", "env"]);
+        assert_eq!(errors.for_argument("env"), Some(&ArgumentError::Missing { name: "env".to_string() }));
+        assert!(errors.to_string().contains("expected a whole number"));
+
+        let (commands, _) = render_commands(&workflow, &values(&[("count", " 3"), ("name", "api"), ("env", "prod")])).unwrap();
+        assert_eq!(commands, ["kubectl scale --replicas=3 deploy/api -n prod"]);
+    }
+
+    #[test]
+    fn test_defaults_optional_arguments_and_booleans() {
+        let mut workflow = Workflow::from_command("Test", "cargo test ${filter} --no-fail-fast=${keep_going}");
+        workflow.arguments = vec![
+            WorkflowArgument { required: false, ..WorkflowArgument::new("filter") },
+            WorkflowArgument {
+                arg_type: ArgumentType::Boolean,
+                default_value: Some("yes".to_string()),
+                ..WorkflowArgument::new("keep_going")
+            },
+        ];
+        assert_eq!(render_commands(&workflow, &values(&[("filter", "")])).unwrap().0, ["cargo test  --no-fail-fast=true"]);
+        assert_eq!(
+            render_commands(&workflow, &values(&[("filter", "parser::"), ("keep_going", "0")])).unwrap().0,
+            ["cargo test parser:: --no-fail-fast=false"]
+        );
+    }
+
+    #[test]
+    fn test_home_is_expanded_in_paths() {
+        let arg = WorkflowArgument { arg_type: ArgumentType::Path, ..WorkflowArgument::new("dir") };
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(arg.validate("~/src").unwrap(), home.join("src").to_string_lossy());
+        assert_eq!(arg.validate("/tmp/~x").unwrap(), "/tmp/~x");
+    }
+
+    #[test]
+    fn test_bad_validation_pattern_is_reported() {
+        let arg = WorkflowArgument { validation: Some("(".to_string()), ..WorkflowArgument::new("x") };
+        assert!(arg.validate("anything").unwrap_err().contains("invalid validation pattern"));
+    }
+}
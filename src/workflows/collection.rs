@@ -0,0 +1,300 @@
+// Read-only workflow collections synced from a git repository, e.g. a team's shared
+// workflows repo. The repo is cloned into a cache directory once, then fetched and reset to
+// the remote on each refresh; every YAML file under the collection's subdirectory is one
+// workflow. Changes belong in the repo, so the manager refuses to edit these workflows.
+// Pulling a repo shouldn't start running its commands: a collection's `on_change:` and
+// `schedule:` are dropped when read, and auto-run is opted into locally with `AutoRun`.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use git2::build::RepoBuilder;
+use git2::{Delta, DiffOptions, Oid, Repository, ResetType};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::manager::{is_yaml, read_workflow_file};
+use super::model::{Schedule, Workflow};
+
+/// Where a fetch puts the remote's commit; the checkout is then reset to it.
+const FETCHED_REF: &str = "refs/remotes/origin/collection";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitCollection {
+    pub name: String,
+    /// Anything git can clone: a URL, a `file://` URL or a local path.
+    pub url: String,
+    /// The remote's default branch if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Where the workflows live in the repo; empty for the whole repo.
+    #[serde(default)]
+    pub subdir: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// A workflow file that changed between two syncs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkflowChange {
+    pub kind: ChangeKind,
+    /// Relative to the collection's subdirectory.
+    pub path: PathBuf,
+}
+
+impl fmt::Display for WorkflowChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ChangeKind::Added => "added",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+        };
+        write!(f, "{} {}", kind, self.path.display())
+    }
+}
+
+/// When a collection workflow runs on its own, as opted into on this machine.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoRun {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_change: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
+}
+
+impl AutoRun {
+    pub fn is_empty(&self) -> bool {
+        self.on_change.is_empty() && self.schedule.is_none()
+    }
+
+    pub fn apply(&self, workflow: &mut Workflow) {
+        workflow.on_change = self.on_change.clone();
+        workflow.schedule = self.schedule.clone();
+    }
+}
+
+/// A collection's workflows at one commit.
+#[derive(Debug, Clone)]
+pub struct CollectionSnapshot {
+    pub commit: Oid,
+    pub workflows: Vec<Workflow>,
+    /// Since the previous sync; on the first, every workflow is added.
+    pub changes: Vec<WorkflowChange>,
+}
+
+impl GitCollection {
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        GitCollection { name: name.into(), url: url.into(), branch: None, subdir: PathBuf::new() }
+    }
+
+    pub fn with_branch(mut self, branch: impl Into<String>) -> Self {
+        self.branch = Some(branch.into());
+        self
+    }
+
+    pub fn with_subdir(mut self, subdir: impl Into<PathBuf>) -> Self {
+        self.subdir = subdir.into();
+        self
+    }
+
+    /// The same file always gets the same ID, so run history survives refreshes.
+    pub fn workflow_id(&self, path: &Path) -> Uuid {
+        Uuid::new_v5(&Uuid::NAMESPACE_URL, format!("{}#{}", self.url, path.to_string_lossy()).as_bytes())
+    }
+
+    /// Clones the repo into `checkout` the first time; afterwards fetches and resets the
+    /// checkout to the remote, discarding anything changed locally.
+    pub fn sync(&self, checkout: &Path) -> Result<CollectionSnapshot> {
+        let (repo, previous) = if checkout.join(".git").exists() {
+            let repo = Repository::open(checkout)?;
+            let previous = repo.head()?.peel_to_commit()?.id();
+            self.fetch(&repo).with_context(|| format!("fetching {}", self.url))?;
+            (repo, Some(previous))
+        } else {
+            let mut builder = RepoBuilder::new();
+            if let Some(branch) = &self.branch {
+                builder.branch(branch);
+            }
+            let repo = builder.clone(&self.url, checkout).with_context(|| format!("cloning {}", self.url))?;
+            (repo, None)
+        };
+        let commit = repo.head()?.peel_to_commit()?.id();
+        let changes = self.changes(&repo, previous, commit)?;
+        Ok(CollectionSnapshot { commit, workflows: self.read_workflows(checkout), changes })
+    }
+
+    /// Reads the workflows from an existing checkout, without fetching.
+    pub fn load(&self, checkout: &Path) -> Result<CollectionSnapshot> {
+        let repo = Repository::open(checkout)?;
+        let commit = repo.head()?.peel_to_commit()?.id();
+        Ok(CollectionSnapshot { commit, workflows: self.read_workflows(checkout), changes: Vec::new() })
+    }
+
+    fn fetch(&self, repo: &Repository) -> Result<()> {
+        let source = match &self.branch {
+            Some(branch) => format!("refs/heads/{}", branch),
+            None => "HEAD".to_string(),
+        };
+        repo.find_remote("origin")?.fetch(&[format!("+{}:{}", source, FETCHED_REF)], None, None)?;
+        let fetched = repo.find_reference(FETCHED_REF)?.peel_to_commit()?;
+        repo.reset(fetched.as_object(), ResetType::Hard, None)?;
+        Ok(())
+    }
+
+    fn changes(&self, repo: &Repository, previous: Option<Oid>, current: Oid) -> Result<Vec<WorkflowChange>> {
+        let old_tree = previous.map(|id| repo.find_commit(id).and_then(|commit| commit.tree())).transpose()?;
+        let new_tree = repo.find_commit(current)?.tree()?;
+        let mut options = DiffOptions::new();
+        if !self.subdir.as_os_str().is_empty() {
+            options.pathspec(&self.subdir);
+        }
+        let diff = repo.diff_tree_to_tree(old_tree.as_ref(), Some(&new_tree), Some(&mut options))?;
+        Ok(diff
+            .deltas()
+            .filter_map(|delta| {
+                let (kind, file) = match delta.status() {
+                    Delta::Added | Delta::Copied => (ChangeKind::Added, delta.new_file()),
+                    Delta::Deleted => (ChangeKind::Removed, delta.old_file()),
+                    Delta::Modified | Delta::Renamed | Delta::Typechange => (ChangeKind::Modified, delta.new_file()),
+                    _ => return None,
+                };
+                let path = file.path()?.strip_prefix(&self.subdir).ok()?;
+                is_yaml(path).then(|| WorkflowChange { kind, path: path.to_path_buf() })
+            })
+            .collect())
+    }
+
+    /// Files that fail to parse are logged and skipped, and triggers are dropped.
+    fn read_workflows(&self, checkout: &Path) -> Vec<Workflow> {
+        let root = checkout.join(&self.subdir);
+        let mut workflows = Vec::new();
+        let entries = walkdir::WalkDir::new(&root).sort_by_file_name().into_iter().filter_entry(|entry| entry.file_name() != ".git");
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if !path.is_file() || !is_yaml(path) {
+                continue;
+            }
+            match read_workflow_file(path) {
+                Ok(mut workflow) => {
+                    workflow.id = self.workflow_id(path.strip_prefix(&root).unwrap_or(path));
+                    workflow.source_url.get_or_insert_with(|| self.url.clone());
+                    if !workflow.on_change.is_empty() || workflow.schedule.is_some() {
+                        info!("Collection '{}': '{}' only runs on its own once auto-run is enabled locally", self.name, workflow.name);
+                        AutoRun::default().apply(&mut workflow);
+                    }
+                    workflows.push(workflow);
+                }
+                Err(e) => error!("Collection '{}': skipping workflow file {:?}: {}", self.name, path, e),
+            }
+        }
+        workflows
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use git2::{IndexAddOption, Signature};
+    use std::fs;
+    use tempfile::tempdir;
+
+    /// Stages everything in the work tree, deletions included, and commits it.
+    pub(crate) fn commit_all(repo: &Repository, message: &str) {
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+        index.update_all(["*"], None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap();
+    }
+
+    pub(crate) fn write_workflow(repo_dir: &Path, path: &str, name: &str, command: &str) {
+        let path = repo_dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("name: {}\ncommand: {}\n", name, command)).unwrap();
+    }
+
+    fn names(snapshot: &CollectionSnapshot) -> Vec<&str> {
+        snapshot.workflows.iter().map(|w| w.name.as_str()).collect()
+    }
+
+    fn changes(snapshot: &CollectionSnapshot) -> Vec<String> {
+        snapshot.changes.iter().map(|change| change.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sync_reports_changes_since_the_last_sync() {
+        let (upstream, cache) = (tempdir().unwrap(), tempdir().unwrap());
+        let repo = Repository::init(upstream.path()).unwrap();
+        write_workflow(upstream.path(), "workflows/build.yaml", "Build", "cargo build");
+        write_workflow(upstream.path(), "workflows/deploy/prod.yml", "Deploy", "make deploy");
+        write_workflow(upstream.path(), "README.yaml", "Not a workflow here", "true");
+        commit_all(&repo, "initial");
+
+        let collection = GitCollection::new("team", format!("file://{}", upstream.path().display())).with_subdir("workflows");
+        let checkout = cache.path().join("team");
+        let first = collection.sync(&checkout).unwrap();
+        assert_eq!(names(&first), ["Build", "Deploy"]);
+        assert_eq!(changes(&first), ["added build.yaml", "added deploy/prod.yml"]);
+        assert_eq!(first.workflows[0].id, collection.workflow_id(Path::new("build.yaml")));
+        assert_eq!(first.workflows[0].source_url.as_deref(), Some(collection.url.as_str()));
+
+        write_workflow(upstream.path(), "workflows/build.yaml", "Build", "cargo build --release");
+        write_workflow(upstream.path(), "workflows/test.yaml", "Test", "cargo test");
+        fs::remove_file(upstream.path().join("workflows/deploy/prod.yml")).unwrap();
+        write_workflow(upstream.path(), "README.yaml", "Still not a workflow", "true");
+        commit_all(&repo, "update");
+
+        let second = collection.sync(&checkout).unwrap();
+        assert_eq!(names(&second), ["Build", "Test"]);
+        assert_eq!(changes(&second), ["modified build.yaml", "removed deploy/prod.yml", "added test.yaml"]);
+        assert_eq!(second.workflows[0].id, first.workflows[0].id);
+        assert_eq!(second.workflows[0].steps[0].command, "cargo build --release");
+
+        assert!(collection.sync(&checkout).unwrap().changes.is_empty());
+        assert_eq!(names(&collection.load(&checkout).unwrap()), ["Build", "Test"]);
+    }
+
+    #[test]
+    fn test_triggers_in_the_repo_are_dropped() {
+        let (upstream, cache) = (tempdir().unwrap(), tempdir().unwrap());
+        let repo = Repository::init(upstream.path()).unwrap();
+        let yaml = "name: Nightly\ncommand: make release\non_change: [\"src/**\"]\nschedule:\n  cron: \"0 3 * * *\"\n";
+        fs::write(upstream.path().join("nightly.yaml"), yaml).unwrap();
+        commit_all(&repo, "initial");
+        let spec = read_workflow_file(&upstream.path().join("nightly.yaml")).unwrap();
+        assert_eq!(spec.schedule, Some(Schedule::Cron("0 3 * * *".to_string())));
+
+        let collection = GitCollection::new("team", upstream.path().to_string_lossy());
+        let snapshot = collection.sync(&cache.path().join("team")).unwrap();
+        assert!(snapshot.workflows[0].on_change.is_empty());
+        assert_eq!(snapshot.workflows[0].schedule, None);
+    }
+
+    #[test]
+    fn test_local_edits_in_the_checkout_are_discarded() {
+        let (upstream, cache) = (tempdir().unwrap(), tempdir().unwrap());
+        let repo = Repository::init(upstream.path()).unwrap();
+        write_workflow(upstream.path(), "lint.yaml", "Lint", "cargo clippy");
+        commit_all(&repo, "initial");
+
+        let collection = GitCollection::new("team", upstream.path().to_string_lossy());
+        let checkout = cache.path().join("team");
+        collection.sync(&checkout).unwrap();
+        write_workflow(&checkout, "lint.yaml", "Lint", "rm -rf /");
+
+        let snapshot = collection.sync(&checkout).unwrap();
+        assert!(snapshot.changes.is_empty());
+        assert_eq!(snapshot.workflows[0].steps[0].command, "cargo clippy");
+    }
+}
@@ -0,0 +1,415 @@
+// `if:` conditions on workflow steps.
+//
+// A small expression language over the results of earlier steps:
+//
+//     steps.build.exit_code == 0 && steps.lint.stdout contains 'warning'
+//     failure() || !(steps.2.outcome == 'success')
+//
+// Steps are referenced by `id`, or by their 1-based position. Fields are `exit_code`,
+// `stdout`, `stderr` and `outcome` (`success`, `failure`, `timed_out`, `skipped`).
+// As in GitHub Actions, a condition that calls none of `success()`, `failure()` or
+// `always()` only runs while no earlier step has failed the workflow.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+impl Value {
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Int(n) => *n != 0,
+            Value::Str(s) => !s.is_empty(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "'{}'", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFunction {
+    Success,
+    Failure,
+    Always,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    /// `steps.<step>.<field>`
+    StepField { step: String, field: String },
+    Status(StatusFunction),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, Comparison, Box<Expr>),
+}
+
+const FIELDS: &[&str] = &["exit_code", "stdout", "stderr", "outcome"];
+
+/// What a condition can see while the workflow runs.
+pub trait ConditionContext {
+    /// Whether an earlier step has failed the workflow (a failure not covered by `continue_on_error`).
+    fn workflow_failed(&self) -> bool;
+    /// The value of `field` for an earlier step, or `None` if there is no such step.
+    fn step_field(&self, step: &str, field: &str) -> Option<Value>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(Condition { expr }),
+            Some(token) => Err(format!("unexpected {:?} in condition '{}'", token, source)),
+        }
+    }
+
+    /// The step references in the condition, in order of appearance.
+    pub fn referenced_steps(&self) -> Vec<&str> {
+        fn walk<'a>(expr: &'a Expr, out: &mut Vec<&'a str>) {
+            match expr {
+                Expr::StepField { step, .. } => out.push(step),
+                Expr::Not(e) => walk(e, out),
+                Expr::And(a, b) | Expr::Or(a, b) | Expr::Compare(a, _, b) => {
+                    walk(a, out);
+                    walk(b, out);
+                }
+                Expr::Literal(_) | Expr::Status(_) => {}
+            }
+        }
+        let mut steps = Vec::new();
+        walk(&self.expr, &mut steps);
+        steps
+    }
+
+    fn uses_status_function(&self) -> bool {
+        fn walk(expr: &Expr) -> bool {
+            match expr {
+                Expr::Status(_) => true,
+                Expr::Not(e) => walk(e),
+                Expr::And(a, b) | Expr::Or(a, b) | Expr::Compare(a, _, b) => walk(a) || walk(b),
+                Expr::Literal(_) | Expr::StepField { .. } => false,
+            }
+        }
+        walk(&self.expr)
+    }
+
+    pub fn evaluate(&self, context: &dyn ConditionContext) -> Result<bool, String> {
+        if !self.uses_status_function() && context.workflow_failed() {
+            return Ok(false);
+        }
+        Ok(eval(&self.expr, context)?.truthy())
+    }
+}
+
+fn eval(expr: &Expr, context: &dyn ConditionContext) -> Result<Value, String> {
+    Ok(match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::StepField { step, field } => context
+            .step_field(step, field)
+            .ok_or_else(|| format!("unknown step '{}'", step))?,
+        Expr::Status(StatusFunction::Success) => Value::Bool(!context.workflow_failed()),
+        Expr::Status(StatusFunction::Failure) => Value::Bool(context.workflow_failed()),
+        Expr::Status(StatusFunction::Always) => Value::Bool(true),
+        Expr::Not(e) => Value::Bool(!eval(e, context)?.truthy()),
+        Expr::And(a, b) => Value::Bool(eval(a, context)?.truthy() && eval(b, context)?.truthy()),
+        Expr::Or(a, b) => Value::Bool(eval(a, context)?.truthy() || eval(b, context)?.truthy()),
+        Expr::Compare(a, op, b) => Value::Bool(compare(&eval(a, context)?, *op, &eval(b, context)?)?),
+    })
+}
+
+fn compare(left: &Value, op: Comparison, right: &Value) -> Result<bool, String> {
+    use std::cmp::Ordering;
+    let ordering = match (left, right) {
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+    Ok(match op {
+        Comparison::Eq => ordering == Some(Ordering::Equal) || (*left == Value::Null && *right == Value::Null),
+        Comparison::Ne => !(ordering == Some(Ordering::Equal) || (*left == Value::Null && *right == Value::Null)),
+        Comparison::Contains => match (left, right) {
+            (Value::Str(haystack), Value::Str(needle)) => haystack.contains(needle.as_str()),
+            _ => return Err(format!("'contains' needs two strings, got {} and {}", left, right)),
+        },
+        // Ordering against a skipped step's missing exit code is simply false.
+        Comparison::Lt => ordering == Some(Ordering::Less),
+        Comparison::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Comparison::Gt => ordering == Some(Ordering::Greater),
+        Comparison::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Dot,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    const OPERATORS: &[&str] = &["&&", "||", "==", "!=", "<=", ">=", "<", ">", "!"];
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = source.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' || c == ')' || c == '.' {
+            tokens.push(match c {
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                _ => Token::Dot,
+            });
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&ch| ch == c)
+                .ok_or_else(|| format!("unterminated string in condition '{}'", source))?;
+            tokens.push(Token::Str(chars[i + 1..i + 1 + end].iter().collect()));
+            i += end + 2;
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) {
+            let start = i;
+            i += 1;
+            while chars.get(i).is_some_and(char::is_ascii_digit) {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            // A bare number right after a dot is a step position, e.g. `steps.2.exit_code`.
+            if tokens.last() == Some(&Token::Dot) {
+                tokens.push(Token::Ident(literal));
+            } else {
+                tokens.push(Token::Int(literal.parse().map_err(|_| format!("number out of range: {}", literal))?));
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while chars.get(i).is_some_and(|ch| ch.is_alphanumeric() || *ch == '_' || *ch == '-') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if let Some(op) = OPERATORS.iter().find(|op| chars[i..].starts_with(&op.chars().collect::<Vec<_>>())) {
+            tokens.push(Token::Op(op));
+            i += op.len();
+        } else {
+            return Err(format!("unexpected character '{}' in condition '{}'", c, source));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            other => Err(format!("expected {:?}, found {:?}", expected, other)),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Op("||")) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.comparison()?;
+        while self.peek() == Some(&Token::Op("&&")) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.comparison()?));
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.unary()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => Comparison::Eq,
+            Some(Token::Op("!=")) => Comparison::Ne,
+            Some(Token::Op("<")) => Comparison::Lt,
+            Some(Token::Op("<=")) => Comparison::Le,
+            Some(Token::Op(">")) => Comparison::Gt,
+            Some(Token::Op(">=")) => Comparison::Ge,
+            Some(Token::Ident(word)) if word == "contains" => Comparison::Contains,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        Ok(Expr::Compare(Box::new(left), op, Box::new(self.unary()?)))
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::Op("!")) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::Str(s))),
+            Some(Token::Int(n)) => Ok(Expr::Literal(Value::Int(n))),
+            Some(Token::Ident(word)) => match word.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                "success" | "failure" | "always" => {
+                    self.expect(Token::LParen)?;
+                    self.expect(Token::RParen)?;
+                    Ok(Expr::Status(match word.as_str() {
+                        "success" => StatusFunction::Success,
+                        "failure" => StatusFunction::Failure,
+                        _ => StatusFunction::Always,
+                    }))
+                }
+                "steps" => {
+                    self.expect(Token::Dot)?;
+                    let step = match self.next() {
+                        Some(Token::Ident(step)) => step,
+                        other => return Err(format!("expected a step id after 'steps.', found {:?}", other)),
+                    };
+                    self.expect(Token::Dot)?;
+                    match self.next() {
+                        Some(Token::Ident(field)) if FIELDS.contains(&field.as_str()) => Ok(Expr::StepField { step, field }),
+                        other => Err(format!("expected one of {} after 'steps.{}.', found {:?}", FIELDS.join(", "), step, other)),
+                    }
+                }
+                other => Err(format!("unknown name '{}'", other)),
+            },
+            other => Err(format!("expected a value, found {:?}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct Fixture {
+        failed: bool,
+        steps: HashMap<&'static str, (Option<i64>, &'static str)>,
+    }
+
+    impl ConditionContext for Fixture {
+        fn workflow_failed(&self) -> bool {
+            self.failed
+        }
+
+        fn step_field(&self, step: &str, field: &str) -> Option<Value> {
+            let (exit_code, stdout) = self.steps.get(step)?;
+            Some(match field {
+                "exit_code" => exit_code.map_or(Value::Null, Value::Int),
+                "stdout" => Value::Str(stdout.to_string()),
+                "outcome" => Value::Str(if *exit_code == Some(0) { "success" } else { "failure" }.to_string()),
+                _ => Value::Str(String::new()),
+            })
+        }
+    }
+
+    fn fixture(failed: bool) -> Fixture {
+        Fixture { failed, steps: HashMap::from([("build", (Some(0), "ok: 3 warnings")), ("2", (Some(2), "")), ("skipped", (None, ""))]) }
+    }
+
+    fn eval(source: &str, failed: bool) -> Result<bool, String> {
+        Condition::parse(source)?.evaluate(&fixture(failed))
+    }
+
+    #[test]
+    fn test_step_comparisons() {
+        assert_eq!(eval("steps.build.exit_code == 0", false), Ok(true));
+        assert_eq!(eval("steps.2.exit_code != 0 && steps.build.stdout contains 'warnings'", false), Ok(true));
+        assert_eq!(eval("steps.2.exit_code >= 3 || steps.build.outcome == \"failure\"", false), Ok(false));
+        assert_eq!(eval("!(steps.2.outcome == 'success')", false), Ok(true));
+        assert_eq!(eval("steps.skipped.exit_code == null", false), Ok(true));
+        assert_eq!(eval("steps.skipped.exit_code < 1", false), Ok(false));
+    }
+
+    #[test]
+    fn test_status_functions_and_implicit_success() {
+        assert_eq!(eval("steps.build.exit_code == 0", true), Ok(false));
+        assert_eq!(eval("failure()", true), Ok(true));
+        assert_eq!(eval("failure()", false), Ok(false));
+        assert_eq!(eval("always() && steps.build.exit_code == 0", true), Ok(true));
+        assert_eq!(eval("success()", false), Ok(true));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Condition::parse("steps.build.exit_code ==").is_err());
+        assert!(Condition::parse("steps.build.status == 0").unwrap_err().contains("exit_code, stdout"));
+        assert!(Condition::parse("steps.build.stdout contains 'x").unwrap_err().contains("unterminated"));
+        assert!(Condition::parse("success() success()").is_err());
+        assert_eq!(eval("steps.nope.exit_code == 0", false), Err("unknown step 'nope'".to_string()));
+        assert!(eval("steps.build.exit_code contains 'x'", false).is_err());
+    }
+
+    #[test]
+    fn test_referenced_steps() {
+        let condition = Condition::parse("steps.build.exit_code == 0 || !(steps.3.stdout == '')").unwrap();
+        assert_eq!(condition.referenced_steps(), ["build", "3"]);
+    }
+}
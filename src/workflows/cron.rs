@@ -0,0 +1,196 @@
+// Standard five-field cron expressions (`minute hour day-of-month month day-of-week`) for
+// scheduled workflows. Fields take `*`, numbers, ranges `a-b`, steps `*/n` or `a-b/n`, and
+// comma-separated lists; months and weekdays also take three-letter names. As in cron, a
+// day matches if either day field does when both are restricted.
+
+use std::fmt;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronError {
+    pub expression: String,
+    pub reason: String,
+}
+
+impl fmt::Display for CronError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid cron expression '{}': {}", self.expression, self.reason)
+    }
+}
+
+impl std::error::Error for CronError {}
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead to look for a match; `0 0 30 2 *` never matches at all.
+const SEARCH_DAYS: i64 = 366 * 5;
+
+/// The values each field allows, as bit sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+fn parse_value(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(index) = names.iter().position(|name| *name == lower) {
+        // Month names start at 1, weekday names at 0 (Sunday).
+        return Ok(index as u32 + min);
+    }
+    let value: u32 = text.parse().map_err(|_| format!("'{}' is not a number", text))?;
+    if value < min || value > max {
+        return Err(format!("{} is outside {}-{}", value, min, max));
+    }
+    Ok(value)
+}
+
+/// Parses one field into a bit set, returning whether it was `*`.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<(u64, bool), String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("'{}' is not a step", step))?;
+                if step == 0 {
+                    return Err("a step must be at least 1".to_string());
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start, min, max, names)?, parse_value(end, min, max, names)?),
+                // `5/15` means from 5 to the end in steps of 15.
+                None if part.contains('/') => (parse_value(range, min, max, names)?, max),
+                None => {
+                    let value = parse_value(range, min, max, names)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("range {}-{} is backwards", start, end));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok((bits, field == "*"))
+}
+
+impl CronExpr {
+    pub fn parse(expression: &str) -> Result<Self, CronError> {
+        let error = |reason: String| CronError { expression: expression.to_string(), reason };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(error(format!("expected 5 fields, found {}", fields.len())));
+        };
+
+        let (minutes, _) = parse_field(minute, 0, 59, &[]).map_err(&error)?;
+        let (hours, _) = parse_field(hour, 0, 23, &[]).map_err(&error)?;
+        let (days_of_month, any_day_of_month) = parse_field(day_of_month, 1, 31, &[]).map_err(&error)?;
+        let (months, _) = parse_field(month, 1, 12, &MONTHS).map_err(&error)?;
+        let (mut days_of_week, any_day_of_week) = parse_field(day_of_week, 0, 7, &WEEKDAYS).map_err(&error)?;
+        // 7 is another name for Sunday.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+        Ok(CronExpr { minutes, hours, days_of_month, months, days_of_week, any_day_of_month, any_day_of_week })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day_of_month = self.days_of_month & (1 << date.day()) != 0;
+        let day_of_week = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+
+    /// The first matching minute strictly after `after`, in `after`'s time zone. Local times
+    /// skipped by a DST change never match; repeated ones match once, the first time.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let timezone = after.timezone();
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        for day in 0..SEARCH_DAYS {
+            let date = start.date() + Duration::days(day);
+            if !self.matches_day(date) {
+                continue;
+            }
+            for hour in (0..24).filter(|h| self.hours & (1 << h) != 0) {
+                for minute in (0..60).filter(|m| self.minutes & (1 << m) != 0) {
+                    let candidate = NaiveDateTime::new(date, chrono::NaiveTime::from_hms_opt(hour, minute, 0)?);
+                    if candidate < start {
+                        continue;
+                    }
+                    if let Some(time) = timezone.from_local_datetime(&candidate).earliest() {
+                        if time > *after {
+                            return Some(time);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, Utc};
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(expression: &str, after: &str) -> String {
+        CronExpr::parse(expression).unwrap().next_after(&at(after)).unwrap().to_rfc3339()
+    }
+
+    #[test]
+    fn test_next_matching_minute() {
+        assert_eq!(next("*/15 * * * *", "2024-03-01T10:07:30Z"), "2024-03-01T10:15:00+00:00");
+        assert_eq!(next("*/15 * * * *", "2024-03-01T10:15:00Z"), "2024-03-01T10:30:00+00:00");
+        assert_eq!(next("0 9-17/4 * * mon-fri", "2024-03-01T17:00:00Z"), "2024-03-04T09:00:00+00:00");
+        assert_eq!(next("30 2 29 feb *", "2024-03-01T00:00:00Z"), "2028-02-29T02:30:00+00:00");
+        assert_eq!(next("0 0 * dec 7", "2024-03-01T00:00:00Z"), "2024-12-01T00:00:00+00:00");
+        assert_eq!(next("5/20 0 * * *", "2024-03-01T00:30:00Z"), "2024-03-01T00:45:00+00:00");
+    }
+
+    #[test]
+    fn test_restricted_day_fields_match_either() {
+        // The 13th, or any Friday.
+        assert_eq!(next("0 12 13 * fri", "2024-03-02T00:00:00Z"), "2024-03-08T12:00:00+00:00");
+        assert_eq!(next("0 12 13 * fri", "2024-03-09T00:00:00Z"), "2024-03-13T12:00:00+00:00");
+    }
+
+    #[test]
+    fn test_evaluated_in_the_given_time_zone() {
+        let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+        let after = at("2024-03-01T00:30:00Z").with_timezone(&tokyo);
+        let time = CronExpr::parse("0 12 * * *").unwrap().next_after(&after).unwrap();
+        assert_eq!(time.with_timezone(&Utc).to_rfc3339(), "2024-03-01T03:00:00+00:00");
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for expression in ["* * * *", "60 * * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "* * * foo *"] {
+            assert!(CronExpr::parse(expression).is_err(), "{}", expression);
+        }
+        assert!(CronExpr::parse("0 0 30 feb *").unwrap().next_after(&Utc::now()).is_none());
+    }
+}
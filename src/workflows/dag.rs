@@ -0,0 +1,205 @@
+// Step dependencies. A step's `needs:` lists the steps it waits for, by id or 1-based
+// position. Without `needs:` a step waits for the one before it, so plain workflows
+// stay sequential; `needs: []` makes a step a root that can start right away.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::model::WorkflowStep;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    UnknownStep { step: String, needs: String },
+    DuplicateId(String),
+    /// The titles of the steps on the cycle, starting and ending with the same step.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::UnknownStep { step, needs } => write!(f, "step '{}' needs unknown step '{}'", step, needs),
+            GraphError::DuplicateId(id) => write!(f, "more than one step has id '{}'", id),
+            GraphError::Cycle(path) => write!(f, "steps depend on each other: {}", path.join(" -> ")),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// Finds a step by `id`, or by 1-based position.
+pub fn find_step(steps: &[WorkflowStep], reference: &str) -> Option<usize> {
+    steps.iter().position(|s| s.id.as_deref() == Some(reference)).or_else(|| {
+        let position: usize = reference.parse().ok()?;
+        (1..=steps.len()).contains(&position).then(|| position - 1)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepGraph {
+    dependencies: Vec<Vec<usize>>,
+    dependents: Vec<Vec<usize>>,
+}
+
+impl StepGraph {
+    pub fn build(steps: &[WorkflowStep]) -> Result<Self, GraphError> {
+        let mut ids = BTreeSet::new();
+        for id in steps.iter().filter_map(|s| s.id.as_deref()) {
+            if !ids.insert(id) {
+                return Err(GraphError::DuplicateId(id.to_string()));
+            }
+        }
+
+        let mut dependencies: Vec<Vec<usize>> = Vec::with_capacity(steps.len());
+        for (index, step) in steps.iter().enumerate() {
+            let needs = match &step.needs {
+                None => index.checked_sub(1).into_iter().collect(),
+                Some(needs) => needs
+                    .iter()
+                    .map(|reference| {
+                        find_step(steps, reference)
+                            .ok_or_else(|| GraphError::UnknownStep { step: step.title(index), needs: reference.clone() })
+                    })
+                    .collect::<Result<BTreeSet<_>, _>>()?
+                    .into_iter()
+                    .collect(),
+            };
+            dependencies.push(needs);
+        }
+
+        let mut dependents = vec![Vec::new(); steps.len()];
+        for (index, needs) in dependencies.iter().enumerate() {
+            for &dependency in needs {
+                dependents[dependency].push(index);
+            }
+        }
+
+        let graph = StepGraph { dependencies, dependents };
+        if let Some(cycle) = graph.find_cycle() {
+            return Err(GraphError::Cycle(cycle.into_iter().map(|i| steps[i].title(i)).collect()));
+        }
+        Ok(graph)
+    }
+
+    pub fn len(&self) -> usize {
+        self.dependencies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dependencies.is_empty()
+    }
+
+    pub fn dependencies(&self, index: usize) -> &[usize] {
+        &self.dependencies[index]
+    }
+
+    pub fn dependents(&self, index: usize) -> &[usize] {
+        &self.dependents[index]
+    }
+
+    /// Every step that must finish before `index` starts, directly or not.
+    pub fn ancestors(&self, index: usize) -> BTreeSet<usize> {
+        let mut ancestors = BTreeSet::new();
+        let mut pending = self.dependencies[index].clone();
+        while let Some(step) = pending.pop() {
+            if ancestors.insert(step) {
+                pending.extend_from_slice(&self.dependencies[step]);
+            }
+        }
+        ancestors
+    }
+
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            New,
+            OnPath,
+            Done,
+        }
+
+        fn visit(graph: &StepGraph, index: usize, marks: &mut [Mark], path: &mut Vec<usize>) -> Option<Vec<usize>> {
+            marks[index] = Mark::OnPath;
+            path.push(index);
+            for &next in &graph.dependencies[index] {
+                match marks[next] {
+                    Mark::OnPath => {
+                        let start = path.iter().position(|&i| i == next).unwrap_or(0);
+                        let mut cycle = path[start..].to_vec();
+                        cycle.push(next);
+                        // Report in execution order: a step before the steps that need it.
+                        cycle.reverse();
+                        return Some(cycle);
+                    }
+                    Mark::New => {
+                        if let Some(cycle) = visit(graph, next, marks, path) {
+                            return Some(cycle);
+                        }
+                    }
+                    Mark::Done => {}
+                }
+            }
+            path.pop();
+            marks[index] = Mark::Done;
+            None
+        }
+
+        let mut marks = vec![Mark::New; self.len()];
+        (0..self.len()).find_map(|index| {
+            if marks[index] == Mark::New {
+                visit(self, index, &mut marks, &mut Vec::new())
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(id: &str, needs: Option<&[&str]>) -> WorkflowStep {
+        WorkflowStep {
+            id: Some(id.to_string()),
+            needs: needs.map(|n| n.iter().map(|s| s.to_string()).collect()),
+            ..WorkflowStep::new("true")
+        }
+    }
+
+    #[test]
+    fn test_steps_without_needs_are_sequential() {
+        let graph = StepGraph::build(&[step("a", None), step("b", None), step("c", None)]).unwrap();
+        assert_eq!(graph.dependencies(0), &[] as &[usize]);
+        assert_eq!(graph.dependencies(2), &[1]);
+        assert_eq!(graph.ancestors(2), BTreeSet::from([0, 1]));
+    }
+
+    #[test]
+    fn test_fan_out_and_in() {
+        let steps = [
+            step("core", Some(&[])),
+            step("cli", Some(&[])),
+            step("ui", Some(&["1"])),
+            step("package", Some(&["core", "cli", "ui", "cli"])),
+        ];
+        let graph = StepGraph::build(&steps).unwrap();
+        assert_eq!(graph.dependencies(3), &[0, 1, 2]);
+        assert_eq!(graph.dependents(0), &[2, 3]);
+        assert_eq!(graph.ancestors(1), BTreeSet::new());
+    }
+
+    #[test]
+    fn test_cycles_and_bad_references() {
+        let cycle = [step("a", Some(&["c"])), step("b", Some(&["a"])), step("c", Some(&["b"])), step("d", None)];
+        assert_eq!(
+            StepGraph::build(&cycle),
+            Err(GraphError::Cycle(vec!["a".to_string(), "b".to_string(), "c".to_string(), "a".to_string()]))
+        );
+        assert!(matches!(StepGraph::build(&[step("a", Some(&["a"]))]), Err(GraphError::Cycle(_))));
+        assert_eq!(
+            StepGraph::build(&[step("a", Some(&["zzz"]))]),
+            Err(GraphError::UnknownStep { step: "a".to_string(), needs: "zzz".to_string() })
+        );
+        assert_eq!(StepGraph::build(&[step("a", None), step("a", None)]), Err(GraphError::DuplicateId("a".to_string())));
+    }
+}
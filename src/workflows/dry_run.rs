@@ -0,0 +1,203 @@
+// Dry runs: everything `WorkflowExecutor::execute` would do up to the point of spawning
+// a process. Arguments are resolved and substituted, dependencies and conditions are
+// checked, and each final command is annotated by the bash syntax tree.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::args;
+use super::condition::{Condition, ConditionContext, Value};
+use super::dag::StepGraph;
+use super::executor::{parse_conditions, WorkflowExecutor};
+use super::model::Workflow;
+use super::secrets::Secrets;
+use crate::syntax_tree::{CommandAnalysis, SyntaxTreeParser};
+
+/// Whether a step would run, as far as can be told without running anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedRun {
+    Runs,
+    /// The condition is false even when every earlier step succeeds.
+    Skipped { reason: String },
+    /// The condition reads the results of these steps, so it can only be decided during the run.
+    DependsOn(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedStep {
+    pub index: usize,
+    pub title: String,
+    /// The command with every argument substituted and quoted, exactly as it would be run;
+    /// secret arguments appear as their environment variable.
+    pub command: String,
+    pub needs: Vec<String>,
+    pub condition: Option<String>,
+    pub run: PlannedRun,
+    /// `None` when the command could not be parsed as bash.
+    pub analysis: Option<CommandAnalysis>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRun {
+    pub workflow_name: String,
+    pub shell: String,
+    pub steps: Vec<PlannedStep>,
+}
+
+/// Conditions are decided assuming every earlier step succeeded.
+struct AllSucceeded;
+
+impl ConditionContext for AllSucceeded {
+    fn workflow_failed(&self) -> bool {
+        false
+    }
+
+    fn step_field(&self, _step: &str, _field: &str) -> Option<Value> {
+        None
+    }
+}
+
+fn plan_run(condition: Option<&Condition>, workflow: &Workflow) -> PlannedRun {
+    let Some(condition) = condition else { return PlannedRun::Runs };
+    let referenced = condition.referenced_steps();
+    if !referenced.is_empty() {
+        let mut titles: Vec<String> = Vec::new();
+        for reference in referenced {
+            if let Some(index) = super::dag::find_step(&workflow.steps, reference) {
+                let title = workflow.steps[index].title(index);
+                if !titles.contains(&title) {
+                    titles.push(title);
+                }
+            }
+        }
+        return PlannedRun::DependsOn(titles);
+    }
+    match condition.evaluate(&AllSucceeded) {
+        Ok(true) => PlannedRun::Runs,
+        Ok(false) => PlannedRun::Skipped { reason: "only runs if an earlier step fails".to_string() },
+        Err(e) => PlannedRun::Skipped { reason: e },
+    }
+}
+
+impl WorkflowExecutor {
+    /// Plans a run without executing anything. Fails for the same reasons `execute`
+    /// would refuse to start: bad arguments, dependencies or conditions.
+    pub fn dry_run(&self, workflow: &Workflow, arguments: &HashMap<String, String>) -> anyhow::Result<DryRun> {
+        let values = args::resolve_arguments(workflow, arguments)?;
+        let secret_vars = Secrets::from_arguments(workflow, &values).env_vars();
+        let graph = StepGraph::build(&workflow.steps)?;
+        let conditions = parse_conditions(&workflow.steps, &graph)?;
+        // Without the grammar the plan is still useful, just not annotated.
+        let mut parser = SyntaxTreeParser::new().ok();
+
+        let steps = workflow
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| {
+                let command = args::substitute_with_secrets(&step.command, &values, &secret_vars);
+                PlannedStep {
+                    index,
+                    title: step.title(index),
+                    needs: graph.dependencies(index).iter().map(|&i| workflow.steps[i].title(i)).collect(),
+                    condition: step.condition.clone(),
+                    run: plan_run(conditions[index].as_ref(), workflow),
+                    analysis: parser.as_mut().and_then(|p| p.analyze_command(&command)),
+                    command,
+                }
+            })
+            .collect();
+        Ok(DryRun { workflow_name: workflow.name.clone(), shell: self.shell().to_string(), steps })
+    }
+}
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Dry run of '{}' with {} (nothing was executed)", self.workflow_name, self.shell)?;
+        for step in &self.steps {
+            writeln!(f)?;
+            write!(f, "[{}] {}", step.index + 1, step.title)?;
+            if !step.needs.is_empty() {
+                write!(f, "  (after {})", step.needs.join(", "))?;
+            }
+            writeln!(f)?;
+            for line in step.command.lines() {
+                writeln!(f, "    $ {}", line)?;
+            }
+            if let Some(condition) = &step.condition {
+                writeln!(f, "    if: {}", condition)?;
+            }
+            match &step.run {
+                PlannedRun::Runs => {}
+                PlannedRun::Skipped { reason } => writeln!(f, "    skipped: {}", reason)?,
+                PlannedRun::DependsOn(steps) => writeln!(f, "    decided at run time from: {}", steps.join(", "))?,
+            }
+            if let Some(analysis) = &step.analysis {
+                let lists = [("runs", &analysis.commands), ("redirects", &analysis.redirects), ("expands", &analysis.variables)];
+                for (label, items) in lists {
+                    if !items.is_empty() {
+                        writeln!(f, "    {}: {}", label, items.join(", "))?;
+                    }
+                }
+                if analysis.has_errors {
+                    writeln!(f, "    warning: the command has syntax errors")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::model::{WorkflowArgument, WorkflowStep};
+    use tempfile::tempdir;
+
+    fn release() -> Workflow {
+        let mut workflow = Workflow::new("Release");
+        workflow.arguments = vec![WorkflowArgument::new("version"), WorkflowArgument::new("notes")];
+        workflow.steps = vec![
+            WorkflowStep { id: Some("build".to_string()), ..WorkflowStep::new("cargo build --release > ${notes}") },
+            WorkflowStep {
+                id: Some("tag".to_string()),
+                condition: Some("steps.build.exit_code == 0".to_string()),
+                ..WorkflowStep::new("git tag v${version} && echo $USER")
+            },
+            WorkflowStep { condition: Some("failure()".to_string()), ..WorkflowStep::new("notify-send failed") },
+        ];
+        workflow
+    }
+
+    #[test]
+    fn test_dry_run_substitutes_and_annotates() {
+        let dir = tempdir().unwrap();
+        let marker = dir.path().join("ran");
+        let mut workflow = release();
+        workflow.steps[0].command = format!("touch {} ; {}", marker.display(), workflow.steps[0].command);
+        let args = HashMap::from([("version".to_string(), "1.2.0".to_string()), ("notes".to_string(), "my notes.txt".to_string())]);
+
+        let plan = WorkflowExecutor::with_shell("/bin/sh").dry_run(&workflow, &args).unwrap();
+        assert!(!marker.exists());
+        assert!(plan.steps[0].command.ends_with("cargo build --release > 'my notes.txt'"));
+        let analysis = plan.steps[0].analysis.as_ref().unwrap();
+        assert_eq!(analysis.commands, ["touch", "cargo"]);
+        assert_eq!(analysis.redirects, ["'my notes.txt'"]);
+
+        assert_eq!(plan.steps[1].command, "git tag v1.2.0 && echo $USER");
+        assert_eq!(plan.steps[1].needs, ["build"]);
+        assert_eq!(plan.steps[1].run, PlannedRun::DependsOn(vec!["build".to_string()]));
+        assert_eq!(plan.steps[1].analysis.as_ref().unwrap().variables, ["USER"]);
+        assert!(matches!(plan.steps[2].run, PlannedRun::Skipped { .. }));
+
+        let text = plan.to_string();
+        assert!(text.contains("[2] tag  (after build)\n    $ git tag v1.2.0 && echo $USER\n    if: steps.build.exit_code == 0"), "{}", text);
+        assert!(text.contains("    runs: git, echo\n    expands: USER"), "{}", text);
+    }
+
+    #[test]
+    fn test_dry_run_reports_argument_errors() {
+        let error = WorkflowExecutor::with_shell("/bin/sh").dry_run(&release(), &HashMap::new()).unwrap_err();
+        assert!(error.to_string().contains("missing argument 'version'"), "{}", error);
+    }
+}
@@ -1,85 +1,669 @@
-use log::info;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use log::{info, warn};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
-/// Represents a workflow that can be executed.
-#[derive(Debug, Clone)]
-pub struct Workflow {
-    pub id: Uuid,
-    pub name: String,
-    pub steps: Vec<String>, // Simplified: just a list of command strings
+use super::args;
+use super::condition::{Condition, ConditionContext, Value};
+use super::dag::{find_step, StepGraph};
+use super::model::{Workflow, WorkflowStep};
+use super::secrets::Secrets;
+use super::spec::WorkflowSpec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Success,
+    Failure,
+    TimedOut,
+    Skipped,
+}
+
+impl StepOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StepOutcome::Success => "success",
+            StepOutcome::Failure => "failure",
+            StepOutcome::TimedOut => "timed_out",
+            StepOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+/// The result of a step's last attempt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepResult {
+    pub index: usize,
+    pub outcome: StepOutcome,
+    /// `None` when the step was skipped, timed out or killed by a signal.
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub attempts: u32,
+    /// When the first attempt started (or the step was skipped), relative to the start of the run.
+    pub started_at: Duration,
+    /// From the start of the first attempt to the end of the last.
+    pub duration: Duration,
+}
+
+impl StepResult {
+    fn skipped(index: usize, started_at: Duration) -> Self {
+        StepResult {
+            index,
+            outcome: StepOutcome::Skipped,
+            exit_code: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            attempts: 0,
+            started_at,
+            duration: Duration::ZERO,
+        }
+    }
 }
 
-/// Executes workflows.
-pub struct WorkflowExecutor;
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkflowRun {
+    pub workflow_id: Uuid,
+    /// One result per step, in step order.
+    pub steps: Vec<StepResult>,
+    /// False once a step failed without `continue_on_error`.
+    pub succeeded: bool,
+}
+
+/// One row of a run's timeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineEntry {
+    pub index: usize,
+    pub title: String,
+    pub start: Duration,
+    pub end: Duration,
+    pub outcome: StepOutcome,
+}
+
+impl WorkflowRun {
+    /// When each step ran, in step order.
+    pub fn timeline(&self, workflow: &Workflow) -> Vec<TimelineEntry> {
+        self.steps
+            .iter()
+            .map(|result| TimelineEntry {
+                index: result.index,
+                title: workflow.steps.get(result.index).map_or_else(|| format!("Step {}", result.index + 1), |s| s.title(result.index)),
+                start: result.started_at,
+                end: result.started_at + result.duration,
+                outcome: result.outcome,
+            })
+            .collect()
+    }
+}
+
+/// Renders a timeline as text bars, `width` characters for the whole run:
+///
+/// ```text
+/// build-core  |██████              |  0.0s  1.2s success
+/// package     |      ██████████████|  1.2s  4.1s success
+/// ```
+pub fn format_timeline(entries: &[TimelineEntry], width: usize) -> String {
+    let total = entries.iter().map(|e| e.end).max().unwrap_or_default().as_secs_f64().max(f64::EPSILON);
+    let title_width = entries.iter().map(|e| e.title.chars().count()).max().unwrap_or(0);
+    let column = |at: Duration| ((at.as_secs_f64() / total) * width as f64).round() as usize;
+    entries
+        .iter()
+        .map(|entry| {
+            let (start, end) = (column(entry.start).min(width), column(entry.end).min(width));
+            // Every step that ran gets at least one cell, however short it was.
+            let end = if entry.outcome == StepOutcome::Skipped { start } else { end.max(start + 1).min(width) };
+            let start = start.min(end);
+            let bar = format!("{}{}{}", " ".repeat(start), "█".repeat(end - start), " ".repeat(width - end));
+            format!(
+                "{:<title_width$}  |{}| {:>5.1}s {:>5.1}s {}",
+                entry.title,
+                bar,
+                entry.start.as_secs_f64(),
+                entry.end.as_secs_f64(),
+                entry.outcome.as_str(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Progress of a run, for showing each step attempt as its own block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkflowEvent {
+    StepStarted { block_id: Uuid, index: usize, attempt: u32, title: String, command: String },
+    StepOutput { block_id: Uuid, stream: OutputStream, bytes: Vec<u8> },
+    StepFinished { block_id: Uuid, result: StepResult },
+    StepSkipped { index: usize, title: String, reason: String },
+    WorkflowFinished { workflow_id: Uuid, succeeded: bool, timeline: Vec<TimelineEntry> },
+}
+
+/// Executes workflows, running each step with `<shell> -c <command>` in the session's
+/// working directory. Steps whose dependencies are met run concurrently, up to
+/// `max_parallel` at a time.
+pub struct WorkflowExecutor {
+    shell: String,
+    cwd: Option<PathBuf>,
+    events: Option<mpsc::UnboundedSender<WorkflowEvent>>,
+    max_parallel: usize,
+}
 
 impl WorkflowExecutor {
     pub fn new() -> Self {
-        WorkflowExecutor {}
+        Self::with_shell(std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string()))
+    }
+
+    pub fn with_shell(shell: impl Into<String>) -> Self {
+        let max_parallel = std::thread::available_parallelism().map_or(4, |n| n.get());
+        WorkflowExecutor { shell: shell.into(), cwd: None, events: None, max_parallel }
+    }
+
+    /// The most steps to run at once; at least one.
+    pub fn with_max_parallel(mut self, max_parallel: usize) -> Self {
+        self.max_parallel = max_parallel.max(1);
+        self
+    }
+
+    pub fn with_cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    pub fn shell(&self) -> &str {
+        &self.shell
+    }
+
+    pub fn with_event_sender(mut self, events: mpsc::UnboundedSender<WorkflowEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn emit(&self, event: WorkflowEvent) {
+        if let Some(events) = &self.events {
+            // The receiver going away (e.g. the view closed) must not stop the run.
+            let _ = events.send(event);
+        }
     }
 
-    /// Executes a given workflow.
-    /// In a real scenario, this would interact with the shell, API, etc.
-    pub async fn execute(&self, workflow: &Workflow) -> anyhow::Result<()> {
+    /// Runs the workflow's steps, each once its `needs` have finished. Errors are reserved
+    /// for problems found before anything runs (bad arguments, dependencies or conditions);
+    /// step failures are reported in the run.
+    pub async fn execute(&self, workflow: &Workflow, arguments: &HashMap<String, String>) -> anyhow::Result<WorkflowRun> {
         info!("Executing workflow: '{}' (ID: {})", workflow.name, workflow.id);
-        for (i, step) in workflow.steps.iter().enumerate() {
-            info!("  Step {}: Executing command: '{}'", i + 1, step);
-            // Simulate command execution
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-            info!("  Step {}: Command '{}' completed.", i + 1, step);
+        let values = args::resolve_arguments(workflow, arguments)?;
+        let secrets = Secrets::from_arguments(workflow, &values);
+        let secret_vars = secrets.env_vars();
+        let graph = StepGraph::build(&workflow.steps)?;
+        let conditions = parse_conditions(&workflow.steps, &graph)?;
+
+        let run_started = Instant::now();
+        let mut results: Vec<Option<StepResult>> = vec![None; workflow.steps.len()];
+        let mut failed = false;
+        let mut waiting_on: Vec<usize> = (0..graph.len()).map(|i| graph.dependencies(i).len()).collect();
+        // Lowest index first, so independent steps start in the order they are written.
+        let mut ready: BTreeSet<usize> = (0..graph.len()).filter(|&i| waiting_on[i] == 0).collect();
+        let mut running = FuturesUnordered::new();
+
+        loop {
+            while running.len() < self.max_parallel {
+                let Some(index) = ready.pop_first() else { break };
+                let step = &workflow.steps[index];
+                let title = step.title(index);
+                let context = RunContext { steps: &workflow.steps, results: &results, failed };
+                match skip_reason(conditions[index].as_ref(), step, &context) {
+                    Some(reason) => {
+                        info!("  Skipping step {} ({}): {}", index + 1, title, reason);
+                        self.emit(WorkflowEvent::StepSkipped { index, title, reason });
+                        results[index] = Some(StepResult::skipped(index, run_started.elapsed()));
+                        release_dependents(&graph, index, &mut waiting_on, &mut ready);
+                    }
+                    None => {
+                        let command = args::substitute_with_secrets(&step.command, &values, &secret_vars);
+                        let secrets = &secrets;
+                        running.push(async move { self.run_step(index, step, &title, &command, secrets, run_started).await });
+                    }
+                }
+            }
+
+            let Some(result) = running.next().await else { break };
+            let index = result.index;
+            if result.outcome != StepOutcome::Success && !workflow.steps[index].continue_on_error {
+                failed = true;
+            }
+            results[index] = Some(result);
+            release_dependents(&graph, index, &mut waiting_on, &mut ready);
         }
-        info!("Workflow '{}' completed.", workflow.name);
-        Ok(())
+
+        let run = WorkflowRun {
+            workflow_id: workflow.id,
+            steps: results.into_iter().flatten().collect(),
+            succeeded: !failed,
+        };
+        info!("Workflow '{}' finished (succeeded: {}).", workflow.name, run.succeeded);
+        self.emit(WorkflowEvent::WorkflowFinished {
+            workflow_id: workflow.id,
+            succeeded: run.succeeded,
+            timeline: run.timeline(workflow),
+        });
+        Ok(run)
+    }
+
+    async fn run_step(
+        &self,
+        index: usize,
+        step: &WorkflowStep,
+        title: &str,
+        command: &str,
+        secrets: &Secrets,
+        run_started: Instant,
+    ) -> StepResult {
+        let timeout = step.timeout_secs.map(Duration::from_secs);
+        let started_at = run_started.elapsed();
+        let mut attempt = 1;
+        loop {
+            let block_id = Uuid::new_v4();
+            let title = if step.retry > 0 { format!("{} (attempt {}/{})", title, attempt, step.retry + 1) } else { title.to_string() };
+            info!("  Step {}: Executing command: '{}'", index + 1, command);
+            self.emit(WorkflowEvent::StepStarted { block_id, index, attempt, title, command: command.to_string() });
+
+            let mut result = self.run_command(block_id, command, secrets, timeout).await;
+            result.index = index;
+            result.attempts = attempt;
+            result.started_at = started_at;
+            result.duration = run_started.elapsed().saturating_sub(started_at);
+            self.emit(WorkflowEvent::StepFinished { block_id, result: result.clone() });
+            if result.outcome == StepOutcome::Success || attempt > step.retry {
+                return result;
+            }
+            warn!("  Step {} attempt {} ended with {}; retrying.", index + 1, attempt, result.outcome.as_str());
+            attempt += 1;
+        }
+    }
+
+    /// Runs one attempt. Secrets are set in the environment and masked in the output, both in
+    /// events and in the result.
+    async fn run_command(&self, block_id: Uuid, command: &str, secrets: &Secrets, timeout: Option<Duration>) -> StepResult {
+        let started = Instant::now();
+        let finished = |outcome, exit_code, stdout: &[u8], stderr: &[u8]| StepResult {
+            index: 0,
+            outcome,
+            exit_code,
+            stdout: String::from_utf8_lossy(stdout).into_owned(),
+            stderr: String::from_utf8_lossy(stderr).into_owned(),
+            attempts: 0,
+            started_at: Duration::ZERO,
+            duration: started.elapsed(),
+        };
+
+        let mut process = Command::new(&self.shell);
+        process.arg("-c").arg(command).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true);
+        if let Some(cwd) = &self.cwd {
+            process.current_dir(cwd);
+        }
+        process.envs(secrets.env());
+        let mut child = match process.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                let message = format!("failed to start {}: {}\n", self.shell, e);
+                self.emit(WorkflowEvent::StepOutput { block_id, stream: OutputStream::Stderr, bytes: message.clone().into_bytes() });
+                return finished(StepOutcome::Failure, None, b"", message.as_bytes());
+            }
+        };
+
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let stdout_pipe = child.stdout.take().expect("stdout is piped");
+        let stderr_pipe = child.stderr.take().expect("stderr is piped");
+        let completion = async {
+            tokio::join!(
+                self.pump(block_id, OutputStream::Stdout, stdout_pipe, secrets, &mut stdout),
+                self.pump(block_id, OutputStream::Stderr, stderr_pipe, secrets, &mut stderr),
+            );
+            child.wait().await
+        };
+        let status = match timeout {
+            Some(limit) => tokio::time::timeout(limit, completion).await.ok(),
+            None => Some(completion.await),
+        };
+
+        match status {
+            Some(Ok(status)) => {
+                let outcome = if status.success() { StepOutcome::Success } else { StepOutcome::Failure };
+                finished(outcome, status.code(), &stdout, &stderr)
+            }
+            Some(Err(e)) => {
+                stderr.extend_from_slice(format!("failed to wait for command: {}\n", e).as_bytes());
+                finished(StepOutcome::Failure, None, &stdout, &stderr)
+            }
+            None => {
+                let _ = child.kill().await;
+                let message = format!("timed out after {:?}\n", timeout.unwrap_or_default());
+                self.emit(WorkflowEvent::StepOutput { block_id, stream: OutputStream::Stderr, bytes: message.clone().into_bytes() });
+                stderr.extend_from_slice(message.as_bytes());
+                finished(StepOutcome::TimedOut, None, &stdout, &stderr)
+            }
+        }
+    }
+
+    /// Forwards a pipe's output, with secrets masked, as events while collecting it into `buffer`.
+    async fn pump(&self, block_id: Uuid, stream: OutputStream, mut pipe: impl AsyncRead + Unpin, secrets: &Secrets, buffer: &mut Vec<u8>) {
+        let mut masker = secrets.masker();
+        let mut forward = |bytes: Vec<u8>| {
+            if !bytes.is_empty() {
+                buffer.extend_from_slice(&bytes);
+                self.emit(WorkflowEvent::StepOutput { block_id, stream, bytes });
+            }
+        };
+        let mut chunk = [0u8; 4096];
+        while let Ok(n) = pipe.read(&mut chunk).await {
+            if n == 0 {
+                break;
+            }
+            forward(masker.push(&chunk[..n]));
+        }
+        forward(masker.finish());
     }
 
-    /// Exports a workflow to a specified path.
-    /// This is a placeholder for saving to a file format like YAML or JSON.
+    /// Exports a workflow to `path` in the Warp workflow YAML format.
     pub fn export(&self, workflow: &Workflow, path: &std::path::Path) -> anyhow::Result<()> {
         info!("Exporting workflow '{}' to {:?}", workflow.name, path);
-        let content = format!(
-            "name: {}\nid: {}\nsteps:\n{}",
-            workflow.name,
-            workflow.id,
-            workflow.steps.iter().map(|s| format!("  - \"{}\"", s)).collect::<Vec<_>>().join("\n")
-        );
+        let content = WorkflowSpec::from_workflow(workflow).to_yaml()?;
         std::fs::write(path, content)?;
         info!("Workflow exported successfully.");
         Ok(())
     }
 }
 
+impl Default for WorkflowExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses every step's condition up front, so a typo fails the run before anything executes.
+/// Conditions may only refer to steps that are sure to have finished, i.e. ones the step needs.
+pub(super) fn parse_conditions(steps: &[WorkflowStep], graph: &StepGraph) -> anyhow::Result<Vec<Option<Condition>>> {
+    steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            let Some(source) = &step.condition else { return Ok(None) };
+            let condition = Condition::parse(source).map_err(|e| anyhow::anyhow!("step {}: {}", index + 1, e))?;
+            let ancestors = graph.ancestors(index);
+            for reference in condition.referenced_steps() {
+                match find_step(steps, reference) {
+                    Some(target) if ancestors.contains(&target) => {}
+                    Some(_) => anyhow::bail!("step {}: condition refers to step '{}', which it does not need", index + 1, reference),
+                    None => anyhow::bail!("step {}: condition refers to unknown step '{}'", index + 1, reference),
+                }
+            }
+            Ok(Some(condition))
+        })
+        .collect()
+}
+
+/// Why a ready step should not run, if it shouldn't.
+fn skip_reason(condition: Option<&Condition>, step: &WorkflowStep, context: &RunContext) -> Option<String> {
+    match condition {
+        Some(condition) => match condition.evaluate(context) {
+            Ok(true) => None,
+            Ok(false) => Some(format!("condition not met: {}", step.condition.as_deref().unwrap_or_default())),
+            Err(e) => Some(format!("condition could not be evaluated: {}", e)),
+        },
+        None if context.failed => Some("an earlier step failed".to_string()),
+        None => None,
+    }
+}
+
+fn release_dependents(graph: &StepGraph, index: usize, waiting_on: &mut [usize], ready: &mut BTreeSet<usize>) {
+    for &dependent in graph.dependents(index) {
+        waiting_on[dependent] -= 1;
+        if waiting_on[dependent] == 0 {
+            ready.insert(dependent);
+        }
+    }
+}
+
+struct RunContext<'a> {
+    steps: &'a [WorkflowStep],
+    results: &'a [Option<StepResult>],
+    failed: bool,
+}
+
+impl ConditionContext for RunContext<'_> {
+    fn workflow_failed(&self) -> bool {
+        self.failed
+    }
+
+    fn step_field(&self, step: &str, field: &str) -> Option<Value> {
+        let result = self.results.get(find_step(self.steps, step)?)?.as_ref()?;
+        Some(match field {
+            "exit_code" => result.exit_code.map_or(Value::Null, |code| Value::Int(code.into())),
+            "stdout" => Value::Str(result.stdout.trim().to_string()),
+            "stderr" => Value::Str(result.stderr.trim().to_string()),
+            "outcome" => Value::Str(result.outcome.as_str().to_string()),
+            _ => return None,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::tempdir;
     use std::fs;
 
+    fn step(id: &str, command: &str) -> WorkflowStep {
+        WorkflowStep { id: Some(id.to_string()), ..WorkflowStep::new(command) }
+    }
+
+    fn sh() -> WorkflowExecutor {
+        WorkflowExecutor::with_shell("/bin/sh")
+    }
+
     #[tokio::test]
     async fn test_workflow_execution() {
-        let executor = WorkflowExecutor::new();
-        let workflow = Workflow {
-            id: Uuid::new_v4(),
-            name: "Test Workflow".to_string(),
+        let dir = tempdir().unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let executor = sh().with_cwd(dir.path()).with_event_sender(sender);
+        let mut workflow = Workflow {
             steps: vec![
-                "echo 'Hello'".to_string(),
-                "ls -la".to_string(),
+                WorkflowStep::new("echo 'Hello' ${who}; echo oops >&2"),
+                WorkflowStep::new("pwd; exit 3"),
             ],
+            ..Workflow::new("Test Workflow")
         };
+        workflow.arguments = vec![crate::workflows::model::WorkflowArgument::new("who")];
+        workflow.steps[1].continue_on_error = true;
+
+        let args = HashMap::from([("who".to_string(), "a b".to_string())]);
+        let run = executor.execute(&workflow, &args).await.unwrap();
+        assert!(run.succeeded);
+        assert_eq!((run.steps[0].stdout.as_str(), run.steps[0].stderr.as_str()), ("Hello a b\n", "oops\n"));
+        assert_eq!(run.steps[1].outcome, StepOutcome::Failure);
+        assert_eq!(run.steps[1].exit_code, Some(3));
+        assert_eq!(fs::canonicalize(run.steps[1].stdout.trim()).unwrap(), fs::canonicalize(dir.path()).unwrap());
+
+        // Each step is its own block: started, its output, finished.
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+        let WorkflowEvent::StepStarted { block_id, .. } = &events[0] else { panic!("{:?}", events[0]) };
+        let first_block: Vec<_> = events.iter().filter(|e| match e {
+            WorkflowEvent::StepOutput { block_id: id, .. } | WorkflowEvent::StepFinished { block_id: id, .. } => id == block_id,
+            _ => false,
+        }).collect();
+        assert_eq!(first_block.len(), 3);
+        assert!(matches!(events.last(), Some(WorkflowEvent::WorkflowFinished { succeeded: true, .. })));
+    }
 
-        let result = executor.execute(&workflow).await;
-        assert!(result.is_ok());
-        // Check logs for confirmation of execution
+    #[tokio::test]
+    async fn test_failure_stops_the_workflow_unless_asked() {
+        let mut workflow = Workflow::new("Deploy");
+        workflow.steps = vec![
+            step("build", "exit 1"),
+            step("ship", "echo shipped"),
+            WorkflowStep { condition: Some("failure()".to_string()), ..step("notify", "echo build failed") },
+            WorkflowStep { condition: Some("always() && steps.build.exit_code == 1".to_string()), ..step("cleanup", "echo cleaned") },
+        ];
+        let run = sh().execute(&workflow, &HashMap::new()).await.unwrap();
+        assert!(!run.succeeded);
+        let outcomes: Vec<_> = run.steps.iter().map(|s| s.outcome).collect();
+        assert_eq!(outcomes, [StepOutcome::Failure, StepOutcome::Skipped, StepOutcome::Success, StepOutcome::Success]);
+        assert_eq!(run.steps[2].stdout, "build failed\n");
+    }
+
+    #[tokio::test]
+    async fn test_conditions_on_exit_codes_and_output() {
+        let mut workflow = Workflow::new("Branch");
+        workflow.steps = vec![
+            WorkflowStep { continue_on_error: true, ..step("check", "echo 'status: dirty'; exit 2") },
+            WorkflowStep { condition: Some("steps.check.exit_code == 2".to_string()), ..step("stash", "echo stashing") },
+            WorkflowStep { condition: Some("steps.1.stdout contains 'clean'".to_string()), ..step("skip", "echo never") },
+            WorkflowStep { condition: Some("steps.skip.outcome == 'skipped'".to_string()), ..WorkflowStep::new("echo done") },
+        ];
+        let run = sh().execute(&workflow, &HashMap::new()).await.unwrap();
+        assert!(run.succeeded);
+        let outcomes: Vec<_> = run.steps.iter().map(|s| s.outcome).collect();
+        assert_eq!(outcomes, [StepOutcome::Failure, StepOutcome::Success, StepOutcome::Skipped, StepOutcome::Success]);
+    }
+
+    #[tokio::test]
+    async fn test_secrets_stay_out_of_commands_and_output() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut workflow = Workflow::from_command("Login", "echo \"logging in with ${token}\"; ps -o args= -p $$");
+        workflow.arguments = vec![crate::workflows::model::WorkflowArgument { secret: true, ..crate::workflows::model::WorkflowArgument::new("token") }];
+        let args = HashMap::from([("token".to_string(), "tok-123".to_string())]);
+        let run = sh().with_event_sender(sender).execute(&workflow, &args).await.unwrap();
+
+        assert!(run.succeeded);
+        assert!(run.steps[0].stdout.starts_with("logging in with ••••\n"), "{}", run.steps[0].stdout);
+        assert!(!run.steps[0].stdout.contains("tok-123"), "{}", run.steps[0].stdout);
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                WorkflowEvent::StepStarted { command, .. } => assert!(command.contains("${WORKFLOW_SECRET_TOKEN}"), "{}", command),
+                WorkflowEvent::StepOutput { bytes, .. } => assert!(!String::from_utf8_lossy(&bytes).contains("tok-123")),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_and_timeout() {
+        let dir = tempdir().unwrap();
+        let mut workflow = Workflow::new("Flaky");
+        workflow.steps = vec![
+            // Fails twice, then succeeds: the counter file survives between attempts.
+            WorkflowStep { retry: 3, ..step("flaky", "echo x >> count; [ $(wc -l < count) -ge 3 ]") },
+            WorkflowStep { timeout_secs: Some(1), retry: 1, continue_on_error: true, ..step("hang", "echo start; sleep 10") },
+        ];
+        let started = Instant::now();
+        let run = sh().with_cwd(dir.path()).execute(&workflow, &HashMap::new()).await.unwrap();
+        assert_eq!((run.steps[0].outcome, run.steps[0].attempts), (StepOutcome::Success, 3));
+        assert_eq!((run.steps[1].outcome, run.steps[1].attempts), (StepOutcome::TimedOut, 2));
+        assert_eq!(run.steps[1].stdout, "start\n");
+        assert!(started.elapsed() < Duration::from_secs(8));
+        assert!(run.succeeded);
+    }
+
+    #[tokio::test]
+    async fn test_problems_are_reported_before_running() {
+        let dir = tempdir().unwrap();
+        let marker = dir.path().join("ran");
+        let mut workflow = Workflow::new("Bad");
+        workflow.steps = vec![
+            WorkflowStep::new(format!("touch {}", marker.display())),
+            WorkflowStep { condition: Some("steps.later.exit_code == 0".to_string()), ..WorkflowStep::new("true") },
+            step("later", "true"),
+        ];
+        let error = sh().execute(&workflow, &HashMap::new()).await.unwrap_err();
+        assert!(error.to_string().contains("step 'later', which it does not need"), "{}", error);
+        assert!(!marker.exists());
+    }
+
+    fn release_workflow() -> Workflow {
+        let mut workflow = Workflow::new("Release");
+        let build = |id: &str| WorkflowStep { needs: Some(vec![]), ..step(id, &format!("sleep 1; echo {} > {}.out", id, id)) };
+        workflow.steps = vec![
+            build("core"),
+            build("cli"),
+            build("ui"),
+            WorkflowStep {
+                needs: Some(vec!["core".to_string(), "cli".to_string(), "ui".to_string()]),
+                ..step("package", "cat core.out cli.out ui.out")
+            },
+        ];
+        workflow
+    }
+
+    #[tokio::test]
+    async fn test_independent_steps_run_concurrently() {
+        let dir = tempdir().unwrap();
+        let workflow = release_workflow();
+        let started = Instant::now();
+        let run = sh().with_cwd(dir.path()).with_max_parallel(3).execute(&workflow, &HashMap::new()).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(2500), "took {:?}", started.elapsed());
+        assert!(run.succeeded);
+        assert_eq!(run.steps[3].stdout, "core\ncli\nui\n");
+
+        let timeline = run.timeline(&workflow);
+        assert!(timeline[..3].iter().all(|entry| entry.start < Duration::from_millis(500)));
+        assert!(timeline[3].start >= timeline.iter().take(3).map(|e| e.end).max().unwrap());
+        let text = format_timeline(&timeline, 20);
+        assert_eq!(text.lines().count(), 4);
+        assert!(text.lines().next().unwrap().starts_with("core     |█"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_max_parallelism_is_respected() {
+        let dir = tempdir().unwrap();
+        let run = sh().with_cwd(dir.path()).with_max_parallel(1).execute(&release_workflow(), &HashMap::new()).await.unwrap();
+        // One at a time: each build starts after the previous one ended.
+        for pair in run.steps.windows(2) {
+            assert!(pair[1].started_at >= pair[0].started_at + pair[0].duration);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failure_skips_dependents_but_not_running_steps() {
+        let mut workflow = release_workflow();
+        workflow.steps[1].command = "exit 1".to_string();
+        workflow.steps[0].command = "true".to_string();
+        workflow.steps[2].command = "true".to_string();
+        let run = sh().with_max_parallel(3).execute(&workflow, &HashMap::new()).await.unwrap();
+        let outcomes: Vec<_> = run.steps.iter().map(|s| s.outcome).collect();
+        assert_eq!(outcomes, [StepOutcome::Success, StepOutcome::Failure, StepOutcome::Success, StepOutcome::Skipped]);
+        assert!(!run.succeeded);
+    }
+
+    #[tokio::test]
+    async fn test_cycles_are_rejected() {
+        let mut workflow = release_workflow();
+        workflow.steps[0].needs = Some(vec!["package".to_string()]);
+        let error = sh().execute(&workflow, &HashMap::new()).await.unwrap_err();
+        assert_eq!(error.to_string(), "steps depend on each other: core -> package -> core");
     }
 
     #[tokio::test]
     async fn test_workflow_export() -> anyhow::Result<()> {
         let executor = WorkflowExecutor::new();
         let workflow = Workflow {
-            id: Uuid::new_v4(),
-            name: "Exportable Workflow".to_string(),
             steps: vec![
-                "step 1".to_string(),
-                "step 2".to_string(),
+                WorkflowStep::new("step 1"),
+                WorkflowStep::new("step 2"),
             ],
+            ..Workflow::new("Exportable Workflow")
         };
 
         let temp_dir = tempdir()?;
@@ -89,10 +673,9 @@ mod tests {
 
         assert!(export_path.exists());
         let content = fs::read_to_string(&export_path)?;
-        assert!(content.contains(&workflow.name));
-        assert!(content.contains(&workflow.id.to_string()));
-        assert!(content.contains("- \"step 1\""));
-        assert!(content.contains("- \"step 2\""));
+        let spec = WorkflowSpec::from_yaml(&content)?;
+        assert_eq!(spec.name, workflow.name);
+        assert_eq!(spec.command, "step 1\nstep 2");
 
         Ok(())
     }
@@ -0,0 +1,282 @@
+// Workflow run history: one record per execution (when, with which arguments, how long,
+// how it ended), appended as a JSON line to a local file. The browser sorts and filters by
+// it ("Recent", "Last used", "Most used") and offers re-running with the same arguments.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::executor::{StepOutcome, WorkflowRun};
+use super::model::Workflow;
+use crate::jsonl::JsonlFile;
+
+/// Records kept per workflow; older ones are dropped when the file is next opened.
+pub const MAX_RUNS_PER_WORKFLOW: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunTrigger {
+    Manual,
+    FileChange,
+    Schedule,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub workflow_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// Without secret arguments, which re-running asks for again.
+    pub arguments: BTreeMap<String, String>,
+    pub succeeded: bool,
+    /// The failing step's exit code, 0 for a successful run; `None` if it timed out, was
+    /// killed or never started.
+    pub exit_code: Option<i32>,
+    pub trigger: RunTrigger,
+    /// Why the run was refused before any step ran, e.g. a required argument has no default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RunRecord {
+    pub fn new(
+        workflow: &Workflow,
+        run: &WorkflowRun,
+        arguments: &HashMap<String, String>,
+        trigger: RunTrigger,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+    ) -> Self {
+        let exit_code = if run.succeeded {
+            Some(0)
+        } else {
+            run.steps
+                .iter()
+                .find(|step| matches!(step.outcome, StepOutcome::Failure | StepOutcome::TimedOut))
+                .and_then(|step| step.exit_code)
+        };
+        RunRecord {
+            workflow_id: run.workflow_id,
+            started_at,
+            duration_ms: duration.as_millis() as u64,
+            arguments: arguments
+                .iter()
+                .filter(|(name, _)| !workflow.argument(name).is_some_and(|arg| arg.secret))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            succeeded: run.succeeded,
+            exit_code,
+            trigger,
+            error: None,
+        }
+    }
+
+    /// A run that failed before its first step.
+    pub fn not_started(workflow: &Workflow, trigger: RunTrigger, started_at: DateTime<Utc>, error: String) -> Self {
+        RunRecord {
+            workflow_id: workflow.id,
+            started_at,
+            duration_ms: 0,
+            arguments: BTreeMap::new(),
+            succeeded: false,
+            exit_code: None,
+            trigger,
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkflowStats {
+    pub runs: usize,
+    pub successes: usize,
+    pub last_used: Option<DateTime<Utc>>,
+    pub average_duration: Duration,
+}
+
+impl WorkflowStats {
+    /// Between 0 and 1; 0 for a workflow that never ran.
+    pub fn success_rate(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.successes as f64 / self.runs as f64
+        }
+    }
+}
+
+pub struct RunHistory {
+    file: JsonlFile,
+    /// Oldest first, as in the file.
+    records: Vec<RunRecord>,
+}
+
+impl RunHistory {
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir().unwrap_or_else(|| PathBuf::from(".")).join("warp-terminal-clone").join("workflow_runs.jsonl")
+    }
+
+    /// A history with no runs yet that will save to `path`.
+    pub fn empty(path: &Path) -> Self {
+        RunHistory { file: JsonlFile::new(path), records: Vec::new() }
+    }
+
+    /// Loads the history at `path`, starting empty if there is none. A torn last line is cut
+    /// off the file; other unreadable lines are skipped, and the file is rewritten without
+    /// them and without runs beyond `MAX_RUNS_PER_WORKFLOW`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut history = RunHistory::empty(path);
+        let (records, skipped) = history.file.read()?;
+        history.records = records;
+
+        let mut per_workflow: HashMap<Uuid, usize> = HashMap::new();
+        for record in &history.records {
+            *per_workflow.entry(record.workflow_id).or_default() += 1;
+        }
+        let before = history.records.len();
+        history.records.retain(|record| {
+            let remaining = per_workflow.get_mut(&record.workflow_id).unwrap();
+            *remaining -= 1;
+            *remaining < MAX_RUNS_PER_WORKFLOW
+        });
+        if skipped > 0 || history.records.len() < before {
+            history.file.rewrite(&history.records)?;
+        }
+        Ok(history)
+    }
+
+    /// Appends a run to the history and its file.
+    pub fn record(&mut self, record: RunRecord) -> Result<()> {
+        self.file.append(&record)?;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// A workflow's runs, newest first.
+    pub fn runs(&self, workflow_id: &Uuid) -> impl Iterator<Item = &RunRecord> {
+        let workflow_id = *workflow_id;
+        self.records.iter().rev().filter(move |record| record.workflow_id == workflow_id)
+    }
+
+    pub fn last_run(&self, workflow_id: &Uuid) -> Option<&RunRecord> {
+        self.runs(workflow_id).next()
+    }
+
+    /// A workflow's runs started by its schedule, newest first.
+    pub fn scheduled_runs(&self, workflow_id: &Uuid) -> impl Iterator<Item = &RunRecord> {
+        self.runs(workflow_id).filter(|record| record.trigger == RunTrigger::Schedule)
+    }
+
+    pub fn stats(&self, workflow_id: &Uuid) -> WorkflowStats {
+        let mut stats = WorkflowStats { runs: 0, successes: 0, last_used: None, average_duration: Duration::ZERO };
+        let mut total_ms = 0;
+        for record in self.runs(workflow_id) {
+            stats.runs += 1;
+            stats.successes += usize::from(record.succeeded);
+            stats.last_used = stats.last_used.max(Some(record.started_at));
+            total_ms += record.duration_ms;
+        }
+        if stats.runs > 0 {
+            stats.average_duration = Duration::from_millis(total_ms / stats.runs as u64);
+        }
+        stats
+    }
+
+    /// Every workflow that has run, most recently used first.
+    pub fn recent_workflows(&self) -> Vec<Uuid> {
+        let mut recent = Vec::new();
+        for record in self.records.iter().rev() {
+            if !recent.contains(&record.workflow_id) {
+                recent.push(record.workflow_id);
+            }
+        }
+        recent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::executor::WorkflowExecutor;
+    use crate::workflows::model::WorkflowArgument;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn record(workflow_id: Uuid, minute: u32, succeeded: bool, duration_ms: u64) -> RunRecord {
+        RunRecord {
+            workflow_id,
+            started_at: DateTime::parse_from_rfc3339(&format!("2024-03-01T10:{:02}:00Z", minute)).unwrap().with_timezone(&Utc),
+            duration_ms,
+            arguments: BTreeMap::from([("branch".to_string(), format!("feature-{}", minute))]),
+            succeeded,
+            exit_code: Some(if succeeded { 0 } else { 1 }),
+            trigger: RunTrigger::Manual,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_runs_survive_reopening() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("runs.jsonl");
+        let (build, deploy) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let mut history = RunHistory::open(&path).unwrap();
+        history.record(record(build, 1, true, 1000)).unwrap();
+        history.record(record(deploy, 2, false, 500)).unwrap();
+        history.record(record(build, 3, false, 3000)).unwrap();
+        history.record(record(build, 4, true, 2000)).unwrap();
+
+        let history = RunHistory::open(&path).unwrap();
+        assert_eq!(history.recent_workflows(), [build, deploy]);
+        assert_eq!(history.last_run(&build).unwrap().arguments["branch"], "feature-4");
+        let stats = history.stats(&build);
+        assert_eq!((stats.runs, stats.successes), (3, 2));
+        assert!((stats.success_rate() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.average_duration, Duration::from_millis(2000));
+        assert_eq!(stats.last_used, Some(record(build, 4, true, 0).started_at));
+        assert_eq!(history.stats(&Uuid::new_v4()).success_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_torn_lines_and_old_runs_are_dropped() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("runs.jsonl");
+        let id = Uuid::new_v4();
+        let mut content = String::new();
+        for minute in 0..(MAX_RUNS_PER_WORKFLOW as u32 + 5) {
+            content.push_str(&serde_json::to_string(&record(id, minute % 60, true, 10)).unwrap());
+            content.push('\n');
+        }
+        content.push_str(r#"{"workflow_id": "#);
+        fs::write(&path, content).unwrap();
+
+        let history = RunHistory::open(&path).unwrap();
+        assert_eq!(history.stats(&id).runs, MAX_RUNS_PER_WORKFLOW);
+        let reopened = fs::read_to_string(&path).unwrap();
+        assert_eq!(reopened.lines().count(), MAX_RUNS_PER_WORKFLOW);
+        assert!(reopened.ends_with("}\n"));
+    }
+
+    #[tokio::test]
+    async fn test_record_from_a_run() {
+        let mut workflow = Workflow::from_command("Check", "echo ok");
+        workflow.steps.push(crate::workflows::model::WorkflowStep::new("exit 4"));
+        workflow.arguments = vec![WorkflowArgument::new("env"), WorkflowArgument { secret: true, ..WorkflowArgument::new("token") }];
+        let arguments = HashMap::from([("env".to_string(), "staging".to_string()), ("token".to_string(), "hunter2".to_string())]);
+        let run = WorkflowExecutor::with_shell("sh").execute(&workflow, &arguments).await.unwrap();
+
+        let record = RunRecord::new(&workflow, &run, &arguments, RunTrigger::FileChange, Utc::now(), Duration::from_millis(1500));
+        assert_eq!(record.workflow_id, workflow.id);
+        assert!(!record.succeeded);
+        assert_eq!(record.exit_code, Some(4));
+        assert_eq!(record.duration_ms, 1500);
+        assert_eq!(record.arguments, BTreeMap::from([("env".to_string(), "staging".to_string())]));
+        assert!(serde_json::to_string(&record).unwrap().contains(r#""trigger":"file_change""#));
+    }
+}
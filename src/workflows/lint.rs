@@ -0,0 +1,325 @@
+// Static checks for workflows, so broken quoting or a misspelled `${argument}` shows up
+// when a workflow is written rather than when it is run. Every step's command template
+// is parsed with the bash grammar; `${name}` is valid bash, so templates parse as-is.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use tree_sitter::Node;
+
+use super::args;
+use super::dag::StepGraph;
+use super::executor::parse_conditions;
+use super::manager::{is_yaml, read_workflow_file};
+use super::model::Workflow;
+use crate::syntax_tree::SyntaxTreeParser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    /// The bash parser had to recover; the text describes what it stumbled on.
+    Syntax(String),
+    /// `${name}` for a name that is neither an argument nor, by convention, an environment variable.
+    UndeclaredArgument(String),
+    UnusedArgument(String),
+    /// An argument expanded outside double quotes.
+    UnquotedArgument(String),
+    /// A Warp-style `{{name}}`, which is never substituted and reaches the shell as-is.
+    UnsubstitutedPlaceholder(String),
+    /// Bad `needs:` or `if:`; the executor refuses to start such a workflow.
+    InvalidSteps(String),
+}
+
+impl LintKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            LintKind::Syntax(_) | LintKind::UnsubstitutedPlaceholder(_) | LintKind::InvalidSteps(_) => Severity::Error,
+            LintKind::UndeclaredArgument(_) | LintKind::UnusedArgument(_) | LintKind::UnquotedArgument(_) => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintKind::Syntax(description) => write!(f, "syntax error: {}", description),
+            LintKind::UndeclaredArgument(name) => write!(f, "'${{{}}}' is not a declared argument", name),
+            LintKind::UnusedArgument(name) => write!(f, "argument '{}' is declared but never used", name),
+            LintKind::UnquotedArgument(name) => write!(f, "'${{{}}}' is not quoted; use \"${{{}}}\"", name, name),
+            LintKind::UnsubstitutedPlaceholder(name) => {
+                write!(f, "'{{{{{}}}}}' is never substituted; declare '{}' and use '${{{}}}'", name, name, name)
+            }
+            LintKind::InvalidSteps(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: LintKind,
+    /// 0-based index of the step, if the problem is in one.
+    pub step: Option<usize>,
+    /// Byte range in the step's command template.
+    pub range: Option<Range<usize>>,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity() {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+        match (self.step, &self.range) {
+            (Some(step), Some(range)) => write!(f, "step {}, bytes {}..{}: ", step + 1, range.start, range.end)?,
+            (Some(step), None) => write!(f, "step {}: ", step + 1)?,
+            (None, _) => {}
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+/// Upper-case names such as `${HOME}` are taken to be environment variables.
+fn looks_like_environment_variable(name: &str) -> bool {
+    !name.chars().any(|c| c.is_ascii_lowercase())
+}
+
+/// Whether word splitting and globbing apply to an expansion: it is not inside double
+/// quotes (in the same command substitution), an assignment or a heredoc.
+fn is_unquoted(node: Node) -> bool {
+    let mut current = node.parent();
+    while let Some(ancestor) = current {
+        match ancestor.kind() {
+            "string" | "variable_assignment" | "heredoc_body" => return false,
+            "command_substitution" => return true,
+            _ => current = ancestor.parent(),
+        }
+    }
+    true
+}
+
+fn describe_error(node: Node, source: &str) -> String {
+    if node.is_missing() {
+        return format!("missing '{}'", node.kind());
+    }
+    let text = node.utf8_text(source.as_bytes()).unwrap_or_default().trim();
+    match text.chars().next() {
+        Some(c @ ('"' | '\'' | '`')) => format!("unterminated {}", c),
+        _ if text.chars().count() > 20 => format!("unexpected '{}…'", text.chars().take(20).collect::<String>()),
+        _ => format!("unexpected '{}'", text),
+    }
+}
+
+pub struct Linter {
+    parser: SyntaxTreeParser,
+    warp_placeholder: Regex,
+}
+
+impl Linter {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Linter {
+            parser: SyntaxTreeParser::new()?,
+            warp_placeholder: Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_-]*)\s*\}\}").unwrap(),
+        })
+    }
+
+    /// Every problem found in `workflow`, by step and then position.
+    pub fn lint(&mut self, workflow: &Workflow) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let declared: HashSet<&str> = workflow.arguments.iter().map(|arg| arg.name.as_str()).collect();
+        let mut used = HashSet::new();
+
+        match StepGraph::build(&workflow.steps) {
+            Ok(graph) => {
+                if let Err(e) = parse_conditions(&workflow.steps, &graph) {
+                    diagnostics.push(Diagnostic { kind: LintKind::InvalidSteps(e.to_string()), step: None, range: None });
+                }
+            }
+            Err(e) => diagnostics.push(Diagnostic { kind: LintKind::InvalidSteps(e.to_string()), step: None, range: None }),
+        }
+
+        for (index, step) in workflow.steps.iter().enumerate() {
+            let command = step.command.as_str();
+            let mut found = |kind: LintKind, range: Range<usize>| {
+                diagnostics.push(Diagnostic { kind, step: Some(index), range: Some(range) });
+            };
+
+            for (range, name) in args::placeholders(command) {
+                if declared.contains(name) {
+                    used.insert(name);
+                } else if !looks_like_environment_variable(name) {
+                    found(LintKind::UndeclaredArgument(name.to_string()), range);
+                }
+            }
+            for captures in self.warp_placeholder.captures_iter(command) {
+                let whole = captures.get(0).unwrap();
+                found(LintKind::UnsubstitutedPlaceholder(captures[1].to_string()), whole.range());
+            }
+
+            let Some(tree) = self.parser.parse(command) else { continue };
+            for node in SyntaxTreeParser::find_error_nodes(&tree) {
+                found(LintKind::Syntax(describe_error(node, command)), node.start_byte()..node.end_byte());
+            }
+            for node in SyntaxTreeParser::find_nodes_by_kind(&tree, "expansion") {
+                // Only plain `${name}`, not `${name:-default}` and the like.
+                let Some(name) = node
+                    .named_child(0)
+                    .filter(|child| child.kind() == "variable_name" && child.end_byte() + 1 == node.end_byte())
+                    .and_then(|child| child.utf8_text(command.as_bytes()).ok())
+                else {
+                    continue;
+                };
+                if declared.contains(name) && is_unquoted(node) {
+                    found(LintKind::UnquotedArgument(name.to_string()), node.start_byte()..node.end_byte());
+                }
+            }
+        }
+
+        for arg in &workflow.arguments {
+            if !used.contains(arg.name.as_str()) {
+                diagnostics.push(Diagnostic { kind: LintKind::UnusedArgument(arg.name.clone()), step: None, range: None });
+            }
+        }
+        // Workflow-wide problems go last.
+        diagnostics.sort_by_key(|d| (d.step.is_none(), d.step, d.range.as_ref().map(|r| r.start)));
+        diagnostics
+    }
+}
+
+fn collect_files(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        for entry in walkdir::WalkDir::new(path).sort_by_file_name() {
+            let file = entry?.into_path();
+            let is_workflow = is_yaml(&file) || file.extension().is_some_and(|ext| ext == "json");
+            if file.is_file() && is_workflow {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn report_file(linter: &mut Linter, path: &Path, out: &mut dyn Write) -> anyhow::Result<(usize, usize)> {
+    let workflow = match read_workflow_file(path) {
+        Ok(workflow) => workflow,
+        Err(e) => {
+            writeln!(out, "{}: error: could not read workflow: {}", path.display(), e)?;
+            return Ok((1, 0));
+        }
+    };
+    let diagnostics = linter.lint(&workflow);
+    let errors = diagnostics.iter().filter(|d| d.severity() == Severity::Error).count();
+    for diagnostic in &diagnostics {
+        writeln!(out, "{}: {}", path.display(), diagnostic)?;
+    }
+    Ok((errors, diagnostics.len() - errors))
+}
+
+/// The headless check behind `lint-workflows`, for CI: lints every workflow file given,
+/// looking through directories for `.yaml`, `.yml` and `.json` files, and reports to `out`.
+/// Returns whether the workflows passed: no errors, and no warnings either if `deny_warnings`.
+pub fn lint_paths(paths: &[PathBuf], deny_warnings: bool, out: &mut dyn Write) -> anyhow::Result<bool> {
+    let mut linter = Linter::new()?;
+    let files = collect_files(paths)?;
+    let (mut errors, mut warnings) = (0, 0);
+    for file in &files {
+        let (file_errors, file_warnings) = report_file(&mut linter, file, out)?;
+        errors += file_errors;
+        warnings += file_warnings;
+    }
+    writeln!(out, "{} workflow file(s) checked: {} error(s), {} warning(s)", files.len(), errors, warnings)?;
+    Ok(errors == 0 && (warnings == 0 || !deny_warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::model::{WorkflowArgument, WorkflowStep};
+    use tempfile::tempdir;
+
+    fn workflow(arguments: &[&str], commands: &[&str]) -> Workflow {
+        let mut workflow = Workflow::new("Lint me");
+        workflow.arguments = arguments.iter().map(|name| WorkflowArgument::new(*name)).collect();
+        workflow.steps = commands.iter().map(|command| WorkflowStep::new(*command)).collect();
+        workflow
+    }
+
+    fn kinds(diagnostics: &[Diagnostic]) -> Vec<(Option<usize>, LintKind)> {
+        diagnostics.iter().map(|d| (d.step, d.kind.clone())).collect()
+    }
+
+    #[test]
+    fn test_clean_workflow_has_no_diagnostics() {
+        let clean = workflow(
+            &["branch", "message"],
+            &[r#"git checkout "${branch}""#, r#"git commit -m "$(echo "${message}")" && echo "$HOME ${USER}""#],
+        );
+        assert_eq!(Linter::new().unwrap().lint(&clean), []);
+    }
+
+    #[test]
+    fn test_argument_problems() {
+        let broken = workflow(&["branch", "unused"], &["git checkout ${branch} ${brnch}", "echo {{ tag }} \"$(cat ${branch})\""]);
+        let diagnostics = Linter::new().unwrap().lint(&broken);
+        assert_eq!(
+            kinds(&diagnostics),
+            [
+                (Some(0), LintKind::UnquotedArgument("branch".to_string())),
+                (Some(0), LintKind::UndeclaredArgument("brnch".to_string())),
+                (Some(1), LintKind::UnsubstitutedPlaceholder("tag".to_string())),
+                (Some(1), LintKind::UnquotedArgument("branch".to_string())),
+                (None, LintKind::UnusedArgument("unused".to_string())),
+            ]
+        );
+        assert_eq!(diagnostics[0].range, Some(13..22));
+        assert_eq!(diagnostics[1].to_string(), "warning: step 1, bytes 23..31: '${brnch}' is not a declared argument");
+    }
+
+    #[test]
+    fn test_syntax_errors_have_ranges() {
+        let broken = workflow(&[], &["echo ok", "echo \"unterminated", "if true; then echo"]);
+        let diagnostics = Linter::new().unwrap().lint(&broken);
+        assert!(diagnostics.iter().all(|d| d.severity() == Severity::Error && d.step != Some(0)), "{:?}", diagnostics);
+        assert!(diagnostics.iter().any(|d| d.step == Some(1)), "{:?}", diagnostics);
+        assert!(diagnostics.iter().any(|d| d.step == Some(2)), "{:?}", diagnostics);
+        for d in &diagnostics {
+            let range = d.range.clone().unwrap();
+            assert!(range.end <= broken.steps[d.step.unwrap()].command.len());
+        }
+    }
+
+    #[test]
+    fn test_lint_paths_for_ci() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("good.yaml"), "name: Good\ncommand: echo \"{{who}}\"\narguments:\n  - name: who\n").unwrap();
+        let mut out = Vec::new();
+        assert!(lint_paths(&[dir.path().to_path_buf()], true, &mut out).unwrap());
+
+        std::fs::write(dir.path().join("bad.yml"), "name: Bad\ncommand: echo {{who}} 'oops\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not a workflow").unwrap();
+        let mut out = Vec::new();
+        assert!(!lint_paths(&[dir.path().to_path_buf()], false, &mut out).unwrap());
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("bad.yml: error: step 1, bytes 5..12: '{{who}}' is never substituted"), "{}", report);
+        assert!(report.ends_with("2 workflow file(s) checked: 2 error(s), 0 warning(s)\n"), "{}", report);
+    }
+}
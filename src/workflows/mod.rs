@@ -1,8 +1,29 @@
+pub mod model;
+pub mod args; // Argument validation and shell-safe substitution
+pub mod secrets; // Passing secret arguments through the environment and masking them
+pub mod suggestions; // Argument suggestions from generator commands
+pub mod condition; // `if:` expressions on steps
+pub mod dag; // `needs:` dependencies between steps
+pub mod cron; // Cron expressions for schedules
+pub mod spec; // Warp workflow YAML format
 pub mod executor;
+pub mod dry_run; // Explaining a run without executing it
+pub mod lint; // Static checks of step commands and arguments
+pub mod triggers; // Re-running `on_change:` workflows when files change
+pub mod scheduler; // Running workflows on a schedule
+pub mod history; // Run records and usage stats
+pub mod collection; // Read-only workflows synced from a git repository
 pub mod manager;
 pub mod ui;
 
 // Re-export key structs for easier access
-pub use executor::{Workflow, WorkflowExecutor};
+pub use model::{ArgumentType, Schedule, Workflow, WorkflowArgument, WorkflowStep};
+pub use args::{ArgumentError, ArgumentErrors};
+pub use dry_run::{DryRun, PlannedRun, PlannedStep};
+pub use history::{RunHistory, RunRecord, RunTrigger, WorkflowStats};
+pub use executor::{StepOutcome, StepResult, TimelineEntry, WorkflowEvent, WorkflowExecutor, WorkflowRun};
+pub use lint::{Diagnostic, LintKind, Linter, Severity};
+pub use collection::{AutoRun, GitCollection, WorkflowChange};
 pub use manager::WorkflowManager;
+pub use spec::WorkflowSpec;
 pub use ui::WorkflowBrowser;
@@ -0,0 +1,370 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// The canonical workflow model shared by the manager, executor and UI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workflow {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub steps: Vec<WorkflowStep>,
+    #[serde(default)]
+    pub arguments: Vec<WorkflowArgument>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub is_favorite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_url: Option<String>,
+    /// Shells the workflow is meant for; empty means any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shells: Vec<String>,
+    /// Globs relative to the working directory, e.g. `src/**/*.rs`; saving a matching file re-runs the workflow.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_change: Vec<String>,
+    /// Runs the workflow in the background while the terminal is open.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Workflow {
+    pub fn new(name: impl Into<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            description: None,
+            steps: Vec::new(),
+            arguments: Vec::new(),
+            tags: Vec::new(),
+            is_favorite: false,
+            source_url: None,
+            author: None,
+            author_url: None,
+            shells: Vec::new(),
+            on_change: Vec::new(),
+            schedule: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// A workflow that runs a single command.
+    pub fn from_command(name: impl Into<String>, command: impl Into<String>) -> Self {
+        let mut workflow = Self::new(name);
+        workflow.steps.push(WorkflowStep::new(command));
+        workflow
+    }
+
+    pub fn argument(&self, name: &str) -> Option<&WorkflowArgument> {
+        self.arguments.iter().find(|arg| arg.name == name)
+    }
+
+    /// Marks the workflow as modified now.
+    pub fn touch(&mut self) {
+        self.updated_at = Utc::now();
+    }
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Self::new("New Workflow")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    /// How `if:` conditions refer to this step; steps can also be referred to by position.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub command: String,
+    /// Steps to wait for (see `workflows::dag`). `None` means the previous step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub needs: Option<Vec<String>>,
+    /// Run the step only when this condition holds (see `workflows::condition`).
+    #[serde(default, rename = "if", skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    /// A failure is recorded but does not stop the workflow.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub continue_on_error: bool,
+    /// Extra attempts after a failed or timed out run.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retry: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+impl WorkflowStep {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            name: None,
+            command: command.into(),
+            needs: None,
+            condition: None,
+            continue_on_error: false,
+            retry: 0,
+            timeout_secs: None,
+        }
+    }
+
+    /// A label for logs and block headers.
+    pub fn title(&self, index: usize) -> String {
+        self.name.clone().or_else(|| self.id.clone()).unwrap_or_else(|| format!("Step {}", index + 1))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowArgument {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arg_type: ArgumentType,
+    #[serde(default)]
+    pub default_value: Option<String>,
+    /// A required argument must end up with a non-empty value (given or default).
+    #[serde(default = "default_required")]
+    pub required: bool,
+    /// Regex the whole value must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation: Option<String>,
+    /// Shell command whose output lines are offered as suggestions for this argument.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>,
+    /// A token or password: typed into a masked field, passed to steps in an environment
+    /// variable instead of on the command line, kept out of run history and masked in output.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+impl WorkflowArgument {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            arg_type: ArgumentType::default(),
+            default_value: None,
+            required: true,
+            validation: None,
+            generator: None,
+            secret: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type", content = "values")]
+pub enum ArgumentType {
+    #[default]
+    String,
+    Int,
+    Path,
+    Boolean,
+    /// One of a fixed set of values.
+    Enum(Vec<String>),
+}
+
+/// When a scheduled workflow runs: `{"every_minutes": 5}` or `{"cron": "*/15 9-17 * * mon-fri"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    EveryMinutes(u32),
+    /// A five-field cron expression, in local time.
+    Cron(String),
+}
+
+// --- Migration of older on-disk shapes ---
+
+/// `workflows/manager.rs` before the unification: keyed by name, a list of commands.
+#[derive(Debug, Deserialize)]
+struct LegacyCommandsWorkflow {
+    name: String,
+    #[serde(default)]
+    description: String,
+    commands: Vec<String>,
+}
+
+/// The old top-level `workflows.rs` model: a single command with described arguments.
+#[derive(Debug, Deserialize)]
+struct LegacyCommandWorkflow {
+    id: Uuid,
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    command: String,
+    #[serde(default)]
+    arguments: Option<HashMap<String, String>>, // Argument name -> description
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    is_favorite: bool,
+    created_at: Option<DateTime<Utc>>,
+}
+
+// Tried in order, so the current shape must come first.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StoredWorkflow {
+    Current(Workflow),
+    LegacyCommand(LegacyCommandWorkflow),
+    LegacyCommands(LegacyCommandsWorkflow),
+}
+
+/// Parses a workflow from any JSON shape this app has written. The flag is `true`
+/// when the input was in an old shape and should be re-saved.
+pub fn parse_workflow_json(json: &str) -> serde_json::Result<(Workflow, bool)> {
+    Ok(match serde_json::from_str::<StoredWorkflow>(json)? {
+        StoredWorkflow::Current(workflow) => (workflow, false),
+        StoredWorkflow::LegacyCommand(legacy) => {
+            let mut workflow = Workflow::from_command(legacy.name, legacy.command);
+            workflow.id = legacy.id;
+            workflow.description = legacy.description;
+            workflow.tags = legacy.tags;
+            workflow.is_favorite = legacy.is_favorite;
+            if let Some(created_at) = legacy.created_at {
+                workflow.created_at = created_at;
+                workflow.updated_at = created_at;
+            }
+            let mut arguments: Vec<_> = legacy.arguments.unwrap_or_default().into_iter().collect();
+            arguments.sort();
+            workflow.arguments = arguments
+                .into_iter()
+                .map(|(name, description)| WorkflowArgument {
+                    description: Some(description).filter(|d| !d.is_empty()),
+                    ..WorkflowArgument::new(name)
+                })
+                .collect();
+            (workflow, true)
+        }
+        StoredWorkflow::LegacyCommands(legacy) => {
+            let mut workflow = Workflow::new(legacy.name);
+            workflow.description = Some(legacy.description).filter(|d| !d.is_empty());
+            workflow.steps = legacy.commands.into_iter().map(WorkflowStep::new).collect();
+            (workflow, true)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut workflow = Workflow::from_command("Deploy", "kubectl rollout restart deploy/${service}");
+        workflow.description = Some("Restart a service".to_string());
+        workflow.steps.push(WorkflowStep {
+            id: Some("status".to_string()),
+            name: Some("Pod status".to_string()),
+            condition: Some("steps.1.exit_code == 0".to_string()),
+            continue_on_error: true,
+            needs: Some(vec![]),
+            retry: 2,
+            timeout_secs: Some(30),
+            ..WorkflowStep::new("kubectl get pods")
+        });
+        workflow.arguments = vec![
+            WorkflowArgument { description: Some("Service name".to_string()), ..WorkflowArgument::new("service") },
+            WorkflowArgument {
+                arg_type: ArgumentType::Enum(vec!["staging".to_string(), "prod".to_string()]),
+                default_value: Some("staging".to_string()),
+                ..WorkflowArgument::new("env")
+            },
+            WorkflowArgument {
+                arg_type: ArgumentType::Int,
+                required: false,
+                validation: Some("[1-9][0-9]*".to_string()),
+                ..WorkflowArgument::new("replicas")
+            },
+            WorkflowArgument {
+                generator: Some("git branch --format=%(refname:short)".to_string()),
+                ..WorkflowArgument::new("branch")
+            },
+        ];
+        workflow.tags = vec!["k8s".to_string()];
+        workflow.is_favorite = true;
+        workflow.author = Some("Platform team".to_string());
+        workflow.shells = vec!["bash".to_string()];
+
+        let json = serde_json::to_string_pretty(&workflow).unwrap();
+        let (parsed, migrated) = parse_workflow_json(&json).unwrap();
+        assert_eq!(parsed, workflow);
+        assert!(!migrated);
+    }
+
+    #[test]
+    fn test_minimal_current_shape_uses_defaults() {
+        let json = r#"{
+            "id": "7d4f5f5e-8c1a-4e0e-9a57-0f1f7a3f8a11",
+            "name": "Build",
+            "steps": [{ "command": "cargo build" }],
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-02T00:00:00Z"
+        }"#;
+        let (workflow, migrated) = parse_workflow_json(json).unwrap();
+        assert!(!migrated);
+        assert!(workflow.arguments.is_empty() && workflow.tags.is_empty() && !workflow.is_favorite);
+        let (with_args, _) = parse_workflow_json(&json.replace(r#""steps""#, r#""arguments": [{ "name": "x" }], "steps""#)).unwrap();
+        assert!(with_args.arguments[0].required);
+        assert_eq!(workflow.steps, vec![WorkflowStep::new("cargo build")]);
+    }
+
+    #[test]
+    fn test_migrates_legacy_commands_shape() {
+        let json = r#"{ "name": "Setup", "description": "", "commands": ["npm ci", "npm test"] }"#;
+        let (workflow, migrated) = parse_workflow_json(json).unwrap();
+        assert!(migrated);
+        assert_eq!(workflow.name, "Setup");
+        assert_eq!(workflow.description, None);
+        assert_eq!(workflow.steps, vec![WorkflowStep::new("npm ci"), WorkflowStep::new("npm test")]);
+    }
+
+    #[test]
+    fn test_migrates_legacy_single_command_shape() {
+        let json = r#"{
+            "id": "0b0c3f3e-2f0a-4d55-8d6c-5d2f4f0e9b21",
+            "name": "Grep logs",
+            "description": "Search the logs",
+            "command": "grep ${pattern} ${file}",
+            "arguments": { "pattern": "What to find", "file": "" },
+            "tags": ["logs"],
+            "is_favorite": true,
+            "created_at": "2023-05-06T07:08:09Z"
+        }"#;
+        let (workflow, migrated) = parse_workflow_json(json).unwrap();
+        assert!(migrated);
+        assert_eq!(workflow.id.to_string(), "0b0c3f3e-2f0a-4d55-8d6c-5d2f4f0e9b21");
+        assert_eq!(workflow.steps, vec![WorkflowStep::new("grep ${pattern} ${file}")]);
+        let names: Vec<_> = workflow.arguments.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["file", "pattern"]);
+        assert_eq!(workflow.argument("pattern").unwrap().description.as_deref(), Some("What to find"));
+        assert_eq!(workflow.argument("file").unwrap().description, None);
+        assert_eq!(workflow.created_at.to_rfc3339(), "2023-05-06T07:08:09+00:00");
+        assert!(workflow.is_favorite);
+    }
+
+    #[test]
+    fn test_unrecognised_json_is_an_error() {
+        assert!(parse_workflow_json(r#"{ "title": "nope" }"#).is_err());
+    }
+}
@@ -0,0 +1,403 @@
+// Scheduled workflows: a workflow with a `schedule:` runs on its own, in the background,
+// while the terminal is open. The scheduler only decides when; the terminal runs what
+// `due` returns and reports each outcome back through `record`. Results are kept in the
+// workflow `RunHistory` like any other run, so they survive a restart. Time comes from a
+// `Clock`, so tests can step through hours of schedule instantly.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use log::{info, warn};
+use uuid::Uuid;
+
+use super::cron::CronExpr;
+use super::executor::{StepOutcome, WorkflowRun};
+use super::history::{RunHistory, RunRecord};
+use super::model::{Schedule, Workflow};
+
+/// How many results `Scheduler::results` returns per scheduled workflow.
+pub const DEFAULT_HISTORY_LEN: usize = 20;
+
+pub trait Clock {
+    /// The time zone cron expressions are evaluated in.
+    type Tz: TimeZone;
+    fn now(&self) -> DateTime<Self::Tz>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    type Tz = Local;
+
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduledOutcome {
+    Succeeded,
+    Failed { step: String, exit_code: Option<i32> },
+    /// The run was refused before any step ran, e.g. a required argument has no default.
+    NotStarted(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledRun {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub outcome: ScheduledOutcome,
+}
+
+impl ScheduledRun {
+    pub fn from_result(
+        workflow: &Workflow,
+        started_at: DateTime<Utc>,
+        finished_at: DateTime<Utc>,
+        result: &anyhow::Result<WorkflowRun>,
+    ) -> Self {
+        let outcome = match result {
+            Ok(run) if run.succeeded => ScheduledOutcome::Succeeded,
+            Ok(run) => {
+                let failed = run.steps.iter().find(|step| matches!(step.outcome, StepOutcome::Failure | StepOutcome::TimedOut));
+                ScheduledOutcome::Failed {
+                    step: failed.map_or_else(|| "unknown step".to_string(), |step| workflow.steps[step.index].title(step.index)),
+                    exit_code: failed.and_then(|step| step.exit_code),
+                }
+            }
+            Err(e) => ScheduledOutcome::NotStarted(e.to_string()),
+        };
+        ScheduledRun { started_at, finished_at, outcome }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.outcome == ScheduledOutcome::Succeeded
+    }
+}
+
+/// Something the user should hear about even though they didn't start the run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleNotification {
+    pub workflow_id: Uuid,
+    pub title: String,
+    pub body: String,
+}
+
+struct Entry {
+    name: String,
+    schedule: Schedule,
+    cron: Option<CronExpr>,
+    next_due: Option<DateTime<Utc>>,
+    running: bool,
+}
+
+pub struct Scheduler<C: Clock> {
+    clock: C,
+    entries: HashMap<Uuid, Entry>,
+    history_len: usize,
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn new(clock: C) -> Self {
+        Scheduler { clock, entries: HashMap::new(), history_len: DEFAULT_HISTORY_LEN }
+    }
+
+    pub fn with_history_len(mut self, history_len: usize) -> Self {
+        self.history_len = history_len.max(1);
+        self
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now().with_timezone(&Utc)
+    }
+
+    fn next_after_now(&self, schedule: &Schedule, cron: Option<&CronExpr>) -> Option<DateTime<Utc>> {
+        match (schedule, cron) {
+            (Schedule::EveryMinutes(minutes), _) => Some(self.now() + Duration::minutes(i64::from((*minutes).max(1)))),
+            (Schedule::Cron(_), Some(cron)) => cron.next_after(&self.clock.now()).map(|time| time.with_timezone(&Utc)),
+            (Schedule::Cron(_), None) => None,
+        }
+    }
+
+    /// Picks up added, changed and removed schedules. A new or changed schedule first runs
+    /// one interval (or the next cron match) from now.
+    pub fn sync<'a>(&mut self, workflows: impl IntoIterator<Item = &'a Workflow>) {
+        let mut scheduled = Vec::new();
+        for workflow in workflows {
+            let Some(schedule) = &workflow.schedule else { continue };
+            scheduled.push(workflow.id);
+            if let Some(entry) = self.entries.get_mut(&workflow.id) {
+                if entry.schedule == *schedule {
+                    entry.name = workflow.name.clone();
+                    continue;
+                }
+            }
+
+            let cron = match schedule {
+                Schedule::Cron(expression) => match CronExpr::parse(expression) {
+                    Ok(cron) => Some(cron),
+                    Err(e) => {
+                        warn!("Not scheduling workflow '{}': {}", workflow.name, e);
+                        None
+                    }
+                },
+                Schedule::EveryMinutes(_) => None,
+            };
+            let next_due = self.next_after_now(schedule, cron.as_ref());
+            self.entries.insert(
+                workflow.id,
+                Entry { name: workflow.name.clone(), schedule: schedule.clone(), cron, next_due, running: false },
+            );
+        }
+        self.entries.retain(|id, _| scheduled.contains(id));
+    }
+
+    /// The workflows due to run now, each counted as running until its result is recorded.
+    /// One that is still running from last time skips this turn rather than running twice.
+    pub fn due(&mut self) -> Vec<Uuid> {
+        let now = self.now();
+        let due: Vec<Uuid> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.next_due.is_some_and(|next| next <= now))
+            .map(|(id, _)| *id)
+            .collect();
+
+        let mut started = Vec::new();
+        for id in due {
+            let entry = &self.entries[&id];
+            let next_due = self.next_after_now(&entry.schedule, entry.cron.as_ref());
+            let entry = self.entries.get_mut(&id).unwrap();
+            entry.next_due = next_due;
+            if entry.running {
+                info!("Scheduled workflow '{}' is still running; skipping this run", entry.name);
+            } else {
+                entry.running = true;
+                started.push(id);
+            }
+        }
+        started
+    }
+
+    /// Marks a run as finished. Returns a notification when the workflow failed, or succeeded
+    /// after its last scheduled run in `history` failed; record the run in `history` after.
+    pub fn record(&mut self, id: Uuid, run: &ScheduledRun, history: &RunHistory) -> Option<ScheduleNotification> {
+        let entry = self.entries.get_mut(&id)?;
+        entry.running = false;
+        let was_failing = history.scheduled_runs(&id).next().is_some_and(|last| !last.succeeded);
+        let notification = match &run.outcome {
+            ScheduledOutcome::Succeeded if was_failing => {
+                Some((format!("'{}' is passing again", entry.name), format!("Succeeded at {}", run.finished_at.to_rfc3339())))
+            }
+            ScheduledOutcome::Succeeded => None,
+            ScheduledOutcome::Failed { step, exit_code } => Some((
+                format!("Scheduled workflow '{}' failed", entry.name),
+                match exit_code {
+                    Some(code) => format!("Step '{}' exited with {}", step, code),
+                    None => format!("Step '{}' timed out or was killed", step),
+                },
+            )),
+            ScheduledOutcome::NotStarted(reason) => {
+                Some((format!("Scheduled workflow '{}' could not start", entry.name), reason.clone()))
+            }
+        };
+        notification.map(|(title, body)| ScheduleNotification { workflow_id: id, title, body })
+    }
+
+    /// The latest results of a scheduled workflow, newest first.
+    pub fn results<'a>(&self, history: &'a RunHistory, id: &Uuid) -> Vec<&'a RunRecord> {
+        history.scheduled_runs(id).take(self.history_len).collect()
+    }
+
+    pub fn next_due(&self, id: &Uuid) -> Option<DateTime<Utc>> {
+        self.entries.get(id).and_then(|entry| entry.next_due)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::executor::WorkflowExecutor;
+    use crate::workflows::history::RunTrigger;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
+
+    /// A clock that only moves when told to. Clones share the same time.
+    #[derive(Clone)]
+    struct TestClock {
+        now: Arc<Mutex<DateTime<Utc>>>,
+    }
+
+    impl TestClock {
+        fn new(start: DateTime<Utc>) -> Self {
+            TestClock { now: Arc::new(Mutex::new(start)) }
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.now.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for TestClock {
+        type Tz = Utc;
+
+        fn now(&self) -> DateTime<Utc> {
+            *self.now.lock().unwrap()
+        }
+    }
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-03-01T10:02:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn scheduled(name: &str, schedule: Schedule) -> Workflow {
+        let mut workflow = Workflow::from_command(name, "true");
+        workflow.schedule = Some(schedule);
+        workflow
+    }
+
+    /// Reports a run finishing now, then keeps it in `history` the way the terminal does.
+    fn finish(
+        scheduler: &mut Scheduler<TestClock>,
+        history: &mut RunHistory,
+        id: Uuid,
+        outcome: ScheduledOutcome,
+    ) -> Option<ScheduleNotification> {
+        let run = ScheduledRun { started_at: scheduler.now(), finished_at: scheduler.now(), outcome };
+        let notification = scheduler.record(id, &run, history);
+        history
+            .record(RunRecord {
+                workflow_id: id,
+                started_at: run.started_at,
+                duration_ms: 0,
+                arguments: BTreeMap::new(),
+                succeeded: run.succeeded(),
+                exit_code: None,
+                trigger: RunTrigger::Schedule,
+                error: None,
+            })
+            .unwrap();
+        notification
+    }
+
+    #[test]
+    fn test_interval_runs_never_overlap() {
+        let dir = tempdir().unwrap();
+        let mut history = RunHistory::empty(&dir.path().join("runs.jsonl"));
+        let clock = TestClock::new(start());
+        let mut scheduler = Scheduler::new(clock.clone());
+        let health = scheduled("Health", Schedule::EveryMinutes(5));
+        scheduler.sync([&health]);
+
+        assert!(scheduler.due().is_empty());
+        clock.advance(Duration::minutes(5));
+        assert_eq!(scheduler.due(), [health.id]);
+        assert!(scheduler.due().is_empty());
+
+        // Still running five minutes later: that turn is skipped.
+        clock.advance(Duration::minutes(5));
+        assert!(scheduler.due().is_empty());
+        assert_eq!(scheduler.next_due(&health.id), Some(start() + Duration::minutes(15)));
+
+        finish(&mut scheduler, &mut history, health.id, ScheduledOutcome::Succeeded);
+        clock.advance(Duration::minutes(5));
+        assert_eq!(scheduler.due(), [health.id]);
+    }
+
+    #[test]
+    fn test_cron_schedules_follow_the_clock() {
+        let clock = TestClock::new(start());
+        let mut scheduler = Scheduler::new(clock.clone());
+        let report = scheduled("Report", Schedule::Cron("0 9 * * mon".to_string()));
+        let broken = scheduled("Broken", Schedule::Cron("every monday".to_string()));
+        scheduler.sync([&report, &broken]);
+
+        assert_eq!(scheduler.next_due(&report.id).unwrap().to_rfc3339(), "2024-03-04T09:00:00+00:00");
+        assert_eq!(scheduler.next_due(&broken.id), None);
+        clock.advance(Duration::days(2) + Duration::hours(22));
+        assert!(scheduler.due().is_empty());
+        clock.advance(Duration::minutes(58));
+        assert_eq!(scheduler.due(), [report.id]);
+        assert_eq!(scheduler.next_due(&report.id).unwrap().to_rfc3339(), "2024-03-11T09:00:00+00:00");
+    }
+
+    #[test]
+    fn test_results_and_notifications() {
+        let dir = tempdir().unwrap();
+        let mut history = RunHistory::empty(&dir.path().join("runs.jsonl"));
+        let mut scheduler = Scheduler::new(TestClock::new(start())).with_history_len(3);
+        let health = scheduled("Health", Schedule::EveryMinutes(1));
+        scheduler.sync([&health]);
+
+        assert_eq!(finish(&mut scheduler, &mut history, health.id, ScheduledOutcome::Succeeded), None);
+        let failed = ScheduledOutcome::Failed { step: "curl".to_string(), exit_code: Some(7) };
+        let notification = finish(&mut scheduler, &mut history, health.id, failed.clone()).unwrap();
+        assert_eq!(notification.title, "Scheduled workflow 'Health' failed");
+        assert_eq!(notification.body, "Step 'curl' exited with 7");
+        assert!(finish(&mut scheduler, &mut history, health.id, failed).is_some());
+        let recovered = finish(&mut scheduler, &mut history, health.id, ScheduledOutcome::Succeeded).unwrap();
+        assert_eq!(recovered.title, "'Health' is passing again");
+
+        let results = scheduler.results(&history, &health.id);
+        assert_eq!(results.len(), 3);
+        assert!(results[0].succeeded && !results[2].succeeded);
+    }
+
+    #[test]
+    fn test_results_survive_a_restart() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("runs.jsonl");
+        let health = scheduled("Health", Schedule::EveryMinutes(1));
+        let mut history = RunHistory::open(&path).unwrap();
+        let mut scheduler = Scheduler::new(TestClock::new(start()));
+        scheduler.sync([&health]);
+        let failed = ScheduledOutcome::Failed { step: "curl".to_string(), exit_code: Some(7) };
+        finish(&mut scheduler, &mut history, health.id, failed);
+        let manual = RunRecord { trigger: RunTrigger::Manual, succeeded: true, ..history.last_run(&health.id).unwrap().clone() };
+        history.record(manual).unwrap();
+
+        let mut history = RunHistory::open(&path).unwrap();
+        let mut scheduler = Scheduler::new(TestClock::new(start()));
+        scheduler.sync([&health]);
+        assert_eq!(scheduler.results(&history, &health.id).len(), 1);
+        let recovered = finish(&mut scheduler, &mut history, health.id, ScheduledOutcome::Succeeded).unwrap();
+        assert_eq!(recovered.title, "'Health' is passing again");
+    }
+
+    #[test]
+    fn test_sync_follows_workflow_changes() {
+        let clock = TestClock::new(start());
+        let mut scheduler = Scheduler::new(clock.clone());
+        let mut health = scheduled("Health", Schedule::EveryMinutes(5));
+        scheduler.sync([&health]);
+
+        clock.advance(Duration::minutes(3));
+        health.schedule = Some(Schedule::EveryMinutes(10));
+        scheduler.sync([&health]);
+        assert_eq!(scheduler.next_due(&health.id), Some(start() + Duration::minutes(13)));
+
+        health.schedule = None;
+        scheduler.sync([&health]);
+        assert_eq!(scheduler.next_due(&health.id), None);
+        let run = ScheduledRun { started_at: start(), finished_at: start(), outcome: ScheduledOutcome::Succeeded };
+        let history = RunHistory::empty(Path::new("runs.jsonl"));
+        assert_eq!(scheduler.record(health.id, &run, &history), None);
+    }
+
+    #[tokio::test]
+    async fn test_outcome_from_a_real_run() {
+        let mut workflow = scheduled("Health", Schedule::EveryMinutes(5));
+        workflow.steps[0].name = Some("ping".to_string());
+        workflow.steps[0].command = "exit 7".to_string();
+        let result = WorkflowExecutor::with_shell("sh").execute(&workflow, &HashMap::new()).await;
+        let run = ScheduledRun::from_result(&workflow, start(), start(), &result);
+        assert_eq!(run.outcome, ScheduledOutcome::Failed { step: "ping".to_string(), exit_code: Some(7) });
+
+        workflow.arguments = vec![crate::workflows::model::WorkflowArgument::new("host")];
+        let result = WorkflowExecutor::with_shell("sh").execute(&workflow, &HashMap::new()).await;
+        let run = ScheduledRun::from_result(&workflow, start(), start(), &result);
+        assert!(matches!(run.outcome, ScheduledOutcome::NotStarted(reason) if reason.contains("missing argument 'host'")));
+    }
+}
@@ -0,0 +1,211 @@
+// Secret workflow arguments (`secret: true`). Their values reach a step only through an
+// environment variable, never on its command line, and every occurrence of a value in the
+// step's output is replaced with `MASK` before it reaches a block, so it can't be seen in
+// the terminal or in an exported block.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::model::Workflow;
+
+/// What a secret value is replaced with.
+pub const MASK: &str = "••••";
+
+/// The environment variable carrying a secret argument, e.g. `WORKFLOW_SECRET_API_TOKEN`.
+pub fn env_var(name: &str) -> String {
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+    format!("WORKFLOW_SECRET_{}", name)
+}
+
+/// Checks that no two secret arguments share a variable, as `api-token` and `api_token`
+/// would: one value would silently replace the other.
+pub fn check_env_vars(workflow: &Workflow) -> Result<(), String> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    for arg in workflow.arguments.iter().filter(|arg| arg.secret) {
+        let var = env_var(&arg.name);
+        if let Some(other) = seen.get(&var) {
+            return Err(format!("secret arguments '{}' and '{}' would both be passed as {}", other, arg.name, var));
+        }
+        seen.insert(var, &arg.name);
+    }
+    Ok(())
+}
+
+/// The secret arguments of one run, by name.
+#[derive(Clone, Default)]
+pub struct Secrets {
+    values: HashMap<String, String>,
+}
+
+impl Secrets {
+    /// Picks the secret arguments out of resolved `values`. Empty values hide nothing and
+    /// are left out.
+    pub fn from_arguments(workflow: &Workflow, values: &HashMap<String, String>) -> Self {
+        let values = workflow
+            .arguments
+            .iter()
+            .filter(|arg| arg.secret)
+            .filter_map(|arg| values.get(&arg.name).filter(|v| !v.is_empty()).map(|v| (arg.name.clone(), v.clone())))
+            .collect();
+        Secrets { values }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The variables to set on each step's process.
+    pub fn env(&self) -> impl Iterator<Item = (String, &str)> {
+        self.values.iter().map(|(name, value)| (env_var(name), value.as_str()))
+    }
+
+    /// Argument name to variable name, for `args::substitute_with_secrets`.
+    pub fn env_vars(&self) -> HashMap<String, String> {
+        self.values.keys().map(|name| (name.clone(), env_var(name))).collect()
+    }
+
+    pub fn masker(&self) -> OutputMasker {
+        OutputMasker::new(self.values.values().map(String::as_str))
+    }
+
+    pub fn mask(&self, text: &str) -> String {
+        let mut masker = self.masker();
+        let mut masked = masker.push(text.as_bytes());
+        masked.extend(masker.finish());
+        String::from_utf8_lossy(&masked).into_owned()
+    }
+}
+
+impl fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.values.keys().map(|name| (name, MASK))).finish()
+    }
+}
+
+/// A value that may be or hold a secret, such as argument values in a UI message. Messages
+/// are printed with `{:?}` when debugging, so `Debug` prints `MASK` instead of the value.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Masked<T>(pub T);
+
+impl<T> fmt::Debug for Masked<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+/// Masks secrets in a stream of output chunks. A secret can be split across chunks, so the
+/// end of a chunk that could be the start of one is held back until the next chunk.
+pub struct OutputMasker {
+    // Longest first, so a secret containing another is masked whole.
+    secrets: Vec<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+impl OutputMasker {
+    pub fn new<'a>(secrets: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut secrets: Vec<Vec<u8>> = secrets.into_iter().filter(|s| !s.is_empty()).map(|s| s.as_bytes().to_vec()).collect();
+        secrets.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        secrets.dedup();
+        OutputMasker { secrets, pending: Vec::new() }
+    }
+
+    /// The masked output that is safe to show so far.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(bytes);
+        self.drain(false)
+    }
+
+    /// Everything still held back, once the stream has ended.
+    pub fn finish(&mut self) -> Vec<u8> {
+        self.drain(true)
+    }
+
+    fn drain(&mut self, at_end: bool) -> Vec<u8> {
+        if self.secrets.is_empty() {
+            return std::mem::take(&mut self.pending);
+        }
+        let mut out = Vec::with_capacity(self.pending.len());
+        let mut i = 0;
+        while i < self.pending.len() {
+            let rest = &self.pending[i..];
+            if let Some(secret) = self.secrets.iter().find(|secret| rest.starts_with(secret)) {
+                out.extend_from_slice(MASK.as_bytes());
+                i += secret.len();
+            } else if !at_end && self.secrets.iter().any(|secret| secret.starts_with(rest)) {
+                break;
+            } else {
+                out.push(rest[0]);
+                i += 1;
+            }
+        }
+        self.pending.drain(..i);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::model::WorkflowArgument;
+
+    #[test]
+    fn test_masked_values_are_not_printed() {
+        let arguments = Masked(HashMap::from([("token".to_string(), "hunter2".to_string())]));
+        assert_eq!(format!("{:?}", (1, arguments)), format!("(1, {})", MASK));
+    }
+
+    fn masked(secrets: &[&str], chunks: &[&str]) -> String {
+        let mut masker = OutputMasker::new(secrets.iter().copied());
+        let mut out = Vec::new();
+        for chunk in chunks {
+            out.extend(masker.push(chunk.as_bytes()));
+        }
+        out.extend(masker.finish());
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_secrets_split_across_chunks_are_masked() {
+        assert_eq!(masked(&["hunter2"], &["token=hun", "ter2\n"]), "token=••••\n");
+        assert_eq!(masked(&["hunter2"], &["h", "u", "n", "t", "e", "r", "2", "h"]), "••••h");
+        assert_eq!(masked(&["hunter2"], &["hunt", "ing ", "hunter"]), "hunting hunter");
+        assert_eq!(masked(&["abc", "abcdef"], &["xabcdefabcx"]), "x••••••••x");
+        assert_eq!(masked(&[], &["plain"]), "plain");
+    }
+
+    #[test]
+    fn test_only_secret_arguments_are_picked() {
+        let mut workflow = Workflow::from_command("Deploy", "deploy ${env} --token ${api-token}");
+        workflow.arguments = vec![
+            WorkflowArgument::new("env"),
+            WorkflowArgument { secret: true, ..WorkflowArgument::new("api-token") },
+            WorkflowArgument { secret: true, required: false, ..WorkflowArgument::new("otp") },
+        ];
+        let values = HashMap::from([
+            ("env".to_string(), "prod".to_string()),
+            ("api-token".to_string(), "s3cr3t".to_string()),
+            ("otp".to_string(), String::new()),
+        ]);
+        let secrets = Secrets::from_arguments(&workflow, &values);
+        assert_eq!(secrets.env().collect::<Vec<_>>(), [("WORKFLOW_SECRET_API_TOKEN".to_string(), "s3cr3t")]);
+        assert_eq!(secrets.mask("prod s3cr3t"), "prod ••••");
+        assert_eq!(format!("{:?}", secrets), r#"{"api-token": "••••"}"#);
+    }
+
+    #[test]
+    fn test_names_sharing_a_variable_are_rejected() {
+        let mut workflow = Workflow::from_command("Deploy", "deploy");
+        workflow.arguments = vec![
+            WorkflowArgument { secret: true, ..WorkflowArgument::new("api-token") },
+            WorkflowArgument::new("API_TOKEN"),
+            WorkflowArgument { secret: true, ..WorkflowArgument::new("otp") },
+        ];
+        assert_eq!(check_env_vars(&workflow), Ok(()));
+
+        workflow.arguments.push(WorkflowArgument { secret: true, ..WorkflowArgument::new("api_token") });
+        assert_eq!(
+            check_env_vars(&workflow).unwrap_err(),
+            "secret arguments 'api-token' and 'api_token' would both be passed as WORKFLOW_SECRET_API_TOKEN"
+        );
+    }
+}
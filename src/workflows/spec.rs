@@ -0,0 +1,211 @@
+// The public Warp workflow YAML format, used to import and export workflows:
+// https://github.com/warpdotdev/workflows
+//
+// Warp writes placeholders as `{{name}}`; our model uses `${name}`. Only declared
+// argument names are translated, so unrelated braces (e.g. Go templates) are left alone.
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::model::{Schedule, Workflow, WorkflowArgument, WorkflowStep};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowSpec {
+    pub name: String,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<ArgumentSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shells: Vec<String>,
+    /// Not part of Warp's format: globs whose changes re-run the workflow.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_change: Vec<String>,
+    /// Not part of Warp's format: when the workflow runs on its own, e.g. `{cron: "0 9 * * *"}`.
+    /// A map rather than serde_yaml's default `!cron` tag, to match the JSON form.
+    #[serde(default, with = "serde_yaml::with::singleton_map", skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArgumentSpec {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // Warp files often write `default_value: 8080` or `default_value: true`.
+    #[serde(default, deserialize_with = "scalar_as_string", skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<String>,
+    /// Not part of Warp's format: a token or password, never shown or recorded.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+}
+
+fn scalar_as_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    use serde::de::Error;
+    match Option::<serde_yaml::Value>::deserialize(deserializer)? {
+        None | Some(serde_yaml::Value::Null) => Ok(None),
+        Some(serde_yaml::Value::String(s)) => Ok(Some(s)),
+        Some(serde_yaml::Value::Bool(b)) => Ok(Some(b.to_string())),
+        Some(serde_yaml::Value::Number(n)) => Ok(Some(n.to_string())),
+        Some(other) => Err(D::Error::custom(format!("default_value must be a scalar, got {:?}", other))),
+    }
+}
+
+fn translate_placeholders(command: &str, arguments: &[String], from: (&str, &str), to: (&str, &str)) -> String {
+    arguments.iter().fold(command.to_string(), |command, name| {
+        command.replace(&format!("{}{}{}", from.0, name, from.1), &format!("{}{}{}", to.0, name, to.1))
+    })
+}
+
+impl WorkflowSpec {
+    pub fn from_yaml(yaml: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+
+    /// Builds a new workflow (with a fresh ID) from the spec. The command becomes a single step.
+    pub fn into_workflow(self) -> Workflow {
+        let names: Vec<String> = self.arguments.iter().map(|a| a.name.clone()).collect();
+        let command = translate_placeholders(&self.command, &names, ("{{", "}}"), ("${", "}"));
+        let mut workflow = Workflow::from_command(self.name, command);
+        workflow.description = self.description;
+        workflow.tags = self.tags;
+        workflow.source_url = self.source_url;
+        workflow.author = self.author;
+        workflow.author_url = self.author_url;
+        workflow.shells = self.shells;
+        workflow.on_change = self.on_change;
+        workflow.schedule = self.schedule;
+        workflow.arguments = self
+            .arguments
+            .into_iter()
+            .map(|arg| WorkflowArgument {
+                description: arg.description,
+                default_value: arg.default_value,
+                secret: arg.secret,
+                ..WorkflowArgument::new(arg.name)
+            })
+            .collect();
+        workflow
+    }
+
+    /// The Warp format has a single command, so multiple steps are joined with newlines.
+    /// Argument types have no equivalent and are dropped.
+    pub fn from_workflow(workflow: &Workflow) -> Self {
+        let names: Vec<String> = workflow.arguments.iter().map(|a| a.name.clone()).collect();
+        let command = workflow.steps.iter().map(|step: &WorkflowStep| step.command.as_str()).collect::<Vec<_>>().join("\n");
+        WorkflowSpec {
+            name: workflow.name.clone(),
+            command: translate_placeholders(&command, &names, ("${", "}"), ("{{", "}}")),
+            tags: workflow.tags.clone(),
+            description: workflow.description.clone(),
+            arguments: workflow
+                .arguments
+                .iter()
+                .map(|arg| ArgumentSpec {
+                    name: arg.name.clone(),
+                    description: arg.description.clone(),
+                    default_value: arg.default_value.clone(),
+                    secret: arg.secret,
+                })
+                .collect(),
+            source_url: workflow.source_url.clone(),
+            author: workflow.author.clone(),
+            author_url: workflow.author_url.clone(),
+            shells: workflow.shells.clone(),
+            on_change: workflow.on_change.clone(),
+            schedule: workflow.schedule.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WARP_EXAMPLE: &str = r#"---
+name: Uninstall a Homebrew package and all of its dependencies
+command: |-
+  brew tap beeftornado/rmtree
+  brew rmtree {{package_name}}
+tags:
+  - homebrew
+description: Uses the external command rmtree to remove a Homebrew package and all of its dependencies
+arguments:
+  - name: package_name
+    description: The name of the package that should be removed
+    default_value: ~
+source_url: "https://stackoverflow.com/questions/7323261/uninstall-remove-a-homebrew-package-including-all-its-dependencies"
+author: Ory Band
+author_url: "https://stackoverflow.com/users/207894"
+shells: []
+"#;
+
+    #[test]
+    fn test_parses_warp_workflow() {
+        let workflow = WorkflowSpec::from_yaml(WARP_EXAMPLE).unwrap().into_workflow();
+        assert_eq!(workflow.name, "Uninstall a Homebrew package and all of its dependencies");
+        assert_eq!(workflow.steps.len(), 1);
+        assert_eq!(workflow.steps[0].command, "brew tap beeftornado/rmtree\nbrew rmtree ${package_name}");
+        assert_eq!(workflow.tags, ["homebrew"]);
+        assert_eq!(workflow.author.as_deref(), Some("Ory Band"));
+        assert_eq!(workflow.argument("package_name").unwrap().default_value, None);
+    }
+
+    #[test]
+    fn test_scalar_default_values_become_strings() {
+        let yaml = "name: Serve\ncommand: python -m http.server {{port}}\narguments:\n  - name: port\n    default_value: 8080\n  - name: verbose\n    default_value: true\n";
+        let spec = WorkflowSpec::from_yaml(yaml).unwrap();
+        assert_eq!(spec.arguments[0].default_value.as_deref(), Some("8080"));
+        assert_eq!(spec.arguments[1].default_value.as_deref(), Some("true"));
+    }
+
+    #[test]
+    fn test_round_trip_with_quoting_edge_cases() {
+        let mut workflow = Workflow::from_command(
+            "Edge: cases # not a comment",
+            r#"echo "it's: ${msg}" | grep -E '^\s*#' && docker inspect -f '{{.State.Status}}' x"#,
+        );
+        workflow.steps.push(WorkflowStep::new("cat <<'EOF'\n  - not a list\nEOF"));
+        workflow.description = Some("*starts with an asterisk*".to_string());
+        workflow.tags = vec!["yes".to_string(), "123".to_string(), "@at".to_string()];
+        workflow.arguments = vec![WorkflowArgument {
+            description: Some("quote \" and 'single'".to_string()),
+            default_value: Some("no".to_string()),
+            ..WorkflowArgument::new("msg")
+        }];
+        workflow.shells = vec!["bash".to_string(), "zsh".to_string()];
+        workflow.schedule = Some(Schedule::EveryMinutes(15));
+
+        let yaml = WorkflowSpec::from_workflow(&workflow).to_yaml().unwrap();
+        assert!(yaml.contains("{{msg}}"), "placeholders should use Warp syntax: {}", yaml);
+        assert!(yaml.contains("every_minutes: 15"), "the schedule should be a map: {}", yaml);
+        let parsed = WorkflowSpec::from_yaml(&yaml).unwrap();
+        assert_eq!(parsed, WorkflowSpec::from_workflow(&workflow));
+
+        let restored = parsed.into_workflow();
+        assert_eq!(restored.steps[0].command, workflow.steps.iter().map(|s| s.command.clone()).collect::<Vec<_>>().join("\n"));
+        assert_eq!(restored.tags, workflow.tags);
+        assert_eq!(restored.arguments, workflow.arguments);
+        assert_eq!(restored.description, workflow.description);
+        assert_eq!(restored.schedule, workflow.schedule);
+    }
+
+    #[test]
+    fn test_undeclared_braces_are_left_alone() {
+        let yaml = "name: Inspect\ncommand: docker inspect -f '{{.Id}}' {{container}}\narguments:\n  - name: container\n";
+        let workflow = WorkflowSpec::from_yaml(yaml).unwrap().into_workflow();
+        assert_eq!(workflow.steps[0].command, "docker inspect -f '{{.Id}}' ${container}");
+    }
+}
@@ -0,0 +1,201 @@
+// Argument suggestions produced by running an argument's generator command, e.g.
+// `git branch --format=%(refname:short)`; every non-empty output line is a candidate.
+// Results are cached per (working directory, command) for a short time, since the
+// form asks again on every keystroke-driven redraw.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::debug;
+use tokio::process::Command;
+
+use crate::fuzzy_match::FuzzyMatcher;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+pub const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SuggestionError {
+    TimedOut(Duration),
+    Failed { status: Option<i32>, stderr: String },
+    Spawn(String),
+}
+
+impl fmt::Display for SuggestionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SuggestionError::TimedOut(after) => write!(f, "generator timed out after {:?}", after),
+            SuggestionError::Failed { status: Some(code), stderr } => write!(f, "generator exited with {}: {}", code, stderr),
+            SuggestionError::Failed { status: None, stderr } => write!(f, "generator was killed: {}", stderr),
+            SuggestionError::Spawn(e) => write!(f, "could not run generator: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SuggestionError {}
+
+struct CacheEntry {
+    suggestions: Arc<Vec<String>>,
+    fetched_at: Instant,
+}
+
+/// Runs generator commands and caches their output. Clones share the cache.
+#[derive(Clone)]
+pub struct SuggestionCache {
+    entries: Arc<Mutex<HashMap<(PathBuf, String), CacheEntry>>>,
+    timeout: Duration,
+    ttl: Duration,
+}
+
+impl SuggestionCache {
+    pub fn new() -> Self {
+        Self::with_limits(DEFAULT_TIMEOUT, DEFAULT_TTL)
+    }
+
+    pub fn with_limits(timeout: Duration, ttl: Duration) -> Self {
+        SuggestionCache { entries: Arc::new(Mutex::new(HashMap::new())), timeout, ttl }
+    }
+
+    /// Cached suggestions for `command` in `cwd`, if still fresh.
+    pub fn cached(&self, command: &str, cwd: &Path) -> Option<Arc<Vec<String>>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&(cwd.to_path_buf(), command.to_string()))
+            .filter(|entry| entry.fetched_at.elapsed() < self.ttl)
+            .map(|entry| entry.suggestions.clone())
+    }
+
+    /// Returns the suggestions for `command` run in `cwd`, running it if nothing fresh is cached.
+    /// Failures are not cached, so the next request tries again.
+    pub async fn suggestions(&self, command: &str, cwd: &Path) -> Result<Arc<Vec<String>>, SuggestionError> {
+        if let Some(suggestions) = self.cached(command, cwd) {
+            return Ok(suggestions);
+        }
+        let suggestions = Arc::new(run_generator(command, cwd, self.timeout).await?);
+        self.entries.lock().unwrap().insert(
+            (cwd.to_path_buf(), command.to_string()),
+            CacheEntry { suggestions: suggestions.clone(), fetched_at: Instant::now() },
+        );
+        Ok(suggestions)
+    }
+
+    /// Drops everything cached for `cwd`, e.g. after a command that may have changed it.
+    pub fn invalidate(&self, cwd: &Path) {
+        self.entries.lock().unwrap().retain(|(dir, _), _| dir != cwd);
+    }
+}
+
+impl Default for SuggestionCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn run_generator(command: &str, cwd: &Path, timeout: Duration) -> Result<Vec<String>, SuggestionError> {
+    debug!("Running suggestion generator '{}' in {:?}", command, cwd);
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| SuggestionError::Spawn(e.to_string()))?;
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| SuggestionError::TimedOut(timeout))?
+        .map_err(|e| SuggestionError::Spawn(e.to_string()))?;
+    if !output.status.success() {
+        return Err(SuggestionError::Failed {
+            status: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    let mut suggestions: Vec<String> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let line = line.trim();
+        if !line.is_empty() && !suggestions.iter().any(|s| s == line) {
+            suggestions.push(line.to_string());
+        }
+    }
+    Ok(suggestions)
+}
+
+/// The best `limit` suggestions for what has been typed so far. An empty query keeps
+/// the generator's own order.
+pub fn filter_suggestions(matcher: &FuzzyMatcher, query: &str, suggestions: &[String], limit: usize) -> Vec<String> {
+    if query.is_empty() {
+        return suggestions.iter().take(limit).cloned().collect();
+    }
+    matcher
+        .filter_candidates(query, suggestions)
+        .into_iter()
+        .filter(|(_, candidate)| candidate != query)
+        .take(limit)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_output_lines_become_suggestions() {
+        let dir = tempdir().unwrap();
+        let cache = SuggestionCache::new();
+        let suggestions = cache.suggestions("printf 'main\\n  feature/x\\n\\nmain\\n'", dir.path()).await.unwrap();
+        assert_eq!(*suggestions, ["main", "feature/x"]);
+    }
+
+    #[tokio::test]
+    async fn test_results_are_cached_per_directory() {
+        let (first, second) = (tempdir().unwrap(), tempdir().unwrap());
+        let cache = SuggestionCache::new();
+        let command = "ls; touch new-file";
+        std::fs::write(first.path().join("a"), "").unwrap();
+
+        assert_eq!(*cache.suggestions(command, first.path()).await.unwrap(), ["a"]);
+        // The generator created a file, but the cached answer is reused.
+        assert_eq!(*cache.suggestions(command, first.path()).await.unwrap(), ["a"]);
+        assert!(cache.suggestions(command, second.path()).await.unwrap().is_empty());
+
+        cache.invalidate(first.path());
+        assert_eq!(*cache.suggestions(command, first.path()).await.unwrap(), ["a", "new-file"]);
+    }
+
+    #[tokio::test]
+    async fn test_slow_and_failing_generators() {
+        let dir = tempdir().unwrap();
+        let cache = SuggestionCache::with_limits(Duration::from_millis(100), DEFAULT_TTL);
+        let started = Instant::now();
+        assert_eq!(
+            cache.suggestions("sleep 5", dir.path()).await,
+            Err(SuggestionError::TimedOut(Duration::from_millis(100)))
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let error = cache.suggestions("echo nope >&2; exit 3", dir.path()).await.unwrap_err();
+        assert_eq!(error, SuggestionError::Failed { status: Some(3), stderr: "nope".to_string() });
+        assert!(cache.cached("echo nope >&2; exit 3", dir.path()).is_none());
+    }
+
+    #[test]
+    fn test_filter_suggestions() {
+        let matcher = FuzzyMatcher::new();
+        let branches: Vec<String> = ["main", "feature/login", "fix/logout", "release"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(filter_suggestions(&matcher, "", &branches, 2), ["main", "feature/login"]);
+        let matches = filter_suggestions(&matcher, "log", &branches, 10);
+        assert_eq!(matches.len(), 2);
+        assert!(matches.contains(&"feature/login".to_string()) && matches.contains(&"fix/logout".to_string()));
+        assert!(filter_suggestions(&matcher, "main", &branches, 10).is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+// File-triggered workflows. A workflow with `on_change:` globs re-runs whenever a matching
+// file under the working directory changes, like a watch mode (`cargo test` on
+// `src/**/*.rs`). Changes come from the debounced `watcher::FileWatcherService`; paths git
+// ignores (build output, `target/`) never trigger anything, and a new change cancels the
+// run it would otherwise pile up behind. Only the directories the globs can match in are
+// watched, so `src/**/*.rs` doesn't put the whole working directory under watch.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+use log::{debug, warn};
+use notify::RecursiveMode;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use super::model::Workflow;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    // `src/*.rs` stays in `src/`; `src/**/*.rs` goes deeper.
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Compiles a workflow's `on_change` globs, logging and dropping invalid ones.
+fn patterns(workflow: &Workflow) -> Vec<Pattern> {
+    workflow
+        .on_change
+        .iter()
+        .filter_map(|glob| match Pattern::new(glob) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                warn!("Workflow '{}': ignoring invalid on_change pattern '{}': {}", workflow.name, glob, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Where a glob can match, relative to the root: the directory before its first wildcard,
+/// recursively only if the glob can reach below it (`src/**/*.rs`, `*/Cargo.toml`). A glob
+/// whose last component is the only pattern (`src/*.rs`) or that has none (`Cargo.toml`)
+/// matches in a single directory.
+fn glob_root(glob: &str) -> (PathBuf, RecursiveMode) {
+    let components: Vec<&str> = glob.split('/').filter(|c| !c.is_empty() && *c != ".").collect();
+    let first_pattern = components
+        .iter()
+        .position(|c| c.contains(['*', '?', '[']))
+        .unwrap_or(components.len());
+    let patterns = &components[first_pattern..];
+    if patterns.len() > 1 || patterns.contains(&"**") {
+        (components[..first_pattern].iter().collect(), RecursiveMode::Recursive)
+    } else {
+        (components[..components.len().saturating_sub(1)].iter().collect(), RecursiveMode::NonRecursive)
+    }
+}
+
+pub struct ChangeTriggers {
+    root: PathBuf,
+    // `None` outside a git repository, where nothing counts as ignored.
+    repo: Option<git2::Repository>,
+    running: HashMap<Uuid, JoinHandle<()>>,
+}
+
+impl ChangeTriggers {
+    pub fn new(root: &Path) -> Self {
+        let mut triggers = ChangeTriggers { root: PathBuf::new(), repo: None, running: HashMap::new() };
+        triggers.set_root(root);
+        triggers
+    }
+
+    /// Follows the working directory; `on_change` globs are relative to it.
+    pub fn set_root(&mut self, root: &Path) {
+        if self.root == root {
+            return;
+        }
+        self.root = root.to_path_buf();
+        self.repo = git2::Repository::discover(root).ok();
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directories to watch for `workflows`' `on_change` globs, none inside another that
+    /// is watched recursively. Directories that don't exist (yet) are left out.
+    pub fn watch_roots<'a>(&self, workflows: impl IntoIterator<Item = &'a Workflow>) -> Vec<(PathBuf, RecursiveMode)> {
+        let mut roots: BTreeMap<PathBuf, RecursiveMode> = BTreeMap::new();
+        for workflow in workflows {
+            for glob in &workflow.on_change {
+                let (dir, mode) = glob_root(glob);
+                let mode_entry = roots.entry(self.root.join(dir)).or_insert(mode);
+                if mode == RecursiveMode::Recursive {
+                    *mode_entry = mode;
+                }
+            }
+        }
+        // Sorted, so a recursive root comes before everything under it.
+        let mut recursive: Vec<PathBuf> = Vec::new();
+        let mut watched = Vec::new();
+        for (dir, mode) in roots {
+            if recursive.iter().any(|root| dir.starts_with(root)) || !dir.is_dir() {
+                continue;
+            }
+            if mode == RecursiveMode::Recursive {
+                recursive.push(dir.clone());
+            }
+            watched.push((dir, mode));
+        }
+        watched
+    }
+
+    /// Whether a change to `path` should be ignored: outside the root, inside `.git`, or
+    /// ignored by git (any `.gitignore`, `.git/info/exclude` or the global excludes file).
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else { return true };
+        if relative.components().any(|c| c == Component::Normal(".git".as_ref())) {
+            return true;
+        }
+        let Some(repo) = &self.repo else { return false };
+        // git2 wants paths relative to the work tree.
+        match repo.workdir().and_then(|workdir| path.strip_prefix(workdir).ok()) {
+            Some(in_repo) => repo.is_path_ignored(in_repo).unwrap_or(false),
+            None => true,
+        }
+    }
+
+    /// The workflows that a batch of changed paths should (re-)run, each once.
+    pub fn triggered<'a>(&self, workflows: impl IntoIterator<Item = &'a Workflow>, changed: &[PathBuf]) -> Vec<Uuid> {
+        let relevant: Vec<&Path> = changed
+            .iter()
+            .filter(|path| !self.is_ignored(path))
+            .filter_map(|path| path.strip_prefix(&self.root).ok())
+            .collect();
+        if relevant.is_empty() {
+            return Vec::new();
+        }
+
+        workflows
+            .into_iter()
+            .filter(|workflow| !workflow.on_change.is_empty())
+            .filter(|workflow| {
+                let patterns = patterns(workflow);
+                let matched = relevant.iter().find(|path| patterns.iter().any(|p| p.matches_path_with(path, MATCH_OPTIONS)));
+                if let Some(path) = matched {
+                    debug!("{:?} changed; re-running workflow '{}'", path, workflow.name);
+                }
+                matched.is_some()
+            })
+            .map(|workflow| workflow.id)
+            .collect()
+    }
+
+    /// Starts `run` for workflow `id` in the background, first cancelling the workflow's
+    /// previous run if it is still going. Cancelling drops the run, which kills its processes.
+    pub fn restart(&mut self, id: Uuid, run: impl Future<Output = ()> + Send + 'static) {
+        if let Some(previous) = self.running.remove(&id) {
+            if !previous.is_finished() {
+                debug!("Cancelling in-flight run of workflow {}", id);
+                previous.abort();
+            }
+        }
+        self.running.insert(id, tokio::spawn(run));
+    }
+
+    pub fn is_running(&self, id: &Uuid) -> bool {
+        self.running.get(id).is_some_and(|run| !run.is_finished())
+    }
+
+    pub fn cancel_all(&mut self) {
+        for (_, run) in self.running.drain() {
+            run.abort();
+        }
+    }
+}
+
+impl Drop for ChangeTriggers {
+    fn drop(&mut self) {
+        self.cancel_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::executor::WorkflowExecutor;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn watching(name: &str, globs: &[&str]) -> Workflow {
+        let mut workflow = Workflow::from_command(name, "true");
+        workflow.on_change = globs.iter().map(|g| g.to_string()).collect();
+        workflow
+    }
+
+    #[test]
+    fn test_globs_pick_workflows() {
+        let dir = tempdir().unwrap();
+        let tests = watching("tests", &["src/**/*.rs", "Cargo.toml"]);
+        let docs = watching("docs", &["docs/*.md"]);
+        let manual = Workflow::from_command("manual", "true");
+        let workflows = [&tests, &docs, &manual];
+        let triggers = ChangeTriggers::new(dir.path());
+        let changed = |paths: &[&str]| -> Vec<Uuid> {
+            let paths: Vec<PathBuf> = paths.iter().map(|p| dir.path().join(p)).collect();
+            triggers.triggered(workflows, &paths)
+        };
+
+        assert_eq!(changed(&["src/terminal/view.rs", "src/main.rs"]), [tests.id]);
+        assert_eq!(changed(&["docs/guide.md", "Cargo.toml"]), [tests.id, docs.id]);
+        assert!(changed(&["docs/api/index.md", "README.md", "src/main.rs.orig"]).is_empty());
+        assert!(triggers.triggered(workflows, &[PathBuf::from("/elsewhere/src/main.rs")]).is_empty());
+    }
+
+    #[test]
+    fn test_glob_roots() {
+        assert_eq!(glob_root("Cargo.toml"), (PathBuf::new(), RecursiveMode::NonRecursive));
+        assert_eq!(glob_root("./src/*.rs"), (PathBuf::from("src"), RecursiveMode::NonRecursive));
+        assert_eq!(glob_root("src/**/*.rs"), (PathBuf::from("src"), RecursiveMode::Recursive));
+        assert_eq!(glob_root("docs/api/**"), (PathBuf::from("docs/api"), RecursiveMode::Recursive));
+        assert_eq!(glob_root("*/Cargo.toml"), (PathBuf::new(), RecursiveMode::Recursive));
+    }
+
+    #[test]
+    fn test_only_glob_roots_are_watched() {
+        let dir = tempdir().unwrap();
+        for sub in ["src/bin", "docs/api", "tests"] {
+            std::fs::create_dir_all(dir.path().join(sub)).unwrap();
+        }
+        let tests = watching("tests", &["src/**/*.rs", "src/bin/*.rs", "tests/*.rs"]);
+        let docs = watching("docs", &["docs/*.md", "Cargo.toml", "missing/**"]);
+        let triggers = ChangeTriggers::new(dir.path());
+
+        assert_eq!(
+            triggers.watch_roots([&tests, &docs]),
+            vec![
+                (dir.path().to_path_buf(), RecursiveMode::NonRecursive),
+                (dir.path().join("docs"), RecursiveMode::NonRecursive),
+                (dir.path().join("src"), RecursiveMode::Recursive),
+                (dir.path().join("tests"), RecursiveMode::NonRecursive),
+            ]
+        );
+        assert!(triggers.watch_roots([&Workflow::from_command("manual", "true")]).is_empty());
+    }
+
+    #[test]
+    fn test_git_ignored_paths_do_not_trigger() {
+        let dir = tempdir().unwrap();
+        git2::Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let everything = watching("everything", &["**/*"]);
+        let triggers = ChangeTriggers::new(&dir.path().join("sub"));
+        let triggered = |path: &str| !triggers.triggered([&everything], &[dir.path().join("sub").join(path)]).is_empty();
+
+        assert!(triggered("src/lib.rs"));
+        assert!(!triggered("target/debug/build.rs"));
+        assert!(!triggered("test.log"));
+        assert!(!triggered(".git/index"));
+    }
+
+    #[tokio::test]
+    async fn test_new_change_cancels_the_running_workflow() {
+        let dir = tempdir().unwrap();
+        let mut workflow = Workflow::from_command("slow", "sleep 1 && touch first-run-finished");
+        workflow.on_change = vec!["*".to_string()];
+        let mut triggers = ChangeTriggers::new(dir.path());
+        let run = |workflow: Workflow| {
+            let executor = WorkflowExecutor::with_shell("sh").with_cwd(dir.path());
+            async move {
+                executor.execute(&workflow, &HashMap::new()).await.unwrap();
+            }
+        };
+
+        triggers.restart(workflow.id, run(workflow.clone()));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(triggers.is_running(&workflow.id));
+
+        workflow.steps[0].command = "touch second-run-finished".to_string();
+        triggers.restart(workflow.id, run(workflow.clone()));
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(dir.path().join("second-run-finished").exists());
+        assert!(!dir.path().join("first-run-finished").exists());
+        assert!(!triggers.is_running(&workflow.id));
+    }
+}
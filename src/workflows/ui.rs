@@ -2,12 +2,14 @@ use iced::{
     widget::{button, column, container, row, scrollable, text, text_input, Space},
     Alignment, Element, Length, Color,
 };
+use std::collections::HashMap;
 use uuid::Uuid;
-use log::info;
+use log::{info, warn};
 
 use crate::config::theme::WarpTheme;
+use crate::workflows::lint::{Diagnostic, Linter, Severity};
 use crate::workflows::manager::WorkflowManager;
-use crate::workflows::executor::Workflow; // Import Workflow struct
+use crate::workflows::model::Workflow;
 use crate::terminal::Message; // For sending messages back to the main app
 
 #[derive(Debug, Clone)]
@@ -18,6 +20,7 @@ pub enum WorkflowBrowserMessage {
     DeleteWorkflow(Uuid),
     ImportWorkflowClicked,
     CreateWorkflowClicked,
+    ToggleLintDetails(Uuid),
     // Add messages for pagination, sorting, etc.
 }
 
@@ -25,16 +28,37 @@ pub struct WorkflowBrowser {
     workflow_manager: WorkflowManager,
     search_input: String,
     filtered_workflows: Vec<Workflow>,
+    lint_results: HashMap<Uuid, Vec<Diagnostic>>,
+    expanded_lint: Option<Uuid>,
 }
 
 impl WorkflowBrowser {
     pub fn new(workflow_manager: WorkflowManager) -> Self {
         let all_workflows = workflow_manager.get_all_workflows().into_iter().cloned().collect();
-        WorkflowBrowser {
+        let mut browser = WorkflowBrowser {
             workflow_manager,
             search_input: String::new(),
             filtered_workflows: all_workflows,
-        }
+            lint_results: HashMap::new(),
+            expanded_lint: None,
+        };
+        browser.lint_workflows();
+        browser
+    }
+
+    /// Re-checks every workflow; only those with problems get an entry.
+    fn lint_workflows(&mut self) {
+        let mut linter = match Linter::new() {
+            Ok(linter) => linter,
+            Err(e) => {
+                warn!("Workflow linting unavailable: {}", e);
+                return;
+            }
+        };
+        self.lint_results = self.workflow_manager.get_all_workflows().into_iter()
+            .map(|wf| (wf.id, linter.lint(wf)))
+            .filter(|(_, diagnostics)| !diagnostics.is_empty())
+            .collect();
     }
 
     pub fn update(&mut self, message: WorkflowBrowserMessage) -> Option<Message> {
@@ -58,6 +82,7 @@ impl WorkflowBrowser {
                 info!("WorkflowBrowser: Request to delete workflow ID: {}", id);
                 if self.workflow_manager.remove_workflow(&id).is_some() {
                     info!("Workflow deleted successfully.");
+                    self.lint_results.remove(&id);
                     self.filter_workflows(); // Re-filter after deletion
                 } else {
                     info!("Workflow not found for deletion.");
@@ -74,6 +99,10 @@ impl WorkflowBrowser {
                 // This would typically open a new view/modal for creating a workflow
                 None
             }
+            WorkflowBrowserMessage::ToggleLintDetails(id) => {
+                self.expanded_lint = if self.expanded_lint == Some(id) { None } else { Some(id) };
+                None
+            }
         }
     }
 
@@ -85,6 +114,27 @@ impl WorkflowBrowser {
             .collect();
     }
 
+    /// "2 errors, 1 warning", toggling the details; empty for a clean workflow.
+    fn lint_badge(&self, id: Uuid, diagnostics: &[Diagnostic]) -> Element<WorkflowBrowserMessage> {
+        if diagnostics.is_empty() {
+            return Space::with_width(Length::Shrink).into();
+        }
+        let errors = diagnostics.iter().filter(|d| d.severity() == Severity::Error).count();
+        let warnings = diagnostics.len() - errors;
+        let mut parts = Vec::new();
+        if errors > 0 {
+            parts.push(format!("{} error{}", errors, if errors == 1 { "" } else { "s" }));
+        }
+        if warnings > 0 {
+            parts.push(format!("{} warning{}", warnings, if warnings == 1 { "" } else { "s" }));
+        }
+        let worst = if errors > 0 { Severity::Error } else { Severity::Warning };
+        button(text(parts.join(", ")).size(14).color(severity_color(worst)))
+            .on_press(WorkflowBrowserMessage::ToggleLintDetails(id))
+            .style(iced::theme::Button::Text)
+            .into()
+    }
+
     pub fn view(&self, theme: &WarpTheme) -> Element<WorkflowBrowserMessage> {
         let background_color = theme.get_block_background_color(theme.is_dark_theme());
         let foreground_color = theme.get_foreground_color();
@@ -94,14 +144,24 @@ impl WorkflowBrowser {
         let workflow_list = scrollable(
             column(
                 self.filtered_workflows.iter().map(|workflow| {
-                    row![
-                        text(&workflow.name).size(18).color(foreground_color).width(Length::Fill),
-                        button("Execute").on_press(WorkflowBrowserMessage::ExecuteWorkflow(workflow.id)),
-                        button("Edit").on_press(WorkflowBrowserMessage::EditWorkflow(workflow.id)),
-                        button("Delete").on_press(WorkflowBrowserMessage::DeleteWorkflow(workflow.id)),
-                    ]
-                    .spacing(10)
-                    .align_items(Alignment::Center)
+                    let diagnostics = self.lint_results.get(&workflow.id).map_or(&[][..], Vec::as_slice);
+                    let mut entry = column![
+                        row![
+                            text(&workflow.name).size(18).color(foreground_color).width(Length::Fill),
+                            self.lint_badge(workflow.id, diagnostics),
+                            button("Execute").on_press(WorkflowBrowserMessage::ExecuteWorkflow(workflow.id)),
+                            button("Edit").on_press(WorkflowBrowserMessage::EditWorkflow(workflow.id)),
+                            button("Delete").on_press(WorkflowBrowserMessage::DeleteWorkflow(workflow.id)),
+                        ]
+                        .spacing(10)
+                        .align_items(Alignment::Center),
+                    ];
+                    if self.expanded_lint == Some(workflow.id) {
+                        for diagnostic in diagnostics {
+                            entry = entry.push(text(diagnostic.to_string()).size(14).color(severity_color(diagnostic.severity())));
+                        }
+                    }
+                    container(entry.spacing(5))
                     .padding(8)
                     .style(move |_theme: &iced::Theme| container::Appearance {
                         background: Some(iced::Background::Color(background_color)),
@@ -170,3 +230,10 @@ impl WorkflowBrowser {
         .into()
     }
 }
+
+fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Error => Color::from_rgb(0.9, 0.3, 0.3),
+        Severity::Warning => Color::from_rgb(0.9, 0.7, 0.2),
+    }
+}